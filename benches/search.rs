@@ -0,0 +1,83 @@
+//! 合成したカタログでのキーワード検索・あいまい検索・全文検索の速度
+//!
+//! `cargo bench --bench search` で実行する（全文検索は `--features fulltext` を付けた場合のみ）。
+//! 100万冊のカタログは生成と索引に時間がかかるので、`BENCH_MAX_BOOKS` で上限を下げられる。
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rust_mcp::Book;
+use rust_mcp::search::{SearchLimits, SearchQuery, run_search};
+use rust_mcp::synthetic;
+use std::hint::black_box;
+
+/// カタログの冊数
+const SIZES: &[usize] = &[1_000, 100_000, 1_000_000];
+
+/// 結果を毎回同じにするためのシード
+const SEED: u64 = 42;
+
+const LIMITS: SearchLimits = SearchLimits {
+    default: 10,
+    max: rust_mcp::search::MAX_SEARCH_LIMIT,
+};
+
+fn sizes() -> impl Iterator<Item = usize> {
+    let max = std::env::var("BENCH_MAX_BOOKS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(usize::MAX);
+    SIZES.iter().copied().filter(move |size| *size <= max)
+}
+
+fn query(value: serde_json::Value) -> SearchQuery {
+    serde_json::from_value(value).expect("valid search query")
+}
+
+fn bench_query(c: &mut Criterion, name: &str, search: &SearchQuery) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+    for size in sizes() {
+        let books: Vec<Book> = synthetic::books(size, SEED);
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &books, |b, books| {
+            b.iter(|| run_search(black_box(books), black_box(search), LIMITS, None).expect("search succeeds"));
+        });
+    }
+    group.finish();
+}
+
+fn keyword(c: &mut Criterion) {
+    bench_query(c, "keyword", &query(serde_json::json!({ "keyword": "火星 料理" })));
+    bench_query(
+        c,
+        "keyword_filtered",
+        &query(serde_json::json!({ "keyword": "園芸", "tags": ["mars"], "year_min": 2500 })),
+    );
+}
+
+fn fuzzy(c: &mut Criterion) {
+    // 「タイムトラベル」の打ち間違い
+    bench_query(c, "fuzzy", &query(serde_json::json!({ "keyword": "タイムトラベク", "fuzzy": true })));
+}
+
+#[cfg(feature = "fulltext")]
+fn fulltext(c: &mut Criterion) {
+    use rust_mcp::index::{RankedIndex, TantivyIndex};
+
+    let mut group = c.benchmark_group("fulltext");
+    group.sample_size(10);
+    for size in sizes() {
+        let index = TantivyIndex::in_memory().expect("index is created");
+        index.upsert_all(&synthetic::books(size, SEED)).expect("books are indexed");
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &index, |b, index| {
+            b.iter(|| index.search(black_box("量子コンピュータで料理"), LIMITS.default).expect("search succeeds"));
+        });
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "fulltext"))]
+fn fulltext(_: &mut Criterion) {}
+
+criterion_group!(benches, keyword, fuzzy, fulltext);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+-- 本・レビュー・著者の記録・読書リスト
+-- 登録順を保つため、それぞれ追加した順に増える position を持つ
+
+CREATE TABLE IF NOT EXISTS books (
+    position BIGSERIAL NOT NULL,
+    isbn TEXT PRIMARY KEY,
+    title TEXT NOT NULL,
+    author TEXT NOT NULL,
+    author_id TEXT,
+    year INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    series TEXT,
+    series_volume INTEGER,
+    version BIGINT NOT NULL DEFAULT 0,
+    cover_path TEXT,
+    cover_url TEXT,
+    deleted_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS books_position ON books (position);
+
+CREATE TABLE IF NOT EXISTS reviews (
+    id BIGSERIAL PRIMARY KEY,
+    isbn TEXT NOT NULL,
+    rating SMALLINT NOT NULL,
+    text TEXT NOT NULL,
+    reviewer TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS reviews_isbn ON reviews (isbn);
+
+CREATE TABLE IF NOT EXISTS authors (
+    position BIGSERIAL NOT NULL,
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    bio TEXT NOT NULL DEFAULT '',
+    aliases TEXT[] NOT NULL DEFAULT '{}'
+);
+
+CREATE TABLE IF NOT EXISTS reading_lists (
+    position BIGSERIAL NOT NULL,
+    name TEXT PRIMARY KEY,
+    description TEXT NOT NULL DEFAULT '',
+    isbns TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
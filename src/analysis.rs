@@ -0,0 +1,380 @@
+//! テーマの網羅状況や本同士の関係など、蔵書全体の分析
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::fuzzy::levenshtein;
+use crate::isbn::{self, Isbn};
+use crate::model::Book;
+use crate::search::{matches_query, normalizer, parse_query};
+
+/// `theme_coverage` でテーマが指定されなかった場合に使うテーマ
+pub const DEFAULT_THEMES: &[&str] = &["料理", "宇宙", "AI"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ThemeCoverage {
+    pub theme: String,
+    pub count: usize,
+    pub isbns: Vec<String>,
+}
+
+/// テーマごとに一致する本を数える
+///
+/// テーマは検索キーワードと同じ規則で解釈する。
+pub fn theme_coverage(books: &[Book], themes: &[String]) -> Vec<ThemeCoverage> {
+    themes
+        .iter()
+        .filter(|theme| !theme.trim().is_empty())
+        .map(|theme| {
+            let terms = parse_query(theme);
+            let isbns: Vec<String> = books
+                .iter()
+                .filter(|book| matches_query(book, &terms))
+                .map(|book| book.isbn.clone())
+                .collect();
+            ThemeCoverage {
+                theme: theme.clone(),
+                count: isbns.len(),
+                isbns,
+            }
+        })
+        .collect()
+}
+
+/// ある本の説明文が別の本のタイトルに言及していることを表す
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CrossReference {
+    /// 言及している本のISBN
+    pub from: String,
+    /// 言及されている本のISBN
+    pub to: String,
+}
+
+/// 各本の説明文から、他の本のタイトルへの言及を探す
+pub fn cross_references(books: &[Book]) -> Vec<CrossReference> {
+    let mut references = Vec::new();
+    for book in books {
+        let description = normalizer().normalize(&book.description);
+        for other in books {
+            if other.isbn == book.isbn {
+                continue;
+            }
+            let title = normalizer().normalize(other.title.trim());
+            if !title.is_empty() && description.contains(&title) {
+                references.push(CrossReference {
+                    from: book.isbn.clone(),
+                    to: other.isbn.clone(),
+                });
+            }
+        }
+    }
+    references
+}
+
+/// 著者とテーマのグラフでの、1人の著者のつながり
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuthorCentrality {
+    pub author: String,
+    /// テーマ（タグ）を共有する本を書いた他の著者（名前順）
+    pub connected_authors: Vec<String>,
+    /// 他の著者の本にも付いている、この著者の本のテーマ（名前順）
+    pub shared_themes: Vec<String>,
+}
+
+/// 著者ごとに、テーマを共有する他の著者とそのテーマを求め、つながりの多い順に並べる
+///
+/// つながった著者の数が多い順、同数の場合は共有するテーマの数が多い順、さらに同数なら名前順。
+pub fn author_centrality(books: &[Book]) -> Vec<AuthorCentrality> {
+    let mut themes: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for book in books {
+        themes.entry(book.author.as_str()).or_default().extend(book.tags.iter().map(String::as_str));
+    }
+    let mut centrality: Vec<AuthorCentrality> = themes
+        .iter()
+        .map(|(author, tags)| {
+            let mut connected_authors = Vec::new();
+            let mut shared_themes = BTreeSet::new();
+            for (other, other_tags) in &themes {
+                if other == author {
+                    continue;
+                }
+                let mut shared = tags.intersection(other_tags).peekable();
+                if shared.peek().is_some() {
+                    connected_authors.push(other.to_string());
+                    shared_themes.extend(shared);
+                }
+            }
+            AuthorCentrality {
+                author: author.to_string(),
+                connected_authors,
+                shared_themes: shared_themes.into_iter().map(str::to_string).collect(),
+            }
+        })
+        .collect();
+    centrality.sort_by(|a, b| {
+        b.connected_authors
+            .len()
+            .cmp(&a.connected_authors.len())
+            .then(b.shared_themes.len().cmp(&a.shared_themes.len()))
+    });
+    centrality
+}
+
+/// タグごとの本の数を、多い順（同数の場合は名前順）に並べる
+pub fn tag_counts(books: &[Book]) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for book in books {
+        for tag in &book.tags {
+            *counts.entry(tag.clone()).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1));
+    counts
+}
+
+/// 1人の著者の本の数
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct AuthorCount {
+    pub author: String,
+    pub count: usize,
+}
+
+/// 出版年の区間（`from` 年から `to` 年まで）に含まれる本の数
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct YearBucket {
+    pub from: i32,
+    pub to: i32,
+    pub count: usize,
+}
+
+/// 蔵書全体の統計
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CatalogStats {
+    pub total_books: usize,
+    /// カタログをJSONにしたときのバイト数
+    pub total_bytes: usize,
+    /// 著者ごとの本の数（多い順、同数の場合は名前順）
+    pub authors: Vec<AuthorCount>,
+    /// 10年ごとの出版年の分布（古い順）
+    pub decades: Vec<YearBucket>,
+    /// 100年ごとの出版年の分布（古い順）
+    pub centuries: Vec<YearBucket>,
+    /// タグごとの本の数（`tag_counts` と同じ順）
+    pub tags: Vec<(String, usize)>,
+    /// 説明文の平均の長さ（文字数。本がなければ 0.0）
+    pub average_description_chars: f64,
+}
+
+/// 出版年を `width` 年ごとの区間に分けて数える（紀元前の年も区間の幅を揃える）
+fn year_histogram(books: &[Book], width: i32) -> Vec<YearBucket> {
+    let mut counts: BTreeMap<i32, usize> = BTreeMap::new();
+    for book in books {
+        *counts.entry(book.year.div_euclid(width) * width).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(from, count)| YearBucket {
+            from,
+            to: from + width - 1,
+            count,
+        })
+        .collect()
+}
+
+/// 著者・出版年・タグの分布と、説明文の長さ・全体の大きさを集計する
+pub fn catalog_stats(books: &[Book]) -> CatalogStats {
+    let mut authors: BTreeMap<&str, usize> = BTreeMap::new();
+    for book in books {
+        *authors.entry(book.author.as_str()).or_default() += 1;
+    }
+    let mut authors: Vec<AuthorCount> = authors
+        .into_iter()
+        .map(|(author, count)| AuthorCount {
+            author: author.to_string(),
+            count,
+        })
+        .collect();
+    authors.sort_by(|a, b| b.count.cmp(&a.count));
+
+    let description_chars: usize = books.iter().map(|book| book.description.chars().count()).sum();
+    CatalogStats {
+        total_books: books.len(),
+        total_bytes: serde_json::to_vec(books).map_or(0, |json| json.len()),
+        authors,
+        decades: year_histogram(books, 10),
+        centuries: year_histogram(books, 100),
+        tags: tag_counts(books),
+        average_description_chars: if books.is_empty() {
+            0.0
+        } else {
+            description_chars as f64 / books.len() as f64
+        },
+    }
+}
+
+/// `recommend_similar` で件数が指定されなかった場合に返す本の数
+pub const DEFAULT_RECOMMENDATIONS: usize = 3;
+
+/// ある本に似ている本と、その類似度
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarBook<'a> {
+    pub book: &'a Book,
+    /// 0.0〜1.0 の類似度（タグの重なりと説明文の類似度の平均）
+    pub score: f64,
+    /// 基準の本と共通するタグ
+    pub shared_tags: Vec<String>,
+}
+
+/// 基準の本に似ている本を類似度の高い順に最大 `limit` 冊返す
+///
+/// 類似度はタグのJaccard係数と、説明文の文字bigramのTF-IDFベクトルのコサイン類似度の平均。
+/// 日本語の説明文は単語に区切られていないため、単語ではなく文字bigramを使う。
+/// 基準の本が見つからない場合は `None` を返す。
+pub fn similar_books<'a>(books: &'a [Book], isbn: &str, limit: usize) -> Option<Vec<SimilarBook<'a>>> {
+    let target = books.iter().position(|book| book.isbn == isbn)?;
+    let vectors = tfidf_vectors(books);
+    let tags: Vec<HashSet<String>> = books
+        .iter()
+        .map(|book| book.tags.iter().map(|tag| normalizer().normalize(tag)).collect())
+        .collect();
+
+    let mut similar: Vec<SimilarBook> = books
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != target)
+        .map(|(i, book)| {
+            let shared: HashSet<&String> = tags[target].intersection(&tags[i]).collect();
+            let union = tags[target].union(&tags[i]).count();
+            let tag_score = if union == 0 { 0.0 } else { shared.len() as f64 / union as f64 };
+            let text_score = cosine(&vectors[target], &vectors[i]);
+            SimilarBook {
+                book,
+                score: (tag_score + text_score) / 2.0,
+                shared_tags: book
+                    .tags
+                    .iter()
+                    .filter(|tag| shared.contains(&normalizer().normalize(tag)))
+                    .cloned()
+                    .collect(),
+            }
+        })
+        .filter(|similar| similar.score > 0.0)
+        .collect();
+    similar.sort_by(|a, b| b.score.total_cmp(&a.score));
+    similar.truncate(limit);
+    Some(similar)
+}
+
+/// `find_duplicates` でしきい値が指定されなかった場合に使う類似度
+pub const DEFAULT_DUPLICATE_THRESHOLD: f64 = 0.85;
+
+/// 重複と判断した理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// 正規化したISBNが同じ（ISBN-10とISBN-13の表記違いなど）
+    SameIsbn,
+    /// タイトルと著者がどちらも似ている
+    SimilarTitleAuthor,
+}
+
+/// 重複の疑いがある本の組
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicatePair {
+    pub isbns: [String; 2],
+    pub reason: DuplicateReason,
+    /// 0.0〜1.0 の類似度（タイトルと著者の類似度の低い方。ISBNが同じ場合は 1.0）
+    pub score: f64,
+}
+
+/// ISBN-13に揃えたISBN（ISBNとして解釈できなければ表記だけを正規化する）
+fn canonical_isbn(raw: &str) -> String {
+    Isbn::parse(raw).map(|isbn| isbn.to_isbn13()).unwrap_or_else(|_| isbn::normalize(raw))
+}
+
+/// 正規化した2つの文字列の編集距離から 0.0〜1.0 の類似度を求める
+fn text_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = normalizer().normalize(a.trim()).chars().collect();
+    let b: Vec<char> = normalizer().normalize(b.trim()).chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+/// 重複の疑いがある本の組を、類似度の高い順に返す
+///
+/// ISBNが同じ組は常に含め、そうでない組はタイトルと著者の類似度がどちらも `threshold` 以上のものを含める。
+pub fn find_duplicates(books: &[Book], threshold: f64) -> Vec<DuplicatePair> {
+    let isbns: Vec<String> = books.iter().map(|book| canonical_isbn(&book.isbn)).collect();
+    let mut pairs = Vec::new();
+    for (i, book) in books.iter().enumerate() {
+        for (j, other) in books.iter().enumerate().skip(i + 1) {
+            let (reason, score) = if isbns[i] == isbns[j] {
+                (DuplicateReason::SameIsbn, 1.0)
+            } else {
+                let score = text_similarity(&book.title, &other.title).min(text_similarity(&book.author, &other.author));
+                (DuplicateReason::SimilarTitleAuthor, score)
+            };
+            if reason == DuplicateReason::SameIsbn || score >= threshold {
+                pairs.push(DuplicatePair {
+                    isbns: [book.isbn.clone(), other.isbn.clone()],
+                    reason,
+                    score,
+                });
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.score.total_cmp(&a.score));
+    pairs
+}
+
+/// 各本の説明文の文字bigramをTF-IDFで重み付けしたベクトル
+fn tfidf_vectors(books: &[Book]) -> Vec<HashMap<String, f64>> {
+    let counts: Vec<HashMap<String, usize>> = books
+        .iter()
+        .map(|book| {
+            let chars: Vec<char> = normalizer()
+                .normalize(&book.description)
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            let mut counts = HashMap::new();
+            for pair in chars.windows(2) {
+                *counts.entry(pair.iter().collect::<String>()).or_default() += 1;
+            }
+            counts
+        })
+        .collect();
+
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for terms in &counts {
+        for term in terms.keys() {
+            *document_frequency.entry(term.as_str()).or_default() += 1;
+        }
+    }
+
+    let total = books.len() as f64;
+    counts
+        .iter()
+        .map(|terms| {
+            terms
+                .iter()
+                .map(|(term, count)| {
+                    let idf = (total / document_frequency[term.as_str()] as f64).ln() + 1.0;
+                    (term.clone(), *count as f64 * idf)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn cosine(a: &HashMap<String, f64>, b: &HashMap<String, f64>) -> f64 {
+    let dot: f64 = a.iter().filter_map(|(term, x)| b.get(term).map(|y| x * y)).sum();
+    let norm = |v: &HashMap<String, f64>| v.values().map(|x| x * x).sum::<f64>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 { 0.0 } else { dot / denominator }
+}
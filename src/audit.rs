@@ -0,0 +1,161 @@
+//! カタログを変更したツール呼び出しの監査ログ
+//!
+//! 1回の呼び出しを1行のJSONとしてファイルの末尾に追記していく。ファイルを指定しない場合はメモリ上にだけ保持する。
+//! メモリ上には新しい方から決まった数の記録だけを残す。
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// メモリ上に残す記録の数の既定値
+pub const DEFAULT_AUDIT_CAPACITY: usize = 10_000;
+
+/// 監査ログに記録するツール（カタログや貸し出し状況を変更するもの）
+pub const AUDITED_TOOLS: &[&str] = &[
+    "add_book",
+    "update_book",
+    "delete_book",
+    "restore_book",
+    "purge_trash",
+    "bulk_update_books",
+    "import_books",
+    "generate_fake_books",
+    "add_review",
+    "checkout_book",
+    "return_book",
+    "restore_snapshot",
+    "merge_books",
+    "update_author",
+    "create_reading_list",
+    "add_to_reading_list",
+    "remove_from_reading_list",
+    "undo_last_change",
+    "redo",
+    "create_catalog",
+];
+
+/// 1回のツール呼び出しの記録
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// 呼び出したセッションの番号（プロセスの起動ごとに1から振り直す）
+    pub session: u64,
+    pub tool: String,
+    /// 引数のJSONのSHA-256（16進数）
+    pub digest: String,
+    /// `"ok"` / `"tool_error"` / `"error"`
+    pub outcome: String,
+}
+
+/// ツールの引数から監査ログに残すダイジェストを求める
+pub fn digest(arguments: &serde_json::Value) -> String {
+    let bytes = serde_json::to_vec(arguments).unwrap_or_default();
+    Sha256::digest(&bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Debug, Default)]
+struct AuditState {
+    entries: VecDeque<AuditEntry>,
+    file: Option<File>,
+}
+
+impl AuditState {
+    /// 記録を追加し、`capacity` を超えた古い記録を捨てる
+    fn push(&mut self, entry: AuditEntry, capacity: usize) {
+        self.entries.push_back(entry);
+        while self.entries.len() > capacity {
+            self.entries.pop_front();
+        }
+    }
+}
+
+/// 全セッションで共有する監査ログ
+#[derive(Debug)]
+pub struct AuditLog {
+    capacity: usize,
+    state: Mutex<AuditState>,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_AUDIT_CAPACITY)
+    }
+}
+
+impl AuditLog {
+    /// メモリ上にだけ、新しい方から `capacity` 件までの記録を保持する
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::default(),
+        }
+    }
+
+    /// JSONLファイルを開き、既存の記録を読み込んだ上で追記できるようにする
+    ///
+    /// ファイルには全ての記録を残し、メモリ上には新しい方から `capacity` 件までを読み込む。
+    pub fn open(path: &Path, capacity: usize) -> Result<Self> {
+        let mut state = AuditState::default();
+        if path.exists() {
+            let file = File::open(path)
+                .with_context(|| format!("failed to open audit log {}", path.display()))?;
+            for (number, line) in BufReader::new(file).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry = serde_json::from_str(&line).with_context(|| {
+                    format!("invalid audit entry at {}:{}", path.display(), number + 1)
+                })?;
+                state.push(entry, capacity);
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open audit log {}", path.display()))?;
+
+        state.file = Some(file);
+
+        Ok(Self {
+            capacity,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// 記録を追加する（ファイルへの書き込みに失敗してもメモリ上には残す）
+    pub fn record(&self, entry: AuditEntry) {
+        let mut state = self.state.lock().expect("audit log lock poisoned");
+        if let Some(file) = state.file.as_mut() {
+            let written = serde_json::to_string(&entry)
+                .map_err(std::io::Error::from)
+                .and_then(|line| writeln!(file, "{}", line));
+            if let Err(e) = written {
+                tracing::error!("Failed to write audit entry: {}", e);
+            }
+        }
+        state.push(entry, self.capacity);
+    }
+
+    /// `since` 以降 `until` 以前の記録を古い順に返す（メモリ上に残っているものだけ）
+    pub fn entries(&self, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> Vec<AuditEntry> {
+        let state = self.state.lock().expect("audit log lock poisoned");
+        state
+            .entries
+            .iter()
+            .filter(|entry| since.is_none_or(|since| entry.timestamp >= since))
+            .filter(|entry| until.is_none_or(|until| entry.timestamp <= until))
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,178 @@
+//! ネットワーク越しのトランスポートでのベアラートークン認証
+//!
+//! MCPのセッションを確立する前に `Authorization: Bearer ...` ヘッダーを検証し、
+//! 正しくなければ 401 を返す。stdio では使わない。
+//!
+//! トークンごとに呼び出せるツールを限れる（[`ToolPolicy`]）。JWTの場合は `tools` クレームで指定する。
+
+use anyhow::Result;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::config::AuthConfig;
+
+/// 認証したトークンに許可するツール
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ToolPolicy {
+    /// 全てのツール（stdio の接続や、ツールを限らないトークン）
+    #[default]
+    All,
+    /// 名前を挙げたツールだけ
+    Only(BTreeSet<String>),
+}
+
+impl ToolPolicy {
+    /// ツール名の一覧から作る（`"*"` を含めば全てのツールを許可する）
+    pub fn from_tools<S: AsRef<str>>(tools: &[S]) -> Self {
+        if tools.iter().any(|tool| tool.as_ref() == "*") {
+            Self::All
+        } else {
+            Self::Only(tools.iter().map(|tool| tool.as_ref().to_string()).collect())
+        }
+    }
+
+    pub fn allows(&self, tool: &str) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(tools) => tools.contains(tool),
+        }
+    }
+}
+
+/// ヘッダーを拒否した理由（ログに残す）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// `Authorization` ヘッダーがない
+    Missing,
+    /// `Bearer` 形式ではない
+    Malformed,
+    /// トークンが一致しない、またはJWTの検証に失敗した
+    Invalid(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing Authorization header"),
+            Self::Malformed => write!(f, "Authorization header is not a bearer token"),
+            Self::Invalid(reason) => write!(f, "invalid bearer token: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+enum Verifier {
+    /// 固定のトークンと、それぞれに許可するツール
+    Tokens(Vec<(String, ToolPolicy)>),
+    Jwt {
+        key: DecodingKey,
+        validation: Box<Validation>,
+    },
+}
+
+/// 設定されたトークンまたはJWTの鍵でヘッダーを検証する
+pub struct Authenticator {
+    verifier: Verifier,
+}
+
+impl fmt::Debug for Authenticator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.verifier {
+            Verifier::Tokens(_) => "token",
+            Verifier::Jwt { .. } => "jwt",
+        };
+        f.debug_struct("Authenticator").field("kind", &kind).finish()
+    }
+}
+
+/// 長さ以外から一致した位置が分からないように、全バイトを比べる
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl Authenticator {
+    /// 設定から検証方法を作る（トークンとJWTの鍵の両方を指定した場合や、どちらもなければエラー）
+    pub fn from_config(config: &AuthConfig) -> Result<Self> {
+        let has_tokens = config.token.is_some() || !config.tokens.is_empty();
+        let verifier = match &config.jwt_secret {
+            None if has_tokens => {
+                let tokens = config
+                    .token
+                    .iter()
+                    .map(|token| (token.clone(), ToolPolicy::All))
+                    .chain(config.tokens.iter().map(|token| (token.token.clone(), ToolPolicy::from_tools(&token.tools))))
+                    .collect();
+                Verifier::Tokens(tokens)
+            }
+            Some(secret) if !has_tokens => Verifier::Jwt {
+                key: DecodingKey::from_secret(secret.as_bytes()),
+                validation: Box::new(Validation::new(Algorithm::HS256)),
+            },
+            _ => anyhow::bail!("auth requires either auth.token / auth.tokens or auth.jwt_secret"),
+        };
+        Ok(Self { verifier })
+    }
+
+    /// `Authorization` ヘッダーの値を検証し、そのトークンに許可するツールを返す
+    pub fn verify(&self, authorization: Option<&str>) -> Result<ToolPolicy, AuthError> {
+        let header = authorization.ok_or(AuthError::Missing)?;
+        let token = header
+            .strip_prefix("Bearer ")
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or(AuthError::Malformed)?;
+
+        match &self.verifier {
+            Verifier::Tokens(tokens) => tokens
+                .iter()
+                .find(|(expected, _)| constant_time_eq(token.as_bytes(), expected.as_bytes()))
+                .map(|(_, policy)| policy.clone())
+                .ok_or_else(|| AuthError::Invalid("token mismatch".to_string())),
+            Verifier::Jwt { key, validation } => {
+                let claims = jsonwebtoken::decode::<serde_json::Value>(token, key, validation)
+                    .map_err(|e| AuthError::Invalid(e.to_string()))?
+                    .claims;
+                // `tools` クレームがなければツールを限らない
+                match claims.get("tools") {
+                    None => Ok(ToolPolicy::All),
+                    Some(tools) => serde_json::from_value::<Vec<String>>(tools.clone())
+                        .map(|tools| ToolPolicy::from_tools(&tools))
+                        .map_err(|_| AuthError::Invalid("tools claim must be an array of tool names".to_string())),
+                }
+            }
+        }
+    }
+}
+
+/// axum のルーターに、認証を通ったリクエストだけを通すミドルウェアを付ける
+///
+/// 認証したトークンの [`ToolPolicy`] はリクエストの extensions に入れ、ツールの呼び出し時に参照する。
+#[cfg(any(feature = "sse", feature = "streamable-http"))]
+pub(crate) fn require_bearer(router: axum::Router, auth: std::sync::Arc<Authenticator>) -> axum::Router {
+    use axum::extract::Request;
+    use axum::http::{StatusCode, header};
+    use axum::middleware::{self, Next};
+    use axum::response::IntoResponse;
+
+    router.layer(middleware::from_fn(move |mut request: Request, next: Next| {
+        let auth = auth.clone();
+        async move {
+            let authorization = request
+                .headers()
+                .get(header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok());
+            match auth.verify(authorization) {
+                Ok(policy) => {
+                    request.extensions_mut().insert(policy);
+                    next.run(request).await
+                }
+                Err(e) => {
+                    tracing::warn!("Rejected unauthenticated request to {}: {}", request.uri(), e);
+                    (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")]).into_response()
+                }
+            }
+        }
+    }))
+}
@@ -0,0 +1,113 @@
+//! 著者の記録と、本の著者名との対応付け
+//!
+//! 本は `author_id` で著者の記録を参照する。記録を参照していない本は、著者名が記録の名前か
+//! 別名に一致すればその著者の本として扱い、どれにも一致しなければ著者名から決まるIDを持つ、
+//! 記録のない著者の本として扱う。記録の名前を変えた場合は、その著者の本の著者名も書き換える。
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::model::{Author, Book};
+use crate::search::collation_key;
+
+/// 表記の揺れ（全角半角・大文字小文字・空白）を無視して著者名を比べるための形
+fn normalize(name: &str) -> String {
+    crate::search::normalizer()
+        .normalize(name)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 記録のない著者のID（表記の揺れを無視した著者名から決まる）
+pub fn author_id(name: &str) -> String {
+    let digest = Sha256::digest(normalize(name).as_bytes());
+    let hex: String = digest.iter().take(6).map(|byte| format!("{:02x}", byte)).collect();
+    format!("author-{}", hex)
+}
+
+/// 著者1人分と、その著者の本
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorEntry {
+    #[serde(flatten)]
+    pub author: Author,
+    /// 保存された記録があるか（`false` の場合は本の著者名から作った仮の記録）
+    pub registered: bool,
+    /// 著者の本のISBN（登録順）
+    pub books: Vec<String>,
+}
+
+/// 全ての著者と本の対応
+#[derive(Debug, Default)]
+pub struct Directory {
+    entries: BTreeMap<String, AuthorEntry>,
+}
+
+impl Directory {
+    /// 保存された著者の記録と本から、著者ごとの本の一覧を作る
+    pub fn build(records: Vec<Author>, books: &[Book]) -> Self {
+        let mut names = HashMap::new();
+        for record in &records {
+            for name in std::iter::once(&record.name).chain(&record.aliases) {
+                names.entry(normalize(name)).or_insert_with(|| record.id.clone());
+            }
+        }
+        let mut entries: BTreeMap<String, AuthorEntry> = records
+            .into_iter()
+            .map(|author| {
+                let entry = AuthorEntry {
+                    author: author.clone(),
+                    registered: true,
+                    books: Vec::new(),
+                };
+                (author.id, entry)
+            })
+            .collect();
+
+        for book in books {
+            let id = match &book.author_id {
+                Some(id) if entries.get(id).is_some_and(|entry| entry.registered) => id.clone(),
+                _ => names
+                    .get(&normalize(&book.author))
+                    .cloned()
+                    .unwrap_or_else(|| author_id(&book.author)),
+            };
+            entries
+                .entry(id.clone())
+                .or_insert_with(|| AuthorEntry {
+                    author: Author {
+                        id,
+                        name: book.author.clone(),
+                        bio: String::new(),
+                        aliases: Vec::new(),
+                    },
+                    registered: false,
+                    books: Vec::new(),
+                })
+                .books
+                .push(book.isbn.clone());
+        }
+        Self { entries }
+    }
+
+    /// IDで著者を探し、なければ名前か別名が一致する著者を探す
+    pub fn find(&self, id_or_name: &str) -> Option<&AuthorEntry> {
+        if let Some(entry) = self.entries.get(id_or_name) {
+            return Some(entry);
+        }
+        let name = normalize(id_or_name);
+        self.entries.values().find(|entry| {
+            std::iter::once(&entry.author.name)
+                .chain(&entry.author.aliases)
+                .any(|candidate| normalize(candidate) == name)
+        })
+    }
+
+    /// 全ての著者を名前の五十音順に返す
+    pub fn sorted(&self) -> Vec<&AuthorEntry> {
+        let mut entries: Vec<&AuthorEntry> = self.entries.values().collect();
+        entries.sort_by_cached_key(|entry| (collation_key(&entry.author.name), entry.author.id.clone()));
+        entries
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use clap::Parser;
+use rmcp::{
+    RoleClient, ServiceExt,
+    model::{CallToolRequestParam, CallToolResult},
+    service::RunningService,
+    transport::TokioChildProcess,
+};
+use serde_json::json;
+use std::io::Write;
+use std::path::PathBuf;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+use tracing_subscriber::{self, EnvFilter};
+
+/// book_server に接続して対話的に検索するMCPクライアント
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// 起動するサーバーの実行ファイル（省略時はこのクライアントと同じディレクトリの book_server）
+    #[arg(long)]
+    server: Option<PathBuf>,
+
+    /// 起動済みのサーバーにSSEで接続する場合のURL（例: http://127.0.0.1:8000/sse）
+    #[arg(long, conflicts_with = "server")]
+    sse: Option<String>,
+
+    /// 1件あたりの最大結果数
+    #[arg(long, default_value_t = 5)]
+    limit: i32,
+}
+
+type Client = RunningService<RoleClient, ()>;
+
+/// サーバーの実行ファイルを子プロセスとして起動し、標準入出力で接続する
+async fn connect_stdio(server: Option<PathBuf>) -> Result<Client> {
+    let path = match server {
+        Some(path) => path,
+        None => std::env::current_exe()?.with_file_name("book_server"),
+    };
+    tracing::info!("Spawning {}", path.display());
+    Ok(().serve(TokioChildProcess::new(&mut Command::new(path))?).await?)
+}
+
+#[cfg(feature = "sse-client")]
+async fn connect_sse(url: &str) -> Result<Client> {
+    use rmcp::transport::SseTransport;
+
+    tracing::info!("Connecting to {}", url);
+    Ok(().serve(SseTransport::start(url).await?).await?)
+}
+
+#[cfg(not(feature = "sse-client"))]
+async fn connect_sse(_url: &str) -> Result<Client> {
+    anyhow::bail!("connecting over SSE requires building with the `sse-client` feature")
+}
+
+/// ツールの結果に含まれるテキストを表示する
+fn print_result(result: &CallToolResult) {
+    for content in &result.content {
+        match content.raw.as_text() {
+            Some(text) => println!("{}", text.text),
+            None => println!("{:?}", content.raw),
+        }
+    }
+    if result.is_error == Some(true) {
+        eprintln!("(ツールがエラーを返しました)");
+    }
+}
+
+async fn list_tools(client: &Client) -> Result<()> {
+    for tool in client.list_all_tools().await? {
+        println!("{}: {}", tool.name, tool.description);
+    }
+    Ok(())
+}
+
+async fn search(client: &Client, keyword: &str, limit: i32) -> Result<()> {
+    let result = client
+        .call_tool(CallToolRequestParam {
+            name: "search".into(),
+            arguments: json!({
+                "keyword": keyword,
+                "limit": limit,
+            })
+            .as_object()
+            .cloned(),
+        })
+        .await?;
+    print_result(&result);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+    .with_env_filter(EnvFilter::from_default_env().add_directive(tracing::Level::INFO.into()))
+    .with_writer(std::io::stderr)
+    .with_ansi(false)
+    .init();
+
+    let cli = Cli::parse();
+    let client = match &cli.sse {
+        Some(url) => connect_sse(url).await?,
+        None => connect_stdio(cli.server).await?,
+    };
+
+    if let Some(info) = client.peer_info() {
+        tracing::info!("Connected to {} {}", info.server_info.name, info.server_info.version);
+    }
+    list_tools(&client).await?;
+
+    println!("キーワードを入力してください（:tools でツール一覧、:quit で終了）");
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        match line.trim() {
+            "" => continue,
+            ":quit" | ":q" => break,
+            ":tools" => list_tools(&client).await?,
+            keyword => {
+                if let Err(e) = search(&client, keyword, cli.limit).await {
+                    eprintln!("検索に失敗しました: {:?}", e);
+                }
+            }
+        }
+    }
+
+    client.cancel().await?;
+    Ok(())
+}
@@ -1,213 +1,223 @@
 use anyhow::Result;
-use rmcp::{ServiceExt, transport::stdio};
-use serde_json::json;
-
-use tracing_subscriber::{self, EnvFilter};
-use std::sync::OnceLock;
-
-use rmcp::{
-    Error as McpError, RoleServer, ServerHandler, model::*,
-    service::RequestContext, tool,
-};
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-pub struct Book {
-    #[schemars(description = "本のタイトル")]
-    pub title: String,
-    #[schemars(description = "著者名")]
-    pub author: String,
-    #[schemars(description = "出版年（架空）")]
-    pub year: i32,
-    #[schemars(description = "本の説明")]
-    pub description: String,
-    #[schemars(description = "架空のISBN")]
-    pub isbn: String,
+use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rust_mcp::index::{self, RankedIndex};
+use rust_mcp::BookStore;
+use rust_mcp::audit::AuditLog;
+use rust_mcp::catalog::Catalogs;
+use rust_mcp::auth::Authenticator;
+use rust_mcp::config::{AuthConfig, Capability, EmbedderKind, SemanticConfig};
+use rust_mcp::events::CatalogEvents;
+use rust_mcp::{BookSearch, ServerConfig, Transport, import, logging, store, synthetic, transport};
+
+/// 架空の本を検索するMCPサーバー
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// サーバーの設定ファイル（TOML）。コマンドラインの指定はファイルの値より優先される
+    #[arg(long, env = "BOOK_SERVER_CONFIG", conflicts_with = "container")]
+    config: Option<PathBuf>,
+
+    /// 設定ファイルを使わず、全ての設定を環境変数から読むコンテナ向けのモード
+    /// （`MCP_TRANSPORT`・`PORT`・`BOOK_DB_PATH`・`MCP_AUTH_TOKEN`・`LOG_FORMAT` など。
+    /// 既定では 0.0.0.0:8000 の Streamable HTTP で待ち受け、JSONのログを標準出力へ書く）
+    #[arg(long, env = "BOOK_SERVER_CONTAINER")]
+    container: bool,
+
+    /// 本を保存するSQLiteデータベース、または `.json` のファイル（省略時はメモリ上に保持する）
+    #[arg(long, env = "BOOK_DB_PATH")]
+    db: Option<PathBuf>,
+
+    /// 使用するトランスポート
+    #[arg(long, value_enum)]
+    transport: Option<Transport>,
+
+    /// sse / streamable-http / ws で待ち受けるアドレス（既定: 127.0.0.1:8000）
+    #[arg(long)]
+    listen: Option<SocketAddr>,
+
+    /// 起動時にストアへ取り込むJSONまたはCSVファイル
+    #[arg(long)]
+    books: Option<PathBuf>,
+
+    /// 起動時にストアへ追加する、生成した架空の本の冊数
+    #[arg(long)]
+    seed_count: Option<usize>,
+
+    /// `--seed-count` で本を生成するときの乱数のシード
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// 設定ファイルで無効にした機能を有効にする（複数指定可）
+    #[arg(long, value_enum)]
+    enable: Vec<Capability>,
+
+    /// 公開しない機能（複数指定可。例: `--disable prompts --disable resources` でツールだけのサーバーになる）
+    #[arg(long, value_enum)]
+    disable: Vec<Capability>,
+
+    /// カタログやサーバー側のファイルを変更するツールを拒否する
+    #[arg(long)]
+    read_only: bool,
+
+    /// ネットワーク越しの接続に要求するベアラートークン（設定ファイルの `[auth]` より優先される）
+    #[arg(long, env = "MCP_AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
 }
 
-#[derive(Debug, Deserialize, JsonSchema)]
-pub struct SearchQuery {
-    #[schemars(description = "検索キーワード")]
-    pub keyword: String,
-    #[schemars(description = "最大結果数")]
-    pub limit: Option<i32>,
-}
-
-#[derive(Debug, Clone)]
-pub struct BookSearch;
-
-static FAKE_BOOKS: OnceLock<Vec<Book>> = OnceLock::new();
-
-fn get_fake_books() -> &'static [Book] {
-    FAKE_BOOKS.get_or_init(|| {
-        vec![
-            Book {
-                title: "量子コンピュータで料理する方法".to_string(),
-                author: "Dr. スーパーサイエンティスト".to_string(),
-                year: 2157,
-                description: "量子コンピュータを使用して、分子レベルで料理を再構築する革新的な方法を解説".to_string(),
-                isbn: "978-0-123456-47-11".to_string(),
-            },
-            Book {
-                title: "タイムトラベルと税金対策".to_string(),
-                author: "未来の会計士".to_string(),
-                year: 3000,
-                description: "タイムトラベルを活用した効率的な税金対策を解説".to_string(),
-                isbn: "978-0-123456-47-12".to_string(),
-            },
-            Book {
-                title: "火星での園芸入門".to_string(),
-                author: "火星の園芸家".to_string(),
-                year: 2250,
-                description: "火星の特殊な環境で植物を育てる方法を解説。".to_string(),
-                isbn: "978-0-123456-47-13".to_string(),
-            },
-            Book {
-                title: "AIと恋愛の心理学".to_string(),
-                author: "ロボット心理学者".to_string(),
-                year: 2200,
-                description: "AIとの恋愛関係における心理学的な考察と実践的なアドバイス。".to_string(),
-                isbn: "978-0-123456-47-14".to_string(),
-            },
-            Book {
-                title: "テレパシーでプログラミング".to_string(),
-                author: "サイキックエンジニア".to_string(),
-                year: 2300,
-                description: "テレパシー能力を使用してコードを書く方法を解説。".to_string(),
-                isbn: "978-0-123456-47-15".to_string(),
-            },
-        ]
-    })
-}
-
-#[tool(tool_box)]
-impl BookSearch {
-    pub fn new() -> Self {
-        Self
-    }
-
-    /// 架空の本を検索するツール
-    /// 
-    /// # 引数
-    /// * SearchQuery - 検索クエリを含むリクエスト構造体
-    /// 
-    /// # 戻り値
-    /// * Result<CallToolResult, McpError> - 検索結果
-    #[tool(description = "Search for book in our fictional database")]
-    fn search(&self, #[tool(aggr)] SearchQuery { keyword, limit}: SearchQuery) -> Result<CallToolResult, McpError> {
-        let limit = limit.unwrap_or(5) as usize;
-        let results: Vec<_> = get_fake_books()
-            .iter()
-            .filter(|book| {
-                book.title.to_lowercase().contains(&keyword.to_lowercase()) ||
-                book.author.to_lowercase().contains(&keyword.to_lowercase()) ||
-                book.description.to_lowercase().contains(&keyword.to_lowercase())
-            })
-            .take(limit)
-            .collect();
-
-        let output = if results.is_empty() {
-            format!("キーワード '{}' に一致する本が見つかりませんでした。", keyword)
-        } else {
-            let mut output = format!("キーワード '{}' の検索結果:\n\n", keyword);
-            for book in results {
-                output.push_str(&format!(
-                    "タイトル: {}\n著者: {}\n出版年: {}\nISBN: {}\n説明: {}\n\n",
-                    book.title, book.author, book.year, book.isbn, book.description
-                ));
-            }
-            output
+impl Cli {
+    /// 設定ファイル（コンテナ向けのモードでは環境変数）を読み込み、コマンドラインで指定された値で上書きする
+    fn into_config(self) -> Result<(ServerConfig, StartupBooks)> {
+        let mut config = match &self.config {
+            _ if self.container => ServerConfig::from_env()?,
+            Some(path) => ServerConfig::load(path)?,
+            None => ServerConfig::default(),
         };
-
-        Ok(CallToolResult::success(vec![Content::text(output)]))
-    }
-}
-
-#[tool(tool_box)]
-impl ServerHandler for BookSearch {
-    fn get_info(&self)  -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder()
-                .enable_prompts()
-                .enable_resources()
-                .enable_tools()
-                .build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some("架空の本のデータベースを検索するサーバーです。タイトル、著者、説明文で検索できます。".into()),
+        if let Some(db) = self.db {
+            config.data_file = Some(db);
         }
+        if let Some(transport) = self.transport {
+            config.transport = transport;
+        }
+        if let Some(listen) = self.listen {
+            config.listen = listen;
+        }
+        if self.read_only {
+            config.read_only = true;
+        }
+        for capability in self.enable {
+            config.capabilities.set(capability, true);
+        }
+        for capability in self.disable {
+            config.capabilities.set(capability, false);
+        }
+        if let Some(token) = self.auth_token {
+            config.auth = Some(AuthConfig {
+                token: Some(token),
+                ..AuthConfig::default()
+            });
+        }
+        let startup = StartupBooks {
+            file: self.books,
+            generated: self.seed_count.map(|count| (count, self.seed)),
+        };
+        Ok((config, startup))
     }
+}
 
-    async fn list_resources(
-        &self,
-        _request: PaginatedRequestParam,
-        _: RequestContext<RoleServer>,
-    ) -> Result<ListResourcesResult, McpError> {
-        Ok(ListResourcesResult {
-            resources: vec![],
-            next_cursor: None,
-        })
-    }
-
-    async fn read_resource(
-        &self,
-        ReadResourceRequestParam { uri }: ReadResourceRequestParam,
-        _: RequestContext<RoleServer>,
-    ) -> Result<ReadResourceResult, McpError> {
-        Err(McpError::resource_not_found(
-            "resource_not_found",
-            Some(json!({
-                "uri": uri
-            }))
-        ))
-    }
-
-    async fn list_prompts(
-        &self,
-        _request: PaginatedRequestParam,
-        _: RequestContext<RoleServer>,
-    ) -> Result<ListPromptsResult, McpError> {
-        Ok(ListPromptsResult {
-            next_cursor: None,
-            prompts: vec![],
-        })
-    }
-
-    async fn get_prompt(
-        &self,
-        GetPromptRequestParam { name, arguments: _}: GetPromptRequestParam,
-        _: RequestContext<RoleServer>,
-    ) -> Result<GetPromptResult, McpError> {
-        Err(McpError::invalid_params("prompt not found", None))
-    }
+/// 起動時にストアへ追加する本
+struct StartupBooks {
+    /// 取り込むJSONまたはCSVファイル
+    file: Option<PathBuf>,
+    /// 生成する冊数とシード
+    generated: Option<(usize, u64)>,
+}
 
-    async fn list_resource_templates(
-        &self,
-        _request: PaginatedRequestParam,
-        _: RequestContext<RoleServer>
-    ) -> Result<ListResourceTemplatesResult, McpError> {
-        Ok(ListResourceTemplatesResult {
-            next_cursor: None,
-            resource_templates: Vec::new(),
-        })
-    }
+/// 設定に従って意味検索インデックスを作る
+fn semantic_index(config: &SemanticConfig) -> Result<Arc<dyn RankedIndex>> {
+    let embedder: Arc<dyn index::Embedder> = match config.embedder {
+        EmbedderKind::Local => Arc::new(index::HashingEmbedder),
+        #[cfg(feature = "embeddings-api")]
+        EmbedderKind::Api => {
+            let api_key = config.api_key_env.as_deref().and_then(|name| std::env::var(name).ok());
+            // 設定の検証で api_url と model があることを確かめている
+            let (url, model) = (config.api_url.clone().unwrap_or_default(), config.model.clone().unwrap_or_default());
+            Arc::new(index::ApiEmbedder::new(url, model, api_key)?)
+        }
+        #[cfg(not(feature = "embeddings-api"))]
+        EmbedderKind::Api => anyhow::bail!("semantic.embedder = \"api\" requires building with the `embeddings-api` feature"),
+    };
+    Ok(Arc::new(index::SemanticIndex::new(embedder)))
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-    .with_env_filter(EnvFilter::from_default_env().add_directive(tracing::Level::DEBUG.into()))
-    .with_writer(std::io::stderr)
-    .with_ansi(false)
-    .init();
+    let (config, startup) = Cli::parse().into_config()?;
+    let telemetry = logging::init(&config)?;
 
     tracing::info!("Starting MCP book search server");
 
-    let service = BookSearch::new().serve(stdio()).await.inspect_err(|e| {
-        tracing::error!("servign error: {:?}", e);
-    })?;
+    // JSONのデータファイルは、書き換えを監視するために具体的なストアのまま持っておく
+    let json_store = match config.data_file.as_deref().filter(|path| store::is_json_file(path)) {
+        Some(path) => Some(store::open_json(path).await?),
+        None => None,
+    };
+    let store: Arc<dyn BookStore> = match (&json_store, &config.database) {
+        (Some(json_store), _) => json_store.clone(),
+        (None, Some(database)) => store::connect(database).await?,
+        (None, None) => store::open(config.data_file.as_deref(), config.sqlite_retry).await?,
+    };
+
+    // インデックスを作る前に追加し、まとめて索引させる
+    if let Some((count, seed)) = startup.generated {
+        let report = import::import(store.as_ref(), synthetic::books(count, seed).into_iter().map(Ok).collect()).await?;
+        tracing::info!(
+            "Generated {} books with seed {} ({} already present)",
+            report.imported.len(),
+            seed,
+            report.failed.len()
+        );
+    }
+
+    // ストアへの書き込みに追従させるインデックス（データファイルの読み直しでも更新する）
+    let mut indexes: Vec<Arc<dyn RankedIndex>> = Vec::new();
+
+    #[cfg(feature = "fulltext")]
+    let (store, index) = {
+        let index: Arc<dyn RankedIndex> = Arc::new(index::TantivyIndex::in_memory()?);
+        let store: Arc<dyn BookStore> = Arc::new(index::IndexedStore::new(store, index.clone()).await?);
+        indexes.push(index.clone());
+        (store, index)
+    };
+
+    let (store, semantic) = match &config.semantic {
+        Some(semantic) => {
+            let semantic = semantic_index(semantic)?;
+            let store: Arc<dyn BookStore> = Arc::new(index::IndexedStore::new(store, semantic.clone()).await?);
+            indexes.push(semantic.clone());
+            (store, Some(semantic))
+        }
+        None => (store, None),
+    };
 
-    service.waiting().await?;
-    Ok(())
+    let events = CatalogEvents::new();
+    #[cfg(feature = "hot-reload")]
+    if let Some(json_store) = json_store {
+        rust_mcp::watch::spawn(json_store, events.clone(), indexes)?;
+    }
+    #[cfg(not(feature = "hot-reload"))]
+    let _ = (json_store, indexes);
+
+    if let Some(path) = &startup.file {
+        let report = import::import(store.as_ref(), import::read_file(path)?).await?;
+        tracing::info!("Imported {} books from {}", report.imported.len(), path.display());
+        for failure in &report.failed {
+            tracing::warn!("Skipped row {} of {}: {:?}", failure.row, path.display(), failure.errors);
+        }
+    }
+    let catalogs = Arc::new(Catalogs::default());
+    for (name, path) in &config.catalogs {
+        catalogs.create(name, store::open(Some(path), config.sqlite_retry).await?)?;
+        tracing::info!("Opened catalog {} from {}", name, path.display());
+    }
+    let audit = match &config.audit_file {
+        Some(path) => AuditLog::open(path, config.audit_capacity)?,
+        None => AuditLog::new(config.audit_capacity),
+    };
+    let auth = config.auth.as_ref().map(Authenticator::from_config).transpose()?;
+    let (transport, listen, compression) = (config.transport, config.listen, config.compression);
+    let server = BookSearch::with_events(store, events)
+        .with_config(config)
+        .with_audit(Arc::new(audit))
+        .with_catalogs(catalogs);
+    #[cfg(feature = "fulltext")]
+    let server = server.with_index(index);
+    let server = match semantic {
+        Some(semantic) => server.with_semantic_index(semantic),
+        None => server,
+    };
+    telemetry.finish(transport::serve(transport, server, listen, auth, compression).await)
 }
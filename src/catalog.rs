@@ -0,0 +1,99 @@
+//! 1つのサーバーで扱う名前付きのカタログ（"fiction" "technical" など）
+//!
+//! 名前を指定しないツール呼び出しやリソースは、起動時に開いた既定のカタログを使う。
+//! 名前付きのカタログの本は `book://{catalog}/isbn/{isbn}` のようなURIのリソースになる。
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use crate::resources::RESERVED_SEGMENTS;
+use crate::store::BookStore;
+
+/// 既定のカタログを明示的に指定するときの名前
+pub const DEFAULT_CATALOG: &str = "default";
+
+/// カタログの名前の最大の長さ
+const MAX_NAME_LEN: usize = 64;
+
+/// カタログを作成・選択できなかった理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatalogError {
+    /// 英小文字・数字・`-`・`_` 以外を含む名前や、リソースURIと紛らわしい名前
+    InvalidName(String),
+    /// 同じ名前のカタログが既にある
+    AlreadyExists(String),
+    /// 指定した名前のカタログがない
+    NotFound { name: String, available: Vec<String> },
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "カタログ名 {} は使えません（英小文字・数字・-・_ のみ）", name),
+            Self::AlreadyExists(name) => write!(f, "カタログ {} は既にあります", name),
+            Self::NotFound { name, .. } => write!(f, "カタログ {} が見つかりません", name),
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
+
+/// URIの1つ目の区切りに使えるよう、名前を英小文字・数字・`-`・`_` に限る
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name != DEFAULT_CATALOG
+        && !RESERVED_SEGMENTS.contains(&name)
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// 全セッションで共有する名前付きのカタログ（既定のカタログは含まない）
+#[derive(Default)]
+pub struct Catalogs {
+    stores: RwLock<BTreeMap<String, Arc<dyn BookStore>>>,
+}
+
+impl fmt::Debug for Catalogs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Catalogs").field("names", &self.names()).finish()
+    }
+}
+
+impl Catalogs {
+    pub fn get(&self, name: &str) -> Result<Arc<dyn BookStore>, CatalogError> {
+        let stores = self.stores.read().expect("catalogs lock poisoned");
+        stores.get(name).cloned().ok_or_else(|| CatalogError::NotFound {
+            name: name.to_string(),
+            available: stores.keys().cloned().collect(),
+        })
+    }
+
+    /// 新しいカタログを登録する
+    pub fn create(&self, name: &str, store: Arc<dyn BookStore>) -> Result<(), CatalogError> {
+        if !is_valid_name(name) {
+            return Err(CatalogError::InvalidName(name.to_string()));
+        }
+        let mut stores = self.stores.write().expect("catalogs lock poisoned");
+        if stores.contains_key(name) {
+            return Err(CatalogError::AlreadyExists(name.to_string()));
+        }
+        stores.insert(name.to_string(), store);
+        Ok(())
+    }
+
+    /// 登録されているカタログの名前を名前順に返す
+    pub fn names(&self) -> Vec<String> {
+        self.stores.read().expect("catalogs lock poisoned").keys().cloned().collect()
+    }
+
+    /// 名前とストアの組を名前順に返す
+    pub fn all(&self) -> Vec<(String, Arc<dyn BookStore>)> {
+        self.stores
+            .read()
+            .expect("catalogs lock poisoned")
+            .iter()
+            .map(|(name, store)| (name.clone(), store.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,67 @@
+//! プロンプトの引数とリソーステンプレートの補完候補
+
+use rmcp::model::{ArgumentInfo, CompletionInfo, Reference};
+use std::collections::BTreeSet;
+
+use crate::model::Book;
+use crate::search::normalizer;
+
+/// 1回の補完で返す候補の最大数（MCPの仕様上の上限）
+const MAX_COMPLETIONS: usize = 100;
+
+/// 補完の対象の引数に対応する候補を、登録順に重複なく集める
+fn candidates(reference: &Reference, argument: &str, books: &[Book]) -> Vec<String> {
+    let values: Vec<&str> = match (reference, argument) {
+        (Reference::Prompt(prompt), "genre") if prompt.name == "recommend_book" => {
+            books.iter().flat_map(|book| book.tags.iter().map(String::as_str)).collect()
+        }
+        (Reference::Prompt(prompt), "isbn") if prompt.name == "summarize_book" => {
+            books.iter().map(|book| book.isbn.as_str()).collect()
+        }
+        (Reference::Resource(resource), "author") if resource.uri == "book://author/{author}" => {
+            books.iter().map(|book| book.author.as_str()).collect()
+        }
+        (Reference::Resource(resource), "isbn") if resource.uri == "book://isbn/{isbn}" => {
+            books.iter().map(|book| book.isbn.as_str()).collect()
+        }
+        (Reference::Resource(resource), "isbn") if resource.uri == "book://cover/{isbn}" => books
+            .iter()
+            .filter(|book| book.cover_path.is_some() || book.cover_url.is_some())
+            .map(|book| book.isbn.as_str())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut seen = BTreeSet::new();
+    values
+        .into_iter()
+        .filter(|value| seen.insert(*value))
+        .map(str::to_string)
+        .collect()
+}
+
+/// 入力途中の値に一致する候補を返す
+///
+/// 前方一致する候補を先に、部分一致する候補をその後に並べる。知らない引数には空の候補を返す。
+pub fn complete(reference: &Reference, argument: &ArgumentInfo, books: &[Book]) -> CompletionInfo {
+    let normalizer = normalizer();
+    let typed = normalizer.normalize(argument.value.trim());
+    let (mut prefixed, mut contained) = (Vec::new(), Vec::new());
+    for candidate in candidates(reference, &argument.name, books) {
+        let normalized = normalizer.normalize(&candidate);
+        if normalized.starts_with(&typed) {
+            prefixed.push(candidate);
+        } else if normalized.contains(&typed) {
+            contained.push(candidate);
+        }
+    }
+    prefixed.append(&mut contained);
+
+    let total = prefixed.len();
+    prefixed.truncate(MAX_COMPLETIONS);
+    CompletionInfo {
+        values: prefixed,
+        total: Some(total as u32),
+        has_more: Some(total > MAX_COMPLETIONS),
+    }
+}
@@ -0,0 +1,544 @@
+//! `config.toml` から読み込むサーバーの設定
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use std::path::{Path, PathBuf};
+
+use crate::audit::DEFAULT_AUDIT_CAPACITY;
+use crate::history::DEFAULT_HISTORY_DEPTH;
+use crate::i18n::Lang;
+use crate::limits::{DEFAULT_MAX_INPUT_BYTES, DEFAULT_MAX_OUTPUT_BYTES, ToolLimits};
+use crate::naming::Naming;
+use crate::result_cache::DEFAULT_RESULT_TTL_SECS;
+use crate::search::{DEFAULT_SEARCH_LIMIT, MAX_SEARCH_LIMIT, SearchQuery, check_query};
+use crate::transport::Transport;
+
+/// サーバー全体の設定
+///
+/// 省略した項目には既定値が使われるので、空のファイルでも有効な設定になる。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// 使用するトランスポート
+    pub transport: Transport,
+    /// sse / streamable-http / ws で待ち受けるアドレス
+    pub listen: SocketAddr,
+    /// ログの出力レベル（`RUST_LOG` と同じ書式のディレクティブ）
+    pub log_level: String,
+    /// ログの書式
+    pub log_format: LogFormat,
+    /// ログの出力先（stdio トランスポートでは `stdout` にできない）
+    pub log_target: LogTarget,
+    /// リクエストごとのトレースをOTLPで送るコレクターのURL（`otlp` 機能が必要。省略時は送らない）
+    pub otlp_endpoint: Option<String>,
+    /// 本を保存するSQLiteデータベースファイル。拡張子が `.json` ならJSONファイルに保存する
+    /// （省略時はメモリ上に保持する）
+    pub data_file: Option<PathBuf>,
+    /// SQLiteのストアが、ビジー・ロックのエラーで失敗した操作を繰り返す設定
+    pub sqlite_retry: RetryConfig,
+    /// 本を保存するPostgresのデータベース（`data_file` の代わりに使う。`postgres` 機能が必要）
+    pub database: Option<DatabaseConfig>,
+    /// 起動時に開く名前付きのカタログと、その本を保存するファイル（`data_file` と同じ形式）
+    pub catalogs: BTreeMap<String, PathBuf>,
+    /// 変更操作を追記するJSONLの監査ログ（省略時はメモリ上に保持する）
+    pub audit_file: Option<PathBuf>,
+    /// `get_audit_log` で返せるよう、メモリ上に残す監査ログの記録の数（古いものから捨てる）
+    pub audit_capacity: usize,
+    /// `create_snapshot` がスナップショットを書き出すディレクトリ
+    pub snapshot_dir: PathBuf,
+    /// `limit` を省略した検索で返す件数
+    pub default_search_limit: usize,
+    /// 検索の `limit` に指定できる最大の件数（これを超える値は切り詰める）
+    pub max_search_limit: usize,
+    /// `run_named_query` で名前を指定して実行できる検索クエリ
+    pub named_queries: BTreeMap<String, SearchQuery>,
+    /// 終了時に実行中のツール呼び出しを待つ最大秒数
+    pub shutdown_timeout_secs: u64,
+    /// ツール呼び出し1回の制限時間（秒。0 の場合は制限しない）
+    ///
+    /// 待ちの途中で打ち切れるのは、外部への問い合わせなど非同期に待つツールだけ。
+    pub tool_timeout_secs: u64,
+    /// ツールごとの制限時間（秒）。`tool_timeout_secs` より優先される
+    pub tool_timeouts: BTreeMap<String, u64>,
+    /// セッションごとのツール呼び出しの制限（省略時は制限しない）
+    pub rate_limit: Option<RateLimitConfig>,
+    /// ツール呼び出しの引数と結果の大きさの上限
+    pub limits: LimitsConfig,
+    /// カタログやサーバー側のファイルを変更するツールを拒否する（信頼できないエージェントに公開する場合）
+    pub read_only: bool,
+    /// `read_only` のとき、拒否するツールを `tools/list` にも載せない
+    pub hide_mutating_tools: bool,
+    /// `undo_last_change` で取り消せるよう、カタログごとに記録しておく変更の数（0 の場合は記録しない）
+    pub history_depth: usize,
+    /// `refine_search` で絞り込めるよう、検索結果を保持しておく秒数
+    pub result_ttl_secs: u64,
+    /// クライアントに公開する機能
+    pub capabilities: CapabilitiesConfig,
+    /// ツールの応答の言語（検索の `lang` で1回ごとに上書きできる）
+    pub lang: Lang,
+    /// ツールの結果のJSONの項目名の書き方（`snake_case` または `camelCase`）
+    pub naming: Naming,
+    /// 検索結果で一致箇所を囲む目印
+    pub highlight: HighlightConfig,
+    /// ネットワーク越しのトランスポートで要求する認証（省略時は認証しない）
+    pub auth: Option<AuthConfig>,
+    /// HTTPのトランスポートの応答の圧縮（省略時は圧縮しない。stdio と ws では使わない）
+    pub compression: Option<CompressionConfig>,
+    /// 検索の `semantic` に使う埋め込み（省略時は意味検索を提供しない）
+    pub semantic: Option<SemanticConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            transport: Transport::Stdio,
+            listen: SocketAddr::from(([127, 0, 0, 1], 8000)),
+            log_level: "debug".into(),
+            log_format: LogFormat::Text,
+            log_target: LogTarget::Stderr,
+            otlp_endpoint: None,
+            data_file: None,
+            sqlite_retry: RetryConfig::default(),
+            database: None,
+            catalogs: BTreeMap::new(),
+            audit_file: None,
+            audit_capacity: DEFAULT_AUDIT_CAPACITY,
+            snapshot_dir: PathBuf::from("snapshots"),
+            default_search_limit: DEFAULT_SEARCH_LIMIT,
+            max_search_limit: MAX_SEARCH_LIMIT,
+            named_queries: BTreeMap::new(),
+            shutdown_timeout_secs: 10,
+            tool_timeout_secs: 30,
+            tool_timeouts: BTreeMap::new(),
+            rate_limit: None,
+            limits: LimitsConfig::default(),
+            read_only: false,
+            hide_mutating_tools: false,
+            history_depth: DEFAULT_HISTORY_DEPTH,
+            result_ttl_secs: DEFAULT_RESULT_TTL_SECS,
+            capabilities: CapabilitiesConfig::default(),
+            lang: Lang::Ja,
+            naming: Naming::SnakeCase,
+            highlight: HighlightConfig::default(),
+            auth: None,
+            compression: None,
+            semantic: None,
+        }
+    }
+}
+
+/// `get_info` で公開する機能の有効・無効
+///
+/// 無効にした機能のメソッド（`prompts/list` など）は `method not found` エラーを返す。
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CapabilitiesConfig {
+    pub tools: bool,
+    pub resources: bool,
+    pub prompts: bool,
+    pub logging: bool,
+}
+
+/// `--enable` / `--disable` で切り替えられる機能
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Capability {
+    Tools,
+    Resources,
+    Prompts,
+    Logging,
+}
+
+impl CapabilitiesConfig {
+    pub fn is_enabled(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Tools => self.tools,
+            Capability::Resources => self.resources,
+            Capability::Prompts => self.prompts,
+            Capability::Logging => self.logging,
+        }
+    }
+
+    pub fn set(&mut self, capability: Capability, enabled: bool) {
+        match capability {
+            Capability::Tools => self.tools = enabled,
+            Capability::Resources => self.resources = enabled,
+            Capability::Prompts => self.prompts = enabled,
+            Capability::Logging => self.logging = enabled,
+        }
+    }
+}
+
+impl Default for CapabilitiesConfig {
+    fn default() -> Self {
+        Self {
+            tools: true,
+            resources: true,
+            prompts: true,
+            logging: true,
+        }
+    }
+}
+
+/// 検索の `highlight` で一致箇所の前後に挿入する文字列
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightConfig {
+    pub open: String,
+    pub close: String,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        Self {
+            open: "**".into(),
+            close: "**".into(),
+        }
+    }
+}
+
+/// `Authorization: Bearer ...` ヘッダーの検証方法（固定のトークンかJWTのどちらか一方を指定する）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// 全てのツールを呼び出せる固定のトークン
+    pub token: Option<String>,
+    /// 呼び出せるツールを限った固定のトークン
+    pub tokens: Vec<TokenConfig>,
+    /// HS256 で署名されたJWTを検証する鍵（`tools` クレームで呼び出せるツールを限れる）
+    pub jwt_secret: Option<String>,
+}
+
+/// 呼び出せるツールを限ったトークン
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenConfig {
+    pub token: String,
+    /// 呼び出せるツールの名前（`"*"` で全て）
+    pub tools: Vec<String>,
+}
+
+/// 意味検索で本と検索語をベクトルにする方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbedderKind {
+    /// 文字のN-gramから計算する組み込みの埋め込み（モデルや外部のサービスは不要）
+    #[default]
+    Local,
+    /// OpenAI互換の埋め込みAPI（`embeddings-api` 機能付きでビルドした場合のみ）
+    Api,
+}
+
+/// 意味検索の設定
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SemanticConfig {
+    pub embedder: EmbedderKind,
+    /// `embedder = "api"` のときのエンドポイント（例: `https://api.openai.com/v1/embeddings`）
+    pub api_url: Option<String>,
+    /// `embedder = "api"` のときに指定するモデル名
+    pub model: Option<String>,
+    /// APIキーを読み込む環境変数の名前（設定ファイルにキーを書かないため）
+    pub api_key_env: Option<String>,
+}
+
+/// Postgresのデータベースへの接続
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// 接続先のDSN（例: `postgres://library:secret@db/library`）
+    pub url: String,
+    /// プールしておく接続の最大数
+    #[serde(default = "default_max_connections")]
+    pub max_connections: u32,
+}
+
+fn default_max_connections() -> u32 {
+    10
+}
+
+/// セッションごとのツール呼び出しの制限
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// 連続して受け付ける呼び出しの最大回数
+    pub burst: u32,
+    /// 1秒あたりに回復する呼び出しの回数
+    pub per_second: f64,
+}
+
+/// HTTPの応答を、クライアントの `Accept-Encoding` に応じて gzip / br / deflate で圧縮する設定
+///
+/// SSEのイベントストリームは、イベントが届くのを遅らせないよう圧縮しない。
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    /// これより小さい応答（バイト）は圧縮しない
+    pub min_bytes: u16,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self { min_bytes: 1024 }
+    }
+}
+
+/// 一時的なエラーで失敗したストアの操作を繰り返す設定
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// 最初の失敗のあとに繰り返す最大の回数（0 の場合は繰り返さない）
+    pub max_retries: u32,
+    /// 最初に繰り返すまでの待ち時間（ミリ秒）。繰り返すごとに倍にする
+    pub initial_backoff_ms: u64,
+    /// 待ち時間の上限（ミリ秒）
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 500,
+        }
+    }
+}
+
+/// ツール呼び出しの引数と結果の大きさの上限（バイト）
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// 引数のJSONの大きさの上限
+    pub max_input_bytes: usize,
+    /// 結果のJSONの大きさの上限
+    pub max_output_bytes: usize,
+    /// ツールごとの上限。指定した項目だけ全体の上限より優先される
+    pub tools: BTreeMap<String, ToolLimitsOverride>,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_input_bytes: DEFAULT_MAX_INPUT_BYTES,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            tools: BTreeMap::new(),
+        }
+    }
+}
+
+/// 1つのツールの呼び出しの大きさの上限（省略した項目は全体の上限を使う）
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolLimitsOverride {
+    pub max_input_bytes: Option<usize>,
+    pub max_output_bytes: Option<usize>,
+}
+
+impl LimitsConfig {
+    /// ツール `tool` の呼び出しに使う上限
+    pub fn for_tool(&self, tool: &str) -> ToolLimits {
+        let tool = self.tools.get(tool).copied().unwrap_or_default();
+        ToolLimits {
+            max_input_bytes: tool.max_input_bytes.unwrap_or(self.max_input_bytes),
+            max_output_bytes: tool.max_output_bytes.unwrap_or(self.max_output_bytes),
+        }
+    }
+}
+
+/// ログの書式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 人が読むための1行ごとのテキスト
+    Text,
+    /// 1行に1つのJSONオブジェクト（ログの収集基盤に取り込む場合）
+    Json,
+}
+
+/// ログの出力先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    Stderr,
+    Stdout,
+}
+
+/// 環境変数 `name` を読み、空でなければ `parse` で解釈する
+fn env_var<T>(name: &str, parse: impl FnOnce(&str) -> Result<T>) -> Result<Option<T>> {
+    match std::env::var(name) {
+        Ok(value) if !value.trim().is_empty() => parse(value.trim())
+            .with_context(|| format!("invalid value {:?} for environment variable {}", value, name))
+            .map(Some),
+        Ok(_) | Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to read environment variable {}", name)),
+    }
+}
+
+fn parse_value<T: ValueEnum>(value: &str) -> Result<T> {
+    T::from_str(value, true).map_err(|e| anyhow::anyhow!(e))
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => anyhow::bail!("expected true or false"),
+    }
+}
+
+impl ServerConfig {
+    /// 設定ファイルを使わず、環境変数だけから設定を作る（コンテナで動かす場合）
+    ///
+    /// コンテナの外から接続できるように、既定では `0.0.0.0:8000` の Streamable HTTP で待ち受け、
+    /// JSONのログを標準出力へ書く。読む環境変数は次の通り（省略したものは既定値）。
+    ///
+    /// * `MCP_TRANSPORT` - トランスポート（既定: `streamable-http`）
+    /// * `MCP_HOST` / `PORT` - 待ち受けるアドレスとポート（既定: `0.0.0.0` / `8000`）
+    /// * `BOOK_DB_PATH` - 本を保存するファイル（既定: メモリ上に保持する）
+    /// * `DATABASE_URL` - 本を保存するPostgresのDSN（`BOOK_DB_PATH` の代わりに使う）
+    /// * `BOOK_AUDIT_FILE` / `BOOK_SNAPSHOT_DIR` - 監査ログとスナップショットの保存先
+    /// * `MCP_AUTH_TOKEN` - 要求するベアラートークン（既定: 認証しない）
+    /// * `MCP_READ_ONLY` - `true` でカタログを変更するツールを拒否する
+    /// * `MCP_JSON_NAMING` - ツールの結果のJSONの項目名の書き方（既定: `snake_case`）
+    /// * `MCP_COMPRESSION` - `true` でHTTPの応答を `Accept-Encoding` に応じて圧縮する
+    /// * `MAX_TOOL_INPUT_BYTES` / `MAX_TOOL_OUTPUT_BYTES` - ツールの引数と結果の大きさの上限
+    /// * `LOG_LEVEL` / `LOG_FORMAT` - ログのレベルと書式（既定: `info` / `json`）
+    /// * `OTEL_EXPORTER_OTLP_ENDPOINT` - トレースを送るコレクター（`otlp` 機能が必要）
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self {
+            transport: Transport::StreamableHttp,
+            listen: SocketAddr::from(([0, 0, 0, 0], 8000)),
+            log_level: "info".into(),
+            log_format: LogFormat::Json,
+            log_target: LogTarget::Stdout,
+            ..Self::default()
+        };
+        if let Some(transport) = env_var("MCP_TRANSPORT", parse_value)? {
+            config.transport = transport;
+        }
+        let host = env_var("MCP_HOST", |value| Ok(value.parse::<std::net::IpAddr>()?))?;
+        let port = env_var("PORT", |value| Ok(value.parse::<u16>()?))?;
+        config.listen = SocketAddr::new(host.unwrap_or(config.listen.ip()), port.unwrap_or(config.listen.port()));
+        config.data_file = env_var("BOOK_DB_PATH", |value| Ok(PathBuf::from(value)))?;
+        config.database = env_var("DATABASE_URL", |value| {
+            Ok(DatabaseConfig {
+                url: value.to_string(),
+                max_connections: default_max_connections(),
+            })
+        })?;
+        config.audit_file = env_var("BOOK_AUDIT_FILE", |value| Ok(PathBuf::from(value)))?;
+        if let Some(dir) = env_var("BOOK_SNAPSHOT_DIR", |value| Ok(PathBuf::from(value)))? {
+            config.snapshot_dir = dir;
+        }
+        if let Some(token) = env_var("MCP_AUTH_TOKEN", |value| Ok(value.to_string()))? {
+            config.auth = Some(AuthConfig {
+                token: Some(token),
+                ..AuthConfig::default()
+            });
+        }
+        if let Some(read_only) = env_var("MCP_READ_ONLY", parse_bool)? {
+            config.read_only = read_only;
+        }
+        if let Some(naming) = env_var("MCP_JSON_NAMING", parse_value)? {
+            config.naming = naming;
+        }
+        if let Some(compress) = env_var("MCP_COMPRESSION", parse_bool)? {
+            config.compression = compress.then(CompressionConfig::default);
+        }
+        if let Some(bytes) = env_var("MAX_TOOL_INPUT_BYTES", |value| Ok(value.parse::<usize>()?))? {
+            config.limits.max_input_bytes = bytes;
+        }
+        if let Some(bytes) = env_var("MAX_TOOL_OUTPUT_BYTES", |value| Ok(value.parse::<usize>()?))? {
+            config.limits.max_output_bytes = bytes;
+        }
+        if let Some(level) = env_var("LOG_LEVEL", |value| Ok(value.to_string()))? {
+            config.log_level = level;
+        }
+        if let Some(format) = env_var("LOG_FORMAT", parse_value)? {
+            config.log_format = format;
+        }
+        config.otlp_endpoint = env_var("OTEL_EXPORTER_OTLP_ENDPOINT", |value| Ok(value.to_string()))?;
+        // stdio を選んだ場合、標準出力はMCPの通信路になる
+        if config.transport == Transport::Stdio {
+            config.log_target = LogTarget::Stderr;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// TOMLファイルから設定を読み込んで検証する
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let config: Self = toml::from_str(&text)
+            .with_context(|| format!("failed to parse config file {}", path.display()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// ツール `tool` の呼び出しの制限時間（制限しない場合は `None`）
+    pub fn tool_timeout(&self, tool: &str) -> Option<Duration> {
+        let secs = self.tool_timeouts.get(tool).copied().unwrap_or(self.tool_timeout_secs);
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.audit_capacity == 0 {
+            anyhow::bail!("audit_capacity must be positive");
+        }
+        if self.max_search_limit == 0 {
+            anyhow::bail!("max_search_limit must be positive");
+        }
+        if !(1..=self.max_search_limit).contains(&self.default_search_limit) {
+            anyhow::bail!(
+                "default_search_limit must be between 1 and max_search_limit ({}), got {}",
+                self.max_search_limit,
+                self.default_search_limit
+            );
+        }
+        for (name, query) in &self.named_queries {
+            if let Some(problem) = check_query(query).into_iter().next() {
+                anyhow::bail!("named_queries.{} is invalid: {}", name, problem.error.message);
+            }
+        }
+        let invalid_rate_limit = |limit: &RateLimitConfig| limit.burst == 0 || limit.per_second.is_nan() || limit.per_second <= 0.0;
+        if self.rate_limit.as_ref().is_some_and(invalid_rate_limit) {
+            anyhow::bail!("rate_limit.burst and rate_limit.per_second must be positive");
+        }
+        if self.limits.max_input_bytes == 0 || self.limits.max_output_bytes == 0 {
+            anyhow::bail!("limits.max_input_bytes and limits.max_output_bytes must be positive");
+        }
+        let zero_limit = |limits: &ToolLimitsOverride| limits.max_input_bytes == Some(0) || limits.max_output_bytes == Some(0);
+        if let Some(tool) = self.limits.tools.iter().find(|(_, limits)| zero_limit(limits)).map(|(tool, _)| tool) {
+            anyhow::bail!("limits.tools.{} must have positive max_input_bytes and max_output_bytes", tool);
+        }
+        let ambiguous_auth =
+            |auth: &AuthConfig| (auth.token.is_some() || !auth.tokens.is_empty()) == auth.jwt_secret.is_some();
+        if self.auth.as_ref().is_some_and(ambiguous_auth) {
+            anyhow::bail!("auth requires either auth.token / auth.tokens or auth.jwt_secret");
+        }
+        if self.data_file.is_some() && self.database.is_some() {
+            anyhow::bail!("data_file and database cannot be combined");
+        }
+        if self.sqlite_retry.initial_backoff_ms > self.sqlite_retry.max_backoff_ms {
+            anyhow::bail!("sqlite_retry.initial_backoff_ms must not exceed sqlite_retry.max_backoff_ms");
+        }
+        if self.database.as_ref().is_some_and(|database| database.max_connections == 0) {
+            anyhow::bail!("database.max_connections must be positive");
+        }
+        if let Some(name) = self.catalogs.keys().find(|name| !crate::catalog::is_valid_name(name)) {
+            anyhow::bail!("catalog name {:?} must be lowercase letters, digits, '-' or '_' and not reserved", name);
+        }
+        let incomplete_api = |semantic: &SemanticConfig| {
+            semantic.embedder == EmbedderKind::Api && (semantic.api_url.is_none() || semantic.model.is_none())
+        };
+        if self.semantic.as_ref().is_some_and(incomplete_api) {
+            anyhow::bail!("semantic.embedder = \"api\" requires semantic.api_url and semantic.model");
+        }
+        Ok(())
+    }
+}
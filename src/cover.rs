@@ -0,0 +1,73 @@
+//! 本の表紙画像の読み込み
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use crate::model::Book;
+
+/// 読み込む表紙画像の最大サイズ（バイト）
+pub const MAX_COVER_BYTES: usize = 5 * 1024 * 1024;
+
+/// 読み込んだ表紙画像
+#[derive(Debug, Clone)]
+pub struct Cover {
+    pub data: Vec<u8>,
+    pub mime_type: &'static str,
+}
+
+impl Cover {
+    /// 画像のデータをBase64にした文字列（MCPの画像コンテンツ・blobリソースの形式）
+    pub fn base64(&self) -> String {
+        STANDARD.encode(&self.data)
+    }
+}
+
+/// 先頭のバイト列から画像の形式を判定する（画像でなければ `None`）
+fn sniff_mime_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// 本の表紙画像を `cover_path`、なければ `cover_url` から読み込む（どちらもなければ `None`）
+///
+/// 画像以外のファイルを返さないように、中身が PNG / JPEG / GIF / WebP のいずれかであることを確かめる。
+pub async fn load(book: &Book) -> Result<Option<Cover>> {
+    let data = if let Some(path) = &book.cover_path {
+        let metadata = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("failed to read cover {}", path))?;
+        if metadata.len() > MAX_COVER_BYTES as u64 {
+            anyhow::bail!("cover {} is larger than {} bytes", path, MAX_COVER_BYTES);
+        }
+        tokio::fs::read(path)
+            .await
+            .with_context(|| format!("failed to read cover {}", path))?
+    } else if let Some(url) = &book.cover_url {
+        fetch(url).await?
+    } else {
+        return Ok(None);
+    };
+
+    let mime_type = sniff_mime_type(&data).context("cover is not a PNG, JPEG, GIF or WebP image")?;
+    Ok(Some(Cover { data, mime_type }))
+}
+
+#[cfg(feature = "openlibrary")]
+async fn fetch(url: &str) -> Result<Vec<u8>> {
+    crate::openlibrary::fetch_bytes(url, MAX_COVER_BYTES).await
+}
+
+#[cfg(not(feature = "openlibrary"))]
+async fn fetch(url: &str) -> Result<Vec<u8>> {
+    anyhow::bail!("fetching cover {} requires building with the `openlibrary` feature", url)
+}
@@ -0,0 +1,155 @@
+//! 必須の項目が足りない `add_book` の呼び出しで、クライアントに不足分の入力を求める
+//!
+//! クライアントが `elicitation` に対応している場合は、足りない項目だけのフォームを
+//! `elicitation/create` で送り、利用者が入力した値で本を完成させる。
+
+use rmcp::model::{CreateElicitationRequestParam, JsonObject};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::model::{Book, Series, YEAR_RANGE};
+
+/// `add_book` で受け取る、項目が欠けているかもしれない本
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct BookDraft {
+    #[schemars(description = "本のタイトル（省略した場合は、対応するクライアントでは利用者に入力を求める）")]
+    pub title: Option<String>,
+    #[schemars(description = "著者名（省略時は入力を求める）")]
+    pub author: Option<String>,
+    #[schemars(description = "出版年（架空。省略時は入力を求める）")]
+    pub year: Option<i32>,
+    #[schemars(description = "本の説明（省略時は入力を求め、入力がなければ空にする）")]
+    pub description: Option<String>,
+    #[schemars(description = "架空のISBN（省略時は入力を求める）")]
+    pub isbn: Option<String>,
+    #[schemars(description = "ジャンルやテーマを表すタグ")]
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[schemars(description = "本が属するシリーズと巻数")]
+    pub series: Option<Series>,
+    #[schemars(description = "表紙画像のファイルのパス（サーバーから読める場所）")]
+    pub cover_path: Option<String>,
+    #[schemars(description = "表紙画像のURL（cover_path がない場合に使う）")]
+    pub cover_url: Option<String>,
+}
+
+/// 入力を求める項目（`description` 以外は必須）
+const FIELDS: &[&str] = &["isbn", "title", "author", "year", "description"];
+
+fn is_blank(value: &Option<String>) -> bool {
+    value.as_deref().is_none_or(|value| value.trim().is_empty())
+}
+
+impl BookDraft {
+    /// 値がない、または空白だけの項目
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        FIELDS
+            .iter()
+            .copied()
+            .filter(|field| match *field {
+                "isbn" => is_blank(&self.isbn),
+                "title" => is_blank(&self.title),
+                "author" => is_blank(&self.author),
+                "year" => self.year.is_none(),
+                _ => is_blank(&self.description),
+            })
+            .collect()
+    }
+
+    /// 利用者が入力した値で、足りない項目を埋める（既にある値は変えない）
+    pub fn fill(&mut self, content: &Value) {
+        let text = |field: &str| {
+            content
+                .get(field)
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        if is_blank(&self.isbn) {
+            self.isbn = text("isbn");
+        }
+        if is_blank(&self.title) {
+            self.title = text("title");
+        }
+        if is_blank(&self.author) {
+            self.author = text("author");
+        }
+        if self.year.is_none() {
+            self.year = content
+                .get("year")
+                .and_then(Value::as_i64)
+                .and_then(|year| i32::try_from(year).ok());
+        }
+        if is_blank(&self.description) {
+            self.description = text("description");
+        }
+    }
+
+    /// 必須の項目が揃っていれば本にする（揃っていなければ足りない項目のエラーを返す）
+    pub fn into_book(self) -> Result<Book, Vec<String>> {
+        let errors: Vec<String> = self
+            .missing_fields()
+            .into_iter()
+            .filter(|field| *field != "description")
+            .map(|field| format!("{} は必須です", field))
+            .collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        Ok(Book {
+            title: self.title.unwrap_or_default(),
+            author: self.author.unwrap_or_default(),
+            year: self.year.unwrap_or_default(),
+            description: self.description.unwrap_or_default(),
+            isbn: self.isbn.unwrap_or_default(),
+            tags: self.tags,
+            version: 0,
+            cover_path: self.cover_path,
+            cover_url: self.cover_url,
+            deleted_at: None,
+            author_id: None,
+            series: self.series,
+        })
+    }
+}
+
+fn field_schema(field: &str) -> Value {
+    match field {
+        "isbn" => json!({ "type": "string", "title": "ISBN", "description": "ISBN-10 または ISBN-13（ハイフンは省略可）" }),
+        "title" => json!({ "type": "string", "title": "タイトル" }),
+        "author" => json!({ "type": "string", "title": "著者名" }),
+        "year" => json!({
+            "type": "integer",
+            "title": "出版年",
+            "minimum": YEAR_RANGE.start(),
+            "maximum": YEAR_RANGE.end(),
+        }),
+        _ => json!({ "type": "string", "title": "説明" }),
+    }
+}
+
+/// 足りない項目だけを尋ねる `elicitation/create` のリクエスト
+pub fn request(draft: &BookDraft, missing: &[&str]) -> CreateElicitationRequestParam {
+    let properties: JsonObject = missing
+        .iter()
+        .map(|field| (field.to_string(), field_schema(field)))
+        .collect();
+    let required: Vec<&str> = missing.iter().copied().filter(|field| *field != "description").collect();
+    let mut schema = JsonObject::new();
+    schema.insert("type".to_string(), json!("object"));
+    schema.insert("properties".to_string(), Value::Object(properties));
+    schema.insert("required".to_string(), json!(required));
+
+    let known = draft
+        .title
+        .as_deref()
+        .filter(|title| !title.trim().is_empty())
+        .map(|title| format!("「{}」を登録するには", title))
+        .unwrap_or_else(|| "本を登録するには".to_string());
+    CreateElicitationRequestParam {
+        message: format!("{}、次の項目を入力してください: {}", known, missing.join(", ")),
+        requested_schema: schema,
+    }
+}
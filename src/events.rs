@@ -0,0 +1,230 @@
+//! ストアの変更をセッションをまたいで知らせるイベントバスと、リソース購読、一覧の変更の通知
+
+use rmcp::{Peer, RoleServer, model::ResourceUpdatedNotificationParam};
+use std::collections::{BTreeSet, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::resources;
+
+/// 受信側が追いつかない場合に保持しておくイベントの数
+const EVENT_CAPACITY: usize = 256;
+
+/// 一覧の変更をまとめて通知するために待つ時間
+const LIST_DEBOUNCE: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Updated,
+    Removed,
+}
+
+/// 1冊分の変更
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogEvent {
+    pub kind: ChangeKind,
+    pub isbn: String,
+    /// 変更のあった名前付きのカタログ（既定のカタログなら `None`）
+    pub catalog: Option<String>,
+}
+
+impl CatalogEvent {
+    pub fn new(kind: ChangeKind, isbn: impl Into<String>) -> Self {
+        Self {
+            kind,
+            isbn: isbn.into(),
+            catalog: None,
+        }
+    }
+
+    /// 名前付きのカタログでの変更にする
+    pub fn in_catalog(mut self, catalog: Option<String>) -> Self {
+        self.catalog = catalog;
+        self
+    }
+
+    /// この変更で項目が増減する一覧（本の追加と削除だけが一覧を変える）
+    fn changed_lists(&self) -> Vec<ListKind> {
+        match (self.kind, &self.catalog) {
+            (ChangeKind::Updated, _) => Vec::new(),
+            // プロンプトは既定のカタログの本から作る
+            (_, None) => vec![ListKind::Resources, ListKind::Prompts],
+            (_, Some(_)) => vec![ListKind::Resources],
+        }
+    }
+
+    /// この変更で内容が変わるリソースのURI
+    fn affected_uris(&self) -> [String; 2] {
+        let catalog = self.catalog.as_deref();
+        [
+            resources::in_catalog(catalog, resources::CATALOG_URI),
+            resources::in_catalog(catalog, &resources::isbn_uri(&self.isbn)),
+        ]
+    }
+}
+
+/// クライアントに `list_changed` で読み直しを促す一覧
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ListKind {
+    Tools,
+    Resources,
+    Prompts,
+}
+
+/// 全セッションで共有するイベントバス
+#[derive(Debug, Clone)]
+pub struct CatalogEvents {
+    sender: broadcast::Sender<CatalogEvent>,
+    lists: broadcast::Sender<ListKind>,
+}
+
+impl CatalogEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        let (lists, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender, lists }
+    }
+
+    /// 変更を通知する（購読しているセッションがなくてもよい）
+    ///
+    /// 本が増減した場合は、その本が載る一覧の変更も通知する。
+    pub fn publish(&self, event: CatalogEvent) {
+        for list in event.changed_lists() {
+            self.list_changed(list);
+        }
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<CatalogEvent> {
+        self.sender.subscribe()
+    }
+
+    /// 本の変更以外の理由（カタログの作成など）で一覧が変わったことを通知する
+    pub fn list_changed(&self, list: ListKind) {
+        let _ = self.lists.send(list);
+    }
+
+    pub fn subscribe_lists(&self) -> broadcast::Receiver<ListKind> {
+        self.lists.subscribe()
+    }
+}
+
+impl Default for CatalogEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 1セッション分の購読中のリソースURI
+#[derive(Debug, Default)]
+pub struct Subscriptions {
+    uris: Mutex<HashSet<String>>,
+    forwarding: AtomicBool,
+}
+
+impl Subscriptions {
+    pub fn insert(&self, uri: String) {
+        self.uris.lock().expect("subscriptions lock poisoned").insert(uri);
+    }
+
+    pub fn remove(&self, uri: &str) {
+        self.uris.lock().expect("subscriptions lock poisoned").remove(uri);
+    }
+
+    fn contains(&self, uri: &str) -> bool {
+        self.uris.lock().expect("subscriptions lock poisoned").contains(uri)
+    }
+
+    fn snapshot(&self) -> Vec<String> {
+        self.uris
+            .lock()
+            .expect("subscriptions lock poisoned")
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// まだ転送を始めていなければ `true` を返し、以降は `false` を返す
+    pub fn start_forwarding(&self) -> bool {
+        !self.forwarding.swap(true, Ordering::SeqCst)
+    }
+}
+
+/// イベントを受け取り、購読中のURIに関係するものを `notifications/resources/updated` として送る
+///
+/// クライアントへの送信に失敗した（切断された）場合やバスが閉じられた場合に終了する。
+pub async fn forward_updates(
+    mut receiver: broadcast::Receiver<CatalogEvent>,
+    subscriptions: Arc<Subscriptions>,
+    peer: Peer<RoleServer>,
+) {
+    loop {
+        let uris = match receiver.recv().await {
+            Ok(event) => event
+                .affected_uris()
+                .into_iter()
+                .filter(|uri| subscriptions.contains(uri))
+                .collect(),
+            // 取りこぼした変更は特定できないので、購読中の全てのURIを更新扱いにする
+            Err(broadcast::error::RecvError::Lagged(_)) => subscriptions.snapshot(),
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        for uri in uris {
+            if let Err(e) = peer
+                .notify_resource_updated(ResourceUpdatedNotificationParam { uri })
+                .await
+            {
+                tracing::debug!("stopping resource update forwarding: {:?}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// 一覧の変更を受け取り、`enabled` の一覧について `notifications/*/list_changed` を送る
+///
+/// 取り込みなどで続けて届いた変更は、一覧ごとに1回の通知にまとめる。クライアントへの送信に
+/// 失敗した（切断された）場合やバスが閉じられた場合に終了する。
+pub async fn forward_list_changes(
+    mut receiver: broadcast::Receiver<ListKind>,
+    enabled: Vec<ListKind>,
+    peer: Peer<RoleServer>,
+) {
+    loop {
+        let mut changed = BTreeSet::new();
+        match receiver.recv().await {
+            Ok(list) => {
+                changed.insert(list);
+            }
+            // 取りこぼした変更は特定できないので、全ての一覧を読み直してもらう
+            Err(broadcast::error::RecvError::Lagged(_)) => changed.extend(enabled.iter().copied()),
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+        tokio::time::sleep(LIST_DEBOUNCE).await;
+        loop {
+            match receiver.try_recv() {
+                Ok(list) => {
+                    changed.insert(list);
+                }
+                Err(broadcast::error::TryRecvError::Lagged(_)) => changed.extend(enabled.iter().copied()),
+                Err(_) => break,
+            }
+        }
+
+        for list in changed.into_iter().filter(|list| enabled.contains(list)) {
+            let sent = match list {
+                ListKind::Tools => peer.notify_tool_list_changed().await,
+                ListKind::Resources => peer.notify_resource_list_changed().await,
+                ListKind::Prompts => peer.notify_prompt_list_changed().await,
+            };
+            if let Err(e) = sent {
+                tracing::debug!("stopping list change forwarding: {:?}", e);
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! 蔵書の書き出し
+
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::model::Book;
+
+/// `export_catalog` で選べる書き出し形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// 本の配列（`import_books` でそのまま取り込める）
+    Json,
+    /// 1行1冊のCSV（`--books` で取り込める列構成）
+    Csv,
+    /// 1冊ごとの `@book` エントリ
+    Bibtex,
+}
+
+impl ExportFormat {
+    /// 書き出したファイルに付ける拡張子
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Bibtex => "bib",
+        }
+    }
+}
+
+/// HTMLの特殊文字をエスケープする
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 蔵書一覧を外部リソースに依存しない単一のHTML文書にする
+pub fn render_catalog_html(books: &[Book]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html lang=\"ja\">\n<head>\n<meta charset=\"utf-8\">\n<title>蔵書一覧</title>\n</head>\n<body>\n<table>\n<thead>\n<tr><th>タイトル</th><th>著者</th><th>出版年</th><th>ISBN</th><th>説明</th></tr>\n</thead>\n<tbody>\n",
+    );
+    for book in books {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&book.title),
+            escape_html(&book.author),
+            book.year,
+            escape_html(&book.isbn),
+            escape_html(&book.description)
+        ));
+    }
+    html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+    html
+}
+
+/// 蔵書全体を指定された形式の文字列にする
+pub fn render_catalog(books: &[Book], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(books)?),
+        ExportFormat::Csv => render_catalog_csv(books),
+        ExportFormat::Bibtex => Ok(render_catalog_bibtex(books)),
+    }
+}
+
+/// 取り込み時と同じ列構成（タグは `;` 区切りの1列）のCSVにする
+fn render_catalog_csv(books: &[Book]) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["title", "author", "year", "description", "isbn", "tags", "series", "series_volume"])?;
+    for book in books {
+        let series = book.series.as_ref();
+        writer.write_record([
+            book.title.as_str(),
+            book.author.as_str(),
+            &book.year.to_string(),
+            book.description.as_str(),
+            book.isbn.as_str(),
+            &book.tags.join(";"),
+            series.map_or("", |series| series.name.as_str()),
+            &series.and_then(|series| series.volume).map(|volume| volume.to_string()).unwrap_or_default(),
+        ])?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// BibTeXで特別な意味を持つ文字をエスケープする
+fn escape_bibtex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '&' | '%' | '$' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 引用キーに使えるよう、ISBNから英数字だけを取り出す
+fn bibtex_key(book: &Book) -> String {
+    let key: String = book.isbn.chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+    format!("isbn{}", key)
+}
+
+/// 1冊ごとに `@book` エントリを並べたBibTeXにする
+fn render_catalog_bibtex(books: &[Book]) -> String {
+    let mut bibtex = String::new();
+    for book in books {
+        bibtex.push_str(&format!("@book{{{},\n", bibtex_key(book)));
+        bibtex.push_str(&format!("  title = {{{}}},\n", escape_bibtex(&book.title)));
+        bibtex.push_str(&format!("  author = {{{}}},\n", escape_bibtex(&book.author)));
+        bibtex.push_str(&format!("  year = {{{}}},\n", book.year));
+        bibtex.push_str(&format!("  isbn = {{{}}},\n", escape_bibtex(&book.isbn)));
+        if !book.tags.is_empty() {
+            bibtex.push_str(&format!("  keywords = {{{}}},\n", escape_bibtex(&book.tags.join(", "))));
+        }
+        bibtex.push_str(&format!("  abstract = {{{}}}\n", escape_bibtex(&book.description)));
+        bibtex.push_str("}\n\n");
+    }
+    bibtex
+}
@@ -0,0 +1,48 @@
+//! 表記揺れやタイプミスを許容するあいまい一致
+
+/// 2つの文字列の編集距離（Levenshtein距離）を文字単位で求める
+pub fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            current[j + 1] = (previous[j + 1] + 1)
+                .min(current[j] + 1)
+                .min(previous[j] + cost);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// `term` が `text` のどこかにどれだけ近い形で含まれているかを 0.0〜1.0 で返す
+///
+/// 部分文字列として含まれていれば 1.0。そうでなければ、`term` と同程度の長さの
+/// `text` の部分文字列のうち最も近いものとの編集距離から類似度を求める。
+pub fn similarity(term: &str, text: &str) -> f64 {
+    if term.is_empty() || text.contains(term) {
+        return 1.0;
+    }
+
+    let term: Vec<char> = term.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    if text.is_empty() {
+        return 0.0;
+    }
+
+    let min_len = term.len().saturating_sub(1).max(1);
+    let max_len = (term.len() + 1).min(text.len());
+    let mut best = 0.0;
+    for len in min_len..=max_len {
+        for window in text.windows(len) {
+            let distance = levenshtein(&term, window);
+            let score = 1.0 - distance as f64 / term.len().max(len) as f64;
+            if score > best {
+                best = score;
+            }
+        }
+    }
+    best
+}
@@ -0,0 +1,109 @@
+//! HTTPのトランスポートで公開する `/healthz` と `/readyz`
+//!
+//! `/healthz` は保存先に問い合わせられるか（生存）を、`/readyz` はそれに加えてインデックスが使えて
+//! 終了処理に入っていないか（新しいリクエストを受けられるか）を返す。どちらも全ての項目が正常なら
+//! 200、そうでなければ 503 で、本文に項目ごとの結果をJSONで書く。ロードバランサーやKubernetesの
+//! プローブはトークンを持たないので、認証を求めない。
+
+use serde::Serialize;
+
+/// 確認した1項目
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    /// 異常の理由
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    pub fn ok(name: &'static str) -> Self {
+        Self { name, ok: true, detail: None }
+    }
+
+    pub fn failed(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            ok: false,
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+/// サーバーの状態
+#[derive(Debug, Clone, Default)]
+pub struct Health {
+    /// `/healthz` と `/readyz` の両方で確認する項目
+    pub live: Vec<Check>,
+    /// `/readyz` でだけ確認する項目
+    pub ready: Vec<Check>,
+}
+
+/// 応答の本文
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub status: &'static str,
+    pub checks: Vec<&'a Check>,
+}
+
+impl Health {
+    /// `/healthz` の項目と、全て正常か
+    pub fn liveness(&self) -> (bool, Report<'_>) {
+        report(self.live.iter().collect())
+    }
+
+    /// `/readyz` の項目と、全て正常か
+    pub fn readiness(&self) -> (bool, Report<'_>) {
+        report(self.live.iter().chain(&self.ready).collect())
+    }
+}
+
+fn report(checks: Vec<&Check>) -> (bool, Report<'_>) {
+    let ok = checks.iter().all(|check| check.ok);
+    let status = if ok { "ok" } else { "unavailable" };
+    (ok, Report { status, checks })
+}
+
+/// `/healthz` と `/readyz` のルーター（`check` でその時点の状態を調べる）
+#[cfg(any(feature = "sse", feature = "streamable-http"))]
+pub(crate) fn router<F, Fut>(check: F) -> axum::Router
+where
+    F: Fn() -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Health> + Send + 'static,
+{
+    use axum::Json;
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+
+    fn respond(ok: bool, report: Report<'_>) -> Response {
+        let status = if ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+        (status, Json(report)).into_response()
+    }
+
+    let live = check.clone();
+    axum::Router::new()
+        .route(
+            "/healthz",
+            get(move || {
+                let health = live();
+                async move {
+                    let health = health.await;
+                    let (ok, report) = health.liveness();
+                    respond(ok, report)
+                }
+            }),
+        )
+        .route(
+            "/readyz",
+            get(move || {
+                let health = check();
+                async move {
+                    let health = health.await;
+                    let (ok, report) = health.readiness();
+                    respond(ok, report)
+                }
+            }),
+        )
+}
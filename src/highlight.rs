@@ -0,0 +1,97 @@
+//! 検索結果のテキストで、キーワードに一致した箇所を目印で囲む
+
+use std::ops::Range;
+
+use crate::config::HighlightConfig;
+use crate::model::Book;
+use crate::search::{Normalizer, QueryTerm, SearchField, normalizer};
+
+/// 正規化後の文字列と、その各バイトが元の文字列のどの範囲から来たか
+struct NormalizedText {
+    text: String,
+    origins: Vec<Range<usize>>,
+}
+
+/// 1文字ずつ正規化して、正規化後の位置から元の位置を引けるようにする
+///
+/// 文字をまたぐ合成（結合文字など）は検索時の正規化と結果が変わることがあるが、
+/// その場合は一致箇所が見つからず目印が付かないだけで済む。
+fn normalize_with_origins(normalizer: &Normalizer, text: &str) -> NormalizedText {
+    let mut normalized = NormalizedText {
+        text: String::new(),
+        origins: Vec::new(),
+    };
+    for (start, c) in text.char_indices() {
+        let piece = normalizer.normalize(c.encode_utf8(&mut [0; 4]));
+        let origin = start..start + c.len_utf8();
+        normalized.origins.extend(std::iter::repeat_n(origin, piece.len()));
+        normalized.text.push_str(&piece);
+    }
+    normalized
+}
+
+/// `text` の中で、正規化した上で `terms` のいずれかに一致する範囲（元の文字列のバイト位置）
+fn matched_ranges(text: &str, terms: &[String]) -> Vec<Range<usize>> {
+    let normalized = normalize_with_origins(normalizer(), text);
+    let mut ranges: Vec<Range<usize>> = terms
+        .iter()
+        .filter(|term| !term.is_empty())
+        .flat_map(|term| {
+            normalized
+                .text
+                .match_indices(term.as_str())
+                .map(|(start, matched)| {
+                    normalized.origins[start].start..normalized.origins[start + matched.len() - 1].end
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    // 重なったり隣接したりする範囲はまとめて1組の目印で囲む
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// `text` のうち `terms` に一致した箇所を目印で囲む
+fn mark(text: &str, terms: &[String], markers: &HighlightConfig) -> String {
+    let mut marked = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for range in matched_ranges(text, terms) {
+        marked.push_str(&text[cursor..range.start]);
+        marked.push_str(&markers.open);
+        marked.push_str(&text[range.clone()]);
+        marked.push_str(&markers.close);
+        cursor = range.end;
+    }
+    marked.push_str(&text[cursor..]);
+    marked
+}
+
+/// フィールドに適用する検索語を正規化して集める
+fn terms_for(terms: &[QueryTerm], field: SearchField) -> Vec<String> {
+    let normalizer = normalizer();
+    terms
+        .iter()
+        .filter(|term| term.field.is_none_or(|f| f == field))
+        .map(|term| normalizer.normalize(&term.text))
+        .collect()
+}
+
+/// タイトル・著者・説明のうち検索語に一致した箇所を目印で囲んだ本を返す
+///
+/// `title:` のようにフィールドを指定した語は、そのフィールドにだけ目印を付ける。
+pub fn book(book: &Book, terms: &[QueryTerm], markers: &HighlightConfig) -> Book {
+    Book {
+        title: mark(&book.title, &terms_for(terms, SearchField::Title), markers),
+        author: mark(&book.author, &terms_for(terms, SearchField::Author), markers),
+        description: mark(&book.description, &terms_for(terms, SearchField::Description), markers),
+        ..book.clone()
+    }
+}
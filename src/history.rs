@@ -0,0 +1,294 @@
+//! カタログへの変更の履歴と、取り消し・やり直し
+//!
+//! 本を変更するツールは、変更した本の変更前と変更後の状態（本とレビュー）を1つの操作として記録する。
+//! 取り消しは変更前の状態に書き戻し、書き戻した結果をやり直し用の操作として積む。
+//! 記録した後に別の呼び出しがその本を変更していれば、上書きしないように取り消しを拒否する。
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use tokio::sync::Mutex;
+
+use crate::model::{Book, Review};
+use crate::store::{BookStore, VersionCheck};
+
+/// 記録しておく操作の数の既定値
+pub const DEFAULT_HISTORY_DEPTH: usize = 20;
+
+/// ある時点での1冊の本とそのレビュー
+#[derive(Debug, Clone, Serialize)]
+pub struct BookState {
+    pub book: Book,
+    pub reviews: Vec<Review>,
+}
+
+/// 1冊の本の変更（`None` は本がないこと）
+#[derive(Debug, Clone)]
+struct Revision {
+    isbn: String,
+    before: Option<BookState>,
+    after: Option<BookState>,
+}
+
+/// 1回のツール呼び出しによる変更
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub tool: String,
+    pub at: DateTime<Utc>,
+    revisions: Vec<Revision>,
+}
+
+impl Change {
+    /// 変更した本のISBN
+    pub fn isbns(&self) -> Vec<String> {
+        self.revisions.iter().map(|revision| revision.isbn.clone()).collect()
+    }
+}
+
+/// 取り消し・やり直しの結果
+#[derive(Debug, Clone, Serialize)]
+pub struct Reverted {
+    /// 取り消し・やり直した操作のツール名
+    pub tool: String,
+    pub at: DateTime<Utc>,
+    /// 書き戻したことで再び登録された本
+    pub added: Vec<String>,
+    /// 以前の内容に書き戻した本
+    pub updated: Vec<String>,
+    /// 書き戻したことで削除された本
+    pub removed: Vec<String>,
+}
+
+/// 取り消し・やり直しができなかった理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HistoryError {
+    /// 取り消し・やり直しできる操作がない
+    Empty,
+    /// 記録した後に別の呼び出しが本を変更した
+    Modified { isbn: String },
+}
+
+impl std::fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "取り消し・やり直しできる変更がありません"),
+            Self::Modified { isbn } => {
+                write!(f, "ISBN '{}' の本は記録した後に変更されているため、書き戻せません", isbn)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+/// 記録する前に、変更する本の状態を読み取る
+pub async fn capture(store: &dyn BookStore, isbns: &[&str]) -> Result<Vec<(String, Option<BookState>)>> {
+    let mut states = Vec::with_capacity(isbns.len());
+    for isbn in isbns {
+        let state = match store.get(isbn).await? {
+            Some(book) => Some(BookState {
+                book,
+                reviews: store.reviews(isbn).await?,
+            }),
+            None => None,
+        };
+        states.push((isbn.to_string(), state));
+    }
+    Ok(states)
+}
+
+#[derive(Debug, Default)]
+struct Stacks {
+    undo: VecDeque<Change>,
+    redo: Vec<Change>,
+}
+
+/// カタログごとの変更の履歴
+#[derive(Debug)]
+pub struct History {
+    depth: usize,
+    /// 取り消し・やり直しの書き戻しが終わるまで、同じカタログの履歴を他から触らせない
+    stacks: Mutex<HashMap<String, Stacks>>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_DEPTH)
+    }
+}
+
+impl History {
+    /// `depth` 個までの操作を記録する（0 の場合は記録しない）
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            stacks: Mutex::default(),
+        }
+    }
+
+    /// `catalog` への変更を記録する（`before` は `capture` で読み取った変更前の状態）
+    ///
+    /// 新しい変更を記録すると、やり直し用の操作は捨てる。
+    pub async fn record(
+        &self,
+        catalog: &str,
+        tool: &str,
+        store: &dyn BookStore,
+        before: Vec<(String, Option<BookState>)>,
+    ) -> Result<()> {
+        if self.depth == 0 {
+            return Ok(());
+        }
+        let isbns: Vec<&str> = before.iter().map(|(isbn, _)| isbn.as_str()).collect();
+        let after = capture(store, &isbns).await?;
+        let revisions = before
+            .into_iter()
+            .zip(after)
+            .map(|((isbn, before), (_, after))| Revision { isbn, before, after })
+            .collect();
+        let change = Change {
+            tool: tool.to_string(),
+            at: Utc::now(),
+            revisions,
+        };
+
+        let mut stacks = self.stacks.lock().await;
+        let stacks = stacks.entry(catalog.to_string()).or_default();
+        stacks.redo.clear();
+        stacks.undo.push_back(change);
+        while stacks.undo.len() > self.depth {
+            stacks.undo.pop_front();
+        }
+        Ok(())
+    }
+
+    /// 最後の変更を取り消す
+    pub async fn undo(&self, catalog: &str, store: &dyn BookStore) -> Result<Reverted> {
+        let mut stacks = self.stacks.lock().await;
+        let stacks = stacks.entry(catalog.to_string()).or_default();
+        let change = stacks.undo.back().ok_or(HistoryError::Empty)?;
+        let (inverse, reverted) = revert(store, change).await?;
+        stacks.undo.pop_back();
+        stacks.redo.push(inverse);
+        Ok(reverted)
+    }
+
+    /// 最後に取り消した変更をやり直す
+    pub async fn redo(&self, catalog: &str, store: &dyn BookStore) -> Result<Reverted> {
+        let mut stacks = self.stacks.lock().await;
+        let stacks = stacks.entry(catalog.to_string()).or_default();
+        let change = stacks.redo.last().ok_or(HistoryError::Empty)?;
+        let (inverse, reverted) = revert(store, change).await?;
+        stacks.redo.pop();
+        stacks.undo.push_back(inverse);
+        Ok(reverted)
+    }
+
+    /// 取り消せる操作とやり直せる操作（どちらも新しい順）
+    pub async fn pending(&self, catalog: &str) -> (Vec<Change>, Vec<Change>) {
+        let stacks = self.stacks.lock().await;
+        match stacks.get(catalog) {
+            Some(stacks) => (
+                stacks.undo.iter().rev().cloned().collect(),
+                stacks.redo.iter().rev().cloned().collect(),
+            ),
+            None => (Vec::new(), Vec::new()),
+        }
+    }
+}
+
+fn same_reviews(a: &[Review], b: &[Review]) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// `change` の各本を変更前の状態に書き戻し、書き戻しを打ち消す操作を返す
+async fn revert(store: &dyn BookStore, change: &Change) -> Result<(Change, Reverted)> {
+    // 1冊でも変更されていれば、どの本にも書き込まない
+    for revision in &change.revisions {
+        let current = store.get(&revision.isbn).await?.map(|book| book.version);
+        let recorded = revision.after.as_ref().map(|state| state.book.version);
+        if current != recorded {
+            return Err(HistoryError::Modified {
+                isbn: revision.isbn.clone(),
+            }
+            .into());
+        }
+    }
+
+    let mut reverted = Reverted {
+        tool: change.tool.clone(),
+        at: change.at,
+        added: Vec::new(),
+        updated: Vec::new(),
+        removed: Vec::new(),
+    };
+    let before: Vec<(String, Option<BookState>)> = capture(
+        store,
+        &change.revisions.iter().map(|revision| revision.isbn.as_str()).collect::<Vec<_>>(),
+    )
+    .await?;
+    for revision in &change.revisions {
+        let current = store.get(&revision.isbn).await?;
+        match (&revision.before, current) {
+            (None, Some(_)) => {
+                store.remove(&revision.isbn, None).await?;
+                reverted.removed.push(revision.isbn.clone());
+            }
+            (None, None) => {}
+            (Some(target), current) => {
+                match current {
+                    Some(_) => reverted.updated.push(revision.isbn.clone()),
+                    None => reverted.added.push(revision.isbn.clone()),
+                }
+                let reviews_changed = revision
+                    .after
+                    .as_ref()
+                    .is_none_or(|after| !same_reviews(&after.reviews, &target.reviews));
+                match current {
+                    // レビューが変わっていない本は、その後に付いたレビューを残すよう本だけを書き戻す
+                    Some(current) if !reviews_changed => {
+                        let updated = store.update(target.book.clone(), current.version).await?;
+                        if !matches!(updated, VersionCheck::Applied(_)) {
+                            return Err(HistoryError::Modified {
+                                isbn: revision.isbn.clone(),
+                            }
+                            .into());
+                        }
+                    }
+                    current => {
+                        // 版は戻さずに進め、書き戻す前に読み込んだ版での更新を衝突として拒否させる
+                        let version = current.map_or(target.book.version, |current| current.version + 1);
+                        store.remove(&revision.isbn, None).await?;
+                        store
+                            .put(Book {
+                                version: version.max(target.book.version),
+                                ..target.book.clone()
+                            })
+                            .await?;
+                        for review in &target.reviews {
+                            store.add_review(review.clone()).await?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    store.flush().await?;
+
+    let after = capture(
+        store,
+        &change.revisions.iter().map(|revision| revision.isbn.as_str()).collect::<Vec<_>>(),
+    )
+    .await?;
+    let inverse = Change {
+        tool: change.tool.clone(),
+        at: change.at,
+        revisions: before
+            .into_iter()
+            .zip(after)
+            .map(|((isbn, before), (_, after))| Revision { isbn, before, after })
+            .collect(),
+    };
+    Ok((inverse, reverted))
+}
@@ -0,0 +1,125 @@
+//! ツールの応答に使う文言の日本語・英語の切り替え
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// 応答の言語
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    /// 日本語
+    #[default]
+    Ja,
+    /// 英語
+    En,
+}
+
+/// 本を表示するときの項目名
+#[derive(Debug)]
+pub struct Labels {
+    pub title: &'static str,
+    pub author: &'static str,
+    pub year: &'static str,
+    pub isbn: &'static str,
+    pub description: &'static str,
+    pub tags: &'static str,
+    pub series: &'static str,
+    pub rating: &'static str,
+}
+
+const JA_LABELS: Labels = Labels {
+    title: "タイトル",
+    author: "著者",
+    year: "出版年",
+    isbn: "ISBN",
+    description: "説明",
+    tags: "タグ",
+    series: "シリーズ",
+    rating: "平均評価",
+};
+
+const EN_LABELS: Labels = Labels {
+    title: "Title",
+    author: "Author",
+    year: "Year",
+    isbn: "ISBN",
+    description: "Description",
+    tags: "Tags",
+    series: "Series",
+    rating: "Average rating",
+};
+
+impl Lang {
+    pub fn labels(self) -> &'static Labels {
+        match self {
+            Self::Ja => &JA_LABELS,
+            Self::En => &EN_LABELS,
+        }
+    }
+
+    pub fn no_results(self, keyword: &str) -> String {
+        match self {
+            Self::Ja => format!("キーワード '{}' に一致する本が見つかりませんでした。", keyword),
+            Self::En => format!("No books matched the keyword '{}'.", keyword),
+        }
+    }
+
+    pub fn results_header(self, keyword: &str) -> String {
+        match self {
+            Self::Ja => format!("キーワード '{}' の検索結果:\n\n", keyword),
+            Self::En => format!("Search results for '{}':\n\n", keyword),
+        }
+    }
+
+    pub fn filter_line(self, filter: &str, value: impl Display, matched: usize) -> String {
+        match self {
+            Self::Ja => format!("絞り込み {} = {}（単独で{}冊が一致）\n", filter, value, matched),
+            Self::En => format!("Filter {} = {} ({} books match on their own)\n", filter, value, matched),
+        }
+    }
+
+    pub fn more_results(self, cursor: &str) -> String {
+        match self {
+            Self::Ja => format!("続きの結果があります（cursor: {}）\n", cursor),
+            Self::En => format!("More results are available (cursor: {})\n", cursor),
+        }
+    }
+
+    pub fn result_id(self, id: &str) -> String {
+        match self {
+            Self::Ja => format!("この結果を refine_search で絞り込めます（result_id: {}）\n", id),
+            Self::En => format!("Narrow these results with refine_search (result_id: {})\n", id),
+        }
+    }
+
+    /// シリーズ名と巻数（巻数がなければシリーズ名だけ）
+    pub fn volume(self, series: &str, volume: Option<u32>) -> String {
+        match (self, volume) {
+            (Self::Ja, Some(volume)) => format!("{} 第{}巻", series, volume),
+            (Self::En, Some(volume)) => format!("{}, vol. {}", series, volume),
+            (_, None) => series.to_string(),
+        }
+    }
+
+    pub fn rating(self, average: f64, count: usize) -> String {
+        match self {
+            Self::Ja => format!("{:.1}（{}件）", average, count),
+            Self::En => format!("{:.1} ({} reviews)", average, count),
+        }
+    }
+
+    pub fn no_reviews(self) -> &'static str {
+        match self {
+            Self::Ja => "レビューなし",
+            Self::En => "no reviews",
+        }
+    }
+
+    pub fn book_not_found(self, isbn: &str) -> String {
+        match self {
+            Self::Ja => format!("ISBN '{}' の本は見つかりませんでした", isbn),
+            Self::En => format!("No book with ISBN '{}' was found", isbn),
+        }
+    }
+}
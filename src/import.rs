@@ -0,0 +1,176 @@
+//! JSON / CSV からの本の一括取り込み
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::store::BookStore;
+use crate::model::{Book, Series, prepare_new_book};
+
+/// 取り込みに失敗した行
+#[derive(Debug, Clone, Serialize)]
+pub struct RowError {
+    /// 1始まりの行番号（CSVではヘッダーを除く）
+    pub row: usize,
+    pub isbn: Option<String>,
+    pub errors: Vec<String>,
+}
+
+/// 取り込みの結果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    /// 取り込んだ本のISBN
+    pub imported: Vec<String>,
+    pub failed: Vec<RowError>,
+}
+
+/// 読み込んだ行を検証し、問題のない行だけをストアに追加する
+///
+/// 既に登録済みのISBN（同じ取り込み内で先に出てきたものを含む）はエラーとする。
+pub async fn import(store: &dyn BookStore, rows: Vec<Result<Book, String>>) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    for (index, row) in rows.into_iter().enumerate() {
+        import_row(store, index + 1, row, false, &mut report).await?;
+    }
+    Ok(report)
+}
+
+/// 1行分を検証して取り込み、結果を `report` に記録する
+///
+/// 進捗を通知しながら取り込む場合は、`import` の代わりにこれを1行ずつ呼び出す。
+/// `dry_run` の場合は検証だけを行い、取り込めるはずの本を `imported` に記録してストアには追加しない。
+pub async fn import_row(
+    store: &dyn BookStore,
+    row_number: usize,
+    row: Result<Book, String>,
+    dry_run: bool,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let mut book = match row {
+        Ok(book) => book,
+        Err(error) => {
+            report.failed.push(RowError {
+                row: row_number,
+                isbn: None,
+                errors: vec![error],
+            });
+            return Ok(());
+        }
+    };
+
+    let mut errors = prepare_new_book(&mut book);
+    // 試行では実際に追加しないので、同じ取り込み内の重複は記録済みのISBNと照合する
+    if store.get(&book.isbn).await?.is_some() || (dry_run && report.imported.contains(&book.isbn)) {
+        errors.push(format!("ISBN '{}' の本は既に登録されています", book.isbn));
+    }
+    if !errors.is_empty() {
+        report.failed.push(RowError {
+            row: row_number,
+            isbn: Some(book.isbn),
+            errors,
+        });
+        return Ok(());
+    }
+
+    let isbn = book.isbn.clone();
+    if dry_run || store.insert(book).await? {
+        report.imported.push(isbn);
+    } else {
+        // 確認してから追加するまでの間に、別の呼び出しが同じ本を追加した
+        report.failed.push(RowError {
+            row: row_number,
+            errors: vec![format!("ISBN '{}' の本は既に登録されています", isbn)],
+            isbn: Some(isbn),
+        });
+    }
+    Ok(())
+}
+
+/// JSONの値の配列を1行ずつ本として解釈する
+pub fn parse_json_rows(values: Vec<serde_json::Value>) -> Vec<Result<Book, String>> {
+    values
+        .into_iter()
+        .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// CSVの1行（タグは `;` 区切りの1列にまとめる）
+#[derive(Debug, Deserialize)]
+struct CsvBook {
+    title: String,
+    author: String,
+    year: i32,
+    description: String,
+    isbn: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    series: Option<String>,
+    #[serde(default)]
+    series_volume: Option<u32>,
+    #[serde(default)]
+    cover_path: Option<String>,
+    #[serde(default)]
+    cover_url: Option<String>,
+}
+
+impl From<CsvBook> for Book {
+    fn from(row: CsvBook) -> Self {
+        Self {
+            title: row.title,
+            author: row.author,
+            year: row.year,
+            description: row.description,
+            isbn: row.isbn,
+            tags: row
+                .tags
+                .split(';')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+            version: 0,
+            cover_path: row.cover_path.filter(|path| !path.trim().is_empty()),
+            cover_url: row.cover_url.filter(|url| !url.trim().is_empty()),
+            deleted_at: None,
+            author_id: None,
+            series: row
+                .series
+                .filter(|name| !name.trim().is_empty())
+                .map(|name| Series {
+                    name,
+                    volume: row.series_volume,
+                }),
+        }
+    }
+}
+
+/// ファイルを拡張子（`.json` / `.csv`）に応じて読み込む
+///
+/// JSONは本の配列、CSVは `title,author,year,description,isbn,tags,series,series_volume` のヘッダー付き
+/// （`tags` 以降は省略可能）を想定する。
+pub fn read_file(path: &Path) -> Result<Vec<Result<Book, String>>> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    match extension.as_deref() {
+        Some("json") => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let values: Vec<serde_json::Value> = serde_json::from_str(&text)
+                .with_context(|| format!("{} is not a JSON array", path.display()))?;
+            Ok(parse_json_rows(values))
+        }
+        Some("csv") => {
+            let mut reader = csv::Reader::from_path(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            Ok(reader
+                .deserialize::<CsvBook>()
+                .map(|row| row.map(Book::from).map_err(|e| e.to_string()))
+                .collect())
+        }
+        _ => anyhow::bail!("unsupported book file format: {}", path.display()),
+    }
+}
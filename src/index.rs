@@ -0,0 +1,128 @@
+//! キーワードの関連度で本を順位付けする全文検索インデックスと意味検索インデックス
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+
+use crate::model::{Author, Book, ReadingList, Review};
+use crate::store::{BookStore, VersionCheck};
+
+#[cfg(feature = "embeddings-api")]
+mod embedding_api;
+#[cfg(feature = "fulltext")]
+mod fulltext;
+mod semantic;
+
+#[cfg(feature = "embeddings-api")]
+pub use embedding_api::ApiEmbedder;
+#[cfg(feature = "fulltext")]
+pub use fulltext::TantivyIndex;
+pub use semantic::{Embedder, HASHING_DIMENSIONS, HashingEmbedder, SemanticIndex};
+
+/// 本の追加・削除に追従し、キーワードに対する関連度を返すインデックス
+pub trait RankedIndex: Send + Sync {
+    /// 本を追加する（同じISBNの本があれば置き換える）
+    fn upsert(&self, book: &Book) -> Result<()>;
+
+    /// 複数の本をまとめて追加する（起動時など大量に登録する場合に使う）
+    fn upsert_all(&self, books: &[Book]) -> Result<()> {
+        books.iter().try_for_each(|book| self.upsert(book))
+    }
+
+    /// ISBNで本を取り除く
+    fn remove(&self, isbn: &str) -> Result<()>;
+
+    /// キーワードに関連する本のISBNと関連度を、関連度の高い順に最大 `limit` 件返す
+    fn search(&self, keyword: &str, limit: usize) -> Result<Vec<(String, f32)>>;
+
+    /// 検索に使える状態か（更新の途中でパニックして、内容が信用できなくなった場合は `false`）
+    fn is_ready(&self) -> bool {
+        true
+    }
+}
+
+/// 書き込みのたびにインデックスも更新するストア
+pub struct IndexedStore {
+    inner: Arc<dyn BookStore>,
+    index: Arc<dyn RankedIndex>,
+}
+
+impl IndexedStore {
+    /// 既存の本をすべてインデックスに登録してからラップする
+    pub async fn new(inner: Arc<dyn BookStore>, index: Arc<dyn RankedIndex>) -> Result<Self> {
+        let books = inner.all().await?;
+        tracing::info!("Indexing {} books", books.len());
+        index.upsert_all(&books)?;
+        Ok(Self { inner, index })
+    }
+}
+
+#[async_trait]
+impl BookStore for IndexedStore {
+    async fn all(&self) -> Result<Vec<Book>> {
+        self.inner.all().await
+    }
+
+    async fn get(&self, isbn: &str) -> Result<Option<Book>> {
+        self.inner.get(isbn).await
+    }
+
+    async fn put(&self, book: Book) -> Result<()> {
+        self.index.upsert(&book)?;
+        self.inner.put(book).await
+    }
+
+    async fn insert(&self, book: Book) -> Result<bool> {
+        let indexed = book.clone();
+        let inserted = self.inner.insert(book).await?;
+        if inserted {
+            self.index.upsert(&indexed)?;
+        }
+        Ok(inserted)
+    }
+
+    async fn update(&self, book: Book, expected: u64) -> Result<VersionCheck> {
+        let indexed = book.clone();
+        let updated = self.inner.update(book, expected).await?;
+        if matches!(updated, VersionCheck::Applied(_)) {
+            self.index.upsert(&indexed)?;
+        }
+        Ok(updated)
+    }
+
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> Result<VersionCheck> {
+        let removed = self.inner.remove(isbn, expected).await?;
+        if matches!(removed, VersionCheck::Applied(_)) {
+            self.index.remove(isbn)?;
+        }
+        Ok(removed)
+    }
+
+    async fn add_review(&self, review: Review) -> Result<()> {
+        self.inner.add_review(review).await
+    }
+
+    async fn reviews(&self, isbn: &str) -> Result<Vec<Review>> {
+        self.inner.reviews(isbn).await
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>> {
+        self.inner.authors().await
+    }
+
+    async fn put_author(&self, author: Author) -> Result<()> {
+        self.inner.put_author(author).await
+    }
+
+    async fn reading_lists(&self) -> Result<Vec<ReadingList>> {
+        self.inner.reading_lists().await
+    }
+
+    async fn put_reading_list(&self, list: ReadingList) -> Result<()> {
+        self.inner.put_reading_list(list).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.inner.flush().await
+    }
+}
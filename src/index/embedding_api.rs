@@ -0,0 +1,78 @@
+//! OpenAI互換の `/v1/embeddings` APIを使う埋め込み
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+use super::semantic::Embedder;
+
+/// 埋め込みAPIへの1回のリクエストの制限時間
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+/// 外部の埋め込みAPIに文章を送ってベクトルを得る
+///
+/// ストアへの書き込みの途中で呼ばれるため、Tokioのマルチスレッドランタイムの上で
+/// 応答を同期的に待つ。
+pub struct ApiEmbedder {
+    client: reqwest::Client,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl ApiEmbedder {
+    pub fn new(url: impl Into<String>, model: impl Into<String>, api_key: Option<String>) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            client,
+            url: url.into(),
+            model: model.into(),
+            api_key,
+        })
+    }
+
+    async fn request(&self, text: &str) -> Result<Vec<f32>> {
+        let mut request = self.client.post(&self.url).json(&json!({
+            "model": self.model,
+            "input": text,
+        }));
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response: EmbeddingResponse = request
+            .send()
+            .await
+            .with_context(|| format!("failed to reach embedding API {}", self.url))?
+            .error_for_status()?
+            .json()
+            .await
+            .context("unexpected response from embedding API")?;
+        response
+            .data
+            .into_iter()
+            .next()
+            .map(|data| data.embedding)
+            .context("embedding API returned no embeddings")
+    }
+}
+
+impl Embedder for ApiEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let handle = tokio::runtime::Handle::try_current().context("embedding API requires a Tokio runtime")?;
+        tokio::task::block_in_place(|| handle.block_on(self.request(text)))
+    }
+}
@@ -0,0 +1,145 @@
+//! tantivy を使ったBM25による全文検索インデックス
+
+use anyhow::Result;
+use std::sync::Mutex;
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, Occur, Query, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, STORED, STRING, Schema, TantivyDocument, TextFieldIndexing,
+    TextOptions, Value,
+};
+use tantivy::tokenizer::{LowerCaser, NgramTokenizer, TextAnalyzer, TokenStream};
+use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, Term, doc};
+
+use super::RankedIndex;
+use crate::model::Book;
+
+/// 分かち書きのない日本語でも部分一致するよう、1〜2文字のN-gramで索引する
+const TOKENIZER: &str = "ngram";
+
+/// インデックス書き込み用のメモリ（tantivy の最小値）
+const WRITER_MEMORY: usize = 15_000_000;
+
+pub struct TantivyIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    isbn: Field,
+    text_fields: Vec<Field>,
+}
+
+impl TantivyIndex {
+    /// メモリ上に空のインデックスを作る
+    pub fn in_memory() -> Result<Self> {
+        let text = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(TOKENIZER)
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+
+        let mut builder = Schema::builder();
+        let isbn = builder.add_text_field("isbn", STRING | STORED);
+        let title = builder.add_text_field("title", text.clone());
+        let author = builder.add_text_field("author", text.clone());
+        let description = builder.add_text_field("description", text.clone());
+        let tags = builder.add_text_field("tags", text);
+        let index = Index::create_in_ram(builder.build());
+        index.tokenizers().register(TOKENIZER, analyzer()?);
+
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let writer = index.writer(WRITER_MEMORY)?;
+
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            isbn,
+            text_fields: vec![title, author, description, tags],
+        })
+    }
+
+    /// 変更を確定し、検索に反映する
+    fn commit(&self, writer: &mut IndexWriter) -> Result<()> {
+        writer.commit()?;
+        self.reader.reload()?;
+        Ok(())
+    }
+}
+
+fn analyzer() -> Result<TextAnalyzer> {
+    Ok(TextAnalyzer::builder(NgramTokenizer::new(1, 2, false)?)
+        .filter(LowerCaser)
+        .build())
+}
+
+impl RankedIndex for TantivyIndex {
+    fn upsert(&self, book: &Book) -> Result<()> {
+        self.upsert_all(std::slice::from_ref(book))
+    }
+
+    fn upsert_all(&self, books: &[Book]) -> Result<()> {
+        // 確定は遅いので、まとめて追加してから1回だけ行う
+        let mut writer = self.writer.lock().expect("index writer lock poisoned");
+        for book in books {
+            writer.delete_term(Term::from_field_text(self.isbn, &book.isbn));
+            writer.add_document(doc!(
+                self.isbn => book.isbn.as_str(),
+                self.text_fields[0] => book.title.as_str(),
+                self.text_fields[1] => book.author.as_str(),
+                self.text_fields[2] => book.description.as_str(),
+                self.text_fields[3] => book.tags.join(" "),
+            ))?;
+        }
+        self.commit(&mut writer)
+    }
+
+    fn remove(&self, isbn: &str) -> Result<()> {
+        let mut writer = self.writer.lock().expect("index writer lock poisoned");
+        writer.delete_term(Term::from_field_text(self.isbn, isbn));
+        self.commit(&mut writer)
+    }
+
+    fn search(&self, keyword: &str, limit: usize) -> Result<Vec<(String, f32)>> {
+        // 各N-gramをいずれかのフィールドに含む文書をORで集め、BM25の合計で並べる
+        let mut analyzer = self
+            .index
+            .tokenizers()
+            .get(TOKENIZER)
+            .expect("ngram tokenizer is registered");
+        let mut stream = analyzer.token_stream(keyword);
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        while stream.advance() {
+            let text = stream.token().text.clone();
+            for field in &self.text_fields {
+                clauses.push((
+                    Occur::Should,
+                    Box::new(TermQuery::new(
+                        Term::from_field_text(*field, &text),
+                        IndexRecordOption::WithFreqs,
+                    )),
+                ));
+            }
+        }
+        if clauses.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+
+        let searcher = self.reader.searcher();
+        let top = searcher.search(&BooleanQuery::new(clauses), &TopDocs::with_limit(limit))?;
+        let mut results = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let document: TantivyDocument = searcher.doc(address)?;
+            if let Some(isbn) = document.get_first(self.isbn).and_then(|value| value.as_str()) {
+                results.push((isbn.to_string(), score));
+            }
+        }
+        Ok(results)
+    }
+
+    fn is_ready(&self) -> bool {
+        !self.writer.is_poisoned()
+    }
+}
@@ -0,0 +1,118 @@
+//! 埋め込みベクトルの近さで本を順位付けする意味検索インデックス
+//!
+//! 本のタイトルと説明を [`Embedder`] でベクトルにしておき、検索語のベクトルとの
+//! コサイン類似度が高い順に返す。「面白いSFの料理本」のような文章でも検索できる。
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use super::RankedIndex;
+use crate::model::Book;
+use crate::search::normalizer;
+
+/// 文章を埋め込みベクトルに変換する（ローカルのモデルや外部のAPIに差し替えられる）
+pub trait Embedder: Send + Sync {
+    /// `text` の埋め込みベクトルを返す（同じ埋め込みの中では次元数が一定であること）
+    fn embed(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+/// [`HashingEmbedder`] のベクトルの次元数
+pub const HASHING_DIMENSIONS: usize = 512;
+
+/// モデルを使わず、文字の1〜2-gramをハッシュで次元に割り当てるローカルの埋め込み
+///
+/// 分かち書きのない日本語でも語の重なりを捉えられるが、言い換えや同義語は区別しない。
+/// 外部のモデルを用意できない環境での既定の埋め込みとして使う。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HashingEmbedder;
+
+/// 実行ごとに変わらないハッシュ（FNV-1a）
+fn fnv1a(text: &str) -> u64 {
+    text.bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+impl Embedder for HashingEmbedder {
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let chars: Vec<char> = normalizer()
+            .normalize(text)
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect();
+        let mut vector = vec![0.0; HASHING_DIMENSIONS];
+        let unigrams = chars.iter().map(|c| c.to_string());
+        let bigrams = chars.windows(2).map(|pair| pair.iter().collect::<String>());
+        for (gram, weight) in unigrams.map(|gram| (gram, 0.5)).chain(bigrams.map(|gram| (gram, 1.0))) {
+            let hash = fnv1a(&gram);
+            // 衝突した特徴が打ち消し合うよう、ハッシュの上位ビットで符号を決める
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % HASHING_DIMENSIONS as u64) as usize] += sign * weight;
+        }
+        Ok(vector)
+    }
+}
+
+/// 長さ1に揃える（ゼロベクトルはそのまま）
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= norm);
+    }
+    vector
+}
+
+/// 本を埋め込む文章（タイトルと説明）
+fn book_text(book: &Book) -> String {
+    format!("{}\n{}", book.title, book.description)
+}
+
+/// 本ごとの埋め込みベクトルをメモリ上に保持する近傍検索インデックス
+pub struct SemanticIndex {
+    embedder: Arc<dyn Embedder>,
+    vectors: RwLock<HashMap<String, Vec<f32>>>,
+}
+
+impl SemanticIndex {
+    pub fn new(embedder: Arc<dyn Embedder>) -> Self {
+        Self {
+            embedder,
+            vectors: RwLock::default(),
+        }
+    }
+}
+
+impl RankedIndex for SemanticIndex {
+    fn upsert(&self, book: &Book) -> Result<()> {
+        let vector = normalized(self.embedder.embed(&book_text(book))?);
+        self.vectors
+            .write()
+            .expect("semantic index lock poisoned")
+            .insert(book.isbn.clone(), vector);
+        Ok(())
+    }
+
+    fn remove(&self, isbn: &str) -> Result<()> {
+        self.vectors.write().expect("semantic index lock poisoned").remove(isbn);
+        Ok(())
+    }
+
+    fn is_ready(&self) -> bool {
+        !self.vectors.is_poisoned()
+    }
+
+    /// 類似度が正の本だけを、類似度の高い順に返す
+    fn search(&self, keyword: &str, limit: usize) -> Result<Vec<(String, f32)>> {
+        let query = normalized(self.embedder.embed(keyword)?);
+        let vectors = self.vectors.read().expect("semantic index lock poisoned");
+        let mut scored: Vec<(String, f32)> = vectors
+            .iter()
+            .filter(|(_, vector)| vector.len() == query.len())
+            .map(|(isbn, vector)| (isbn.clone(), vector.iter().zip(&query).map(|(a, b)| a * b).sum::<f32>()))
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+}
@@ -0,0 +1,128 @@
+//! ISBN-10 / ISBN-13 の検証・正規化・相互変換
+
+use serde::Serialize;
+use std::fmt;
+
+/// チェックディジットまで検証済みのISBN（ハイフンや空白を除いた形で保持する）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "format", content = "value", rename_all = "lowercase")]
+pub enum Isbn {
+    Isbn10(String),
+    Isbn13(String),
+}
+
+/// ISBNとして受け付けられない理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IsbnError {
+    /// 数字（ISBN-10の末尾のみ `X` も可）以外の文字が含まれている
+    InvalidCharacter(char),
+    /// 10桁でも13桁でもない
+    InvalidLength(usize),
+    /// チェックディジットが一致しない
+    Checksum { expected: char, found: char },
+}
+
+impl fmt::Display for IsbnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCharacter(c) => write!(f, "ISBNに使えない文字 '{}' が含まれています", c),
+            Self::InvalidLength(len) => write!(f, "ISBNは10桁または13桁である必要があります（{}桁）", len),
+            Self::Checksum { expected, found } => {
+                write!(f, "チェックディジットが一致しません（期待値: {}、指定値: {}）", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for IsbnError {}
+
+/// ハイフンと空白を取り除き、ISBN-10のチェックディジット `x` を大文字にする
+pub fn normalize(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| !matches!(c, '-' | ' ' | '\u{2010}'..='\u{2015}'))
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn isbn10_check_digit(digits: &[u32]) -> char {
+    let sum: u32 = digits.iter().zip((2..=10).rev()).map(|(d, w)| d * w).sum();
+    match (11 - sum % 11) % 11 {
+        10 => 'X',
+        n => char::from_digit(n, 10).expect("check digit is below 10"),
+    }
+}
+
+fn isbn13_check_digit(digits: &[u32]) -> char {
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { *d } else { d * 3 })
+        .sum();
+    char::from_digit((10 - sum % 10) % 10, 10).expect("check digit is below 10")
+}
+
+fn digits(body: &str) -> Vec<u32> {
+    body.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// 12桁の数字にチェックディジットを付けてISBN-13にする
+pub fn complete_isbn13(body: &str) -> String {
+    format!("{}{}", body, isbn13_check_digit(&digits(body)))
+}
+
+impl Isbn {
+    /// ハイフン区切りを含むISBN-10またはISBN-13を検証する
+    pub fn parse(raw: &str) -> Result<Self, IsbnError> {
+        let compact = normalize(raw);
+        let len = compact.chars().count();
+        for (i, c) in compact.chars().enumerate() {
+            let allowed = c.is_ascii_digit() || (c == 'X' && len == 10 && i == 9);
+            if !allowed {
+                return Err(IsbnError::InvalidCharacter(c));
+            }
+        }
+
+        let (body, found) = compact.split_at(compact.len().saturating_sub(1));
+        let found = found.chars().next().unwrap_or_default();
+        let expected = match len {
+            10 => isbn10_check_digit(&digits(body)),
+            13 => isbn13_check_digit(&digits(body)),
+            _ => return Err(IsbnError::InvalidLength(len)),
+        };
+        if expected != found {
+            return Err(IsbnError::Checksum { expected, found });
+        }
+
+        Ok(if len == 10 { Self::Isbn10(compact) } else { Self::Isbn13(compact) })
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Isbn10(isbn) | Self::Isbn13(isbn) => isbn,
+        }
+    }
+
+    /// ISBN-13の形式で返す（ISBN-10は `978` を付けて変換する）
+    pub fn to_isbn13(&self) -> String {
+        match self {
+            Self::Isbn13(isbn) => isbn.clone(),
+            Self::Isbn10(isbn) => {
+                let body = format!("978{}", &isbn[..9]);
+                let check = isbn13_check_digit(&digits(&body));
+                format!("{}{}", body, check)
+            }
+        }
+    }
+
+    /// ISBN-10の形式で返す（`978` で始まらないISBN-13は変換できないので `None`）
+    pub fn to_isbn10(&self) -> Option<String> {
+        match self {
+            Self::Isbn10(isbn) => Some(isbn.clone()),
+            Self::Isbn13(isbn) => {
+                let body = isbn.strip_prefix("978")?[..9].to_string();
+                let check = isbn10_check_digit(&digits(&body));
+                Some(format!("{}{}", body, check))
+            }
+        }
+    }
+}
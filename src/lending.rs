@@ -0,0 +1,69 @@
+//! 本の貸し出し状況の管理
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// 貸出期間が指定されなかった場合の日数
+pub const DEFAULT_LOAN_DAYS: u32 = 14;
+
+/// 貸出期間として受け付ける日数
+pub const LOAN_DAYS_RANGE: std::ops::RangeInclusive<u32> = 1..=365;
+
+/// 貸し出し中の本1冊分の記録
+#[derive(Debug, Clone, Serialize)]
+pub struct Checkout {
+    pub isbn: String,
+    pub borrower: String,
+    pub checked_out_at: DateTime<Utc>,
+    pub due: DateTime<Utc>,
+}
+
+impl Checkout {
+    /// 返却期限を過ぎているか
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        now > self.due
+    }
+}
+
+/// ISBNごとの貸し出し状況（全セッションで共有する）
+#[derive(Debug, Default)]
+pub struct Loans {
+    checkouts: Mutex<BTreeMap<String, Checkout>>,
+}
+
+impl Loans {
+    /// 本を貸し出す
+    ///
+    /// 既に貸し出し中の場合は、その貸し出しの記録を `Err` で返す。
+    pub fn checkout(&self, isbn: &str, borrower: &str, days: u32, now: DateTime<Utc>) -> Result<Checkout, Checkout> {
+        let mut checkouts = self.checkouts.lock().expect("loan lock poisoned");
+        if let Some(existing) = checkouts.get(isbn) {
+            return Err(existing.clone());
+        }
+        let checkout = Checkout {
+            isbn: isbn.to_string(),
+            borrower: borrower.to_string(),
+            checked_out_at: now,
+            due: now + Duration::days(i64::from(days)),
+        };
+        checkouts.insert(isbn.to_string(), checkout.clone());
+        Ok(checkout)
+    }
+
+    /// 本を返却する（貸し出し中でなければ `None`）
+    pub fn return_book(&self, isbn: &str) -> Option<Checkout> {
+        self.checkouts.lock().expect("loan lock poisoned").remove(isbn)
+    }
+
+    /// 貸し出し中の本をISBN順に返す
+    /// ISBNの本の貸し出しの記録（貸し出されていなければ `None`）
+    pub fn get(&self, isbn: &str) -> Option<Checkout> {
+        self.checkouts.lock().expect("loan lock poisoned").get(isbn).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Checkout> {
+        self.checkouts.lock().expect("loan lock poisoned").values().cloned().collect()
+    }
+}
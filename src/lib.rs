@@ -0,0 +1,62 @@
+//! 架空の本のデータベースを検索するMCPサーバー
+//!
+//! [`BookSearch`] が MCP の `ServerHandler` を実装しているので、ストアやトランスポートを
+//! 差し替えて他のプロジェクトへ組み込んだり、テストから直接呼び出したりできる。
+
+pub mod analysis;
+pub mod audit;
+pub mod auth;
+pub mod authors;
+pub mod catalog;
+mod completion;
+pub mod cover;
+pub mod elicitation;
+pub mod config;
+pub mod events;
+pub mod export;
+mod fuzzy;
+mod highlight;
+pub mod health;
+pub mod history;
+pub mod i18n;
+pub mod import;
+pub mod index;
+pub mod isbn;
+pub mod lending;
+mod librarian;
+pub mod limits;
+pub mod logging;
+pub mod metrics;
+pub mod model;
+pub mod naming;
+pub mod notes;
+#[cfg(feature = "openlibrary")]
+pub mod openlibrary;
+pub mod output;
+mod pagination;
+mod prompts;
+pub mod protocol;
+pub mod query;
+pub mod rate_limit;
+mod request_log;
+pub mod resources;
+pub mod result_cache;
+pub mod roots;
+pub mod search;
+pub mod server;
+pub mod session;
+pub mod shutdown;
+pub mod snapshot;
+pub mod store;
+pub mod synthetic;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transport;
+#[cfg(feature = "hot-reload")]
+pub mod watch;
+
+pub use config::ServerConfig;
+pub use model::{Book, fake_books};
+pub use server::BookSearch;
+pub use store::{BookStore, MemoryStore};
+pub use transport::Transport;
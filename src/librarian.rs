@@ -0,0 +1,38 @@
+//! クライアント側のLLMにサンプリングで質問する「司書」
+
+use rmcp::model::{Content, CreateMessageRequestParam, CreateMessageResult, Role, SamplingMessage};
+
+use crate::model::{Book, format_book};
+
+/// サンプリングで生成してもらう回答の最大トークン数
+const MAX_ANSWER_TOKENS: u32 = 1000;
+
+const SYSTEM_PROMPT: &str = "あなたは図書館の司書です。渡された蔵書の情報だけを使って、利用者の質問に日本語で簡潔に答えてください。蔵書にない本を勧めてはいけません。";
+
+/// 質問と、回答の根拠にする本からサンプリングのリクエストを組み立てる
+pub fn request(question: &str, books: &[&Book]) -> CreateMessageRequestParam {
+    let mut text = String::from("蔵書:\n\n");
+    for book in books {
+        text.push_str(&format_book(book));
+    }
+    text.push_str(&format!("質問: {}", question));
+
+    CreateMessageRequestParam {
+        messages: vec![SamplingMessage {
+            role: Role::User,
+            content: Content::text(text),
+        }],
+        model_preferences: None,
+        system_prompt: Some(SYSTEM_PROMPT.to_string()),
+        include_context: None,
+        temperature: None,
+        max_tokens: MAX_ANSWER_TOKENS,
+        stop_sequences: None,
+        metadata: None,
+    }
+}
+
+/// サンプリングの結果から回答の文章を取り出す（テキスト以外の応答なら `None`）
+pub fn answer(result: &CreateMessageResult) -> Option<String> {
+    result.message.content.as_text().map(|text| text.text.clone())
+}
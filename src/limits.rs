@@ -0,0 +1,66 @@
+//! ツール呼び出しの引数と結果の大きさの制限
+
+use rmcp::{
+    Error as McpError,
+    model::{CallToolRequestParam, CallToolResult},
+};
+use serde_json::json;
+
+/// 引数のJSONの大きさの既定の上限（バイト）
+pub const DEFAULT_MAX_INPUT_BYTES: usize = 64 * 1024;
+
+/// 結果のJSONの大きさの既定の上限（バイト）
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// ツール呼び出しの引数と結果のサイズ上限
+#[derive(Debug, Clone, Copy)]
+pub struct ToolLimits {
+    pub max_input_bytes: usize,
+    pub max_output_bytes: usize,
+}
+
+impl ToolLimits {
+    pub(crate) fn check_input(&self, request: &CallToolRequestParam) -> Result<(), McpError> {
+        let size = serde_json::to_vec(&request.arguments)
+            .map(|bytes| bytes.len())
+            .unwrap_or(0);
+        if size > self.max_input_bytes {
+            return Err(McpError::invalid_params(
+                "tool arguments too large",
+                Some(json!({
+                    "tool": request.name,
+                    "size": size,
+                    "max": self.max_input_bytes,
+                })),
+            ));
+        }
+        Ok(())
+    }
+
+    /// クライアントへ送る結果（構造化された出力も含む）の大きさを確かめる
+    pub(crate) fn check_output(&self, tool: &str, result: &CallToolResult) -> Result<(), McpError> {
+        let size = serde_json::to_vec(result)
+            .map(|bytes| bytes.len())
+            .unwrap_or(0);
+        if size > self.max_output_bytes {
+            return Err(McpError::internal_error(
+                "tool result too large",
+                Some(json!({
+                    "tool": tool,
+                    "size": size,
+                    "max": self.max_output_bytes,
+                })),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for ToolLimits {
+    fn default() -> Self {
+        Self {
+            max_input_bytes: DEFAULT_MAX_INPUT_BYTES,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+        }
+    }
+}
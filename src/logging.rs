@@ -0,0 +1,310 @@
+//! サーバー共通のログ出力の初期化と、MCPのログ通知への橋渡し
+//!
+//! ログは設定した出力先（既定では標準エラー出力）に書くほか、このクレートのイベントを `ClientLogLayer` でバスに流す。
+//! クライアントが `logging/setLevel` を送ったセッションは、バスから指定した重要度以上のものを
+//! `notifications/message` としてクライアントへ送る。
+//! `otlp` 機能付きでビルドした場合は、リクエストごとのスパンをOTLPのコレクターへも送れる。
+
+use anyhow::{Context, Result};
+use rmcp::model::{LoggingLevel, LoggingMessageNotificationParam};
+use rmcp::{Peer, RoleServer};
+use serde_json::{Map, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt};
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{self, EnvFilter, Layer, Registry};
+
+use crate::config::{LogFormat, LogTarget, ServerConfig};
+use crate::transport::Transport;
+
+/// 送り終えていないログを溜めておける数（超えた分は遅れているセッションで捨てる）
+const CLIENT_LOG_CAPACITY: usize = 256;
+
+/// クライアントへ送るログ1件
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub level: LoggingLevel,
+    /// イベントを出したモジュール（`rust_mcp::server` など）
+    pub logger: String,
+    /// メッセージとフィールド
+    pub data: Value,
+}
+
+fn bus() -> &'static broadcast::Sender<LogRecord> {
+    static BUS: OnceLock<broadcast::Sender<LogRecord>> = OnceLock::new();
+    BUS.get_or_init(|| broadcast::channel(CLIENT_LOG_CAPACITY).0)
+}
+
+/// クライアントへ送るログを受け取る
+pub fn subscribe() -> broadcast::Receiver<LogRecord> {
+    bus().subscribe()
+}
+
+/// 重要度の低い順に並べたときの位置
+fn severity(level: LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+fn to_logging_level(level: &Level) -> LoggingLevel {
+    match *level {
+        Level::ERROR => LoggingLevel::Error,
+        Level::WARN => LoggingLevel::Warning,
+        Level::INFO => LoggingLevel::Info,
+        _ => LoggingLevel::Debug,
+    }
+}
+
+/// イベントのフィールドをJSONのオブジェクトに集める
+#[derive(Default)]
+struct Fields(Map<String, Value>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+/// tracing のイベントをクライアントへ送るログとしてバスに流すレイヤー
+pub struct ClientLogLayer;
+
+impl<S: Subscriber> Layer<S> for ClientLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        let bus = bus();
+        // 送り先のセッションがなければ組み立てない
+        if bus.receiver_count() == 0 {
+            return;
+        }
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let _ = bus.send(LogRecord {
+            level: to_logging_level(metadata.level()),
+            logger: metadata.target().to_string(),
+            data: Value::Object(fields.0),
+        });
+    }
+}
+
+/// 終了時に、まだ送っていないトレースを送り終えるためのガード（`main` の終わりで `shutdown` を呼ぶ）
+///
+/// `shutdown` を呼ばずに捨てた場合（途中でエラーになった場合など）も送り終えるが、失敗はログに残すだけになる。
+#[must_use = "dropping the guard shuts down trace export"]
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+impl Telemetry {
+    /// まだ送っていないトレースを送り終える
+    pub fn shutdown(self) -> Result<()> {
+        #[cfg(feature = "otlp")]
+        {
+            let mut telemetry = self;
+            if let Some(provider) = telemetry.provider.take() {
+                provider.shutdown().context("failed to flush traces")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// サーバーが `served` で終わった後にトレースを送り終え、先に起きた方のエラーを返す
+    ///
+    /// サーバーのエラーを返すときも、トレースを送れなかったことはログに残す。
+    pub fn finish(self, served: Result<()>) -> Result<()> {
+        let flushed = self.shutdown();
+        if let (Err(_), Err(e)) = (&served, &flushed) {
+            tracing::error!("{:#}", e);
+        }
+        served.and(flushed)
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        // サブスクライバーはプロセスの終わりまで残るので、ここでもログに出せる
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                tracing::error!("Failed to flush traces: {:?}", e);
+            }
+        }
+    }
+}
+
+/// `endpoint` のコレクターへリクエストのスパンを送るレイヤー
+#[cfg(feature = "otlp")]
+fn otlp_layer(
+    endpoint: &str,
+) -> Result<(Box<dyn Layer<Registry> + Send + Sync>, opentelemetry_sdk::trace::TracerProvider)> {
+    use opentelemetry::KeyValue;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+    use tracing_subscriber::filter::Targets;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .with_context(|| format!("failed to create OTLP exporter for {}", endpoint))?;
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(opentelemetry_sdk::Resource::new(vec![KeyValue::new(
+            "service.name",
+            env!("CARGO_PKG_NAME"),
+        )]))
+        .build();
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(env!("CARGO_CRATE_NAME")))
+        .with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), Level::INFO));
+    Ok((Box::new(layer), provider))
+}
+
+/// ログの出力と、クライアントへのログの橋渡しを初期化する
+///
+/// ログは `log_format` の書式で `log_target` へ書く。stdioトランスポートでは標準出力がMCPの通信路に
+/// なるため、標準出力は選べない。
+/// `log_level` は `RUST_LOG` と同じ書式のディレクティブで、`RUST_LOG` の設定に追加される。
+/// クライアントへは、ログの設定によらずこのクレートの DEBUG 以上のイベントを流す
+/// （rmcp 自身のイベントは、送信のたびに新しいログを生むので流さない）。
+/// `otlp_endpoint` を指定した場合は、リクエストごとのスパンをOTLPでコレクターへ送る。
+pub fn init(config: &ServerConfig) -> Result<Telemetry> {
+    let directive = config
+        .log_level
+        .parse()
+        .with_context(|| format!("invalid log_level {:?}", config.log_level))?;
+    if config.transport == Transport::Stdio && config.log_target == LogTarget::Stdout {
+        anyhow::bail!("log_target = \"stdout\" cannot be used with the stdio transport");
+    }
+
+    let mut layers: Vec<Box<dyn Layer<Registry> + Send + Sync>> = Vec::new();
+    #[cfg(feature = "otlp")]
+    let provider = match config.otlp_endpoint.as_deref().map(otlp_layer).transpose()? {
+        Some((layer, provider)) => {
+            layers.push(layer);
+            Some(provider)
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "otlp"))]
+    if let Some(endpoint) = &config.otlp_endpoint {
+        anyhow::bail!(
+            "otlp_endpoint {} was given but this binary was built without the `otlp` feature",
+            endpoint
+        );
+    }
+
+    let writer = match config.log_target {
+        LogTarget::Stderr => BoxMakeWriter::new(std::io::stderr),
+        LogTarget::Stdout => BoxMakeWriter::new(std::io::stdout),
+    };
+    let filter = EnvFilter::from_default_env().add_directive(directive);
+    let output = tracing_subscriber::fmt::layer().with_writer(writer).with_ansi(false);
+    layers.push(match config.log_format {
+        LogFormat::Text => output.with_filter(filter).boxed(),
+        // リクエストのスパン（メソッド・ツール・セッション）も各行に入れ、行だけで絞り込めるようにする
+        LogFormat::Json => output
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_filter(filter)
+            .boxed(),
+    });
+    layers.push(
+        ClientLogLayer
+            .with_filter(filter_fn(|metadata| {
+                metadata.target().starts_with(env!("CARGO_CRATE_NAME")) && *metadata.level() <= Level::DEBUG
+            }))
+            .boxed(),
+    );
+    tracing_subscriber::registry().with(layers).init();
+    Ok(Telemetry {
+        #[cfg(feature = "otlp")]
+        provider,
+    })
+}
+
+/// セッションがクライアントへ送るログの重要度
+#[derive(Debug, Default)]
+pub struct ClientLog {
+    level: Mutex<Option<LoggingLevel>>,
+    forwarding: AtomicBool,
+}
+
+impl ClientLog {
+    /// `logging/setLevel` で指定された重要度（指定されるまではログを送らない）
+    pub fn level(&self) -> Option<LoggingLevel> {
+        *self.level.lock().expect("client log lock poisoned")
+    }
+
+    pub fn set_level(&self, level: LoggingLevel) {
+        *self.level.lock().expect("client log lock poisoned") = Some(level);
+    }
+
+    /// まだ転送を始めていなければ `true` を返し、以降は `false` を返す
+    pub fn start_forwarding(&self) -> bool {
+        !self.forwarding.swap(true, Ordering::SeqCst)
+    }
+}
+
+/// バスのログのうち、セッションの重要度以上のものを `notifications/message` として送る
+///
+/// クライアントへの送信に失敗した（切断された）場合に終了する。送信の失敗はログに残さない
+/// （残すとそのログをまた送ろうとする）。
+pub async fn forward_to_client(
+    mut receiver: broadcast::Receiver<LogRecord>,
+    log: Arc<ClientLog>,
+    peer: Peer<RoleServer>,
+) {
+    loop {
+        let record = match receiver.recv().await {
+            Ok(record) => record,
+            // 遅れて捨てられたログは送らずに続ける
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let Some(threshold) = log.level() else { continue };
+        if severity(record.level) < severity(threshold) {
+            continue;
+        }
+        let notification = LoggingMessageNotificationParam {
+            level: record.level,
+            logger: Some(record.logger),
+            data: record.data,
+        };
+        if peer.notify_logging_message(notification).await.is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! ツール呼び出しの回数・エラー率・所要時間の計測
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// 所要時間のヒストグラムのバケットの上限（ミリ秒）
+const LATENCY_BUCKETS_MS: &[f64] = &[1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0];
+
+/// ツールごとの計測値（全セッションで共有する）
+#[derive(Debug, Default)]
+pub struct Metrics {
+    tools: Mutex<BTreeMap<String, ToolMetrics>>,
+}
+
+#[derive(Debug, Clone)]
+struct ToolMetrics {
+    calls: u64,
+    errors: u64,
+    /// `LATENCY_BUCKETS_MS` の各上限以下だった呼び出しの数（最後の要素は上限なし）
+    buckets: Vec<u64>,
+    total_ms: f64,
+    max_ms: f64,
+}
+
+impl Default for ToolMetrics {
+    fn default() -> Self {
+        Self {
+            calls: 0,
+            errors: 0,
+            buckets: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+            total_ms: 0.0,
+            max_ms: 0.0,
+        }
+    }
+}
+
+impl ToolMetrics {
+    /// ヒストグラムから求めた95パーセンタイルの所要時間（バケットの上限で丸める）
+    fn p95_ms(&self) -> f64 {
+        let target = (self.calls as f64 * 0.95).ceil() as u64;
+        let mut seen = 0;
+        for (i, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return LATENCY_BUCKETS_MS.get(i).copied().unwrap_or(self.max_ms).min(self.max_ms);
+            }
+        }
+        self.max_ms
+    }
+}
+
+/// `get_server_stats` で返すツール1つ分の集計
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolStats {
+    pub tool: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub error_rate: f64,
+    pub mean_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Metrics {
+    /// ツール呼び出し1回分を記録する
+    pub fn record(&self, tool: &str, elapsed: Duration, is_error: bool) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        let mut tools = self.tools.lock().expect("metrics lock poisoned");
+        let metrics = tools.entry(tool.to_string()).or_default();
+        metrics.calls += 1;
+        if is_error {
+            metrics.errors += 1;
+        }
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        metrics.buckets[bucket] += 1;
+        metrics.total_ms += ms;
+        metrics.max_ms = metrics.max_ms.max(ms);
+    }
+
+    /// ツール名順の集計
+    pub fn snapshot(&self) -> Vec<ToolStats> {
+        let tools = self.tools.lock().expect("metrics lock poisoned");
+        tools
+            .iter()
+            .map(|(tool, metrics)| ToolStats {
+                tool: tool.clone(),
+                calls: metrics.calls,
+                errors: metrics.errors,
+                error_rate: metrics.errors as f64 / metrics.calls as f64,
+                mean_ms: metrics.total_ms / metrics.calls as f64,
+                p95_ms: metrics.p95_ms(),
+                max_ms: metrics.max_ms,
+            })
+            .collect()
+    }
+
+    /// Prometheus のテキスト形式で書き出す
+    pub fn render_prometheus(&self) -> String {
+        let tools = self.tools.lock().expect("metrics lock poisoned");
+        let mut out = String::new();
+
+        out.push_str("# HELP book_tool_calls_total Number of tool calls.\n");
+        out.push_str("# TYPE book_tool_calls_total counter\n");
+        for (tool, metrics) in tools.iter() {
+            let _ = writeln!(out, "book_tool_calls_total{{tool=\"{}\"}} {}", tool, metrics.calls);
+        }
+
+        out.push_str("# HELP book_tool_errors_total Number of tool calls that failed.\n");
+        out.push_str("# TYPE book_tool_errors_total counter\n");
+        for (tool, metrics) in tools.iter() {
+            let _ = writeln!(out, "book_tool_errors_total{{tool=\"{}\"}} {}", tool, metrics.errors);
+        }
+
+        out.push_str("# HELP book_tool_duration_seconds Tool call latency.\n");
+        out.push_str("# TYPE book_tool_duration_seconds histogram\n");
+        for (tool, metrics) in tools.iter() {
+            let mut cumulative = 0;
+            for (i, count) in metrics.buckets.iter().enumerate() {
+                cumulative += count;
+                let le = match LATENCY_BUCKETS_MS.get(i) {
+                    Some(bound) => (bound / 1000.0).to_string(),
+                    None => "+Inf".to_string(),
+                };
+                let _ = writeln!(
+                    out,
+                    "book_tool_duration_seconds_bucket{{tool=\"{}\",le=\"{}\"}} {}",
+                    tool, le, cumulative
+                );
+            }
+            let _ = writeln!(out, "book_tool_duration_seconds_sum{{tool=\"{}\"}} {}", tool, metrics.total_ms / 1000.0);
+            let _ = writeln!(out, "book_tool_duration_seconds_count{{tool=\"{}\"}} {}", tool, metrics.calls);
+        }
+        out
+    }
+}
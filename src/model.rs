@@ -0,0 +1,394 @@
+//! 本のデータモデルと、初期データ・入力検証・表示用の書式
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::i18n::Lang;
+use crate::isbn::Isbn;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Book {
+    #[schemars(description = "本のタイトル")]
+    pub title: String,
+    #[schemars(description = "著者名")]
+    pub author: String,
+    #[schemars(description = "著者の記録のID（省略時は著者名から著者を決める）")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_id: Option<String>,
+    #[schemars(description = "出版年（架空）")]
+    pub year: i32,
+    #[schemars(description = "本の説明")]
+    pub description: String,
+    #[schemars(description = "架空のISBN")]
+    pub isbn: String,
+    #[schemars(description = "ジャンルやテーマを表すタグ")]
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[schemars(description = "本が属するシリーズと巻数")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series: Option<Series>,
+    #[schemars(description = "更新のたびに1ずつ増える版（登録時は0）")]
+    #[serde(default)]
+    pub version: u64,
+    #[schemars(description = "表紙画像のファイルのパス（サーバーから読める場所）")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_path: Option<String>,
+    #[schemars(description = "表紙画像のURL（cover_path がない場合に使う）")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_url: Option<String>,
+    #[schemars(description = "ごみ箱に移した日時（ごみ箱にない本では省略）")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl Book {
+    /// `delete_book` でごみ箱に移された本か
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+}
+
+/// 本が属するシリーズ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Series {
+    #[schemars(description = "シリーズ名")]
+    pub name: String,
+    #[schemars(description = "シリーズの中での巻数（1から。番号のない外伝などは省略）")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<u32>,
+}
+
+/// 著者の記録
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Author {
+    #[schemars(description = "著者のID")]
+    pub id: String,
+    #[schemars(description = "著者名（本の author に表示する名前）")]
+    pub name: String,
+    #[schemars(description = "著者の紹介")]
+    #[serde(default)]
+    pub bio: String,
+    #[schemars(description = "別名や以前の名前（この名前の本も同じ著者の本として扱う）")]
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+/// 名前を付けて本を選んでおく読書リスト
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ReadingList {
+    #[schemars(description = "リストの名前")]
+    pub name: String,
+    #[schemars(description = "リストの説明")]
+    #[serde(default)]
+    pub description: String,
+    #[schemars(description = "リストの本のISBN（加えた順）")]
+    #[serde(default)]
+    pub isbns: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 本に付けられたレビュー
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Review {
+    #[schemars(description = "レビューする本のISBN")]
+    pub isbn: String,
+    #[schemars(description = "1から5の評価")]
+    pub rating: u8,
+    #[schemars(description = "レビュー本文")]
+    #[serde(default)]
+    pub text: String,
+    #[schemars(description = "レビューした人の名前")]
+    pub reviewer: String,
+}
+
+/// 評価として受け付ける範囲
+pub const RATING_RANGE: std::ops::RangeInclusive<u8> = 1..=5;
+
+/// 出版年として受け付ける範囲
+pub const YEAR_RANGE: std::ops::RangeInclusive<i32> = -3000..=9999;
+
+/// 本の内容の問題点を列挙する
+pub fn validate_book(book: &Book) -> Vec<String> {
+    let mut errors = Vec::new();
+    if book.isbn.trim().is_empty() {
+        errors.push("isbn は必須です".to_string());
+    }
+    if book.title.trim().is_empty() {
+        errors.push("title は必須です".to_string());
+    }
+    if book.author.trim().is_empty() {
+        errors.push("author は必須です".to_string());
+    }
+    if book.tags.iter().any(|tag| tag.trim().is_empty()) {
+        errors.push("tags に空のタグは指定できません".to_string());
+    }
+    if let Some(series) = &book.series {
+        if series.name.trim().is_empty() {
+            errors.push("series.name は必須です".to_string());
+        }
+        if series.volume == Some(0) {
+            errors.push("series.volume は1以上で指定してください".to_string());
+        }
+    }
+    if !YEAR_RANGE.contains(&book.year) {
+        errors.push(format!(
+            "year は{}から{}の範囲で指定してください（指定値: {}）",
+            YEAR_RANGE.start(),
+            YEAR_RANGE.end(),
+            book.year
+        ));
+    }
+    let web_url = |url: &String| url.starts_with("http://") || url.starts_with("https://");
+    if book.cover_url.as_ref().is_some_and(|url| !web_url(url)) {
+        errors.push("cover_url は http:// または https:// で始まるURLを指定してください".to_string());
+    }
+    errors
+}
+
+/// 新しく登録する本を検証し、ISBNをハイフンなしのISBN-13に揃え、版を0にしてごみ箱から出す
+///
+/// 既に登録されている本の更新ではISBNを変えないため、`validate_book` だけを使う。
+pub fn prepare_new_book(book: &mut Book) -> Vec<String> {
+    book.version = 0;
+    book.deleted_at = None;
+    let mut errors = validate_book(book);
+    if !book.isbn.trim().is_empty() {
+        match Isbn::parse(&book.isbn) {
+            Ok(isbn) => book.isbn = isbn.to_isbn13(),
+            Err(e) => errors.push(format!("isbn '{}' は正しくありません: {}", book.isbn, e)),
+        }
+    }
+    errors
+}
+
+/// レビューの内容の問題点を列挙する
+pub fn validate_review(review: &Review) -> Vec<String> {
+    let mut errors = Vec::new();
+    if review.isbn.trim().is_empty() {
+        errors.push("isbn は必須です".to_string());
+    }
+    if review.reviewer.trim().is_empty() {
+        errors.push("reviewer は必須です".to_string());
+    }
+    if !RATING_RANGE.contains(&review.rating) {
+        errors.push(format!(
+            "rating は{}から{}の範囲で指定してください（指定値: {}）",
+            RATING_RANGE.start(),
+            RATING_RANGE.end(),
+            review.rating
+        ));
+    }
+    errors
+}
+
+/// レビューの平均評価と件数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, JsonSchema)]
+pub struct RatingSummary {
+    pub average: f64,
+    pub count: usize,
+}
+
+impl RatingSummary {
+    /// レビューが1件もなければ `None` を返す
+    pub fn of(reviews: &[Review]) -> Option<Self> {
+        if reviews.is_empty() {
+            return None;
+        }
+        let total: u32 = reviews.iter().map(|review| u32::from(review.rating)).sum();
+        Some(Self {
+            average: f64::from(total) / reviews.len() as f64,
+            count: reviews.len(),
+        })
+    }
+}
+
+/// 初期データとして使う架空の本
+pub fn fake_books() -> Vec<Book> {
+    vec![
+        Book {
+            title: "量子コンピュータで料理する方法".to_string(),
+            author: "Dr. スーパーサイエンティスト".to_string(),
+            year: 2157,
+            description: "量子コンピュータを使用して、分子レベルで料理を再構築する革新的な方法を解説".to_string(),
+            isbn: "9784012345618".to_string(),
+            tags: vec!["cooking".to_string(), "science".to_string(), "quantum".to_string()],
+            version: 0,
+            cover_path: None,
+            cover_url: None,
+            deleted_at: None,
+            author_id: None,
+            series: None,
+        },
+        Book {
+            title: "タイムトラベルと税金対策".to_string(),
+            author: "未来の会計士".to_string(),
+            year: 3000,
+            description: "タイムトラベルを活用した効率的な税金対策を解説".to_string(),
+            isbn: "9784012345625".to_string(),
+            tags: vec!["time-travel".to_string(), "finance".to_string()],
+            version: 0,
+            cover_path: None,
+            cover_url: None,
+            deleted_at: None,
+            author_id: None,
+            series: None,
+        },
+        Book {
+            title: "火星での園芸入門".to_string(),
+            author: "火星の園芸家".to_string(),
+            year: 2250,
+            description: "火星の特殊な環境で植物を育てる方法を解説。".to_string(),
+            isbn: "9784012345632".to_string(),
+            tags: vec!["gardening".to_string(), "space".to_string(), "mars".to_string()],
+            version: 0,
+            cover_path: None,
+            cover_url: None,
+            deleted_at: None,
+            author_id: None,
+            series: None,
+        },
+        Book {
+            title: "AIと恋愛の心理学".to_string(),
+            author: "ロボット心理学者".to_string(),
+            year: 2200,
+            description: "AIとの恋愛関係における心理学的な考察と実践的なアドバイス。".to_string(),
+            isbn: "9784012345649".to_string(),
+            tags: vec!["ai".to_string(), "romance".to_string(), "psychology".to_string()],
+            version: 0,
+            cover_path: None,
+            cover_url: None,
+            deleted_at: None,
+            author_id: None,
+            series: None,
+        },
+        Book {
+            title: "テレパシーでプログラミング".to_string(),
+            author: "サイキックエンジニア".to_string(),
+            year: 2300,
+            description: "テレパシー能力を使用してコードを書く方法を解説。".to_string(),
+            isbn: "9784012345656".to_string(),
+            tags: vec!["programming".to_string(), "psychic".to_string()],
+            version: 0,
+            cover_path: None,
+            cover_url: None,
+            deleted_at: None,
+            author_id: None,
+            series: None,
+        },
+    ]
+}
+
+/// 更新で値が変わるフィールド1つ分
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+}
+
+/// 2つの版の本で値が異なるフィールドを列挙する
+pub fn book_changes(before: &Book, after: &Book) -> Vec<FieldChange> {
+    let fields = [
+        ("title", json!(before.title), json!(after.title)),
+        ("author", json!(before.author), json!(after.author)),
+        ("year", json!(before.year), json!(after.year)),
+        ("description", json!(before.description), json!(after.description)),
+        ("isbn", json!(before.isbn), json!(after.isbn)),
+        ("tags", json!(before.tags), json!(after.tags)),
+        ("series", json!(before.series), json!(after.series)),
+        ("cover_path", json!(before.cover_path), json!(after.cover_path)),
+        ("cover_url", json!(before.cover_url), json!(after.cover_url)),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|(field, before, after)| FieldChange { field, before, after })
+        .collect()
+}
+
+/// 複数の本にまとめて適用する部分的な変更（指定した項目だけを変える）
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct BookPatch {
+    #[schemars(description = "著者名を置き換える")]
+    pub author: Option<String>,
+    #[schemars(description = "出版年を置き換える")]
+    pub year: Option<i32>,
+    #[schemars(description = "追加するタグ（既に付いているタグは重複させない）")]
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[schemars(description = "取り除くタグ（大文字小文字は区別しない）")]
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+}
+
+impl BookPatch {
+    pub fn is_empty(&self) -> bool {
+        self.author.is_none() && self.year.is_none() && self.add_tags.is_empty() && self.remove_tags.is_empty()
+    }
+
+    /// 変更を本に適用する（タグは取り除いてから追加する）
+    pub fn apply(&self, book: &mut Book) {
+        if let Some(author) = self.author.as_ref().filter(|author| **author != book.author) {
+            book.author = author.clone();
+            book.author_id = None;
+        }
+        if let Some(year) = self.year {
+            book.year = year;
+        }
+        book.tags
+            .retain(|tag| !self.remove_tags.iter().any(|removed| removed.eq_ignore_ascii_case(tag)));
+        for tag in &self.add_tags {
+            if !book.tags.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+                book.tags.push(tag.clone());
+            }
+        }
+    }
+}
+
+/// 重複している本 `duplicate` の情報を `primary` に取り込む
+///
+/// `primary` の値を優先し、空の説明文だけを補う。タグは両方のものを重複なく並べる。
+pub fn merge_book(primary: &mut Book, duplicate: &Book) {
+    if primary.description.trim().is_empty() {
+        primary.description = duplicate.description.clone();
+    }
+    for tag in &duplicate.tags {
+        if !primary.tags.contains(tag) {
+            primary.tags.push(tag.clone());
+        }
+    }
+}
+
+/// 本1冊分を検索結果と同じ書式のテキストにする
+pub fn format_book(book: &Book) -> String {
+    format_book_in(book, Lang::Ja)
+}
+
+/// 本1冊分を、項目名を `lang` の言語にしてテキストにする
+pub fn format_book_in(book: &Book, lang: Lang) -> String {
+    let labels = lang.labels();
+    let mut text = format!(
+        "{}: {}\n{}: {}\n{}: {}\n{}: {}\n{}: {}\n",
+        labels.title,
+        book.title,
+        labels.author,
+        book.author,
+        labels.year,
+        book.year,
+        labels.isbn,
+        book.isbn,
+        labels.description,
+        book.description
+    );
+    if !book.tags.is_empty() {
+        text.push_str(&format!("{}: {}\n", labels.tags, book.tags.join(", ")));
+    }
+    if let Some(series) = &book.series {
+        text.push_str(&format!("{}: {}\n", labels.series, lang.volume(&series.name, series.volume)));
+    }
+    text.push('\n');
+    text
+}
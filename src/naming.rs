@@ -0,0 +1,123 @@
+//! ツールの結果のJSONの項目名の書き方
+//!
+//! 結果はいつも snake_case の型から組み立て、`camelCase` を選んだ場合だけ、送る直前に
+//! JSONのオブジェクトの項目名を書き換える。値はそのまま残す。
+
+use clap::ValueEnum;
+use rmcp::model::{CallToolResult, JsonObject, RawContent};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+/// 結果のJSONの項目名の書き方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+pub enum Naming {
+    /// `cover_path` のように書く（結果の型の項目名のまま）
+    #[default]
+    #[serde(rename = "snake_case")]
+    #[value(name = "snake_case")]
+    SnakeCase,
+    /// `coverPath` のように書く
+    #[serde(rename = "camelCase")]
+    #[value(name = "camelCase")]
+    CamelCase,
+}
+
+impl Naming {
+    /// 項目名 `key` をこの書き方にする
+    pub fn key(self, key: &str) -> String {
+        match self {
+            Self::SnakeCase => key.to_string(),
+            Self::CamelCase => {
+                let mut renamed = String::with_capacity(key.len());
+                let mut upper = false;
+                for c in key.chars() {
+                    if c == '_' && !renamed.is_empty() {
+                        upper = true;
+                    } else if upper {
+                        renamed.extend(c.to_uppercase());
+                        upper = false;
+                    } else {
+                        renamed.push(c);
+                    }
+                }
+                renamed
+            }
+        }
+    }
+
+    /// `value` の中のすべてのオブジェクトの項目名を書き換える
+    pub fn rename(self, value: Value) -> Value {
+        match value {
+            Value::Object(object) => Value::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (self.key(&key), self.rename(value)))
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(items.into_iter().map(|item| self.rename(item)).collect()),
+            value => value,
+        }
+    }
+
+    /// ツールの結果のJSONのテキストと構造化された結果を書き換える（JSONでないテキストは残す）
+    pub fn rename_result(self, mut result: CallToolResult) -> CallToolResult {
+        if self == Self::SnakeCase {
+            return result;
+        }
+        for content in &mut result.content {
+            if let RawContent::Text(text) = &mut content.raw {
+                let parsed = serde_json::from_str::<Value>(&text.text)
+                    .ok()
+                    .filter(|value| value.is_object() || value.is_array());
+                if let Some(value) = parsed {
+                    text.text = self.rename(value).to_string();
+                }
+            }
+        }
+        result.structured_content = result.structured_content.map(|value| self.rename(value));
+        result
+    }
+
+    /// 出力スキーマの `properties` と `required` の項目名を、書き換えた結果に合わせる
+    pub fn rename_schema(self, schema: Arc<JsonObject>) -> Arc<JsonObject> {
+        if self == Self::SnakeCase {
+            return schema;
+        }
+        let mut schema = Value::Object((*schema).clone());
+        self.rename_schema_value(&mut schema);
+        match schema {
+            Value::Object(schema) => Arc::new(schema),
+            _ => unreachable!("schema stays an object"),
+        }
+    }
+
+    fn rename_schema_value(self, value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                if let Some(Value::Object(properties)) = object.get_mut("properties") {
+                    *properties = std::mem::take(properties)
+                        .into_iter()
+                        .map(|(key, value)| (self.key(&key), value))
+                        .collect();
+                }
+                if let Some(Value::Array(required)) = object.get_mut("required") {
+                    for name in required.iter_mut() {
+                        if let Value::String(name) = name {
+                            *name = self.key(name);
+                        }
+                    }
+                }
+                for value in object.values_mut() {
+                    self.rename_schema_value(value);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.rename_schema_value(item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! ディレクトリ内のMarkdownメモを読み書き・検索するMCPサーバー
+//!
+//! 本の検索サーバーとは別のサンプルで、トランスポート・設定・ログの仕組みを共有する。
+
+use rmcp::{
+    Error as McpError, RoleServer, ServerHandler, handler::server::tool::ToolCallContext,
+    model::*, service::RequestContext, tool,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::request_log;
+use crate::search::normalizer;
+use crate::transport::ManagedServer;
+
+/// `search_notes` で1件のメモから返す一致行の最大数
+const MAX_MATCHED_LINES: usize = 5;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateNoteRequest {
+    #[schemars(description = "メモの名前（拡張子 .md を除いたファイル名。英数字・かな漢字・`-`・`_` が使える）")]
+    pub name: String,
+    #[schemars(description = "メモの本文（Markdown）")]
+    pub content: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReadNoteRequest {
+    #[schemars(description = "読むメモの名前")]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchNotesRequest {
+    #[schemars(description = "検索キーワード（大文字小文字・全角半角を区別しない部分一致）")]
+    pub query: String,
+}
+
+/// メモの名前を検証する（ディレクトリの外を指す名前を拒否する）
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("name は必須です".to_string());
+    }
+    if !name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_')) {
+        return Err(format!("メモの名前 '{}' には使えない文字が含まれています", name));
+    }
+    Ok(())
+}
+
+fn io_error(e: std::io::Error) -> McpError {
+    McpError::internal_error(
+        "notes directory error",
+        Some(json!({
+            "reason": e.to_string()
+        })),
+    )
+}
+
+/// 入力の問題点を `isError` 付きの結果として返す
+fn failure(error: String) -> Result<CallToolResult, McpError> {
+    Ok(CallToolResult::error(vec![Content::json(json!({
+        "errors": [error],
+    }))?]))
+}
+
+#[derive(Clone)]
+pub struct NotesServer {
+    dir: Arc<PathBuf>,
+}
+
+#[tool(tool_box)]
+impl NotesServer {
+    /// `dir` 直下の `*.md` をメモとして扱うサーバーを作成する
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Arc::new(dir.into()),
+        }
+    }
+
+    /// メモを置くディレクトリ
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn note_path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.md", name))
+    }
+
+    /// ディレクトリ内のメモの名前を名前順に返す
+    fn note_names(&self) -> Result<Vec<String>, McpError> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(self.dir.as_path()).map_err(io_error)? {
+            let path = entry.map_err(io_error)?.path();
+            let stem = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .filter(|_| path.extension().is_some_and(|ext| ext == "md"));
+            names.extend(stem.map(str::to_string));
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// メモを新しく作成するツール
+    ///
+    /// # 引数
+    /// * CreateNoteRequest - メモの名前と本文
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 作成したメモの名前（同名のメモがあればエラー）
+    #[tool(description = "Create a new markdown note")]
+    fn create_note(&self, #[tool(aggr)] CreateNoteRequest { name, content }: CreateNoteRequest) -> Result<CallToolResult, McpError> {
+        if let Err(error) = validate_name(&name) {
+            return failure(error);
+        }
+        let path = self.note_path(&name);
+        let file = std::fs::OpenOptions::new().write(true).create_new(true).open(&path);
+        let mut file = match file {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return failure(format!("メモ '{}' は既に存在します", name));
+            }
+            Err(e) => return Err(io_error(e)),
+        };
+        std::io::Write::write_all(&mut file, content.as_bytes()).map_err(io_error)?;
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "created": name,
+            "bytes": content.len(),
+        }))?]))
+    }
+
+    /// メモを読むツール
+    ///
+    /// # 引数
+    /// * ReadNoteRequest - 読むメモの名前
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - メモの本文
+    #[tool(description = "Read a markdown note by name")]
+    fn read_note(&self, #[tool(aggr)] ReadNoteRequest { name }: ReadNoteRequest) -> Result<CallToolResult, McpError> {
+        if let Err(error) = validate_name(&name) {
+            return failure(error);
+        }
+        match std::fs::read_to_string(self.note_path(&name)) {
+            Ok(content) => Ok(CallToolResult::success(vec![Content::text(content)])),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => failure(format!("メモ '{}' は見つかりませんでした", name)),
+            Err(e) => Err(io_error(e)),
+        }
+    }
+
+    /// メモの名前と本文を検索するツール
+    ///
+    /// # 引数
+    /// * SearchNotesRequest - 検索キーワード
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 一致したメモの名前と、一致した行（1件につき最大5行）
+    #[tool(description = "Search notes by name and content")]
+    fn search_notes(&self, #[tool(aggr)] SearchNotesRequest { query }: SearchNotesRequest) -> Result<CallToolResult, McpError> {
+        let normalizer = normalizer();
+        let query = normalizer.normalize(query.trim());
+        let mut matches = Vec::new();
+        for name in self.note_names()? {
+            let content = std::fs::read_to_string(self.note_path(&name)).map_err(io_error)?;
+            let lines: Vec<&str> = content
+                .lines()
+                .filter(|line| normalizer.normalize(line).contains(&query))
+                .take(MAX_MATCHED_LINES)
+                .collect();
+            if !lines.is_empty() || normalizer.normalize(&name).contains(&query) {
+                matches.push(json!({
+                    "name": name,
+                    "lines": lines,
+                }));
+            }
+        }
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": matches.len(),
+            "notes": matches,
+        }))?]))
+    }
+}
+
+impl ManagedServer for NotesServer {}
+
+impl ServerHandler for NotesServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: Implementation::from_build_env(),
+            instructions: Some("ディレクトリ内のMarkdownメモを作成・閲覧・検索するサーバーです。".into()),
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: PaginatedRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        request_log::logged("tools/list", None, None, &context.id, request_log::ok, async {
+            Ok(ListToolsResult {
+                next_cursor: None,
+                tools: Self::tool_box().list(),
+            })
+        })
+        .await
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let name = request.name.clone();
+        let request_id = context.id.clone();
+        request_log::logged("tools/call", Some(&name), None, &request_id, request_log::ok, async {
+            Self::tool_box().call(ToolCallContext::new(self, request, context)).await
+        })
+        .await
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use rust_mcp::auth::Authenticator;
+use rust_mcp::config::AuthConfig;
+use rust_mcp::notes::NotesServer;
+use rust_mcp::{ServerConfig, Transport, logging, transport};
+
+/// ディレクトリ内のMarkdownメモを扱うMCPサーバー
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// メモを置くディレクトリ（なければ作成する）
+    #[arg(long, env = "NOTES_DIR", default_value = "notes")]
+    dir: PathBuf,
+
+    /// サーバーの設定ファイル（TOML）。transport / listen / log_level / auth / compression のみを使う
+    #[arg(long, env = "NOTES_SERVER_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// 使用するトランスポート
+    #[arg(long, value_enum)]
+    transport: Option<Transport>,
+
+    /// sse / streamable-http / ws で待ち受けるアドレス（既定: 127.0.0.1:8000）
+    #[arg(long)]
+    listen: Option<SocketAddr>,
+
+    /// ネットワーク越しの接続に要求するベアラートークン（設定ファイルの `[auth]` より優先される）
+    #[arg(long, env = "MCP_AUTH_TOKEN", hide_env_values = true)]
+    auth_token: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut config = match &cli.config {
+        Some(path) => ServerConfig::load(path)?,
+        None => ServerConfig::default(),
+    };
+    if let Some(transport) = cli.transport {
+        config.transport = transport;
+    }
+    if let Some(listen) = cli.listen {
+        config.listen = listen;
+    }
+    if let Some(token) = cli.auth_token {
+        config.auth = Some(AuthConfig {
+            token: Some(token),
+            jwt_secret: None,
+        });
+    }
+    let telemetry = logging::init(&config)?;
+
+    std::fs::create_dir_all(&cli.dir)
+        .with_context(|| format!("failed to create notes directory {}", cli.dir.display()))?;
+    let server = NotesServer::new(cli.dir);
+    tracing::info!("Starting MCP notes server for {}", server.dir().display());
+    let auth = config.auth.as_ref().map(Authenticator::from_config).transpose()?;
+    telemetry.finish(transport::serve(config.transport, server, config.listen, auth, config.compression).await)
+}
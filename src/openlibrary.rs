@@ -0,0 +1,180 @@
+//! Open Library の API から実在の本の情報を取得する
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::model::Book;
+
+const API_BASE: &str = "https://openlibrary.org";
+
+/// 表紙画像を配信する Open Library のサーバー
+const COVERS_BASE: &str = "https://covers.openlibrary.org";
+
+/// Open Library への1回のリクエストの制限時間
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// 全てのリクエストで共有するHTTPクライアント（作れなかった場合は次の呼び出しで作り直す）
+fn client() -> Result<&'static reqwest::Client> {
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("failed to build HTTP client")?;
+    Ok(CLIENT.get_or_init(|| client))
+}
+
+#[derive(Debug, Deserialize)]
+struct Named {
+    name: String,
+}
+
+/// `/api/books?jscmd=data` の1冊分
+#[derive(Debug, Deserialize)]
+struct EditionData {
+    title: String,
+    #[serde(default)]
+    subtitle: Option<String>,
+    #[serde(default)]
+    authors: Vec<Named>,
+    #[serde(default)]
+    publish_date: Option<String>,
+    #[serde(default)]
+    subjects: Vec<Named>,
+    #[serde(default)]
+    notes: Option<String>,
+}
+
+/// `/search.json` の検索結果1件分
+#[derive(Debug, Deserialize)]
+struct SearchDoc {
+    title: String,
+    #[serde(default)]
+    author_name: Vec<String>,
+    #[serde(default)]
+    first_publish_year: Option<i32>,
+    #[serde(default)]
+    isbn: Vec<String>,
+    #[serde(default)]
+    subject: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    docs: Vec<SearchDoc>,
+}
+
+/// タグとして取り込む件名の最大数
+const MAX_TAGS: usize = 5;
+
+/// 出版日の文字列（"2001" や "March 5, 2001" など）から年を取り出す
+fn parse_year(date: &str) -> Option<i32> {
+    date.split(|c: char| !c.is_ascii_digit())
+        .find(|part| part.len() == 4)
+        .and_then(|year| year.parse().ok())
+}
+
+fn describe(subtitle: Option<String>, notes: Option<String>) -> String {
+    subtitle
+        .or(notes)
+        .unwrap_or_else(|| "Open Library から取得した本".to_string())
+}
+
+/// ISBNから表紙画像（大きいサイズ）のURLを作る
+///
+/// `default=false` を付けると、表紙のない本で空の画像の代わりに 404 が返る。
+fn cover_url(isbn: &str) -> String {
+    format!("{}/b/isbn/{}-L.jpg?default=false", COVERS_BASE, isbn)
+}
+
+/// URLから画像などのデータを最大 `max_bytes` バイトまで取得する
+pub async fn fetch_bytes(url: &str, max_bytes: usize) -> Result<Vec<u8>> {
+    let mut response = client()?
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("failed to reach {}", url))?
+        .error_for_status()?;
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        data.extend_from_slice(&chunk);
+        if data.len() > max_bytes {
+            anyhow::bail!("{} is larger than {} bytes", url, max_bytes);
+        }
+    }
+    Ok(data)
+}
+
+/// ISBNで本を1冊取得する（見つからなければ `None`）
+pub async fn fetch_by_isbn(isbn: &str) -> Result<Option<Book>> {
+    let key = format!("ISBN:{}", isbn);
+    let mut editions: HashMap<String, EditionData> = client()?
+        .get(format!("{}/api/books", API_BASE))
+        .query(&[("bibkeys", key.as_str()), ("format", "json"), ("jscmd", "data")])
+        .send()
+        .await
+        .context("failed to reach Open Library")?
+        .error_for_status()?
+        .json()
+        .await
+        .context("unexpected response from Open Library")?;
+
+    Ok(editions.remove(&key).map(|edition| Book {
+        title: edition.title,
+        author: edition.authors.into_iter().map(|a| a.name).collect::<Vec<_>>().join(", "),
+        year: edition.publish_date.as_deref().and_then(parse_year).unwrap_or_default(),
+        description: describe(edition.subtitle, edition.notes),
+        isbn: isbn.to_string(),
+        tags: edition.subjects.into_iter().take(MAX_TAGS).map(|s| s.name).collect(),
+        version: 0,
+        cover_path: None,
+        cover_url: Some(cover_url(isbn)),
+        deleted_at: None,
+        author_id: None,
+        series: None,
+    }))
+}
+
+/// タイトルで本を検索し、ISBNの分かっている本を最大 `limit` 冊返す
+pub async fn search_by_title(title: &str, limit: usize) -> Result<Vec<Book>> {
+    let response: SearchResponse = client()?
+        .get(format!("{}/search.json", API_BASE))
+        .query(&[("title", title), ("limit", &limit.to_string())])
+        .send()
+        .await
+        .context("failed to reach Open Library")?
+        .error_for_status()?
+        .json()
+        .await
+        .context("unexpected response from Open Library")?;
+
+    Ok(response
+        .docs
+        .into_iter()
+        .filter_map(|doc| {
+            let isbn = doc.isbn.into_iter().next()?;
+            Some(Book {
+                title: doc.title,
+                author: doc.author_name.join(", "),
+                year: doc.first_publish_year.unwrap_or_default(),
+                description: describe(None, None),
+                cover_url: Some(cover_url(&isbn)),
+                isbn,
+                tags: doc.subject.into_iter().take(MAX_TAGS).collect(),
+                version: 0,
+                cover_path: None,
+                deleted_at: None,
+                author_id: None,
+                series: None,
+            })
+        })
+        .take(limit)
+        .collect())
+}
@@ -0,0 +1,150 @@
+//! ツールの結果の型と、`tools/list` で公開する出力スキーマ
+//!
+//! 出力スキーマを持つツールは、結果のJSONをこの型で組み立てる。プロトコルのリビジョンが
+//! 構造化された結果に対応していれば、同じ値を `structuredContent` にも入れて返す。
+
+use rmcp::model::JsonObject;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::analysis::CatalogStats;
+use crate::model::{Book, RatingSummary, Review};
+use crate::search::FilterMatch;
+
+/// `search` の1ページ分の結果
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SearchOutput {
+    pub keyword: String,
+    /// 指定された絞り込み条件と、それぞれに単独で一致した本の数
+    pub filters: Vec<FilterMatch>,
+    pub count: usize,
+    pub books: Vec<BookHit>,
+    /// 続きのページがある場合に次の検索で `cursor` に渡す値
+    pub next_cursor: Option<String>,
+    /// この結果を `refine_search` で絞り込む場合に渡すID（一定時間で無効になる）
+    pub result_id: String,
+}
+
+/// 検索に一致した本
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BookHit {
+    #[serde(flatten)]
+    pub book: Book,
+    /// `include_rating` を指定した場合の平均評価（レビューがなければ `null`）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rating: Option<Option<RatingSummary>>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// `list_tags` の結果（多い順）
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TagList {
+    pub tags: Vec<TagCount>,
+}
+
+/// `get_average_rating` の結果
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RatingOutput {
+    pub isbn: String,
+    /// レビューがなければ `null`
+    pub rating: Option<RatingSummary>,
+}
+
+/// `list_reviews` の結果
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReviewList {
+    pub isbn: String,
+    pub count: usize,
+    pub reviews: Vec<Review>,
+}
+
+/// `validate_isbn` の結果
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct IsbnValidation {
+    pub input: String,
+    pub valid: bool,
+    /// ハイフンと空白を除いた形
+    pub normalized: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isbn13: Option<String>,
+    /// `978` で始まらないISBN-13や不正なISBNでは省略する
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub isbn10: Option<String>,
+    /// 不正な場合の理由
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CatalogSummary {
+    pub catalog: String,
+    pub books: usize,
+    pub uri: String,
+}
+
+/// `list_catalogs` の結果（既定のカタログが先頭）
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CatalogList {
+    pub catalogs: Vec<CatalogSummary>,
+}
+
+/// `health` の結果
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Health {
+    pub status: String,
+    /// `/readyz` と同じ条件で、新しいリクエストを受けられるか
+    pub ready: bool,
+}
+
+/// `generate_fake_books` の結果
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GeneratedBooks {
+    pub dry_run: bool,
+    pub seed: u64,
+    pub start: usize,
+    pub generated: usize,
+    /// 既に登録されていたため追加しなかった冊数
+    pub skipped: usize,
+    pub first_isbn: Option<String>,
+    pub last_isbn: Option<String>,
+    /// 続きを生成する場合に `start` に渡す値
+    pub next_start: usize,
+}
+
+fn schema<T: JsonSchema>() -> Arc<JsonObject> {
+    let schema = schemars::schema_for!(T);
+    match serde_json::to_value(schema) {
+        Ok(serde_json::Value::Object(object)) => Arc::new(object),
+        _ => Arc::new(JsonObject::new()),
+    }
+}
+
+fn schemas() -> &'static BTreeMap<&'static str, Arc<JsonObject>> {
+    static SCHEMAS: OnceLock<BTreeMap<&'static str, Arc<JsonObject>>> = OnceLock::new();
+    SCHEMAS.get_or_init(|| {
+        BTreeMap::from([
+            ("search", schema::<SearchOutput>()),
+            ("refine_search", schema::<SearchOutput>()),
+            ("list_tags", schema::<TagList>()),
+            ("get_average_rating", schema::<RatingOutput>()),
+            ("list_reviews", schema::<ReviewList>()),
+            ("catalog_stats", schema::<CatalogStats>()),
+            ("validate_isbn", schema::<IsbnValidation>()),
+            ("list_catalogs", schema::<CatalogList>()),
+            ("health", schema::<Health>()),
+            ("generate_fake_books", schema::<GeneratedBooks>()),
+        ])
+    })
+}
+
+/// ツールの出力スキーマ（構造化された結果を返さないツールは `None`）
+pub fn output_schema(tool: &str) -> Option<Arc<JsonObject>> {
+    schemas().get(tool).cloned()
+}
@@ -0,0 +1,43 @@
+//! 一覧・検索結果のカーソルによるページ分割
+
+use rmcp::{Error as McpError, model::PaginatedRequestParam};
+use serde_json::json;
+
+/// `list_resources` / `list_prompts` の1ページあたりの件数
+pub const LIST_PAGE_SIZE: usize = 50;
+
+/// カーソルを先頭からの位置に変換する（省略時は先頭）
+///
+/// カーソルはクライアントにとって不透明な文字列で、前回の応答の
+/// `next_cursor` をそのまま渡してもらう。
+pub fn decode_cursor(cursor: Option<&str>) -> Result<usize, McpError> {
+    match cursor {
+        None => Ok(0),
+        Some(cursor) => cursor.parse().map_err(|_| {
+            McpError::invalid_params(
+                "invalid cursor",
+                Some(json!({
+                    "cursor": cursor
+                })),
+            )
+        }),
+    }
+}
+
+fn encode_cursor(offset: usize) -> String {
+    offset.to_string()
+}
+
+/// `offset` から `page_size` 件を取り出し、続きがあれば次のカーソルを返す
+pub fn paginate<T>(items: Vec<T>, offset: usize, page_size: usize) -> (Vec<T>, Option<String>) {
+    let total = items.len();
+    let page: Vec<T> = items.into_iter().skip(offset).take(page_size).collect();
+    let next = offset + page.len();
+    let next_cursor = (next < total && !page.is_empty()).then(|| encode_cursor(next));
+    (page, next_cursor)
+}
+
+/// 一覧系リクエストのカーソルを取り出す
+pub fn request_cursor(request: &PaginatedRequestParam) -> Option<&str> {
+    request.as_ref()?.cursor.as_deref()
+}
@@ -0,0 +1,141 @@
+//! 本の推薦・要約に使うプロンプト
+//!
+//! 決まったプロンプトのほかに、カタログの本ごとに `discuss_<ISBN>` のプロンプトを用意する。
+//! こちらは一覧のたびに現在のカタログから作るので、本を追加・削除すると一覧も変わる。
+
+use rmcp::{Error as McpError, model::*};
+use serde_json::json;
+
+use crate::model::{Book, format_book};
+
+fn argument(name: &str, description: &str, required: bool) -> PromptArgument {
+    PromptArgument {
+        name: name.to_string(),
+        description: Some(description.to_string()),
+        required: Some(required),
+    }
+}
+
+/// 本ごとに作るプロンプトの名前の接頭辞（後ろにISBNを付ける）
+const DISCUSS_PREFIX: &str = "discuss_";
+
+/// 本について話し合うプロンプトの名前
+pub fn discuss_name(isbn: &str) -> String {
+    format!("{}{}", DISCUSS_PREFIX, isbn)
+}
+
+/// 提供するプロンプトの一覧（決まったプロンプトの後に、本ごとのプロンプトを登録順に並べる）
+pub fn list(books: &[Book]) -> Vec<Prompt> {
+    let mut prompts = vec![
+        Prompt::new(
+            "recommend_book",
+            Some("ジャンルや気分に合う本を蔵書から推薦してもらう"),
+            Some(vec![
+                argument("genre", "読みたいジャンル（例: SF、料理）", true),
+                argument("mood", "今の気分（例: 知的好奇心、癒やされたい）", false),
+            ]),
+        ),
+        Prompt::new(
+            "summarize_book",
+            Some("指定した本の内容を要約してもらう"),
+            Some(vec![argument("isbn", "要約する本のISBN", true)]),
+        ),
+    ];
+    prompts.extend(books.iter().map(|book| {
+        Prompt::new(
+            discuss_name(&book.isbn),
+            Some(format!("『{}』（{}）について話し合う", book.title, book.author)),
+            Some(vec![argument("focus", "話し合いたい観点（例: 登場人物、結末）", false)]),
+        )
+    }));
+    prompts
+}
+
+/// 引数から文字列の値を取り出す（未指定または空の場合は `None`）
+fn optional_argument<'a>(arguments: Option<&'a JsonObject>, name: &str) -> Option<&'a str> {
+    arguments?
+        .get(name)?
+        .as_str()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn required_argument<'a>(arguments: Option<&'a JsonObject>, name: &str) -> Result<&'a str, McpError> {
+    optional_argument(arguments, name).ok_or_else(|| {
+        McpError::invalid_params(
+            "missing prompt argument",
+            Some(json!({
+                "argument": name
+            })),
+        )
+    })
+}
+
+/// 名前と引数からプロンプトのメッセージを組み立てる
+pub fn get(name: &str, arguments: Option<&JsonObject>, books: &[Book]) -> Result<GetPromptResult, McpError> {
+    match name {
+        "recommend_book" => {
+            let genre = required_argument(arguments, "genre")?;
+            let mood = optional_argument(arguments, "mood");
+            let catalog: String = books.iter().map(format_book).collect();
+
+            let mut text = format!("ジャンル「{}」", genre);
+            if let Some(mood) = mood {
+                text.push_str(&format!("で、「{}」という気分", mood));
+            }
+            text.push_str(&format!(
+                "に合う本を、次の蔵書の中から1冊選んで理由とともに推薦してください。\n\n{}",
+                catalog
+            ));
+
+            Ok(GetPromptResult {
+                description: Some(format!("「{}」の本の推薦", genre)),
+                messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+            })
+        }
+        "summarize_book" => {
+            let isbn = required_argument(arguments, "isbn")?;
+            let book = books.iter().find(|book| book.isbn == isbn).ok_or_else(|| {
+                McpError::invalid_params(
+                    "book not found",
+                    Some(json!({
+                        "isbn": isbn
+                    })),
+                )
+            })?;
+
+            Ok(GetPromptResult {
+                description: Some(format!("『{}』の要約", book.title)),
+                messages: vec![PromptMessage::new_text(
+                    PromptMessageRole::User,
+                    format!(
+                        "次の本の内容を3文程度で要約してください。\n\n{}",
+                        format_book(book)
+                    ),
+                )],
+            })
+        }
+        _ => {
+            let Some(isbn) = name.strip_prefix(DISCUSS_PREFIX) else {
+                return Err(McpError::invalid_params("prompt not found", None));
+            };
+            let book = books
+                .iter()
+                .find(|book| book.isbn == isbn)
+                .ok_or_else(|| McpError::invalid_params("prompt not found", Some(json!({ "isbn": isbn }))))?;
+            let mut text = format!(
+                "次の本について、読み終えた人どうしのように話し合いましょう。\n\n{}",
+                format_book(book)
+            );
+            match optional_argument(arguments, "focus") {
+                Some(focus) => text.push_str(&format!("特に「{}」について、あなたの考えを聞かせてください。", focus)),
+                None => text.push_str("まず、この本の印象に残った点を挙げてください。"),
+            }
+
+            Ok(GetPromptResult {
+                description: Some(format!("『{}』についての話し合い", book.title)),
+                messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+            })
+        }
+    }
+}
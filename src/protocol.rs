@@ -0,0 +1,63 @@
+//! クライアントと取り決めるMCPのプロトコルのリビジョンと、リビジョンごとに使える機能
+//!
+//! リビジョンは `YYYY-MM-DD` の日付なので、文字列の大小がそのまま新旧の順になる。
+//! クライアントが対応しているリビジョンを要求すればそれを使い、知らないリビジョンなら
+//! サーバーが対応する最新のリビジョンを返す（クライアントはそれを見て切断するか決める）。
+
+use rmcp::model::ProtocolVersion;
+use serde::Serialize;
+
+/// 対応するリビジョン（新しい順）
+pub const SUPPORTED_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// 対応する最新のリビジョン
+pub const LATEST_VERSION: &str = SUPPORTED_VERSIONS[0];
+
+/// 音声コンテンツ・ツールの注釈・`completions` の宣言が加わったリビジョン
+const AUDIO_CONTENT_SINCE: &str = "2025-03-26";
+
+/// ツールの出力スキーマと構造化された結果が加わったリビジョン
+const STRUCTURED_OUTPUT_SINCE: &str = "2025-06-18";
+
+/// `ProtocolVersion` を `"2025-03-26"` のような文字列にする
+pub fn version_str(version: &ProtocolVersion) -> String {
+    serde_json::to_value(version)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// 文字列のリビジョンを `ProtocolVersion` にする
+pub fn to_protocol_version(version: &str) -> ProtocolVersion {
+    serde_json::from_value(serde_json::Value::from(version)).unwrap_or(ProtocolVersion::V_2024_11_05)
+}
+
+/// クライアントが要求したリビジョンに対して、このセッションで使うリビジョンを決める
+pub fn negotiate(requested: &str) -> &'static str {
+    SUPPORTED_VERSIONS
+        .iter()
+        .find(|version| **version == requested)
+        .copied()
+        .unwrap_or(LATEST_VERSION)
+}
+
+/// 取り決めたリビジョンで使える機能
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Features {
+    /// `audio` のコンテンツを返せる
+    pub audio_content: bool,
+    /// `completions` の機能を宣言する（それ以前は宣言なしで補完を使っていた）
+    pub completions: bool,
+    /// ツールの結果に `structuredContent` を付けられる
+    pub structured_output: bool,
+}
+
+impl Features {
+    pub fn of(version: &str) -> Self {
+        Self {
+            audio_content: version >= AUDIO_CONTENT_SINCE,
+            completions: version >= AUDIO_CONTENT_SINCE,
+            structured_output: version >= STRUCTURED_OUTPUT_SINCE,
+        }
+    }
+}
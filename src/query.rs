@@ -0,0 +1,286 @@
+//! `AND` / `OR` / `NOT` と括弧を使える検索式
+//!
+//! 例: `author:"火星" AND (year:>2200 OR tag:gardening)`
+//!
+//! 語を並べただけの場合は `AND` として扱う。演算子は `AND` `OR` `NOT`（大文字のみ）で、
+//! `NOT` が最も強く、`OR` が最も弱く結合する。
+
+use rmcp::Error as McpError;
+use serde_json::json;
+use std::fmt;
+
+use crate::model::Book;
+use crate::search::{QueryTerm, SearchField, SearchFilter};
+
+/// 解析済みの検索式
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    /// タイトル・著者・説明・ISBNの部分一致
+    Term(QueryTerm),
+    /// タグや出版年の条件
+    Filter(SearchFilter),
+}
+
+impl Expr {
+    pub fn matches(&self, book: &Book) -> bool {
+        match self {
+            Self::And(exprs) => exprs.iter().all(|expr| expr.matches(book)),
+            Self::Or(exprs) => exprs.iter().any(|expr| expr.matches(book)),
+            Self::Not(expr) => !expr.matches(book),
+            Self::Term(term) => term.matches(book),
+            Self::Filter(filter) => filter.matches(book),
+        }
+    }
+}
+
+/// 検索式を解釈できなかった位置と理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 問題のある箇所の、先頭から数えた文字の位置（0始まり）
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}文字目: {}", self.position + 1, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+
+    /// 位置を含む `invalid_params` エラーに変換する
+    pub fn into_mcp_error(self, query: &str) -> McpError {
+        McpError::invalid_params(
+            "invalid query expression",
+            Some(json!({
+                "query": query,
+                "position": self.position,
+                "message": self.message,
+            })),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    /// `field:` が付いていれば `Some(field)`
+    Word { field: Option<String>, text: String },
+}
+
+/// 位置付きのトークンに分ける
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        match c {
+            '(' => {
+                tokens.push((start, Token::Open));
+                i += 1;
+                continue;
+            }
+            ')' => {
+                tokens.push((start, Token::Close));
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        // `field:` の接頭辞（英字のみ）を読み取る
+        let mut field = None;
+        let mut j = i;
+        while j < chars.len() && chars[j].is_ascii_alphabetic() {
+            j += 1;
+        }
+        if j > i && j < chars.len() && chars[j] == ':' {
+            field = Some(chars[i..j].iter().collect::<String>());
+            i = j + 1;
+        }
+
+        let (text, quoted) = if chars.get(i) == Some(&'"') {
+            let open = i;
+            i += 1;
+            let mut text = String::new();
+            loop {
+                match chars.get(i) {
+                    Some('"') => {
+                        i += 1;
+                        break;
+                    }
+                    Some('\\') if chars.get(i + 1).is_some() => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(c) => {
+                        text.push(*c);
+                        i += 1;
+                    }
+                    None => return Err(ParseError::new(open, "引用符が閉じられていません")),
+                }
+            }
+            (text, true)
+        } else {
+            let mut text = String::new();
+            while i < chars.len() && !chars[i].is_whitespace() && chars[i] != '(' && chars[i] != ')' {
+                text.push(chars[i]);
+                i += 1;
+            }
+            (text, false)
+        };
+
+        let token = match (&field, text.as_str(), quoted) {
+            (None, "AND", false) => Token::And,
+            (None, "OR", false) => Token::Or,
+            (None, "NOT", false) => Token::Not,
+            (Some(_), "", _) => return Err(ParseError::new(start, "フィールドの値がありません")),
+            _ => Token::Word { field, text },
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// 入力の末尾の位置（式が途中で終わったときのエラーに使う）
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(position, _)| *position)
+    }
+
+    /// or := and ("OR" and)*
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut exprs = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            exprs.push(self.and()?);
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { Expr::Or(exprs) })
+    }
+
+    /// and := unary ("AND"? unary)*
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut exprs = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next += 1;
+                    exprs.push(self.unary()?);
+                }
+                Some(Token::Not | Token::Open | Token::Word { .. }) => exprs.push(self.unary()?),
+                _ => break,
+            }
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { Expr::And(exprs) })
+    }
+
+    /// unary := "NOT" unary | "(" or ")" | word
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        let position = self.position();
+        let Some((_, token)) = self.tokens.get(self.next).cloned() else {
+            return Err(ParseError::new(position, "式が途中で終わっています"));
+        };
+        self.next += 1;
+        match token {
+            Token::Not => Ok(Expr::Not(Box::new(self.unary()?))),
+            Token::Open => {
+                let expr = self.or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(ParseError::new(self.position(), "対応する ')' がありません"));
+                }
+                self.next += 1;
+                Ok(expr)
+            }
+            Token::Close => Err(ParseError::new(position, "対応する '(' がありません")),
+            Token::And | Token::Or => Err(ParseError::new(position, "演算子の前に条件がありません")),
+            Token::Word { field, text } => word(position, field.as_deref(), text),
+        }
+    }
+}
+
+/// `field:値` を条件に変換する
+fn word(position: usize, field: Option<&str>, text: String) -> Result<Expr, ParseError> {
+    let Some(field) = field else {
+        return Ok(Expr::Term(QueryTerm { field: None, text }));
+    };
+    match field.to_lowercase().as_str() {
+        "tag" | "tags" => Ok(Expr::Filter(SearchFilter::Tag(text))),
+        "year" => year(position, &text),
+        other => match SearchField::from_prefix(other) {
+            Some(field) => Ok(Expr::Term(QueryTerm { field: Some(field), text })),
+            None => Err(ParseError::new(position, format!("'{}' は未知のフィールドです", field))),
+        },
+    }
+}
+
+/// `year:2200` `year:>2200` `year:<=2300` を出版年の条件に変換する
+fn year(position: usize, text: &str) -> Result<Expr, ParseError> {
+    let (operator, value) = [">=", "<=", ">", "<", "="]
+        .iter()
+        .find_map(|operator| text.strip_prefix(operator).map(|value| (*operator, value)))
+        .unwrap_or(("=", text));
+    let year: i32 = value
+        .parse()
+        .map_err(|_| ParseError::new(position, format!("'{}' は出版年として解釈できません", value)))?;
+    Ok(match operator {
+        ">=" => Expr::Filter(SearchFilter::YearMin(year)),
+        "<=" => Expr::Filter(SearchFilter::YearMax(year)),
+        ">" => Expr::Filter(SearchFilter::YearMin(year.saturating_add(1))),
+        "<" => Expr::Filter(SearchFilter::YearMax(year.saturating_sub(1))),
+        _ => Expr::And(vec![
+            Expr::Filter(SearchFilter::YearMin(year)),
+            Expr::Filter(SearchFilter::YearMax(year)),
+        ]),
+    })
+}
+
+/// 検索式を解析する
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(input)?;
+    let end = input.chars().count();
+    if tokens.is_empty() {
+        return Err(ParseError::new(0, "検索式が空です"));
+    }
+    let mut parser = Parser { tokens, next: 0, end };
+    let expr = parser.or()?;
+    if parser.next < parser.tokens.len() {
+        let message = match parser.peek() {
+            Some(Token::Close) => "対応する '(' がありません",
+            _ => "式の続きを解釈できません",
+        };
+        return Err(ParseError::new(parser.position(), message));
+    }
+    Ok(expr)
+}
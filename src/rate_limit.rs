@@ -0,0 +1,69 @@
+//! セッションごとのツール呼び出し回数の制限
+
+use rmcp::Error as McpError;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::RateLimitConfig;
+
+/// トークンバケット方式のレート制限
+///
+/// 最大 `burst` 回まで連続して呼び出せ、1秒あたり `per_second` 回分ずつ回復する。
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let capacity = f64::from(config.burst);
+        Self {
+            capacity,
+            per_second: config.per_second,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// 呼び出し1回分を消費する
+    ///
+    /// 残りがなければ、次の1回分が回復するまでの時間を `Err` で返す。
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().expect("rate limit lock poisoned");
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.per_second).min(self.capacity);
+        state.refilled_at = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - state.tokens) / self.per_second))
+        }
+    }
+
+    /// 制限を超えた呼び出しを、再試行までの秒数付きのエラーとして断る
+    pub fn check(&self, tool: &str) -> Result<(), McpError> {
+        self.try_acquire().map_err(|retry_after| {
+            McpError::invalid_request(
+                "tool call rate limit exceeded",
+                Some(json!({
+                    "tool": tool,
+                    "retry_after_secs": retry_after.as_secs_f64(),
+                })),
+            )
+        })
+    }
+}
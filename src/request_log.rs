@@ -0,0 +1,88 @@
+//! MCPリクエストごとの構造化ログ
+//!
+//! リクエストごとに相関IDを振ったスパンの中で処理し、受信・完了（所要時間と結果の分類）を
+//! DEBUGレベルで記録する。ツール内のログも同じスパンに入るため、相関IDで絞り込める。
+//! スパンはメソッド・ツール名・セッションの番号を持ち、`otlp` 機能で有効にしたトレースの
+//! 送信では1回の呼び出しが1つのトレースのスパンになる。
+
+use rmcp::{Error as McpError, model::RequestId};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use tracing::Instrument;
+
+static NEXT_CORRELATION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// MCPのエラーコードをログ用の分類名に変換する
+fn classify(error: &McpError) -> &'static str {
+    match error.code.0 {
+        -32700 => "parse_error",
+        -32600 => "invalid_request",
+        -32601 => "method_not_found",
+        -32602 => "invalid_params",
+        -32603 => "internal_error",
+        -32002 => "resource_not_found",
+        _ => "other",
+    }
+}
+
+/// `handler` を相関ID付きのスパンの中で実行し、受信と結果をログに残す
+///
+/// `subject` にはツール名やリソースのURIなど、リクエストの対象を渡す。`session` は
+/// セッションを区別するサーバーでのセッションの番号。
+/// `outcome` は成功した応答の分類（`isError` 付きのツール結果を区別するためなど）を返す。
+pub(crate) async fn logged<T, F>(
+    method: &'static str,
+    subject: Option<&str>,
+    session: Option<u64>,
+    request_id: &RequestId,
+    outcome: impl FnOnce(&T) -> &'static str,
+    handler: F,
+) -> Result<T, McpError>
+where
+    F: Future<Output = Result<T, McpError>>,
+{
+    let correlation_id = NEXT_CORRELATION_ID.fetch_add(1, Ordering::Relaxed);
+    let tool = subject.filter(|_| method == "tools/call");
+    let name = match subject {
+        Some(subject) => format!("{} {}", method, subject),
+        None => method.to_string(),
+    };
+    let span = tracing::info_span!(
+        "mcp_request",
+        otel.name = %name,
+        otel.status_code = tracing::field::Empty,
+        correlation_id,
+        method,
+        tool,
+        subject,
+        session,
+    );
+
+    async move {
+        tracing::debug!(request_id = ?request_id, "request received");
+        let started = Instant::now();
+        let result = handler.await;
+        let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        match &result {
+            Ok(response) => tracing::debug!(duration_ms, outcome = outcome(response), "request completed"),
+            Err(error) => {
+                tracing::Span::current().record("otel.status_code", "ERROR");
+                tracing::debug!(
+                    duration_ms,
+                    outcome = classify(error),
+                    error = %error.message,
+                    "request failed"
+                )
+            }
+        }
+        result
+    }
+    .instrument(span)
+    .await
+}
+
+/// 応答の中身で分類を変えない場合の `outcome`
+pub(crate) fn ok<T>(_: &T) -> &'static str {
+    "ok"
+}
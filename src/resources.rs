@@ -0,0 +1,259 @@
+//! 本を `book://` URIのMCPリソースとして公開する
+//!
+//! 名前付きのカタログの本は、`book://{catalog}/isbn/{isbn}` のようにURIの先頭にカタログ名を付ける。
+
+use rmcp::{Error as McpError, model::*};
+use serde_json::json;
+
+use crate::cover::Cover;
+use crate::model::{Book, format_book};
+
+/// 全ての本をまとめたリソースのURI
+pub const CATALOG_URI: &str = "book://catalog";
+
+/// 1冊ごとのリソースURIの接頭辞
+const ISBN_URI_PREFIX: &str = "book://isbn/";
+
+/// 著者ごとのリソースURIの接頭辞
+const AUTHOR_URI_PREFIX: &str = "book://author/";
+
+/// 表紙画像のリソースURIの接頭辞
+const COVER_URI_PREFIX: &str = "book://cover/";
+
+/// 既定のカタログのURIで1つ目の区切りに使う語（カタログ名には使えない）
+pub(crate) const RESERVED_SEGMENTS: &[&str] = &["catalog", "isbn", "author", "cover"];
+
+/// URIの先頭の `book://{catalog}/` から名前付きのカタログの名前を取り出す（既定のカタログなら `None`）
+pub fn catalog_of(uri: &str) -> Option<&str> {
+    let (first, _) = uri.strip_prefix("book://")?.split_once('/')?;
+    (!first.is_empty() && !RESERVED_SEGMENTS.contains(&first)).then_some(first)
+}
+
+/// 既定のカタログでのURIに直す（カタログ名を取り除く）
+fn strip_catalog(uri: &str) -> String {
+    match catalog_of(uri) {
+        Some(catalog) => uri.replacen(&format!("book://{}/", catalog), "book://", 1),
+        None => uri.to_string(),
+    }
+}
+
+/// 既定のカタログでのURIに、カタログ名を付ける（`catalog` が `None` ならそのまま）
+pub fn in_catalog(catalog: Option<&str>, uri: &str) -> String {
+    match catalog {
+        Some(catalog) => uri.replacen("book://", &format!("book://{}/", catalog), 1),
+        None => uri.to_string(),
+    }
+}
+
+/// ISBNから1冊分のリソースURIを作る
+pub fn isbn_uri(isbn: &str) -> String {
+    format!("{}{}", ISBN_URI_PREFIX, isbn)
+}
+
+/// リソースの内容の形式（URIの `?format=json` / `?format=text` で指定、省略時はJSON）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceFormat {
+    Json,
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BookResource {
+    Catalog,
+    Isbn(String),
+    Author(String),
+}
+
+/// URIのパス部分の `%XX` をデコードする（不正な場合は `None`）
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// 表紙画像のリソースURIであれば、そのISBNを返す
+///
+/// 表紙は画像を読み込む必要があるため、`read` ではなくサーバーが非同期に読み出す。
+pub fn cover_isbn(uri: &str) -> Option<String> {
+    percent_decode(strip_catalog(uri).strip_prefix(COVER_URI_PREFIX)?).filter(|isbn| !isbn.is_empty())
+}
+
+/// 表紙画像をBase64のblobとしてリソースの内容にする
+pub fn cover_contents(uri: &str, cover: &Cover) -> ReadResourceResult {
+    ReadResourceResult {
+        contents: vec![ResourceContents::BlobResourceContents {
+            uri: uri.to_string(),
+            mime_type: Some(cover.mime_type.to_string()),
+            blob: cover.base64(),
+        }],
+    }
+}
+
+fn parse_uri(uri: &str) -> Option<(BookResource, ResourceFormat)> {
+    let uri = strip_catalog(uri);
+    let (path, query) = match uri.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (uri, None),
+    };
+    let format = match query {
+        None | Some("format=json") => ResourceFormat::Json,
+        Some("format=text") => ResourceFormat::Text,
+        Some(_) => return None,
+    };
+
+    let resource = if path == CATALOG_URI {
+        BookResource::Catalog
+    } else if let Some(isbn) = path.strip_prefix(ISBN_URI_PREFIX) {
+        let isbn = percent_decode(isbn)?;
+        if isbn.is_empty() {
+            return None;
+        }
+        BookResource::Isbn(isbn)
+    } else {
+        let author = percent_decode(path.strip_prefix(AUTHOR_URI_PREFIX)?)?;
+        if author.is_empty() {
+            return None;
+        }
+        BookResource::Author(author)
+    };
+    Some((resource, format))
+}
+
+fn resource(uri: String, name: String, description: String) -> Resource {
+    let mut raw = RawResource::new(uri, name);
+    raw.description = Some(description);
+    raw.mime_type = Some("application/json".to_string());
+    raw.no_annotation()
+}
+
+/// カタログ全体のリソースと、本ごとのリソースを列挙する（`catalog` は名前付きのカタログの名前）
+pub fn list(catalog: Option<&str>, books: &[Book]) -> Vec<Resource> {
+    let (name, description) = match catalog {
+        Some(catalog) => (format!("{}/catalog", catalog), format!("カタログ {} の全ての本（{}冊）", catalog, books.len())),
+        None => ("catalog".to_string(), format!("全ての本（{}冊）", books.len())),
+    };
+    let mut resources = vec![resource(in_catalog(catalog, CATALOG_URI), name, description)];
+    resources.extend(books.iter().map(|book| {
+        resource(
+            in_catalog(catalog, &isbn_uri(&book.isbn)),
+            book.title.clone(),
+            format!("{}（{}）", book.title, book.author),
+        )
+    }));
+    resources
+}
+
+fn template(uri_template: &str, name: &str, description: &str, mime_type: &str) -> ResourceTemplate {
+    RawResourceTemplate {
+        uri_template: uri_template.to_string(),
+        name: name.to_string(),
+        description: Some(description.to_string()),
+        mime_type: Some(mime_type.to_string()),
+    }
+    .no_annotation()
+}
+
+/// クライアントがURIを組み立てるためのリソーステンプレートを列挙する
+pub fn templates() -> Vec<ResourceTemplate> {
+    vec![
+        template(
+            "book://isbn/{isbn}",
+            "book_by_isbn",
+            "ISBNを指定して本を1冊取得する",
+            "application/json",
+        ),
+        template(
+            "book://author/{author}",
+            "books_by_author",
+            "著者名を指定してその著者の本を一覧する（完全一致、大文字小文字は区別しない）",
+            "application/json",
+        ),
+        template(
+            "book://cover/{isbn}",
+            "book_cover",
+            "ISBNを指定して本の表紙画像を取得する（cover_path または cover_url のある本のみ）",
+            "image/*",
+        ),
+        template(
+            "book://{catalog}/isbn/{isbn}",
+            "catalog_book_by_isbn",
+            "名前付きのカタログからISBNを指定して本を1冊取得する",
+            "application/json",
+        ),
+    ]
+}
+
+pub fn not_found(uri: &str) -> McpError {
+    McpError::resource_not_found(
+        "resource_not_found",
+        Some(json!({
+            "uri": uri
+        })),
+    )
+}
+
+fn contents(uri: &str, format: ResourceFormat, json: serde_json::Value, text: String) -> ResourceContents {
+    match format {
+        ResourceFormat::Json => ResourceContents::TextResourceContents {
+            uri: uri.to_string(),
+            mime_type: Some("application/json".to_string()),
+            text: json.to_string(),
+        },
+        ResourceFormat::Text => ResourceContents::TextResourceContents {
+            uri: uri.to_string(),
+            mime_type: Some("text/plain".to_string()),
+            text,
+        },
+    }
+}
+
+/// URIに対応するリソースの内容を読み出す
+pub fn read(uri: &str, books: &[Book]) -> Result<ReadResourceResult, McpError> {
+    let (resource, format) = parse_uri(uri).ok_or_else(|| not_found(uri))?;
+    let contents = match resource {
+        BookResource::Catalog => contents(
+            uri,
+            format,
+            json!(books),
+            books.iter().map(format_book).collect::<String>(),
+        ),
+        BookResource::Isbn(isbn) => {
+            let book = books
+                .iter()
+                .find(|book| book.isbn == isbn)
+                .ok_or_else(|| not_found(uri))?;
+            contents(uri, format, json!(book), format_book(book))
+        }
+        BookResource::Author(author) => {
+            let author = author.to_lowercase();
+            let books: Vec<&Book> = books
+                .iter()
+                .filter(|book| book.author.to_lowercase() == author)
+                .collect();
+            if books.is_empty() {
+                return Err(not_found(uri));
+            }
+            contents(
+                uri,
+                format,
+                json!(books),
+                books.iter().map(|book| format_book(book)).collect::<String>(),
+            )
+        }
+    };
+
+    Ok(ReadResourceResult {
+        contents: vec![contents],
+    })
+}
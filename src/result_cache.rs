@@ -0,0 +1,79 @@
+//! `refine_search` で絞り込めるよう、セッションごとに最近の検索結果を保持する
+//!
+//! 結果は一致した本のISBNの並びとして保存する。有効期限を過ぎたものは次に読み書きしたときに捨て、
+//! 保持できる数を超えた場合は古いものから捨てる。
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 検索結果を保持する秒数の既定値
+pub const DEFAULT_RESULT_TTL_SECS: u64 = 300;
+
+/// 1セッションで保持する検索結果の数
+const MAX_CACHED_RESULTS: usize = 32;
+
+#[derive(Debug)]
+struct CachedResult {
+    id: String,
+    isbns: Vec<String>,
+    stored_at: Instant,
+}
+
+/// 最近の検索結果
+#[derive(Debug)]
+pub struct ResultCache {
+    ttl: Duration,
+    next_id: AtomicU64,
+    results: Mutex<VecDeque<CachedResult>>,
+}
+
+impl Default for ResultCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(DEFAULT_RESULT_TTL_SECS))
+    }
+}
+
+impl ResultCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            next_id: AtomicU64::new(1),
+            results: Mutex::default(),
+        }
+    }
+
+    /// 結果を保持できる時間
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn evict_expired(&self, results: &mut VecDeque<CachedResult>) {
+        let now = Instant::now();
+        results.retain(|result| now.duration_since(result.stored_at) < self.ttl);
+    }
+
+    /// 結果を保存し、`get` に渡すIDを返す
+    pub fn insert(&self, isbns: Vec<String>) -> String {
+        let id = format!("r{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut results = self.results.lock().expect("result cache lock poisoned");
+        self.evict_expired(&mut results);
+        results.push_back(CachedResult {
+            id: id.clone(),
+            isbns,
+            stored_at: Instant::now(),
+        });
+        while results.len() > MAX_CACHED_RESULTS {
+            results.pop_front();
+        }
+        id
+    }
+
+    /// 保存した結果のISBNを一致した順に返す（期限切れや捨てた結果は `None`）
+    pub fn get(&self, id: &str) -> Option<Vec<String>> {
+        let mut results = self.results.lock().expect("result cache lock poisoned");
+        self.evict_expired(&mut results);
+        results.iter().find(|result| result.id == id).map(|result| result.isbns.clone())
+    }
+}
@@ -0,0 +1,64 @@
+//! クライアントが `roots/list` で公開するルートによる、ファイルの置き場所の制限
+//!
+//! ルートは `file://` のURIで渡される。ファイルの場所はいずれかのルートの下でなければならない。
+
+use rmcp::model::Root;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// ファイルの場所をルートの下に解決できなかった理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootsError {
+    /// クライアントが `file://` のルートを1つも公開していない
+    NoRoots,
+    /// 指定した場所がどのルートの下にもない
+    OutsideRoots(PathBuf),
+    /// 置き場所のディレクトリが存在しない、または読み取れない
+    Unreachable(PathBuf, String),
+}
+
+impl fmt::Display for RootsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoRoots => write!(f, "クライアントがファイルのルートを公開していません"),
+            Self::OutsideRoots(path) => write!(f, "{} はクライアントのルートの外にあります", path.display()),
+            Self::Unreachable(path, reason) => write!(f, "{} を開けません: {}", path.display(), reason),
+        }
+    }
+}
+
+impl std::error::Error for RootsError {}
+
+/// `file://` のルートをローカルのパスに変換する（それ以外のURIは無視する）
+pub fn root_paths(roots: &[Root]) -> Vec<PathBuf> {
+    roots
+        .iter()
+        .filter_map(|root| url::Url::parse(&root.uri).ok())
+        .filter(|url| url.scheme() == "file")
+        .filter_map(|url| url.to_file_path().ok())
+        .collect()
+}
+
+/// `path` をいずれかのルートの下の絶対パスに解決する
+///
+/// 相対パスは最初のルートからの相対として扱う。`..` やシンボリックリンクでルートの外へ出る
+/// 場所を拒否するため、実際のパスに変換してから比べる。
+pub fn resolve(path: &Path, roots: &[PathBuf]) -> Result<PathBuf, RootsError> {
+    let first = roots.first().ok_or(RootsError::NoRoots)?;
+    let path = if path.is_absolute() { path.to_path_buf() } else { first.join(path) };
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Err(RootsError::OutsideRoots(path));
+    };
+    let unreachable = |e: std::io::Error| RootsError::Unreachable(path.clone(), e.to_string());
+    // 既にあるファイルはリンク先まで、まだないファイルは親ディレクトリを実際のパスに変換する
+    let resolved = if path.exists() {
+        path.canonicalize().map_err(unreachable)?
+    } else {
+        parent.canonicalize().map_err(unreachable)?.join(file_name)
+    };
+    let inside = roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| resolved.starts_with(root));
+    if inside { Ok(resolved) } else { Err(RootsError::OutsideRoots(resolved)) }
+}
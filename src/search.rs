@@ -0,0 +1,674 @@
+//! キーワードの解釈、正規化、絞り込みと検索の実行
+
+use rmcp::Error as McpError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::i18n::Lang;
+use crate::model::Book;
+use crate::query::{self, Expr};
+use crate::{fuzzy, isbn, pagination};
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+pub struct SearchQuery {
+    #[schemars(description = "検索キーワード（空白区切りのOR検索。`title:` `author:` `description:` `isbn:` でフィールドを指定可能）")]
+    #[serde(default)]
+    pub keyword: String,
+    #[schemars(description = "AND / OR / NOT と括弧を使える検索式で絞り込む（例: author:\"火星\" AND (year:>2200 OR tag:gardening)）")]
+    pub query: Option<String>,
+    #[schemars(description = "著者名で絞り込む（部分一致）")]
+    pub author: Option<String>,
+    #[schemars(description = "この年以降に出版された本に絞り込む")]
+    pub year_min: Option<i32>,
+    #[schemars(description = "この年以前に出版された本に絞り込む")]
+    pub year_max: Option<i32>,
+    #[schemars(description = "ISBNで絞り込む（ハイフンの有無は区別しない）")]
+    pub isbn: Option<String>,
+    #[schemars(description = "全てのタグを持つ本に絞り込む（大文字小文字は区別しない）")]
+    pub tags: Option<Vec<String>>,
+    #[schemars(description = "最大結果数")]
+    pub limit: Option<i32>,
+    #[schemars(description = "先頭から読み飛ばす件数（cursor より優先）")]
+    pub offset: Option<i32>,
+    #[schemars(description = "前回の検索結果の next_cursor（続きのページを取得する）")]
+    pub cursor: Option<String>,
+    #[schemars(description = "結果の形式（\"text\" または \"json\"、省略時は text）")]
+    pub output_format: Option<OutputFormat>,
+    #[schemars(description = "あいまい検索を行うか（タイプミスや表記揺れを許容し、類似度の高い順に並べる）")]
+    pub fuzzy: Option<bool>,
+    #[schemars(description = "あいまい検索で一致とみなす類似度の下限（0.0〜1.0、省略時は0.7）")]
+    pub threshold: Option<f64>,
+    #[schemars(description = "全文検索インデックスを使い、BM25の関連度順に並べるか（サーバーが fulltext 機能付きでビルドされている場合のみ）")]
+    pub ranked: Option<bool>,
+    #[schemars(description = "埋め込みベクトルの近さで、文章の意味が近い本を類似度順に並べるか（例: \"面白いSFの料理本\"。サーバーで意味検索が設定されている場合のみ）")]
+    pub semantic: Option<bool>,
+    #[schemars(description = "各本のレビューの平均評価と件数を結果に含めるか")]
+    pub include_rating: Option<bool>,
+    #[schemars(description = "テキスト形式の結果の言語（\"ja\" または \"en\"、省略時はサーバーの設定）")]
+    pub lang: Option<Lang>,
+    #[schemars(description = "テキスト形式の結果で、キーワードに一致した箇所を目印（既定は **）で囲むか")]
+    pub highlight: Option<bool>,
+    #[schemars(description = "ごみ箱に移した本も結果に含めるか（省略時はセッションの設定、既定は含めない）")]
+    pub include_deleted: Option<bool>,
+    #[schemars(description = "結果の並べ方（\"relevance\" \"title_asc\" \"title_desc\" \"author_asc\" \"author_desc\" \"year_asc\" \"year_desc\"、省略時は relevance）")]
+    pub sort_by: Option<SortBy>,
+}
+
+/// 検索結果の並べ方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    /// 関連度の高い順（`ranked` / `semantic` / `fuzzy` 以外では登録順）
+    #[default]
+    Relevance,
+    TitleAsc,
+    TitleDesc,
+    AuthorAsc,
+    AuthorDesc,
+    YearAsc,
+    YearDesc,
+}
+
+/// 検索結果の返し方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// 人が読むための整形済みテキスト
+    #[default]
+    Text,
+    /// 機械処理向けのJSON
+    Json,
+}
+
+/// 検索語と検索対象の両方に適用する正規化の1ステップ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizeStep {
+    /// 小文字化
+    Lowercase,
+    /// Unicode NFKC 正規化
+    Nfkc,
+    /// カタカナの長音記号（ー）を取り除く
+    FoldLongVowel,
+    /// 全角英数字・記号を半角に揃える
+    FoldWidth,
+    /// カタカナをひらがなに揃え、かなの種類を区別せずに一致させる
+    FoldKana,
+}
+
+impl NormalizeStep {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "lowercase" => Some(Self::Lowercase),
+            "nfkc" => Some(Self::Nfkc),
+            "long_vowel" => Some(Self::FoldLongVowel),
+            "width" => Some(Self::FoldWidth),
+            "kana" => Some(Self::FoldKana),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, text: &str) -> String {
+        match self {
+            Self::Lowercase => text.to_lowercase(),
+            Self::Nfkc => text.nfkc().collect(),
+            Self::FoldLongVowel => text.chars().filter(|c| !matches!(c, 'ー' | 'ｰ')).collect(),
+            Self::FoldWidth => text
+                .chars()
+                .map(|c| match c {
+                    '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+                    '\u{3000}' => ' ',
+                    _ => c,
+                })
+                .collect(),
+            // 半角カタカナは先に NFKC で全角にしておく必要がある
+            Self::FoldKana => text
+                .chars()
+                .map(|c| match c {
+                    '\u{30A1}'..='\u{30F6}' | '\u{30FD}'..='\u{30FE}' => {
+                        char::from_u32(c as u32 - 0x60).unwrap_or(c)
+                    }
+                    _ => c,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// 順番に適用される正規化ステップの列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Normalizer {
+    steps: Vec<NormalizeStep>,
+}
+
+impl Normalizer {
+    pub fn new(steps: Vec<NormalizeStep>) -> Self {
+        Self { steps }
+    }
+
+    /// `SEARCH_NORMALIZATION`（例: `nfkc,lowercase,long_vowel,kana`）から正規化の手順を読み込む
+    ///
+    /// 未設定の場合は NFKC 正規化と小文字化を行う。未知のステップ名は無視する。
+    fn from_env() -> Self {
+        match std::env::var("SEARCH_NORMALIZATION") {
+            Ok(value) => Self::new(value.split(',').filter_map(NormalizeStep::from_name).collect()),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn normalize(&self, text: &str) -> String {
+        self.steps
+            .iter()
+            .fold(text.to_string(), |text, step| step.apply(&text))
+    }
+}
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self::new(vec![NormalizeStep::Nfkc, NormalizeStep::Lowercase])
+    }
+}
+
+static NORMALIZER: OnceLock<Normalizer> = OnceLock::new();
+
+/// 小書きのかなを並のかなにする
+fn large_kana(c: char) -> char {
+    match c {
+        'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'っ' | 'ゃ' | 'ゅ' | 'ょ' | 'ゎ' => char::from_u32(c as u32 + 1).unwrap_or(c),
+        'ゕ' => 'か',
+        'ゖ' => 'け',
+        _ => c,
+    }
+}
+
+/// 日本語の文字列を五十音順に近い順で並べるためのキー
+///
+/// まず全角半角・大文字小文字・ひらがなとカタカナ・濁点と半濁点・小書き・長音記号の違いを無視して比べ、
+/// 同じになった場合だけ、それらを区別して比べる。漢字は読みが分からないため文字コード順になり、
+/// 英数字、かな、漢字の順に並ぶ。
+pub fn collation_key(text: &str) -> (String, String) {
+    let exact = [NormalizeStep::Nfkc, NormalizeStep::Lowercase, NormalizeStep::FoldKana]
+        .iter()
+        .fold(text.trim().to_string(), |text, step| step.apply(&text));
+    let base = exact
+        .nfd()
+        .filter(|c| !matches!(c, '\u{3099}' | '\u{309A}' | 'ー'))
+        .map(large_kana)
+        .collect();
+    (base, exact)
+}
+
+/// `sort_by` の順に並べ替える（同じ値の本はタイトル、ISBNの順にして、毎回同じ順序にする）
+fn sort_results(books: &mut [&Book], sort_by: SortBy) {
+    use std::cmp::Reverse;
+
+    let ties = |book: &Book| (collation_key(&book.title), book.isbn.clone());
+    match sort_by {
+        SortBy::Relevance => {}
+        SortBy::TitleAsc => books.sort_by_cached_key(|book| ties(book)),
+        SortBy::TitleDesc => books.sort_by_cached_key(|book| (Reverse(collation_key(&book.title)), book.isbn.clone())),
+        SortBy::AuthorAsc => books.sort_by_cached_key(|book| (collation_key(&book.author), ties(book))),
+        SortBy::AuthorDesc => books.sort_by_cached_key(|book| (Reverse(collation_key(&book.author)), ties(book))),
+        SortBy::YearAsc => books.sort_by_cached_key(|book| (book.year, ties(book))),
+        SortBy::YearDesc => books.sort_by_cached_key(|book| (Reverse(book.year), ties(book))),
+    }
+}
+
+pub(crate) fn normalizer() -> &'static Normalizer {
+    NORMALIZER.get_or_init(Normalizer::from_env)
+}
+
+/// 検索語を絞り込む対象フィールド
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Title,
+    Author,
+    Description,
+    Isbn,
+}
+
+impl SearchField {
+    /// `title:` のような接頭辞からフィールドを解決する
+    pub(crate) fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix.to_lowercase().as_str() {
+            "title" => Some(Self::Title),
+            "author" => Some(Self::Author),
+            "description" => Some(Self::Description),
+            "isbn" => Some(Self::Isbn),
+            _ => None,
+        }
+    }
+
+    fn value<'a>(&self, book: &'a Book) -> &'a str {
+        match self {
+            Self::Title => &book.title,
+            Self::Author => &book.author,
+            Self::Description => &book.description,
+            Self::Isbn => &book.isbn,
+        }
+    }
+}
+
+/// 検索キーワードを分解した1語分の条件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryTerm {
+    /// `None` の場合はタイトル・著者・説明のいずれかに一致すればよい
+    pub field: Option<SearchField>,
+    pub text: String,
+}
+
+impl QueryTerm {
+    /// 対象フィールドのうち最も近いものとの類似度（0.0〜1.0）
+    fn fuzzy_score(&self, book: &Book) -> f64 {
+        let normalizer = normalizer();
+        let text = normalizer.normalize(&self.text);
+        let fields: Vec<&str> = match self.field {
+            Some(field) => vec![field.value(book)],
+            None => vec![book.title.as_str(), book.author.as_str(), book.description.as_str()],
+        };
+        fields
+            .into_iter()
+            .map(|field| fuzzy::similarity(&text, &normalizer.normalize(field)))
+            .fold(0.0, f64::max)
+    }
+
+    pub(crate) fn matches(&self, book: &Book) -> bool {
+        let normalizer = normalizer();
+        let text = normalizer.normalize(&self.text);
+        match self.field {
+            Some(field) => normalizer.normalize(field.value(book)).contains(&text),
+            None => {
+                normalizer.normalize(&book.title).contains(&text) ||
+                normalizer.normalize(&book.author).contains(&text) ||
+                normalizer.normalize(&book.description).contains(&text)
+            }
+        }
+    }
+}
+
+/// キーワードを空白で区切り、`field:値` 形式の語をフィールド指定付きの条件に変換する
+///
+/// 未知の接頭辞や値が空の語は、そのままの文字列として扱う。
+pub fn parse_query(keyword: &str) -> Vec<QueryTerm> {
+    keyword
+        .split_whitespace()
+        .map(|token| {
+            if let Some((prefix, text)) = token.split_once(':') {
+                if let Some(field) = SearchField::from_prefix(prefix) {
+                    if !text.is_empty() {
+                        return QueryTerm { field: Some(field), text: text.to_string() };
+                    }
+                }
+            }
+            QueryTerm { field: None, text: token.to_string() }
+        })
+        .collect()
+}
+
+/// `limit` 省略時の最大結果数（設定ファイルの `default_search_limit` で変更できる）
+pub const DEFAULT_SEARCH_LIMIT: usize = 5;
+
+/// `limit` に指定できる最大結果数の既定値（設定ファイルの `max_search_limit` で変更できる）
+pub const MAX_SEARCH_LIMIT: usize = 100;
+
+/// 検索で返す件数の既定値と上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// `limit` を省略した検索で返す件数
+    pub default: usize,
+    /// `limit` に指定できる最大結果数（これを超える値は切り詰める）
+    pub max: usize,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            default: DEFAULT_SEARCH_LIMIT,
+            max: MAX_SEARCH_LIMIT,
+        }
+    }
+}
+
+/// あいまい検索の類似度の下限の既定値
+pub(crate) const DEFAULT_FUZZY_THRESHOLD: f64 = 0.7;
+
+/// `threshold` を検証して実際に使う値に変換する
+pub(crate) fn resolve_threshold(threshold: Option<f64>) -> Result<f64, McpError> {
+    match threshold {
+        None => Ok(DEFAULT_FUZZY_THRESHOLD),
+        Some(threshold) if (0.0..=1.0).contains(&threshold) => Ok(threshold),
+        Some(threshold) => Err(McpError::invalid_params(
+            "threshold must be between 0.0 and 1.0",
+            Some(json!({
+                "threshold": threshold
+            })),
+        )),
+    }
+}
+
+/// `limit` を実際に使う件数に変換する（省略時は `limits.default`、`limits.max` を超える値は切り詰める）
+///
+/// 負の値は `usize` へのキャストで巨大な値になってしまうため、エラーとして拒否する。
+pub(crate) fn resolve_limit(limit: Option<i32>, limits: SearchLimits) -> Result<usize, McpError> {
+    match limit {
+        None => Ok(limits.default.min(limits.max)),
+        Some(limit) if limit < 0 => Err(McpError::invalid_params(
+            "limit must not be negative",
+            Some(json!({
+                "limit": limit
+            })),
+        )),
+        Some(limit) => Ok((limit as usize).min(limits.max)),
+    }
+}
+
+/// キーワードとは別に、全てを満たす本だけに絞り込むための条件
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchFilter {
+    Author(String),
+    YearMin(i32),
+    YearMax(i32),
+    Isbn(String),
+    Tag(String),
+}
+
+impl SearchFilter {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Author(_) => "author",
+            Self::YearMin(_) => "year_min",
+            Self::YearMax(_) => "year_max",
+            Self::Isbn(_) => "isbn",
+            Self::Tag(_) => "tag",
+        }
+    }
+
+    fn value(&self) -> serde_json::Value {
+        match self {
+            Self::Author(author) => json!(author),
+            Self::YearMin(year) | Self::YearMax(year) => json!(year),
+            Self::Isbn(isbn) => json!(isbn),
+            Self::Tag(tag) => json!(tag),
+        }
+    }
+
+    pub(crate) fn matches(&self, book: &Book) -> bool {
+        match self {
+            Self::Author(author) => {
+                let normalizer = normalizer();
+                normalizer.normalize(&book.author).contains(&normalizer.normalize(author))
+            }
+            Self::YearMin(year) => book.year >= *year,
+            Self::YearMax(year) => book.year <= *year,
+            Self::Isbn(isbn) => isbn::normalize(&book.isbn) == isbn::normalize(isbn),
+            Self::Tag(tag) => {
+                let normalizer = normalizer();
+                let tag = normalizer.normalize(tag);
+                book.tags.iter().any(|t| normalizer.normalize(t) == tag)
+            }
+        }
+    }
+}
+
+/// 検索クエリで指定された絞り込み条件を集める
+pub(crate) fn query_filters(query: &SearchQuery) -> Result<Vec<SearchFilter>, McpError> {
+    check_year_range(query)?;
+
+    let mut filters = Vec::new();
+    if let Some(author) = query.author.as_ref().filter(|author| !author.trim().is_empty()) {
+        filters.push(SearchFilter::Author(author.clone()));
+    }
+    if let Some(year_min) = query.year_min {
+        filters.push(SearchFilter::YearMin(year_min));
+    }
+    if let Some(year_max) = query.year_max {
+        filters.push(SearchFilter::YearMax(year_max));
+    }
+    if let Some(isbn) = query.isbn.as_ref().filter(|isbn| !isbn.trim().is_empty()) {
+        filters.push(SearchFilter::Isbn(isbn.clone()));
+    }
+    for tag in query.tags.iter().flatten().filter(|tag| !tag.trim().is_empty()) {
+        filters.push(SearchFilter::Tag(tag.trim().to_string()));
+    }
+    Ok(filters)
+}
+
+/// `year_min` が `year_max` より大きければエラーにする
+fn check_year_range(query: &SearchQuery) -> Result<(), McpError> {
+    match (query.year_min, query.year_max) {
+        (Some(year_min), Some(year_max)) if year_min > year_max => Err(McpError::invalid_params(
+            "year_min must not be greater than year_max",
+            Some(json!({
+                "year_min": year_min,
+                "year_max": year_max,
+            })),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// `ranked` と `semantic` が両方指定されていればエラーにする
+pub(crate) fn check_ranking(query: &SearchQuery) -> Result<(), McpError> {
+    if query.ranked.unwrap_or(false) && query.semantic.unwrap_or(false) {
+        return Err(McpError::invalid_params("ranked and semantic cannot be combined", None));
+    }
+    Ok(())
+}
+
+/// 検索クエリの `query` の文字列（空白だけの場合は `None`）
+fn expression(query: &SearchQuery) -> Option<&str> {
+    query.query.as_deref().filter(|text| !text.trim().is_empty())
+}
+
+/// 検索クエリの `query` を解析する（指定がなければ `None`）
+pub(crate) fn query_expr(query: &SearchQuery) -> Result<Option<Expr>, McpError> {
+    match expression(query) {
+        Some(text) => query::parse(text).map(Some).map_err(|e| e.into_mcp_error(text)),
+        None => Ok(None),
+    }
+}
+
+/// 絞り込み条件ごとに、単独で一致する本の数
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FilterMatch {
+    pub filter: &'static str,
+    pub value: serde_json::Value,
+    pub matched: usize,
+}
+
+/// `offset` / `cursor` から先頭から読み飛ばす件数を求める
+///
+/// 負の `offset` は `limit` と同様にエラーとして拒否する。
+pub(crate) fn resolve_offset(query: &SearchQuery) -> Result<usize, McpError> {
+    match query.offset {
+        Some(offset) if offset < 0 => Err(McpError::invalid_params(
+            "offset must not be negative",
+            Some(json!({
+                "offset": offset
+            })),
+        )),
+        Some(offset) => Ok(offset as usize),
+        None => pagination::decode_cursor(query.cursor.as_deref()),
+    }
+}
+
+/// 検索を実行する前に分かる、検索クエリの誤り1つ
+#[derive(Debug)]
+pub struct QueryProblem {
+    /// `validate_query` で返す説明
+    pub message: String,
+    /// 検索を実行した場合に返すエラー
+    pub error: McpError,
+}
+
+/// 検索クエリの誤りを全て集める（カタログには触れない）
+///
+/// `run_search` はこのうち最初の誤りをエラーとして返し、`validate_query` は全てを一覧にする。
+/// `threshold` は `fuzzy` を指定していなくても検証する。
+pub fn check_query(query: &SearchQuery) -> Vec<QueryProblem> {
+    let mut problems = Vec::new();
+    let mut push = |message: String, result: Result<(), McpError>| {
+        if let Err(error) = result {
+            problems.push(QueryProblem { message, error });
+        }
+    };
+
+    let limit = query.limit.unwrap_or_default();
+    push(
+        format!("limit は0以上である必要があります（指定値: {}）", limit),
+        resolve_limit(query.limit, SearchLimits::default()).map(drop),
+    );
+    let offset = match query.offset {
+        Some(offset) => format!("offset は0以上である必要があります（指定値: {}）", offset),
+        None => format!("cursor '{}' は不正です", query.cursor.as_deref().unwrap_or_default()),
+    };
+    push(offset, resolve_offset(query).map(drop));
+    push(
+        format!(
+            "year_min（{}）が year_max（{}）より大きくなっています",
+            query.year_min.unwrap_or_default(),
+            query.year_max.unwrap_or_default()
+        ),
+        check_year_range(query),
+    );
+    push(
+        format!(
+            "threshold は0.0から1.0の範囲で指定してください（指定値: {}）",
+            query.threshold.unwrap_or_default()
+        ),
+        resolve_threshold(query.threshold).map(drop),
+    );
+    push("ranked と semantic は同時に指定できません".to_string(), check_ranking(query));
+    if let Some(text) = expression(query) {
+        if let Err(e) = query::parse(text) {
+            push(format!("query を解釈できません（{}）", e), Err(e.into_mcp_error(text)));
+        }
+    }
+    problems
+}
+
+/// 検索クエリの問題点を列挙する（カタログには触れない）
+///
+/// `check_query` の誤りに加えて、文字列として検索されるキーワードの `field:` の誤りも挙げる。
+pub fn query_problems(query: &SearchQuery) -> Vec<String> {
+    let mut problems: Vec<String> = check_query(query).into_iter().map(|problem| problem.message).collect();
+
+    for token in query.keyword.split_whitespace() {
+        let Some((prefix, text)) = token.split_once(':') else {
+            continue;
+        };
+        match SearchField::from_prefix(prefix) {
+            Some(_) if text.is_empty() => {
+                problems.push(format!("'{}' にフィールドの値がありません", token));
+            }
+            Some(_) => {}
+            None if !prefix.is_empty() && prefix.chars().all(|c| c.is_ascii_alphabetic()) => {
+                problems.push(format!(
+                    "'{}' は未知のフィールドです（文字列 '{}' として検索されます）",
+                    prefix, token
+                ));
+            }
+            None => {}
+        }
+    }
+
+    problems
+}
+
+/// いずれかの条件に一致すれば真（条件がない場合はすべて一致）
+pub fn matches_query(book: &Book, terms: &[QueryTerm]) -> bool {
+    terms.is_empty() || terms.iter().any(|term| term.matches(book))
+}
+
+/// 条件のうち最も近いものとの類似度（条件がない場合は 1.0）
+pub(crate) fn fuzzy_score(book: &Book, terms: &[QueryTerm]) -> f64 {
+    if terms.is_empty() {
+        return 1.0;
+    }
+    terms
+        .iter()
+        .map(|term| term.fuzzy_score(book))
+        .fold(0.0, f64::max)
+}
+
+/// 1ページ分の検索結果
+#[derive(Debug)]
+pub struct SearchResults<'a> {
+    pub books: Vec<&'a Book>,
+    /// 続きのページがある場合に次の検索で `cursor` に渡す値
+    pub next_cursor: Option<String>,
+    /// 指定された絞り込み条件と、それぞれに一致した本の数
+    pub filters: Vec<FilterMatch>,
+    /// ページに分ける前の、一致した全ての本のISBN（結果の順）
+    pub matched: Vec<&'a str>,
+}
+
+/// 検索クエリに一致する本を1ページ分返す
+///
+/// `limit` が省略されたクエリには `limits.default` 件を、`limits.max` 件を超えない範囲で返す。
+/// `scores` を渡した場合は、キーワードの代わりに全文検索インデックスの関連度
+/// （ISBNごと）で一致を判定し、関連度の高い順に並べる。`sort_by` を指定した場合はその順に並べ替える。
+pub fn run_search<'a>(
+    books: &'a [Book],
+    query: &SearchQuery,
+    limits: SearchLimits,
+    scores: Option<&HashMap<String, f32>>,
+) -> Result<SearchResults<'a>, McpError> {
+    if let Some(problem) = check_query(query).into_iter().next() {
+        return Err(problem.error);
+    }
+    let limit = resolve_limit(query.limit, limits)?;
+    let offset = resolve_offset(query)?;
+    let terms = parse_query(&query.keyword);
+    let filters = query_filters(query)?;
+    let filter_matches = filters
+        .iter()
+        .map(|filter| FilterMatch {
+            filter: filter.name(),
+            value: filter.value(),
+            matched: books.iter().filter(|book| filter.matches(book)).count(),
+        })
+        .collect();
+    let expr = query_expr(query)?;
+    let candidates = books
+        .iter()
+        .filter(|book| filters.iter().all(|filter| filter.matches(book)))
+        .filter(|book| expr.as_ref().is_none_or(|expr| expr.matches(book)));
+
+    let mut matched: Vec<&Book> = if let Some(scores) = scores {
+        let mut scored: Vec<(f32, &Book)> = candidates
+            .filter_map(|book| scores.get(&book.isbn).map(|score| (*score, book)))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().map(|(_, book)| book).collect()
+    } else if query.fuzzy.unwrap_or(false) {
+        let threshold = resolve_threshold(query.threshold)?;
+        let mut scored: Vec<(f64, &Book)> = candidates
+            .map(|book| (fuzzy_score(book, &terms), book))
+            .filter(|(score, _)| *score >= threshold)
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().map(|(_, book)| book).collect()
+    } else {
+        candidates
+            .filter(|book| matches_query(book, &terms))
+            .collect()
+    };
+    sort_results(&mut matched, query.sort_by.unwrap_or_default());
+
+    let isbns = matched.iter().map(|book| book.isbn.as_str()).collect();
+    let (books, next_cursor) = pagination::paginate(matched, offset, limit);
+    Ok(SearchResults {
+        books,
+        next_cursor,
+        filters: filter_matches,
+        matched: isbns,
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,224 @@
+//! ランダムなカタログと検索条件で確かめる、検索と検索式の性質
+
+use proptest::prelude::*;
+use serde_json::json;
+use std::collections::HashSet;
+
+use super::{
+    MAX_SEARCH_LIMIT, QueryTerm, SearchField, SearchLimits, SearchQuery, collation_key, parse_query, resolve_limit,
+    resolve_offset, run_search,
+};
+use crate::model::Book;
+use crate::query;
+
+/// タイトルと説明に使う語（検索語と重なるよう少数に絞る）
+const WORDS: &[&str] = &["火星", "料理", "量子", "猫", "税金", "庭", "AI", "時間"];
+const AUTHORS: &[&str] = &["火星の園芸家", "未来の会計士", "猫の教授"];
+const TAGS: &[&str] = &["space", "cooking", "mars", "finance", "Cats"];
+
+fn word() -> impl Strategy<Value = &'static str> {
+    proptest::sample::select(WORDS)
+}
+
+fn tags() -> impl Strategy<Value = Vec<String>> {
+    proptest::sample::subsequence(TAGS, 0..=3).prop_map(|tags| tags.into_iter().map(String::from).collect())
+}
+
+/// ISBNが重ならないカタログ
+fn catalog() -> impl Strategy<Value = Vec<Book>> {
+    let book = (
+        proptest::collection::vec(word(), 1..=3),
+        proptest::sample::select(AUTHORS),
+        1900..3100i32,
+        proptest::collection::vec(word(), 0..=4),
+        tags(),
+    );
+    proptest::collection::vec(book, 0..40).prop_map(|books| {
+        books
+            .into_iter()
+            .enumerate()
+            .map(|(i, (title, author, year, description, tags))| Book {
+                title: title.concat(),
+                author: author.to_string(),
+                year,
+                description: description.join("、"),
+                isbn: format!("978{:010}", i),
+                tags,
+                version: 0,
+                cover_path: None,
+                cover_url: None,
+                deleted_at: None,
+                author_id: None,
+                series: None,
+            })
+            .collect()
+    })
+}
+
+fn keyword() -> impl Strategy<Value = String> {
+    prop_oneof![Just(String::new()), word().prop_map(String::from), (word(), word()).prop_map(|(a, b)| format!("{} {}", a, b))]
+}
+
+fn search_query(value: serde_json::Value) -> SearchQuery {
+    serde_json::from_value(value).expect("valid search query")
+}
+
+fn isbns(books: &[&Book]) -> Vec<String> {
+    books.iter().map(|book| book.isbn.clone()).collect()
+}
+
+/// 検索式の断片（`NOT` / `AND` / `OR` と括弧で組み合わせる）
+fn expr() -> impl Strategy<Value = String> {
+    let atom = prop_oneof![
+        word().prop_map(String::from),
+        proptest::sample::select(TAGS).prop_map(|tag| format!("tag:{}", tag)),
+        (1900..3100i32).prop_map(|year| format!("year:>{}", year)),
+        (1900..3100i32).prop_map(|year| format!("year:<={}", year)),
+        proptest::sample::select(AUTHORS).prop_map(|author| format!("author:\"{}\"", author)),
+    ];
+    atom.prop_recursive(3, 16, 2, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|e| format!("NOT ({})", e)),
+            (inner.clone(), inner.clone()).prop_map(|(a, b)| format!("({}) AND ({})", a, b)),
+            (inner.clone(), inner).prop_map(|(a, b)| format!("({}) OR ({})", a, b)),
+        ]
+    })
+}
+
+proptest! {
+    #[test]
+    fn results_respect_limit(books in catalog(), keyword in keyword(), limit in 0..60i32) {
+        let query = search_query(json!({ "keyword": keyword, "limit": limit }));
+        let results = run_search(&books, &query, SearchLimits::default(), None).unwrap();
+        prop_assert!(results.books.len() <= (limit as usize).min(MAX_SEARCH_LIMIT));
+    }
+
+    #[test]
+    fn filters_never_return_non_matching_books(
+        books in catalog(),
+        keyword in keyword(),
+        tags in tags(),
+        year_min in proptest::option::of(1900..3100i32),
+        year_max in proptest::option::of(1900..3100i32),
+    ) {
+        // year_min > year_max は検索の入力エラーになる
+        if let (Some(min), Some(max)) = (year_min, year_max) {
+            prop_assume!(min <= max);
+        }
+        let query = search_query(json!({
+            "keyword": keyword,
+            "tags": tags,
+            "year_min": year_min,
+            "year_max": year_max,
+            "limit": MAX_SEARCH_LIMIT,
+        }));
+        let results = run_search(&books, &query, SearchLimits::default(), None).unwrap();
+        for book in results.books {
+            prop_assert!(year_min.is_none_or(|year| book.year >= year));
+            prop_assert!(year_max.is_none_or(|year| book.year <= year));
+            for tag in &tags {
+                prop_assert!(book.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)), "{} lacks tag {}", book.isbn, tag);
+            }
+        }
+    }
+
+    #[test]
+    fn pagination_covers_all_results_exactly_once(books in catalog(), keyword in keyword(), page_size in 1..8i32) {
+        let all = run_search(&books, &search_query(json!({ "keyword": keyword, "limit": MAX_SEARCH_LIMIT })), SearchLimits::default(), None).unwrap();
+        let expected = isbns(&all.books);
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let query = search_query(json!({ "keyword": keyword, "limit": page_size, "cursor": cursor }));
+            let page = run_search(&books, &query, SearchLimits::default(), None).unwrap();
+            prop_assert!(!page.books.is_empty() || seen.is_empty(), "a cursor led to an empty page");
+            seen.extend(isbns(&page.books));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        prop_assert_eq!(&seen, &expected);
+        prop_assert_eq!(seen.iter().collect::<HashSet<_>>().len(), seen.len());
+    }
+
+    #[test]
+    fn sorting_reorders_the_same_results(books in catalog(), keyword in keyword(), descending in any::<bool>()) {
+        let sort_by = if descending { "year_desc" } else { "year_asc" };
+        let unsorted = run_search(&books, &search_query(json!({ "keyword": keyword, "limit": MAX_SEARCH_LIMIT })), SearchLimits::default(), None).unwrap();
+        let query = search_query(json!({ "keyword": keyword, "limit": MAX_SEARCH_LIMIT, "sort_by": sort_by }));
+        let sorted = run_search(&books, &query, SearchLimits::default(), None).unwrap();
+
+        let mut expected = isbns(&unsorted.books);
+        let mut actual = isbns(&sorted.books);
+        for pair in sorted.books.windows(2) {
+            prop_assert!(if descending { pair[0].year >= pair[1].year } else { pair[0].year <= pair[1].year });
+        }
+        expected.sort();
+        actual.sort();
+        prop_assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn query_field_filters_like_the_expression(books in catalog(), expr in expr()) {
+        let parsed = query::parse(&expr).unwrap();
+        let query = search_query(json!({ "keyword": "", "query": expr, "limit": MAX_SEARCH_LIMIT }));
+        let results = run_search(&books, &query, SearchLimits::default(), None).unwrap();
+        let expected: Vec<&Book> = books.iter().filter(|book| parsed.matches(book)).collect();
+        prop_assert_eq!(isbns(&results.books), isbns(&expected));
+    }
+
+    #[test]
+    fn not_is_the_complement(books in catalog(), expr in expr()) {
+        let positive = query::parse(&expr).unwrap();
+        let negative = query::parse(&format!("NOT ({})", expr)).unwrap();
+        for book in &books {
+            prop_assert_ne!(positive.matches(book), negative.matches(book));
+        }
+    }
+
+    #[test]
+    fn and_or_combine_like_boolean_operators(books in catalog(), a in expr(), b in expr()) {
+        let (left, right) = (query::parse(&a).unwrap(), query::parse(&b).unwrap());
+        let and = query::parse(&format!("({}) AND ({})", a, b)).unwrap();
+        let implicit_and = query::parse(&format!("({}) ({})", a, b)).unwrap();
+        let or = query::parse(&format!("({}) OR ({})", a, b)).unwrap();
+        for book in &books {
+            let (l, r) = (left.matches(book), right.matches(book));
+            prop_assert_eq!(and.matches(book), l && r);
+            prop_assert_eq!(implicit_and.matches(book), l && r);
+            prop_assert_eq!(or.matches(book), l || r);
+        }
+    }
+}
+
+#[test]
+fn collation_orders_kana_by_reading() {
+    let mut titles = vec!["ロボット", "がっこう", "かっこう", "アイス", "ｶｷ", "火星"];
+    titles.sort_by_key(|title| collation_key(title));
+    assert_eq!(titles, vec!["アイス", "ｶｷ", "かっこう", "がっこう", "ロボット", "火星"]);
+}
+
+#[test]
+fn field_prefix_scopes_only_its_own_token() {
+    assert_eq!(
+        parse_query("title:火星 コンピュータ"),
+        vec![
+            QueryTerm { field: Some(SearchField::Title), text: "火星".to_string() },
+            QueryTerm { field: None, text: "コンピュータ".to_string() },
+        ]
+    );
+    // 未知の接頭辞はそのままの文字列として扱う
+    assert_eq!(parse_query("genre:SF"), vec![QueryTerm { field: None, text: "genre:SF".to_string() }]);
+}
+
+#[test]
+fn limits_are_clamped_to_the_maximum_and_negative_values_rejected() {
+    let limits = SearchLimits { default: 5, max: 20 };
+    assert_eq!(resolve_limit(None, limits).unwrap(), 5);
+    assert_eq!(resolve_limit(Some(1000), limits).unwrap(), 20);
+    assert_eq!(resolve_limit(Some(i32::MAX), limits).unwrap(), 20);
+    assert!(resolve_limit(Some(-1), limits).is_err());
+    assert!(resolve_offset(&search_query(json!({ "keyword": "", "offset": -1 }))).is_err());
+}
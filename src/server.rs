@@ -0,0 +1,3741 @@
+//! MCPの `ServerHandler` として本の検索・管理を提供するサーバー本体
+
+use rmcp::{
+    Error as McpError, RoleServer, ServerHandler, handler::server::tool::ToolCallContext,
+    model::*, service::{NotificationContext, RequestContext}, tool,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::analysis::{
+    DEFAULT_DUPLICATE_THRESHOLD, DEFAULT_RECOMMENDATIONS, DEFAULT_THEMES, author_centrality, catalog_stats,
+    cross_references, find_duplicates, similar_books, tag_counts, theme_coverage,
+};
+use crate::auth::ToolPolicy;
+use crate::catalog::{CatalogError, Catalogs, DEFAULT_CATALOG};
+use crate::audit::{self, AUDITED_TOOLS, AuditEntry, AuditLog};
+use crate::authors::{AuthorEntry, Directory};
+use crate::completion;
+use crate::cover;
+use crate::elicitation::{self, BookDraft};
+use crate::config::{Capability, ServerConfig};
+use crate::events::{self, CatalogEvent, CatalogEvents, ChangeKind, ListKind, Subscriptions};
+use crate::export::{ExportFormat, render_catalog, render_catalog_html};
+use crate::health::{Check, Health as ServerHealth};
+use crate::highlight;
+use crate::history::{self, BookState, History, HistoryError};
+use crate::i18n::Lang;
+use crate::import;
+use crate::index::RankedIndex;
+use crate::isbn::{self, Isbn};
+use crate::lending::{DEFAULT_LOAN_DAYS, LOAN_DAYS_RANGE, Loans};
+use crate::librarian;
+use crate::logging::{self, ClientLog};
+use crate::metrics::Metrics;
+use crate::model::{
+    Book, BookPatch, RatingSummary, ReadingList, Review, Series, book_changes, fake_books, format_book_in, merge_book, prepare_new_book,
+    validate_book, validate_review,
+};
+#[cfg(feature = "openlibrary")]
+use crate::openlibrary;
+use crate::output::{
+    self, BookHit, CatalogList, CatalogSummary, GeneratedBooks, Health, IsbnValidation, RatingOutput,
+    ReviewList, SearchOutput, TagCount, TagList,
+};
+use crate::pagination;
+use crate::prompts;
+use crate::protocol;
+use crate::rate_limit::TokenBucket;
+use crate::result_cache::ResultCache;
+use crate::request_log;
+use crate::resources;
+use crate::roots;
+use crate::session::{Preferences, SessionHandle, Sessions};
+use crate::search::{
+    OutputFormat, SearchLimits, SearchQuery, SearchResults, SortBy, check_ranking, collation_key, matches_query,
+    parse_query, query_problems, resolve_limit, resolve_offset, resolve_threshold, run_search,
+};
+use crate::shutdown::Drain;
+use crate::snapshot::{self, SnapshotError};
+use crate::synthetic;
+use crate::store::{BookStore, JsonFileStore, MemoryStore, VersionCheck};
+use crate::transport::{ManagedServer, Transport};
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ThemeCoverageRequest {
+    #[schemars(description = "調べるテーマの一覧（省略時は既定のテーマ）")]
+    pub themes: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SearchDiffRequest {
+    #[schemars(description = "比較元の検索クエリ")]
+    pub left: SearchQuery,
+    #[schemars(description = "比較先の検索クエリ")]
+    pub right: SearchQuery,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RefineSearchRequest {
+    #[schemars(description = "絞り込む検索結果の result_id（search や refine_search の結果に含まれる）")]
+    pub result_id: String,
+    #[schemars(description = "結果をさらに絞り込むキーワード（search の keyword と同じ書式）")]
+    pub keyword: String,
+    #[schemars(description = "最大結果数")]
+    pub limit: Option<i32>,
+    #[schemars(description = "前回の refine_search の next_cursor（続きのページを取得する）")]
+    pub cursor: Option<String>,
+    #[schemars(description = "結果の並べ方（search の sort_by と同じ。省略時は元の結果の順）")]
+    pub sort_by: Option<SortBy>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct ListAuthorsRequest {
+    #[schemars(description = "著者名か別名の一部で絞り込む")]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AuthorRequest {
+    #[schemars(description = "著者のID、または著者名・別名")]
+    pub author: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct BooksByAuthorRequest {
+    #[schemars(description = "著者のID、または著者名・別名")]
+    pub author: String,
+    #[schemars(description = "最大結果数")]
+    pub limit: Option<i32>,
+    #[schemars(description = "前回の結果の next_cursor（続きのページを取得する）")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct SeriesRequest {
+    #[schemars(description = "シリーズ名")]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateReadingListRequest {
+    #[schemars(description = "リストの名前（他のリストと重ならない名前）")]
+    pub name: String,
+    #[schemars(description = "リストの説明")]
+    #[serde(default)]
+    pub description: String,
+    #[schemars(description = "最初に加える本のISBN")]
+    #[serde(default)]
+    pub isbns: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReadingListBooksRequest {
+    #[schemars(description = "読書リストの名前")]
+    pub name: String,
+    #[schemars(description = "加える・外す本のISBN")]
+    pub isbns: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReadingListRequest {
+    #[schemars(description = "読書リストの名前")]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateAuthorRequest {
+    #[schemars(description = "更新する著者のID、または著者名・別名")]
+    pub author: String,
+    #[schemars(description = "新しい著者名（この著者の全ての本の author も書き換え、以前の名前は別名に加える）")]
+    pub name: Option<String>,
+    #[schemars(description = "著者の紹介")]
+    pub bio: Option<String>,
+    #[schemars(description = "別名の一覧（指定した場合は置き換える）")]
+    pub aliases: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct NamedQueryRequest {
+    #[schemars(description = "実行する名前付きクエリの名前")]
+    pub name: String,
+}
+
+/// `batch_search` の1回の呼び出しで実行できる検索の最大数
+const MAX_BATCH_QUERIES: usize = 20;
+
+/// `search_stream` が1回の呼び出しで返す本の上限
+const MAX_STREAM_RESULTS: usize = 10_000;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct BatchSearchRequest {
+    #[schemars(description = "実行する検索クエリの配列（最大20件、結果は同じ順に返す）")]
+    pub queries: Vec<SearchQuery>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AddBookRequest {
+    #[serde(flatten)]
+    pub book: BookDraft,
+    #[schemars(description = "true の場合は検証と変更内容の確認だけを行い、カタログは変更しない")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct UpdateBookRequest {
+    #[schemars(description = "更新する本のISBN")]
+    pub isbn: String,
+    #[schemars(description = "新しいタイトル")]
+    pub title: Option<String>,
+    #[schemars(description = "新しい著者名")]
+    pub author: Option<String>,
+    #[schemars(description = "新しい出版年")]
+    pub year: Option<i32>,
+    #[schemars(description = "新しい説明")]
+    pub description: Option<String>,
+    #[schemars(description = "新しいタグ（指定した場合は置き換える）")]
+    pub tags: Option<Vec<String>>,
+    #[schemars(description = "新しいシリーズと巻数（name を空文字列にするとシリーズから外す）")]
+    pub series: Option<Series>,
+    #[schemars(description = "新しい表紙画像のファイルのパス（空文字列で削除）")]
+    pub cover_path: Option<String>,
+    #[schemars(description = "新しい表紙画像のURL（空文字列で削除）")]
+    pub cover_url: Option<String>,
+    #[schemars(description = "更新前に取得した本の version（指定した場合、他のセッションが先に変更していればエラーにする）")]
+    pub expected_version: Option<u64>,
+    #[schemars(description = "true の場合は検証と変更内容の確認だけを行い、カタログは変更しない")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ImportBooksRequest {
+    #[schemars(description = "取り込む本の配列（各要素は title, author, year, description, isbn を持つオブジェクト）")]
+    pub books: Vec<serde_json::Value>,
+    #[schemars(description = "true の場合は検証と変更内容の確認だけを行い、カタログは変更しない")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GenerateFakeBooksRequest {
+    #[schemars(description = "生成する本の冊数（1回に最大100000冊）")]
+    pub count: usize,
+    #[schemars(description = "乱数のシード（同じシードと番号からは常に同じ本ができる。省略時は0）")]
+    #[serde(default)]
+    pub seed: u64,
+    #[schemars(description = "何番目の本から生成するか（前回の続きを追加する場合に指定する。省略時は0）")]
+    #[serde(default)]
+    pub start: usize,
+    #[schemars(description = "true の場合は検証と変更内容の確認だけを行い、カタログは変更しない")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct DeleteBookRequest {
+    #[schemars(description = "ごみ箱に移す本のISBN")]
+    pub isbn: String,
+    #[schemars(description = "削除前に取得した本の version（指定した場合、他のセッションが先に変更していればエラーにする）")]
+    pub expected_version: Option<u64>,
+    #[schemars(description = "true の場合は検証と変更内容の確認だけを行い、カタログは変更しない")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RestoreBookRequest {
+    #[schemars(description = "ごみ箱から戻す本のISBN")]
+    pub isbn: String,
+    #[schemars(description = "list_trash で取得した本の version（指定した場合、他のセッションが先に変更していればエラーにする）")]
+    pub expected_version: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct PurgeTrashRequest {
+    #[schemars(description = "完全に削除する本のISBN（省略時はごみ箱の全ての本）")]
+    pub isbn: Option<String>,
+    #[schemars(description = "ごみ箱に移してからこの日数以上経った本だけを削除する")]
+    pub older_than_days: Option<u32>,
+    #[schemars(description = "true の場合は削除する本の確認だけを行い、カタログは変更しない")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RecommendSimilarRequest {
+    #[schemars(description = "基準にする本のISBN")]
+    pub isbn: String,
+    #[schemars(description = "返す本の最大数（省略時は3、最大100）")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct FindDuplicatesRequest {
+    #[schemars(description = "タイトルと著者の類似度のしきい値（0.0〜1.0、省略時は0.85）")]
+    pub threshold: Option<f64>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct BulkUpdateBooksRequest {
+    #[schemars(description = "変更する本を選ぶ検索式（search の query と同じ書式。例: author:\"火星\" AND tag:space）")]
+    pub query: String,
+    #[schemars(description = "一致した全ての本に適用する変更")]
+    pub patch: BookPatch,
+    #[schemars(description = "true の場合は変更内容の確認だけを行い、カタログは変更しない")]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct MergeBooksRequest {
+    #[schemars(description = "残す本のISBN")]
+    pub primary: String,
+    #[schemars(description = "primary に統合して削除する本のISBN")]
+    pub duplicates: Vec<String>,
+    #[serde(default)]
+    #[schemars(description = "true の場合、統合結果を返すだけでカタログは変更しない")]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct BookCoverRequest {
+    #[schemars(description = "表紙画像を取得する本のISBN")]
+    pub isbn: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReviewsRequest {
+    #[schemars(description = "レビューを取得する本のISBN")]
+    pub isbn: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CheckoutBookRequest {
+    #[schemars(description = "貸し出す本のISBN")]
+    pub isbn: String,
+    #[schemars(description = "借りる人の名前")]
+    pub borrower: String,
+    #[schemars(description = "貸出期間の日数（省略時は14日、1から365）")]
+    pub days: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ReturnBookRequest {
+    #[schemars(description = "返却する本のISBN")]
+    pub isbn: String,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct ServerStatsRequest {
+    #[schemars(description = "`prometheus` を指定すると Prometheus のテキスト形式で返す")]
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct AuditLogRequest {
+    #[schemars(description = "この時刻以降の記録に絞り込む（RFC 3339、例: 2025-01-01T00:00:00Z）")]
+    pub since: Option<String>,
+    #[schemars(description = "この時刻以前の記録に絞り込む（RFC 3339）")]
+    pub until: Option<String>,
+    #[schemars(description = "このツールの記録に絞り込む")]
+    pub tool: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct CreateSnapshotRequest {
+    #[schemars(description = "スナップショット名の末尾に付ける目印（英数字・`-`・`_`）")]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RestoreSnapshotRequest {
+    #[schemars(description = "create_snapshot が返したスナップショットの名前")]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct OpenFileStoreRequest {
+    #[schemars(description = "本を保存するJSONファイル（相対パスはクライアントの最初のルートからの相対）")]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct SetPreferencesRequest {
+    #[serde(flatten)]
+    pub preferences: Preferences,
+    #[serde(default)]
+    #[schemars(description = "true の場合、先に全ての設定を消してサーバーの既定値に戻す")]
+    pub clear: bool,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct CreateCatalogRequest {
+    #[schemars(description = "作成するカタログの名前（英小文字・数字・-・_。ツールの catalog やリソースURIの book://{catalog}/ に使う）")]
+    pub name: String,
+    #[schemars(description = "本をコピーする元のカタログ（\"default\" で既定のカタログ。省略時は空のカタログ）")]
+    pub copy_from: Option<String>,
+}
+
+/// RFC 3339 形式の時刻を解釈する
+fn parse_timestamp(field: &str, value: Option<&str>) -> Result<Option<chrono::DateTime<chrono::Utc>>, McpError> {
+    value
+        .map(|value| {
+            chrono::DateTime::parse_from_rfc3339(value)
+                .map(|time| time.with_timezone(&chrono::Utc))
+                .map_err(|e| {
+                    McpError::invalid_params(
+                        format!("{} must be an RFC 3339 timestamp", field),
+                        Some(json!({
+                            field: value,
+                            "reason": e.to_string(),
+                        })),
+                    )
+                })
+        })
+        .transpose()
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ValidateIsbnRequest {
+    #[schemars(description = "検証するISBN-10またはISBN-13（ハイフン区切りも可）")]
+    pub isbn: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ExportCatalogRequest {
+    #[schemars(description = "書き出す形式（json / csv / bibtex）")]
+    pub format: ExportFormat,
+    #[schemars(description = "書き出すファイルのパス（stdioで起動した場合のみ。省略時は結果に含めて返す）")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct AskLibrarianRequest {
+    #[schemars(description = "司書への質問（例: 宇宙が好きな人に向いている本は？）")]
+    pub question: String,
+    #[schemars(description = "回答の根拠にする本を絞り込む検索キーワード（省略時は蔵書全体）")]
+    pub keyword: Option<String>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct FetchRealBookRequest {
+    #[schemars(description = "取得する本のISBN（title と同時には指定できない）")]
+    pub isbn: Option<String>,
+    #[schemars(description = "検索する本のタイトル（isbn と同時には指定できない）")]
+    pub title: Option<String>,
+    #[schemars(description = "取得した本をカタログに追加するか（省略時は追加しない）")]
+    pub insert: Option<bool>,
+    #[schemars(description = "タイトル検索で返す本の最大数（省略時は5、最大100）")]
+    pub limit: Option<usize>,
+}
+
+/// クライアントが取り消したリクエストに返すエラーのコード（LSP の RequestCancelled と同じ値）
+const REQUEST_CANCELLED: ErrorCode = ErrorCode(-32800);
+
+/// `notifications/cancelled` で取り消されたツール呼び出しに返すエラー
+fn tool_cancelled(tool: &str) -> McpError {
+    McpError::new(
+        REQUEST_CANCELLED,
+        "request cancelled",
+        Some(json!({
+            "tool": tool,
+        })),
+    )
+}
+
+/// 制限時間を過ぎたツール呼び出しに返すエラー
+fn tool_timed_out(tool: &str, timeout: Duration) -> McpError {
+    McpError::internal_error(
+        "tool call timed out",
+        Some(json!({
+            "tool": tool,
+            "timeout_secs": timeout.as_secs(),
+        })),
+    )
+}
+
+/// トークンに許可されていないツールの呼び出しに返すエラー
+fn tool_not_permitted(tool: &str) -> McpError {
+    McpError::invalid_request(
+        "permission denied: the tool is not allowed for this token",
+        Some(json!({
+            "tool": tool,
+            "reason": "policy",
+        })),
+    )
+}
+
+/// 監査対象のツールのほかに、サーバー側にファイルを書くため読み取り専用モードで拒否するツール
+const FILE_WRITING_TOOLS: &[&str] = &["create_snapshot", "open_file_store"];
+
+/// 読み取り専用モードで拒否するツールか
+///
+/// 引数によってだけ書き込むツール（`fetch_real_book` の `insert`、`export_catalog` の `path`）は
+/// ツールの中で拒否する。
+pub fn is_mutating_tool(tool: &str) -> bool {
+    AUDITED_TOOLS.contains(&tool) || FILE_WRITING_TOOLS.contains(&tool)
+}
+
+/// 読み取り専用モードで変更を伴う呼び出しに返すエラー
+fn read_only_denied(tool: &str) -> McpError {
+    McpError::invalid_request(
+        "permission denied: the server is read-only",
+        Some(json!({
+            "tool": tool,
+            "reason": "read_only",
+        })),
+    )
+}
+
+/// 設定で無効にした機能のメソッドに返すエラー
+fn capability_disabled(method: &str) -> McpError {
+    McpError::new(
+        ErrorCode::METHOD_NOT_FOUND,
+        "method not found",
+        Some(json!({
+            "method": method,
+            "reason": "disabled by server configuration",
+        })),
+    )
+}
+
+/// 他のセッションが先に本を変更していたことを知らせるエラー
+fn version_conflict(isbn: &str, expected: u64, current: u64) -> McpError {
+    McpError::invalid_request(
+        "book was modified by another session",
+        Some(json!({
+            "isbn": isbn,
+            "expected_version": expected,
+            "current_version": current,
+        })),
+    )
+}
+
+/// 本を `state` の内容とレビューに置き換える（本がなければ作り直す）
+///
+/// 版は戻さずに進め、書き戻す前に読み込んだ版での更新を衝突として拒否させる。
+async fn restore_state(store: &dyn BookStore, state: &BookState) -> anyhow::Result<()> {
+    let version = match store.get(&state.book.isbn).await? {
+        Some(current) => current.version + 1,
+        None => state.book.version,
+    };
+    store.remove(&state.book.isbn, None).await?;
+    store
+        .put(Book {
+            version: version.max(state.book.version),
+            ..state.book.clone()
+        })
+        .await?;
+    for review in &state.reviews {
+        store.add_review(review.clone()).await?;
+    }
+    Ok(())
+}
+
+/// 途中で失敗した変更を書き戻せなかったときに返すエラー
+fn rollback_failed(isbns: &[String]) -> McpError {
+    McpError::internal_error(
+        "failed to roll back partially applied changes",
+        Some(json!({
+            "isbns": isbns,
+        })),
+    )
+}
+
+/// 本をシリーズごとにまとめる（シリーズは名前の五十音順、本は巻数・出版年・ISBNの順）
+///
+/// 表記の揺れだけが違うシリーズ名は同じシリーズとし、最初に登録された本の表記を名前にする。
+fn series_volumes(books: &[Book]) -> Vec<(String, Vec<&Book>)> {
+    let normalizer = crate::search::normalizer();
+    let mut groups: Vec<(String, String, Vec<&Book>)> = Vec::new();
+    for book in books {
+        let Some(series) = &book.series else { continue };
+        let key = normalizer.normalize(series.name.trim());
+        match groups.iter_mut().find(|(existing, _, _)| *existing == key) {
+            Some((_, _, volumes)) => volumes.push(book),
+            None => groups.push((key, series.name.clone(), vec![book])),
+        }
+    }
+    let mut groups: Vec<(String, Vec<&Book>)> = groups.into_iter().map(|(_, name, volumes)| (name, volumes)).collect();
+    for (_, volumes) in &mut groups {
+        volumes.sort_by_key(|book| {
+            let volume = book.series.as_ref().and_then(|series| series.volume);
+            (volume.is_none(), volume, book.year, book.isbn.clone())
+        });
+    }
+    groups.sort_by_cached_key(|(name, _)| collation_key(name));
+    groups
+}
+
+fn author_not_found(author: &str) -> String {
+    format!("著者 '{}' は見つかりませんでした", author)
+}
+
+fn series_not_found(name: &str) -> String {
+    format!("シリーズ '{}' は見つかりませんでした", name)
+}
+
+fn reading_list_not_found(name: &str) -> String {
+    format!("読書リスト '{}' は見つかりませんでした", name)
+}
+
+/// 入力の問題点を `isError` 付きの構造化された結果として返す
+fn validation_failure(errors: Vec<String>) -> Result<CallToolResult, McpError> {
+    Ok(CallToolResult::error(vec![Content::json(json!({
+        "errors": errors,
+    }))?]))
+}
+
+/// 取り込み中に進捗を通知する間隔（行数）
+const IMPORT_PROGRESS_INTERVAL: usize = 100;
+
+/// `generate_fake_books` の1回の呼び出しで生成できる冊数
+const MAX_GENERATED_BOOKS: usize = 100_000;
+
+/// リクエストに進捗トークンが付いている場合に、クライアントへ進捗を通知する
+struct ProgressReporter<'a> {
+    context: &'a RequestContext<RoleServer>,
+    token: Option<ProgressToken>,
+    total: usize,
+}
+
+impl<'a> ProgressReporter<'a> {
+    fn new(context: &'a RequestContext<RoleServer>, total: usize) -> Self {
+        Self {
+            context,
+            token: context.meta.get_progress_token(),
+            total,
+        }
+    }
+
+    /// 進捗トークンが付いているか（付いていなければ進捗を通知しない）
+    fn is_active(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// 進捗を通知する（トークンがなければ何もしない。通知の失敗は処理を止めない）
+    async fn report(&self, done: usize, message: &str) {
+        let Some(token) = &self.token else {
+            return;
+        };
+        let result = self
+            .context
+            .peer
+            .notify_progress(ProgressNotificationParam {
+                progress_token: token.clone(),
+                progress: done as u32,
+                total: Some(self.total as u32),
+                message: Some(message.to_string()),
+            })
+            .await;
+        if let Err(e) = result {
+            tracing::warn!("failed to send progress notification: {:?}", e);
+        }
+    }
+}
+
+/// スナップショットの名前の誤りは入力エラーとして、それ以外は内部エラーとして返す
+fn snapshot_failure(e: anyhow::Error) -> Result<CallToolResult, McpError> {
+    match e.downcast_ref::<SnapshotError>() {
+        Some(SnapshotError::NotFound { available, .. }) => Ok(CallToolResult::error(vec![Content::json(json!({
+            "errors": [e.to_string()],
+            "available": available,
+        }))?])),
+        Some(SnapshotError::InvalidName(_)) => validation_failure(vec![e.to_string()]),
+        None => Err(store_error(e)),
+    }
+}
+
+/// 取り消し・やり直しできる変更がない、または本が変更されていた場合は入力エラーとして返す
+fn history_failure(e: anyhow::Error) -> Result<CallToolResult, McpError> {
+    match e.downcast_ref::<HistoryError>() {
+        Some(error) => validation_failure(vec![error.to_string()]),
+        None => Err(store_error(e)),
+    }
+}
+
+/// カタログを作成・選択できなかったときのエラー
+fn catalog_error(e: CatalogError) -> McpError {
+    let message = e.to_string();
+    match e {
+        CatalogError::NotFound { name, available } => McpError::invalid_params(
+            "unknown catalog",
+            Some(json!({
+                "catalog": name,
+                "available": available,
+                "message": message,
+            })),
+        ),
+        CatalogError::InvalidName(name) | CatalogError::AlreadyExists(name) => McpError::invalid_params(
+            "invalid catalog name",
+            Some(json!({
+                "catalog": name,
+                "message": message,
+            })),
+        ),
+    }
+}
+
+/// `catalog` 引数を取らない、サーバー全体やセッションに対するツール
+const SERVER_TOOLS: &[&str] = &[
+    "create_catalog",
+    "list_catalogs",
+    "open_file_store",
+    "close_file_store",
+    "set_preferences",
+    "get_server_stats",
+    "get_audit_log",
+    "diagnostics",
+    "health",
+    "resource_sitemap",
+    "validate_isbn",
+];
+
+/// ツールの入力スキーマに、操作するカタログを選ぶ `catalog` を加える
+///
+/// 選べる名前は `catalogs` で、カタログを作るとツールの一覧が変わる。
+fn with_catalog_argument(tool: &mut Tool, catalogs: &[String]) {
+    let mut schema = (*tool.input_schema).clone();
+    let properties = schema
+        .entry("properties")
+        .or_insert_with(|| serde_json::Value::Object(Default::default()));
+    if let Some(properties) = properties.as_object_mut() {
+        properties.insert(
+            "catalog".to_string(),
+            json!({
+                "type": "string",
+                "description": "操作するカタログの名前（省略時は既定のカタログ）",
+                "enum": catalogs,
+            }),
+        );
+    }
+    tool.input_schema = Arc::new(schema);
+}
+
+/// ストアのエラーをMCPのエラーに変換する
+fn store_error(e: anyhow::Error) -> McpError {
+    tracing::error!("Store error: {:#}", e);
+    McpError::internal_error(
+        "store error",
+        Some(json!({
+            "reason": e.to_string()
+        })),
+    )
+}
+
+#[derive(Clone)]
+pub struct BookSearch {
+    store: Arc<dyn BookStore>,
+    /// 全セッションで共有する変更通知
+    events: CatalogEvents,
+    /// このセッションで購読中のリソース
+    subscriptions: Arc<Subscriptions>,
+    /// `ranked` 検索に使う全文検索インデックス
+    index: Option<Arc<dyn RankedIndex>>,
+    /// `semantic` 検索に使う埋め込みベクトルのインデックス
+    semantic_index: Option<Arc<dyn RankedIndex>>,
+    config: Arc<ServerConfig>,
+    /// 全セッションで共有する実行中のツール呼び出しの数
+    drain: Arc<Drain>,
+    /// 全セッションで共有する貸し出し状況
+    loans: Arc<Loans>,
+    /// このセッションのツール呼び出しの制限
+    rate_limit: Option<Arc<TokenBucket>>,
+    /// 全セッションで共有するツール呼び出しの計測値
+    metrics: Arc<Metrics>,
+    /// 全セッションで共有する変更操作の記録
+    audit: Arc<AuditLog>,
+    /// 全セッションで共有するセッションの一覧
+    sessions: Arc<Sessions>,
+    /// このセッションの登録（接続時刻と設定）
+    session: Arc<SessionHandle>,
+    /// `open_file_store` で開いた、このセッションだけが使うファイルのストア
+    file_store: Arc<RwLock<Option<Arc<JsonFileStore>>>>,
+    /// 接続時に認証したトークンに許可するツール
+    policy: ToolPolicy,
+    /// 全セッションで共有する名前付きのカタログ
+    catalogs: Arc<Catalogs>,
+    /// この呼び出しで操作する名前付きのカタログ（既定のカタログなら `None`）
+    catalog: Option<String>,
+    /// 全セッションで共有する、カタログごとの変更の履歴
+    history: Arc<History>,
+    /// `refine_search` で絞り込めるよう、このセッションで最近返した検索結果
+    results: Arc<ResultCache>,
+    /// `logging/setLevel` で指定された、このセッションのクライアントへ送るログの重要度
+    client_log: Arc<ClientLog>,
+}
+
+/// プロセスの起動時刻（稼働時間の算出に使う）
+static STARTED_AT: OnceLock<Instant> = OnceLock::new();
+
+/// 起動時に選択されたトランスポート
+static ACTIVE_TRANSPORT: OnceLock<Transport> = OnceLock::new();
+
+/// 起動したトランスポートを記録し、稼働時間の計測を始める
+pub(crate) fn record_start(transport: Transport) {
+    STARTED_AT.get_or_init(Instant::now);
+    ACTIVE_TRANSPORT.get_or_init(|| transport);
+}
+
+#[tool(tool_box)]
+impl BookSearch {
+    /// 架空の本を初期データとするメモリ上のストアでサーバーを作成する
+    pub fn new() -> Self {
+        Self::with_store(Arc::new(MemoryStore::new(fake_books())))
+    }
+
+    pub fn with_store(store: Arc<dyn BookStore>) -> Self {
+        Self::with_events(store, CatalogEvents::new())
+    }
+
+    /// 他のセッションと変更通知を共有するサーバーを作成する
+    pub fn with_events(store: Arc<dyn BookStore>, events: CatalogEvents) -> Self {
+        let sessions = Arc::new(Sessions::default());
+        Self {
+            store,
+            events,
+            subscriptions: Arc::new(Subscriptions::default()),
+            index: None,
+            semantic_index: None,
+            config: Arc::new(ServerConfig::default()),
+            drain: Arc::new(Drain::default()),
+            loans: Arc::new(Loans::default()),
+            rate_limit: None,
+            metrics: Arc::new(Metrics::default()),
+            audit: Arc::new(AuditLog::default()),
+            session: Arc::new(sessions.open()),
+            sessions,
+            file_store: Arc::default(),
+            policy: ToolPolicy::All,
+            catalogs: Arc::default(),
+            catalog: None,
+            history: Arc::default(),
+            results: Arc::default(),
+            client_log: Arc::default(),
+        }
+    }
+
+    /// 検索件数の既定値や公開する機能などの設定を適用する
+    pub fn with_config(mut self, config: ServerConfig) -> Self {
+        self.rate_limit = config.rate_limit.as_ref().map(|limit| Arc::new(TokenBucket::new(limit)));
+        self.history = Arc::new(History::new(config.history_depth));
+        self.results = Arc::new(ResultCache::new(Duration::from_secs(config.result_ttl_secs)));
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// 変更操作を記録する監査ログを設定する
+    pub fn with_audit(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// `ranked` 検索に使う全文検索インデックスを設定する
+    ///
+    /// インデックスを最新に保つため、ストアは同じインデックスを持つ `IndexedStore` であること。
+    pub fn with_index(mut self, index: Arc<dyn RankedIndex>) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    /// `semantic` 検索に使う意味検索インデックス（[`crate::index::SemanticIndex`]）を設定する
+    ///
+    /// `with_index` と同じく、ストアは同じインデックスを持つ `IndexedStore` であること。
+    pub fn with_semantic_index(mut self, index: Arc<dyn RankedIndex>) -> Self {
+        self.semantic_index = Some(index);
+        self
+    }
+
+    /// 起動時に開いた名前付きのカタログを設定する
+    pub fn with_catalogs(mut self, catalogs: Arc<Catalogs>) -> Self {
+        self.catalogs = catalogs;
+        self
+    }
+
+    /// このセッションで呼び出せるツールを限る
+    pub fn with_policy(mut self, policy: ToolPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// ストアと変更通知を共有したまま、購読などのセッション固有の状態を持たない新しいセッションを作る
+    pub fn new_session(&self) -> Self {
+        let mut session = Self::with_events(self.store.clone(), self.events.clone());
+        session.index = self.index.clone();
+        session.semantic_index = self.semantic_index.clone();
+        session.catalogs = self.catalogs.clone();
+        session.config = self.config.clone();
+        session.drain = self.drain.clone();
+        session.loans = self.loans.clone();
+        session.metrics = self.metrics.clone();
+        session.audit = self.audit.clone();
+        session.history = self.history.clone();
+        session.results = Arc::new(ResultCache::new(self.results.ttl()));
+        session.sessions = self.sessions.clone();
+        session.session = Arc::new(self.sessions.open());
+        session.rate_limit = self.config.rate_limit.as_ref().map(|limit| Arc::new(TokenBucket::new(limit)));
+        session
+    }
+
+    /// 新しいツール呼び出しを断り、実行中の呼び出しを待ってからストアの変更を書き出す
+    ///
+    /// 待つのは設定の `shutdown_timeout_secs` 秒までで、それを過ぎた呼び出しは打ち切る。
+    pub async fn shutdown(&self) -> anyhow::Result<()> {
+        let timeout = Duration::from_secs(self.config.shutdown_timeout_secs);
+        if !self.drain.drain(timeout).await {
+            tracing::warn!(
+                "Gave up waiting for {} in-flight tool calls after {:?}",
+                self.drain.in_flight(),
+                timeout
+            );
+        }
+        self.store.flush().await
+    }
+
+    /// `/healthz` と `/readyz` で返す状態
+    ///
+    /// 保存先に問い合わせられることを生存の条件に、インデックスが使えることと
+    /// 終了処理に入っていないことを準備の条件にする。
+    pub async fn server_health(&self) -> ServerHealth {
+        let mut health = ServerHealth::default();
+        // 存在しないISBNを引いて、保存先が応答するかだけを確かめる
+        health.live.push(match self.store.get("").await {
+            Ok(_) => Check::ok("store"),
+            Err(e) => Check::failed("store", format!("{:#}", e)),
+        });
+        for (name, index) in [("index", &self.index), ("semantic_index", &self.semantic_index)] {
+            match index {
+                Some(index) if !index.is_ready() => health.ready.push(Check::failed(name, "index is unusable")),
+                Some(_) => health.ready.push(Check::ok(name)),
+                None => {}
+            }
+        }
+        health.ready.push(if self.drain.is_draining() {
+            Check::failed("accepting_requests", "shutting down")
+        } else {
+            Check::ok("accepting_requests")
+        });
+        health
+    }
+
+    /// 名前付きのカタログ `name` を操作する呼び出し用に、ストアを差し替えた複製を作る
+    ///
+    /// インデックスは既定のカタログにしかないので、複製では `ranked` / `semantic` 検索を使えない。
+    fn in_catalog(&self, name: &str) -> Result<Self, CatalogError> {
+        if name == DEFAULT_CATALOG {
+            return Ok(self.clone());
+        }
+        let mut handler = self.clone();
+        handler.store = self.catalogs.get(name)?;
+        handler.catalog = Some(name.to_string());
+        handler.index = None;
+        handler.semantic_index = None;
+        // セッションのファイルのストアより、指定したカタログを優先する
+        handler.file_store = Arc::default();
+        Ok(handler)
+    }
+
+    /// リソースURIの `book://{catalog}/` が指すカタログを操作する複製を作る
+    fn for_uri(&self, uri: &str) -> Result<Self, McpError> {
+        match resources::catalog_of(uri) {
+            Some(name) => self.in_catalog(name).map_err(|_| resources::not_found(uri)),
+            None => Ok(self.clone()),
+        }
+    }
+
+    /// 操作中のカタログの変更を通知する
+    fn publish(&self, event: CatalogEvent) {
+        self.events.publish(event.in_catalog(self.catalog.clone()));
+    }
+
+    /// `ranked` か `semantic` が指定されていれば、インデックスからISBNごとの関連度を得る
+    fn ranked_scores(&self, query: &SearchQuery, limit: usize) -> Result<Option<HashMap<String, f32>>, McpError> {
+        let (ranked, semantic) = (query.ranked.unwrap_or(false), query.semantic.unwrap_or(false));
+        if !ranked && !semantic {
+            return Ok(None);
+        }
+        check_ranking(query)?;
+        if let Some(catalog) = &self.catalog {
+            return Err(McpError::invalid_params(
+                "ranked and semantic search are only available in the default catalog",
+                Some(json!({
+                    "catalog": catalog,
+                })),
+            ));
+        }
+        if self.file_store.read().expect("file store lock poisoned").is_some() {
+            return Err(McpError::invalid_params(
+                "ranked search is not available while a file store is open",
+                None,
+            ));
+        }
+        let index = if semantic { &self.semantic_index } else { &self.index };
+        let Some(index) = index else {
+            let message = if semantic {
+                "semantic search is not configured on this server (set [semantic] in the config)"
+            } else {
+                "ranked search requires the full-text index (build with the `fulltext` feature)"
+            };
+            return Err(McpError::invalid_params(message, None));
+        };
+        let scores = index.search(&query.keyword, limit).map_err(store_error)?;
+        Ok(Some(scores.into_iter().collect()))
+    }
+
+    /// `capability` が設定で無効になっていれば `method` を拒否する
+    fn require(&self, capability: Capability, method: &str) -> Result<(), McpError> {
+        if self.config.capabilities.is_enabled(capability) {
+            Ok(())
+        } else {
+            Err(capability_disabled(method))
+        }
+    }
+
+    /// リクエストを送ったトークンに許可するツール
+    ///
+    /// HTTPのトランスポートではリクエストごとに認証するので、ミドルウェアがリクエストに付けた
+    /// ものを優先する。それ以外は接続時に設定したものを使う。
+    fn tool_policy(&self, context: &RequestContext<RoleServer>) -> ToolPolicy {
+        #[cfg(any(feature = "sse", feature = "streamable-http"))]
+        if let Some(policy) = context
+            .extensions
+            .get::<axum::http::request::Parts>()
+            .and_then(|parts| parts.extensions.get::<ToolPolicy>())
+        {
+            return policy.clone();
+        }
+        let _ = context;
+        self.policy.clone()
+    }
+
+    /// 読み取り専用モードであれば `tool` の変更を拒否する
+    fn require_writable(&self, tool: &str) -> Result<(), McpError> {
+        if self.config.read_only { Err(read_only_denied(tool)) } else { Ok(()) }
+    }
+
+    /// このセッションで取り決めたプロトコルのリビジョン（初期化前は対応する最新のもの）
+    fn protocol_version(&self) -> String {
+        self.session
+            .protocol_version()
+            .unwrap_or_else(|| protocol::LATEST_VERSION.to_string())
+    }
+
+    /// 変更の履歴を記録するカタログの名前（セッションのファイルのストアを使っている間は記録しない）
+    fn history_catalog(&self) -> Option<&str> {
+        if self.file_store.read().expect("file store lock poisoned").is_some() {
+            return None;
+        }
+        Some(self.catalog.as_deref().unwrap_or(DEFAULT_CATALOG))
+    }
+
+    /// 変更する前の本の状態を読み取る（履歴を記録しない場合は空）
+    async fn capture(&self, isbns: &[&str]) -> Result<Vec<(String, Option<BookState>)>, McpError> {
+        if self.history_catalog().is_none() {
+            return Ok(Vec::new());
+        }
+        history::capture(self.store().as_ref(), isbns).await.map_err(store_error)
+    }
+
+    /// `capture` で読み取った本の、変更後の状態と合わせて履歴に記録する
+    async fn record_change(&self, tool: &str, before: Vec<(String, Option<BookState>)>) -> Result<(), McpError> {
+        match self.history_catalog() {
+            Some(catalog) if !before.is_empty() => self
+                .history
+                .record(catalog, tool, self.store().as_ref(), before)
+                .await
+                .map_err(store_error),
+            _ => Ok(()),
+        }
+    }
+
+    /// 書き換えた本を、読み込んだときの内容に書き戻す
+    ///
+    /// 書き戻せなかった（別の呼び出しが先に書き換えた、または保存先のエラー）本のISBNを返す。
+    async fn roll_back(&self, applied: &[(&Book, u64)]) -> Vec<String> {
+        let store = self.store();
+        let mut failed = Vec::new();
+        for (original, version) in applied {
+            match store.update((*original).clone(), *version).await {
+                Ok(VersionCheck::Applied(_)) => {}
+                Ok(_) => failed.push(original.isbn.clone()),
+                Err(e) => {
+                    tracing::error!("Failed to roll back {}: {:#}", original.isbn, e);
+                    failed.push(original.isbn.clone());
+                }
+            }
+        }
+        failed
+    }
+
+    /// 統合の途中で失敗したとき、残す本と削除した本を統合前の内容（レビューも含む）に書き戻す
+    ///
+    /// 書き戻せなかった本のISBNを返す。
+    async fn restore_merged(&self, primary: &BookState, removed: &[&BookState]) -> Vec<String> {
+        let store = self.store();
+        let mut failed = Vec::new();
+        for state in std::iter::once(primary).chain(removed.iter().copied()) {
+            if let Err(e) = restore_state(store.as_ref(), state).await {
+                tracing::error!("Failed to restore {}: {:#}", state.book.isbn, e);
+                failed.push(state.book.isbn.clone());
+            }
+        }
+        failed
+    }
+
+    /// 取り消し・やり直しで書き戻した本の変更を通知し、結果を返す
+    fn reverted(&self, reverted: history::Reverted) -> Result<CallToolResult, McpError> {
+        for isbn in &reverted.removed {
+            self.publish(CatalogEvent::new(ChangeKind::Removed, isbn));
+        }
+        for isbn in &reverted.updated {
+            self.publish(CatalogEvent::new(ChangeKind::Updated, isbn));
+        }
+        for isbn in &reverted.added {
+            self.publish(CatalogEvent::new(ChangeKind::Added, isbn));
+        }
+        tracing::info!("Reverted {} from {}", reverted.tool, reverted.at);
+        Ok(CallToolResult::success(vec![Content::json(&reverted)?]))
+    }
+
+    /// 取り決めたリビジョンで使える機能
+    pub(crate) fn features(&self) -> protocol::Features {
+        protocol::Features::of(&self.protocol_version())
+    }
+
+    /// 出力スキーマを持つツールの結果に、最初のJSONのコンテンツを構造化された結果として付ける
+    fn with_structured_content(&self, tool: &str, mut result: CallToolResult) -> CallToolResult {
+        let wanted = self.features().structured_output
+            && result.is_error != Some(true)
+            && result.structured_content.is_none()
+            && output::output_schema(tool).is_some();
+        if wanted {
+            result.structured_content = result
+                .content
+                .first()
+                .and_then(|content| content.as_text())
+                .and_then(|text| serde_json::from_str(&text.text).ok());
+        }
+        result
+    }
+
+    /// このセッションの応答の言語（`set_preferences` で変更できる）
+    fn lang(&self) -> Lang {
+        self.session.preferences().lang.unwrap_or(self.config.lang)
+    }
+
+    /// このセッションで `limit` を省略した検索が返す件数
+    fn default_limit(&self) -> usize {
+        self.session.preferences().default_limit.unwrap_or(self.config.default_search_limit)
+    }
+
+    /// このセッションの検索で返す件数の既定値と、設定された上限
+    fn search_limits(&self) -> SearchLimits {
+        SearchLimits {
+            default: self.default_limit(),
+            max: self.config.max_search_limit,
+        }
+    }
+
+    /// このセッションが読み書きするストア（ファイルのストアを開いていればそれを使う）
+    fn store(&self) -> Arc<dyn BookStore> {
+        match &*self.file_store.read().expect("file store lock poisoned") {
+            Some(store) => store.clone(),
+            None => self.store.clone(),
+        }
+    }
+
+    /// ごみ箱にない本を登録順に返す
+    async fn books(&self) -> Result<Vec<Book>, McpError> {
+        self.books_including(false).await
+    }
+
+    /// `include_deleted` の場合はごみ箱の本も含めて返す
+    async fn books_including(&self, include_deleted: bool) -> Result<Vec<Book>, McpError> {
+        let mut books = self.store().all().await.map_err(store_error)?;
+        if !include_deleted {
+            books.retain(|book| !book.is_deleted());
+        }
+        Ok(books)
+    }
+
+    /// このセッションで、ごみ箱の本も一覧に含める設定になっているか
+    fn include_deleted(&self) -> bool {
+        self.session.preferences().include_deleted.unwrap_or(false)
+    }
+
+    /// ISBNでごみ箱にない本を1冊取得する
+    async fn book(&self, isbn: &str) -> Result<Option<Book>, McpError> {
+        Ok(self.store().get(isbn).await.map_err(store_error)?.filter(|book| !book.is_deleted()))
+    }
+
+    /// カタログにない（またはごみ箱にある）本のISBNについての入力エラー
+    async fn unknown_books(&self, isbns: &[String]) -> Result<Vec<String>, McpError> {
+        let mut errors = Vec::new();
+        for isbn in isbns {
+            if self.book(isbn).await?.is_none() {
+                errors.push(self.lang().book_not_found(isbn));
+            }
+        }
+        Ok(errors)
+    }
+
+    /// 著者と本の対応（`include_deleted` の場合はごみ箱の本も含める）
+    async fn authors(&self, include_deleted: bool) -> Result<Directory, McpError> {
+        let records = self.store().authors().await.map_err(store_error)?;
+        Ok(Directory::build(records, &self.books_including(include_deleted).await?))
+    }
+
+    /// 著者の本を登録順に返す
+    async fn author_books(&self, entry: &AuthorEntry) -> Result<Vec<Book>, McpError> {
+        let store = self.store();
+        let mut books = Vec::new();
+        for isbn in &entry.books {
+            books.extend(store.get(isbn).await.map_err(store_error)?);
+        }
+        Ok(books)
+    }
+
+    /// 既定のカタログと名前付きのカタログの、全てのリソース
+    async fn all_resources(&self) -> Result<Vec<Resource>, McpError> {
+        let include_deleted = self.include_deleted();
+        let mut all = resources::list(None, &self.books_including(include_deleted).await?);
+        for (name, store) in self.catalogs.all() {
+            let mut books = store.all().await.map_err(store_error)?;
+            books.retain(|book| include_deleted || !book.is_deleted());
+            all.extend(resources::list(Some(&name), &books));
+        }
+        Ok(all)
+    }
+
+    /// 本のレビューの平均評価（レビューがなければ `None`）
+    async fn rating(&self, isbn: &str) -> Result<Option<RatingSummary>, McpError> {
+        let reviews = self.store().reviews(isbn).await.map_err(store_error)?;
+        Ok(RatingSummary::of(&reviews))
+    }
+
+    /// 架空の本を検索するツール
+    /// 
+    /// # 引数
+    /// * SearchQuery - 検索クエリを含むリクエスト構造体
+    /// 
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 検索結果
+    #[tool(description = "Search for book in our fictional database")]
+    async fn search(&self, #[tool(aggr)] query: SearchQuery) -> Result<CallToolResult, McpError> {
+        let books = self.books_including(query.include_deleted.unwrap_or(self.include_deleted())).await?;
+        let scores = self.ranked_scores(&query, books.len())?;
+        let SearchResults { books: results, next_cursor, filters, matched } = run_search(&books, &query, self.search_limits(), scores.as_ref())?;
+        let result_id = self.results.insert(matched.iter().map(|isbn| isbn.to_string()).collect());
+        let keyword = query.keyword;
+        let mut ratings = vec![None; results.len()];
+        if query.include_rating.unwrap_or(false) {
+            for (rating, book) in ratings.iter_mut().zip(&results) {
+                *rating = Some(self.rating(&book.isbn).await?);
+            }
+        }
+
+        let structured = SearchOutput {
+            keyword: keyword.clone(),
+            filters: filters.clone(),
+            count: results.len(),
+            books: results
+                .iter()
+                .zip(&ratings)
+                .map(|(book, rating)| BookHit {
+                    book: (*book).clone(),
+                    rating: *rating,
+                })
+                .collect(),
+            next_cursor: next_cursor.clone(),
+            result_id: result_id.clone(),
+        };
+        if query.output_format.unwrap_or_default() == OutputFormat::Json {
+            return Ok(CallToolResult::success(vec![Content::json(&structured)?]));
+        }
+
+        let lang = query.lang.unwrap_or(self.lang());
+        let output = if results.is_empty() {
+            lang.no_results(&keyword)
+        } else {
+            let mut output = lang.results_header(&keyword);
+            for filter in &filters {
+                output.push_str(&lang.filter_line(filter.filter, &filter.value, filter.matched));
+            }
+            if !filters.is_empty() {
+                output.push('\n');
+            }
+            let rating_label = lang.labels().rating;
+            let terms = query.highlight.unwrap_or(false).then(|| parse_query(&keyword));
+            for (book, rating) in results.iter().zip(&ratings) {
+                let mut text = match &terms {
+                    Some(terms) => format_book_in(&highlight::book(book, terms, &self.config.highlight), lang),
+                    None => format_book_in(book, lang),
+                };
+                match rating {
+                    Some(Some(rating)) => {
+                        text.pop();
+                        text.push_str(&format!("{}: {}\n\n", rating_label, lang.rating(rating.average, rating.count)));
+                    }
+                    Some(None) => {
+                        text.pop();
+                        text.push_str(&format!("{}: {}\n\n", rating_label, lang.no_reviews()));
+                    }
+                    None => {}
+                }
+                output.push_str(&text);
+            }
+            if let Some(cursor) = next_cursor {
+                output.push_str(&lang.more_results(&cursor));
+            }
+            output.push_str(&lang.result_id(&result_id));
+            output
+        };
+
+        // テキストの結果にも、対応するクライアントには同じ内容の構造化された結果を付ける
+        let mut result = CallToolResult::success(vec![Content::text(output)]);
+        if self.features().structured_output {
+            result.structured_content = Some(serde_json::to_value(&structured).map_err(|e| McpError::internal_error(e.to_string(), None))?);
+        }
+        Ok(result)
+    }
+
+    /// 以前の検索結果を、検索し直さずにキーワードでさらに絞り込むツール
+    ///
+    /// 元の検索で一致した本だけを対象にするので、元の検索条件を繰り返す必要はない。
+    /// 絞り込んだ結果にも新しい `result_id` が付くので、続けて絞り込める。
+    ///
+    /// # 引数
+    /// * RefineSearchRequest - 元の結果の `result_id` と、追加のキーワード
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 絞り込んだ検索結果（`search` の JSON 形式と同じ）
+    #[tool(description = "Narrow a previous search result by an additional keyword without re-running the query")]
+    async fn refine_search(
+        &self,
+        #[tool(aggr)] RefineSearchRequest { result_id, keyword, limit, cursor, sort_by }: RefineSearchRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(isbns) = self.results.get(&result_id) else {
+            return validation_failure(vec![format!(
+                "result_id '{}' は見つからないか、有効期限（{}秒）が切れています。search で検索し直してください",
+                result_id,
+                self.results.ttl().as_secs()
+            )]);
+        };
+        // 元の結果の順に並べる（検索した後に完全に削除された本は除く）
+        let mut by_isbn: HashMap<String, Book> = self
+            .books_including(true)
+            .await?
+            .into_iter()
+            .map(|book| (book.isbn.clone(), book))
+            .collect();
+        let books: Vec<Book> = isbns.iter().filter_map(|isbn| by_isbn.remove(isbn)).collect();
+
+        let query = SearchQuery {
+            keyword,
+            limit,
+            cursor,
+            sort_by,
+            ..SearchQuery::default()
+        };
+        let SearchResults { books: results, next_cursor, matched, .. } = run_search(&books, &query, self.search_limits(), None)?;
+        let structured = SearchOutput {
+            keyword: query.keyword.clone(),
+            filters: Vec::new(),
+            count: results.len(),
+            books: results
+                .iter()
+                .map(|book| BookHit {
+                    book: (*book).clone(),
+                    rating: None,
+                })
+                .collect(),
+            next_cursor,
+            result_id: self.results.insert(matched.iter().map(|isbn| isbn.to_string()).collect()),
+        };
+        Ok(CallToolResult::success(vec![Content::json(&structured)?]))
+    }
+
+    /// タグの一覧と、それぞれのタグが付いた本の数を返すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - タグと冊数の一覧（多い順）
+    #[tool(description = "List tags with the number of books carrying each")]
+    async fn list_tags(&self) -> Result<CallToolResult, McpError> {
+        let tags = tag_counts(&self.books().await?)
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag, count })
+            .collect();
+        Ok(CallToolResult::success(vec![Content::json(TagList { tags })?]))
+    }
+
+    /// 著者の一覧を返すツール
+    ///
+    /// # 引数
+    /// * ListAuthorsRequest - 著者名で絞り込む語（省略可）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 著者と本の冊数の一覧（名前の五十音順）
+    #[tool(description = "List authors with the number of books by each")]
+    async fn list_authors(&self, #[tool(aggr)] ListAuthorsRequest { query }: ListAuthorsRequest) -> Result<CallToolResult, McpError> {
+        let directory = self.authors(false).await?;
+        let normalizer = crate::search::normalizer();
+        let query = query.map(|query| normalizer.normalize(query.trim()));
+        let authors: Vec<_> = directory
+            .sorted()
+            .into_iter()
+            .filter(|entry| {
+                let mut names = std::iter::once(&entry.author.name).chain(&entry.author.aliases);
+                query.as_ref().is_none_or(|query| names.any(|name| normalizer.normalize(name).contains(query.as_str())))
+            })
+            .map(|entry| {
+                json!({
+                    "id": entry.author.id,
+                    "name": entry.author.name,
+                    "aliases": entry.author.aliases,
+                    "registered": entry.registered,
+                    "book_count": entry.books.len(),
+                })
+            })
+            .collect();
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": authors.len(),
+            "authors": authors,
+        }))?]))
+    }
+
+    /// 著者の記録と、その著者の本の一覧を返すツール
+    ///
+    /// # 引数
+    /// * AuthorRequest - 著者のID、または著者名
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 著者の名前・紹介・別名と本の一覧
+    #[tool(description = "Get an author by ID or name, with their books")]
+    async fn get_author(&self, #[tool(aggr)] AuthorRequest { author }: AuthorRequest) -> Result<CallToolResult, McpError> {
+        let directory = self.authors(false).await?;
+        let Some(entry) = directory.find(&author) else {
+            return validation_failure(vec![author_not_found(&author)]);
+        };
+        let books: Vec<_> = self
+            .author_books(entry)
+            .await?
+            .into_iter()
+            .map(|book| json!({ "isbn": book.isbn, "title": book.title, "year": book.year }))
+            .collect();
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "id": entry.author.id,
+            "name": entry.author.name,
+            "bio": entry.author.bio,
+            "aliases": entry.author.aliases,
+            "registered": entry.registered,
+            "books": books,
+        }))?]))
+    }
+
+    /// 著者の本を返すツール
+    ///
+    /// 著者名の文字列ではなく著者の記録で探すので、別名や以前の名前で登録された本も含まれる。
+    ///
+    /// # 引数
+    /// * BooksByAuthorRequest - 著者のID、または著者名と、ページの指定
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 著者の本（登録順）
+    #[tool(description = "List books by an author (by ID or name, including aliases)")]
+    async fn books_by_author(
+        &self,
+        #[tool(aggr)] BooksByAuthorRequest { author, limit, cursor }: BooksByAuthorRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let limit = resolve_limit(limit, self.search_limits())?;
+        let offset = pagination::decode_cursor(cursor.as_deref())?;
+        let directory = self.authors(false).await?;
+        let Some(entry) = directory.find(&author) else {
+            return validation_failure(vec![author_not_found(&author)]);
+        };
+        let books = self.author_books(entry).await?;
+        let count = books.len();
+        let (books, next_cursor) = pagination::paginate(books, offset, limit);
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "author": { "id": entry.author.id, "name": entry.author.name },
+            "count": count,
+            "books": books,
+            "next_cursor": next_cursor,
+        }))?]))
+    }
+
+    /// シリーズの一覧を返すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - シリーズ名・冊数・著者の一覧（名前の五十音順）
+    #[tool(description = "List book series with their volume counts and authors")]
+    async fn list_series(&self) -> Result<CallToolResult, McpError> {
+        let series: Vec<_> = series_volumes(&self.books().await?)
+            .into_iter()
+            .map(|(name, books)| {
+                let mut authors: Vec<&str> = Vec::new();
+                for book in &books {
+                    if !authors.contains(&book.author.as_str()) {
+                        authors.push(&book.author);
+                    }
+                }
+                json!({ "name": name, "volumes": books.len(), "authors": authors })
+            })
+            .collect();
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": series.len(),
+            "series": series,
+        }))?]))
+    }
+
+    /// シリーズの本を巻の順に返すツール
+    ///
+    /// # 引数
+    /// * SeriesRequest - シリーズ名（全角半角・大文字小文字の違いは無視する）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - シリーズの本（巻数の順。巻数のない本は最後）
+    #[tool(description = "Get the volumes of a series in order")]
+    async fn get_series(&self, #[tool(aggr)] SeriesRequest { name }: SeriesRequest) -> Result<CallToolResult, McpError> {
+        let normalizer = crate::search::normalizer();
+        let wanted = normalizer.normalize(name.trim());
+        let books = self.books().await?;
+        let Some((name, volumes)) = series_volumes(&books)
+            .into_iter()
+            .find(|(series, _)| normalizer.normalize(series.trim()) == wanted)
+        else {
+            return validation_failure(vec![series_not_found(&name)]);
+        };
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "name": name,
+            "count": volumes.len(),
+            "volumes": volumes,
+        }))?]))
+    }
+
+    /// 著者の記録を作成・更新するツール
+    ///
+    /// 名前を変えると、その著者の全ての本（ごみ箱の本も含む）の著者名を書き換え、本を著者の記録に結び付ける。
+    /// 別の呼び出しが先に書き換えた本があれば、書き換えた本を元に戻し、著者の記録も変えない。
+    ///
+    /// # 引数
+    /// * UpdateAuthorRequest - 著者のIDか名前と、変更する項目
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 更新した著者の記録と、著者名を書き換えた本のISBN
+    #[tool(description = "Create or update an author record; renaming propagates to all of the author's books")]
+    async fn update_author(
+        &self,
+        #[tool(aggr)] UpdateAuthorRequest { author, name, bio, aliases }: UpdateAuthorRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let directory = self.authors(true).await?;
+        let Some(entry) = directory.find(&author) else {
+            return validation_failure(vec![author_not_found(&author)]);
+        };
+        let mut record = entry.author.clone();
+        if let Some(name) = name.map(|name| name.trim().to_string()) {
+            if name.is_empty() {
+                return validation_failure(vec!["name は空にできません".to_string()]);
+            }
+            if name != record.name {
+                let previous = std::mem::replace(&mut record.name, name);
+                record.aliases.push(previous);
+            }
+        }
+        if let Some(bio) = bio {
+            record.bio = bio;
+        }
+        if let Some(aliases) = aliases {
+            record.aliases = aliases;
+        }
+        let mut aliases = Vec::new();
+        for alias in std::mem::take(&mut record.aliases) {
+            let alias = alias.trim().to_string();
+            if !alias.is_empty() && alias != record.name && !aliases.contains(&alias) {
+                aliases.push(alias);
+            }
+        }
+        record.aliases = aliases;
+
+        // 本を先に書き換え、全て書き換えられたときだけ著者を保存する
+        let store = self.store();
+        let isbns: Vec<&str> = entry.books.iter().map(String::as_str).collect();
+        let before = self.capture(&isbns).await?;
+        let originals = self.author_books(entry).await?;
+        let mut applied: Vec<(&Book, u64)> = Vec::new();
+        for original in &originals {
+            if original.author == record.name && original.author_id.as_deref() == Some(record.id.as_str()) {
+                continue;
+            }
+            let mut book = original.clone();
+            book.author = record.name.clone();
+            book.author_id = Some(record.id.clone());
+            let failure = match store.update(book, original.version).await {
+                Ok(VersionCheck::Applied(version)) => {
+                    applied.push((original, version));
+                    continue;
+                }
+                Ok(VersionCheck::Conflict(version)) => Err(version_conflict(&original.isbn, original.version, version)),
+                Ok(VersionCheck::NotFound) => validation_failure(vec![self.lang().book_not_found(&original.isbn)]),
+                Err(e) => Err(store_error(e)),
+            };
+            let failed = self.roll_back(&applied).await;
+            if !failed.is_empty() {
+                return Err(rollback_failed(&failed));
+            }
+            return failure;
+        }
+        if let Err(e) = store.put_author(record.clone()).await {
+            let failed = self.roll_back(&applied).await;
+            if !failed.is_empty() {
+                return Err(rollback_failed(&failed));
+            }
+            return Err(store_error(e));
+        }
+        let renamed: Vec<&str> = applied.iter().map(|(book, _)| book.isbn.as_str()).collect();
+        if !renamed.is_empty() {
+            self.record_change("update_author", before).await?;
+        }
+        for isbn in &renamed {
+            self.publish(CatalogEvent::new(ChangeKind::Updated, isbn));
+        }
+        tracing::info!("Updated author {} ({} books relinked)", record.id, renamed.len());
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "author": record,
+            "updated_books": renamed,
+        }))?]))
+    }
+
+    /// 読書リストを作成するツール
+    ///
+    /// 読書リストはカタログと一緒に保存されるので、別の呼び出しやセッションからも続けて使える。
+    ///
+    /// # 引数
+    /// * CreateReadingListRequest - リストの名前と説明、最初に加える本
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 作成した読書リスト
+    #[tool(description = "Create a named reading list, optionally with initial books")]
+    async fn create_reading_list(
+        &self,
+        #[tool(aggr)] CreateReadingListRequest { name, description, isbns }: CreateReadingListRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return validation_failure(vec!["name は空にできません".to_string()]);
+        }
+        if self.store().reading_list(&name).await.map_err(store_error)?.is_some() {
+            return validation_failure(vec![format!("読書リスト '{}' は既に存在します", name)]);
+        }
+        let errors = self.unknown_books(&isbns).await?;
+        if !errors.is_empty() {
+            return validation_failure(errors);
+        }
+
+        let mut unique = Vec::new();
+        for isbn in isbns {
+            if !unique.contains(&isbn) {
+                unique.push(isbn);
+            }
+        }
+        let now = chrono::Utc::now();
+        let list = ReadingList {
+            name,
+            description,
+            isbns: unique,
+            created_at: now,
+            updated_at: now,
+        };
+        self.store().put_reading_list(list.clone()).await.map_err(store_error)?;
+        tracing::info!("Created reading list {} ({} books)", list.name, list.isbns.len());
+        Ok(CallToolResult::success(vec![Content::json(&list)?]))
+    }
+
+    /// 読書リストに本を加えるツール
+    ///
+    /// # 引数
+    /// * ReadingListBooksRequest - リストの名前と、加える本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 加えた本と、既にリストにあった本
+    #[tool(description = "Add books to a reading list")]
+    async fn add_to_reading_list(
+        &self,
+        #[tool(aggr)] ReadingListBooksRequest { name, isbns }: ReadingListBooksRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(mut list) = self.store().reading_list(name.trim()).await.map_err(store_error)? else {
+            return validation_failure(vec![reading_list_not_found(&name)]);
+        };
+        let errors = self.unknown_books(&isbns).await?;
+        if !errors.is_empty() {
+            return validation_failure(errors);
+        }
+
+        let mut added = Vec::new();
+        let mut already_listed = Vec::new();
+        for isbn in isbns {
+            if list.isbns.contains(&isbn) {
+                already_listed.push(isbn);
+            } else {
+                list.isbns.push(isbn.clone());
+                added.push(isbn);
+            }
+        }
+        if !added.is_empty() {
+            list.updated_at = chrono::Utc::now();
+            self.store().put_reading_list(list.clone()).await.map_err(store_error)?;
+        }
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "name": list.name,
+            "added": added,
+            "already_listed": already_listed,
+            "count": list.isbns.len(),
+        }))?]))
+    }
+
+    /// 読書リストから本を外すツール
+    ///
+    /// # 引数
+    /// * ReadingListBooksRequest - リストの名前と、外す本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 外した本と、リストになかった本
+    #[tool(description = "Remove books from a reading list")]
+    async fn remove_from_reading_list(
+        &self,
+        #[tool(aggr)] ReadingListBooksRequest { name, isbns }: ReadingListBooksRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(mut list) = self.store().reading_list(name.trim()).await.map_err(store_error)? else {
+            return validation_failure(vec![reading_list_not_found(&name)]);
+        };
+        let (removed, not_listed): (Vec<String>, Vec<String>) =
+            isbns.into_iter().partition(|isbn| list.isbns.contains(isbn));
+        if !removed.is_empty() {
+            list.isbns.retain(|isbn| !removed.contains(isbn));
+            list.updated_at = chrono::Utc::now();
+            self.store().put_reading_list(list.clone()).await.map_err(store_error)?;
+        }
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "name": list.name,
+            "removed": removed,
+            "not_listed": not_listed,
+            "count": list.isbns.len(),
+        }))?]))
+    }
+
+    /// 読書リストとその本を返すツール
+    ///
+    /// # 引数
+    /// * ReadingListRequest - リストの名前
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - リストの説明と本（加えた順）。削除された本やごみ箱の本は `missing` に入る
+    #[tool(description = "Get a reading list with its books")]
+    async fn get_reading_list(&self, #[tool(aggr)] ReadingListRequest { name }: ReadingListRequest) -> Result<CallToolResult, McpError> {
+        let Some(list) = self.store().reading_list(name.trim()).await.map_err(store_error)? else {
+            return validation_failure(vec![reading_list_not_found(&name)]);
+        };
+        let mut books = Vec::new();
+        let mut missing = Vec::new();
+        for isbn in &list.isbns {
+            match self.book(isbn).await? {
+                Some(book) => books.push(book),
+                None => missing.push(isbn.clone()),
+            }
+        }
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "name": list.name,
+            "description": list.description,
+            "created_at": list.created_at,
+            "updated_at": list.updated_at,
+            "count": books.len(),
+            "books": books,
+            "missing": missing,
+        }))?]))
+    }
+
+    /// 本を追加するツール
+    ///
+    /// 必須の項目が足りない場合、クライアントがエリシテーションに対応していれば
+    /// 利用者に不足分の入力を求めてから追加する。
+    ///
+    /// # 引数
+    /// * AddBookRequest - 追加する本と、試行だけを行うか
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 追加した本（ISBNの重複や入力の不備はエラーとして返す）
+    #[tool(description = "Add a book to the catalog")]
+    async fn add_book(
+        &self,
+        #[tool(aggr)] AddBookRequest { book: mut draft, dry_run }: AddBookRequest,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let missing = draft.missing_fields();
+        if !missing.is_empty() && context.peer.peer_info().capabilities.elicitation.is_some() {
+            let result = context
+                .peer
+                .create_elicitation(elicitation::request(&draft, &missing))
+                .await
+                .map_err(|e| McpError::internal_error(format!("elicitation request failed: {}", e), None))?;
+            match (result.action, result.content) {
+                (ElicitationAction::Accept, Some(content)) => draft.fill(&content),
+                (ElicitationAction::Accept, None) => {}
+                (ElicitationAction::Decline | ElicitationAction::Cancel, _) => {
+                    tracing::info!("Client declined to fill in {:?} for add_book", missing);
+                    return validation_failure(vec!["利用者が不足している項目の入力を取りやめました".to_string()]);
+                }
+            }
+        }
+        let mut book = match draft.into_book() {
+            Ok(book) => book,
+            Err(errors) => return validation_failure(errors),
+        };
+        let mut errors = prepare_new_book(&mut book);
+        match self.store().get(&book.isbn).await.map_err(store_error)? {
+            Some(existing) if existing.is_deleted() => errors.push(format!(
+                "ISBN '{}' の本はごみ箱にあります（restore_book で戻すか、purge_trash で完全に削除してください）",
+                book.isbn
+            )),
+            Some(_) => errors.push(format!("ISBN '{}' の本は既に登録されています", book.isbn)),
+            None => {}
+        }
+        if !errors.is_empty() {
+            return validation_failure(errors);
+        }
+        if dry_run {
+            return Ok(CallToolResult::success(vec![Content::json(json!({
+                "dry_run": true,
+                "would_add": book,
+            }))?]));
+        }
+
+        let before = self.capture(&[&book.isbn]).await?;
+        if !self.store().insert(book.clone()).await.map_err(store_error)? {
+            // 確認してから追加するまでの間に、別の呼び出しが同じ本を追加した
+            return validation_failure(vec![format!("ISBN '{}' の本は既に登録されています", book.isbn)]);
+        }
+        self.record_change("add_book", before).await?;
+        self.publish(CatalogEvent::new(ChangeKind::Added, &book.isbn));
+        Ok(CallToolResult::success(vec![Content::json(&book)?]))
+    }
+
+    /// 本の情報を更新するツール
+    ///
+    /// # 引数
+    /// * UpdateBookRequest - 更新する本のISBNと、変更するフィールド
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 更新後の本
+    #[tool(description = "Update fields of an existing book")]
+    async fn update_book(&self, #[tool(aggr)] request: UpdateBookRequest) -> Result<CallToolResult, McpError> {
+        let Some(current) = self.book(&request.isbn).await? else {
+            return validation_failure(vec![self.lang().book_not_found(&request.isbn)]);
+        };
+        // 省略時も読み込んだ版を期待値にして、読み込みから書き込みまでの間の変更を上書きしないようにする
+        let expected = request.expected_version.unwrap_or(current.version);
+        if expected != current.version {
+            return Err(version_conflict(&current.isbn, expected, current.version));
+        }
+        let mut book = current.clone();
+
+        if let Some(title) = request.title {
+            book.title = title;
+        }
+        if let Some(author) = request.author {
+            // 著者の記録との結び付きを外し、新しい著者名に一致する著者の本にする
+            if author != book.author {
+                book.author_id = None;
+            }
+            book.author = author;
+        }
+        if let Some(year) = request.year {
+            book.year = year;
+        }
+        if let Some(description) = request.description {
+            book.description = description;
+        }
+        if let Some(tags) = request.tags {
+            book.tags = tags;
+        }
+        if let Some(series) = request.series {
+            book.series = Some(series).filter(|series| !series.name.is_empty());
+        }
+        if let Some(cover_path) = request.cover_path {
+            book.cover_path = Some(cover_path).filter(|path| !path.is_empty());
+        }
+        if let Some(cover_url) = request.cover_url {
+            book.cover_url = Some(cover_url).filter(|url| !url.is_empty());
+        }
+
+        let errors = validate_book(&book);
+        if !errors.is_empty() {
+            return validation_failure(errors);
+        }
+        if request.dry_run {
+            return Ok(CallToolResult::success(vec![Content::json(json!({
+                "dry_run": true,
+                "isbn": book.isbn,
+                "changes": book_changes(&current, &book),
+            }))?]));
+        }
+
+        let before = self.capture(&[&book.isbn]).await?;
+        match self.store().update(book.clone(), expected).await.map_err(store_error)? {
+            VersionCheck::Applied(version) => book.version = version,
+            VersionCheck::NotFound => return validation_failure(vec![self.lang().book_not_found(&book.isbn)]),
+            VersionCheck::Conflict(current) => return Err(version_conflict(&book.isbn, expected, current)),
+        }
+        self.record_change("update_book", before).await?;
+        self.publish(CatalogEvent::new(ChangeKind::Updated, &book.isbn));
+        Ok(CallToolResult::success(vec![Content::json(&book)?]))
+    }
+
+    /// 本をごみ箱に移すツール
+    ///
+    /// ごみ箱の本は検索やリソースの一覧に出なくなるが、レビューとともに残り、`restore_book` で戻せる。
+    /// 完全に削除するには `purge_trash` を使う。
+    ///
+    /// # 引数
+    /// * DeleteBookRequest - 削除する本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - ごみ箱に移した本のISBNと版
+    #[tool(description = "Move a book to the trash (restore it with restore_book, or delete it for good with purge_trash)")]
+    async fn delete_book(
+        &self,
+        #[tool(aggr)] DeleteBookRequest { isbn, expected_version, dry_run }: DeleteBookRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(mut book) = self.book(&isbn).await? else {
+            return validation_failure(vec![self.lang().book_not_found(&isbn)]);
+        };
+        let expected = book.version;
+        if let Some(requested) = expected_version.filter(|requested| *requested != expected) {
+            return Err(version_conflict(&isbn, requested, expected));
+        }
+        if dry_run {
+            let reviews = self.store().reviews(&isbn).await.map_err(store_error)?;
+            return Ok(CallToolResult::success(vec![Content::json(json!({
+                "dry_run": true,
+                "would_delete": book,
+                "reviews_kept": reviews.len(),
+            }))?]));
+        }
+
+        let before = self.capture(&[&isbn]).await?;
+        book.deleted_at = Some(chrono::Utc::now());
+        let version = match self.store().update(book, expected).await.map_err(store_error)? {
+            VersionCheck::Applied(version) => version,
+            VersionCheck::NotFound => return validation_failure(vec![self.lang().book_not_found(&isbn)]),
+            VersionCheck::Conflict(current) => return Err(version_conflict(&isbn, expected, current)),
+        };
+        self.record_change("delete_book", before).await?;
+        self.publish(CatalogEvent::new(ChangeKind::Removed, &isbn));
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "deleted": isbn,
+            "version": version,
+            "trashed": true,
+        }))?]))
+    }
+
+    /// ごみ箱にある本の一覧を返すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - ごみ箱の本（新しく移した順）
+    #[tool(description = "List books in the trash, most recently deleted first")]
+    async fn list_trash(&self) -> Result<CallToolResult, McpError> {
+        let mut books: Vec<Book> = self.books_including(true).await?.into_iter().filter(Book::is_deleted).collect();
+        books.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": books.len(),
+            "books": books,
+        }))?]))
+    }
+
+    /// ごみ箱の本を元に戻すツール
+    ///
+    /// # 引数
+    /// * RestoreBookRequest - 戻す本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 戻した本
+    #[tool(description = "Restore a book from the trash")]
+    async fn restore_book(
+        &self,
+        #[tool(aggr)] RestoreBookRequest { isbn, expected_version }: RestoreBookRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(mut book) = self.store().get(&isbn).await.map_err(store_error)? else {
+            return validation_failure(vec![self.lang().book_not_found(&isbn)]);
+        };
+        if !book.is_deleted() {
+            return validation_failure(vec![format!("ISBN '{}' の本はごみ箱にありません", isbn)]);
+        }
+        let expected = book.version;
+        if let Some(requested) = expected_version.filter(|requested| *requested != expected) {
+            return Err(version_conflict(&isbn, requested, expected));
+        }
+
+        let before = self.capture(&[&isbn]).await?;
+        book.deleted_at = None;
+        match self.store().update(book.clone(), expected).await.map_err(store_error)? {
+            VersionCheck::Applied(version) => book.version = version,
+            VersionCheck::NotFound => return validation_failure(vec![self.lang().book_not_found(&isbn)]),
+            VersionCheck::Conflict(current) => return Err(version_conflict(&isbn, expected, current)),
+        }
+        self.record_change("restore_book", before).await?;
+        self.publish(CatalogEvent::new(ChangeKind::Added, &isbn));
+        Ok(CallToolResult::success(vec![Content::json(&book)?]))
+    }
+
+    /// ごみ箱の本をレビューとともに完全に削除するツール
+    ///
+    /// # 引数
+    /// * PurgeTrashRequest - 削除する本のISBN、またはごみ箱に移してからの日数（どちらも省略すると全て）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 完全に削除した本のISBN
+    #[tool(description = "Permanently delete books in the trash, optionally only one ISBN or those trashed a number of days ago")]
+    async fn purge_trash(
+        &self,
+        #[tool(aggr)] PurgeTrashRequest { isbn, older_than_days, dry_run }: PurgeTrashRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let cutoff = older_than_days.map(|days| chrono::Utc::now() - chrono::Duration::days(i64::from(days)));
+        let trashed: Vec<Book> = self
+            .books_including(true)
+            .await?
+            .into_iter()
+            .filter(|book| book.deleted_at.is_some_and(|at| cutoff.is_none_or(|cutoff| at <= cutoff)))
+            .filter(|book| isbn.as_ref().is_none_or(|isbn| book.isbn == *isbn))
+            .collect();
+        if let Some(isbn) = isbn.as_ref().filter(|_| trashed.is_empty() && older_than_days.is_none()) {
+            return validation_failure(vec![format!("ISBN '{}' の本はごみ箱にありません", isbn)]);
+        }
+        let isbns: Vec<&str> = trashed.iter().map(|book| book.isbn.as_str()).collect();
+        if dry_run || isbns.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::json(json!({
+                "dry_run": dry_run,
+                "count": isbns.len(),
+                "isbns": isbns,
+            }))?]));
+        }
+
+        let before = self.capture(&isbns).await?;
+        let mut purged = Vec::new();
+        for book in &trashed {
+            // 一覧を読んだ後に戻された本は消さない
+            if let VersionCheck::Applied(_) = self.store().remove(&book.isbn, Some(book.version)).await.map_err(store_error)? {
+                purged.push(book.isbn.as_str());
+            }
+        }
+        self.record_change("purge_trash", before).await?;
+        tracing::info!("Purged {} books from the trash", purged.len());
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "dry_run": false,
+            "count": purged.len(),
+            "isbns": purged,
+        }))?]))
+    }
+
+    /// 検索式に一致する全ての本に、同じ部分的な変更を適用するツール
+    ///
+    /// 変更は全ての本に適用するか、1冊も適用しないかのどちらかになる。途中で別の呼び出しが
+    /// 本を変更していた場合は、それまでに適用した本を元に戻してエラーを返す。
+    ///
+    /// # 引数
+    /// * BulkUpdateBooksRequest - 本を選ぶ検索式、適用する変更、試行だけを行うか
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 変更した冊数とISBN（試行の場合は本ごとの変更内容）
+    #[tool(description = "Apply a partial update to every book matching a query expression")]
+    async fn bulk_update_books(
+        &self,
+        #[tool(aggr)] BulkUpdateBooksRequest { query, patch, dry_run }: BulkUpdateBooksRequest,
+    ) -> Result<CallToolResult, McpError> {
+        if query.trim().is_empty() {
+            return validation_failure(vec!["query は必須です".to_string()]);
+        }
+        if patch.is_empty() {
+            return validation_failure(vec!["patch に変更する項目を1つ以上指定してください".to_string()]);
+        }
+        let expr = crate::query::parse(&query).map_err(|e| e.into_mcp_error(&query))?;
+
+        let mut errors = Vec::new();
+        let mut updates = Vec::new();
+        for current in self.books().await?.into_iter().filter(|book| expr.matches(book)) {
+            let mut book = current.clone();
+            patch.apply(&mut book);
+            errors.extend(validate_book(&book).into_iter().map(|error| format!("{}: {}", book.isbn, error)));
+            if !book_changes(&current, &book).is_empty() {
+                updates.push((current, book));
+            }
+        }
+        if !errors.is_empty() {
+            return validation_failure(errors);
+        }
+        let isbns: Vec<&str> = updates.iter().map(|(current, _)| current.isbn.as_str()).collect();
+        if dry_run {
+            let changes: Vec<_> = updates
+                .iter()
+                .map(|(current, book)| json!({ "isbn": current.isbn, "changes": book_changes(current, book) }))
+                .collect();
+            return Ok(CallToolResult::success(vec![Content::json(json!({
+                "dry_run": true,
+                "count": updates.len(),
+                "books": changes,
+            }))?]));
+        }
+
+        if updates.is_empty() {
+            return Ok(CallToolResult::success(vec![Content::json(json!({ "count": 0, "isbns": isbns }))?]));
+        }
+
+        let store = self.store();
+        let before = self.capture(&isbns).await?;
+        let mut applied: Vec<(&Book, u64)> = Vec::new();
+        for (current, book) in &updates {
+            let failure = match store.update(book.clone(), current.version).await {
+                Ok(VersionCheck::Applied(version)) => {
+                    applied.push((current, version));
+                    continue;
+                }
+                Ok(VersionCheck::Conflict(version)) => Err(version_conflict(&current.isbn, current.version, version)),
+                Ok(VersionCheck::NotFound) => validation_failure(vec![self.lang().book_not_found(&current.isbn)]),
+                Err(e) => Err(store_error(e)),
+            };
+            // 適用済みの本を読み込んだときの内容に書き戻す
+            let failed = self.roll_back(&applied).await;
+            if !failed.is_empty() {
+                return Err(rollback_failed(&failed));
+            }
+            return failure;
+        }
+        self.record_change("bulk_update_books", before).await?;
+        for isbn in &isbns {
+            self.publish(CatalogEvent::new(ChangeKind::Updated, *isbn));
+        }
+        tracing::info!("Bulk-updated {} books matching {}", isbns.len(), query);
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": isbns.len(),
+            "isbns": isbns,
+        }))?]))
+    }
+
+    /// 本にレビューを追加するツール
+    ///
+    /// # 引数
+    /// * Review - レビューする本のISBN、1から5の評価、本文、レビューした人
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 追加したレビューと、更新後の平均評価
+    #[tool(description = "Add a review with a 1-5 rating to a book")]
+    async fn add_review(&self, #[tool(aggr)] review: Review) -> Result<CallToolResult, McpError> {
+        let mut errors = validate_review(&review);
+        if !review.isbn.trim().is_empty() && self.book(&review.isbn).await?.is_none() {
+            errors.push(self.lang().book_not_found(&review.isbn));
+        }
+        if !errors.is_empty() {
+            return validation_failure(errors);
+        }
+
+        self.store().add_review(review.clone()).await.map_err(store_error)?;
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "review": review,
+            "rating": self.rating(&review.isbn).await?,
+        }))?]))
+    }
+
+    /// 本の表紙画像を画像コンテンツとして返すツール
+    ///
+    /// # 引数
+    /// * BookCoverRequest - 本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - Base64の画像コンテンツ
+    #[tool(description = "Get a book's cover as an image")]
+    async fn get_book_cover(&self, #[tool(aggr)] BookCoverRequest { isbn }: BookCoverRequest) -> Result<CallToolResult, McpError> {
+        let Some(book) = self.book(&isbn).await? else {
+            return validation_failure(vec![self.lang().book_not_found(&isbn)]);
+        };
+        match cover::load(&book).await {
+            Ok(Some(cover)) => Ok(CallToolResult::success(vec![Content::image(cover.base64(), cover.mime_type)])),
+            Ok(None) => validation_failure(vec![format!("ISBN '{}' の本には表紙画像が登録されていません", isbn)]),
+            Err(e) => {
+                tracing::warn!("Failed to load cover for {}: {:#}", isbn, e);
+                validation_failure(vec![format!("表紙画像を読み込めませんでした: {:#}", e)])
+            }
+        }
+    }
+
+    /// 本に付いたレビューを一覧するツール
+    ///
+    /// # 引数
+    /// * ReviewsRequest - 本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 投稿順のレビューの一覧
+    #[tool(description = "List the reviews of a book")]
+    async fn list_reviews(&self, #[tool(aggr)] ReviewsRequest { isbn }: ReviewsRequest) -> Result<CallToolResult, McpError> {
+        if self.book(&isbn).await?.is_none() {
+            return validation_failure(vec![self.lang().book_not_found(&isbn)]);
+        }
+        let reviews = self.store().reviews(&isbn).await.map_err(store_error)?;
+        Ok(CallToolResult::success(vec![Content::json(ReviewList {
+            count: reviews.len(),
+            isbn,
+            reviews,
+        })?]))
+    }
+
+    /// 本のレビューの平均評価を返すツール
+    ///
+    /// # 引数
+    /// * ReviewsRequest - 本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 平均評価とレビューの件数（レビューがなければ `rating` は null）
+    #[tool(description = "Get the average rating of a book")]
+    async fn get_average_rating(&self, #[tool(aggr)] ReviewsRequest { isbn }: ReviewsRequest) -> Result<CallToolResult, McpError> {
+        if self.book(&isbn).await?.is_none() {
+            return validation_failure(vec![self.lang().book_not_found(&isbn)]);
+        }
+        let rating = self.rating(&isbn).await?;
+        Ok(CallToolResult::success(vec![Content::json(RatingOutput { isbn, rating })?]))
+    }
+
+    /// 本を貸し出すツール
+    ///
+    /// # 引数
+    /// * CheckoutBookRequest - 貸し出す本のISBN、借りる人、貸出期間の日数
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 貸し出しの記録と返却期限
+    #[tool(description = "Check out a book to a borrower with a due date")]
+    async fn checkout_book(&self, #[tool(aggr)] CheckoutBookRequest { isbn, borrower, days }: CheckoutBookRequest) -> Result<CallToolResult, McpError> {
+        let days = days.unwrap_or(DEFAULT_LOAN_DAYS);
+        let mut errors = Vec::new();
+        if self.book(&isbn).await?.is_none() {
+            errors.push(self.lang().book_not_found(&isbn));
+        }
+        if borrower.trim().is_empty() {
+            errors.push("borrower は必須です".to_string());
+        }
+        if !LOAN_DAYS_RANGE.contains(&days) {
+            errors.push(format!(
+                "days は{}から{}の範囲で指定してください（指定値: {}）",
+                LOAN_DAYS_RANGE.start(),
+                LOAN_DAYS_RANGE.end(),
+                days
+            ));
+        }
+        if !errors.is_empty() {
+            return validation_failure(errors);
+        }
+
+        match self.loans.checkout(&isbn, &borrower, days, chrono::Utc::now()) {
+            Ok(checkout) => Ok(CallToolResult::success(vec![Content::json(&checkout)?])),
+            Err(existing) => validation_failure(vec![format!(
+                "ISBN '{}' の本は {} さんに貸し出し中です（返却期限: {}）",
+                isbn,
+                existing.borrower,
+                existing.due.to_rfc3339()
+            )]),
+        }
+    }
+
+    /// 貸し出した本を返却するツール
+    ///
+    /// # 引数
+    /// * ReturnBookRequest - 返却する本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 終了した貸し出しの記録と、期限を過ぎていたか
+    #[tool(description = "Return a checked-out book")]
+    fn return_book(&self, #[tool(aggr)] ReturnBookRequest { isbn }: ReturnBookRequest) -> Result<CallToolResult, McpError> {
+        let Some(checkout) = self.loans.return_book(&isbn) else {
+            return validation_failure(vec![format!("ISBN '{}' の本は貸し出されていません", isbn)]);
+        };
+        let overdue = checkout.is_overdue(chrono::Utc::now());
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "returned": checkout,
+            "overdue": overdue,
+        }))?]))
+    }
+
+    /// 貸し出し中の本を一覧するツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - ISBN順の貸し出しの記録と、それぞれ期限を過ぎているか
+    #[tool(description = "List books that are currently checked out")]
+    fn list_checkouts(&self) -> Result<CallToolResult, McpError> {
+        let now = chrono::Utc::now();
+        let checkouts: Vec<_> = self
+            .loans
+            .list()
+            .into_iter()
+            .map(|checkout| {
+                let overdue = checkout.is_overdue(now);
+                json!({
+                    "checkout": checkout,
+                    "overdue": overdue,
+                })
+            })
+            .collect();
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": checkouts.len(),
+            "checkouts": checkouts,
+        }))?]))
+    }
+
+    /// 本を一括で取り込むツール
+    ///
+    /// # 引数
+    /// * ImportBooksRequest - 取り込む本の配列
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 取り込んだISBNと、行ごとの検証エラー
+    #[tool(description = "Bulk-import books from inline JSON")]
+    async fn import_books(
+        &self,
+        #[tool(aggr)] ImportBooksRequest { books, dry_run }: ImportBooksRequest,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let rows = import::parse_json_rows(books);
+        let total = rows.len();
+        let progress = ProgressReporter::new(&context, total);
+
+        let store = self.store();
+        let mut report = import::ImportReport::default();
+        for (index, row) in rows.into_iter().enumerate() {
+            import::import_row(store.as_ref(), index + 1, row, dry_run, &mut report).await.map_err(store_error)?;
+            if (index + 1) % IMPORT_PROGRESS_INTERVAL == 0 {
+                progress.report(index + 1, "取り込み中").await;
+            }
+        }
+        progress.report(total, "取り込み完了").await;
+        if dry_run {
+            return Ok(CallToolResult::success(vec![Content::json(json!({
+                "dry_run": true,
+                "would_import": report.imported,
+                "failed": report.failed,
+            }))?]));
+        }
+        for isbn in &report.imported {
+            self.publish(CatalogEvent::new(ChangeKind::Added, isbn));
+        }
+        tracing::info!("Imported {} books ({} rows failed)", report.imported.len(), report.failed.len());
+
+        Ok(CallToolResult::success(vec![Content::json(&report)?]))
+    }
+
+    /// それらしい架空の本を、シードから決まる内容で生成して追加するツール
+    ///
+    /// 大量の本でのページングやインデックスの動作を、外部のデータなしで確かめるために使う。
+    /// 既に登録されているISBNの本は追加せず、`skipped` に数える。
+    ///
+    /// # 引数
+    /// * GenerateFakeBooksRequest - 冊数、シード、開始番号
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 追加した冊数と、最初と最後の本のISBN
+    #[tool(description = "Generate plausible fictional books from a deterministic seed")]
+    async fn generate_fake_books(
+        &self,
+        #[tool(aggr)] GenerateFakeBooksRequest { count, seed, start, dry_run }: GenerateFakeBooksRequest,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if !(1..=MAX_GENERATED_BOOKS).contains(&count) {
+            return validation_failure(vec![format!(
+                "count は1から{}の範囲で指定してください（指定値: {}）",
+                MAX_GENERATED_BOOKS, count
+            )]);
+        }
+        let progress = ProgressReporter::new(&context, count);
+
+        let store = self.store();
+        let mut report = import::ImportReport::default();
+        for (index, book) in synthetic::books_from(start, count, seed).into_iter().enumerate() {
+            import::import_row(store.as_ref(), index + 1, Ok(book), dry_run, &mut report).await.map_err(store_error)?;
+            if (index + 1) % IMPORT_PROGRESS_INTERVAL == 0 {
+                progress.report(index + 1, "生成中").await;
+            }
+        }
+        progress.report(count, "生成完了").await;
+        if !dry_run {
+            for isbn in &report.imported {
+                self.publish(CatalogEvent::new(ChangeKind::Added, isbn));
+            }
+        }
+
+        Ok(CallToolResult::success(vec![Content::json(GeneratedBooks {
+            dry_run,
+            seed,
+            start,
+            generated: report.imported.len(),
+            skipped: report.failed.len(),
+            first_isbn: report.imported.first().cloned(),
+            last_isbn: report.imported.last().cloned(),
+            next_start: start + count,
+        })?]))
+    }
+
+    /// 接続中のクライアントのLLMに、蔵書をもとに質問へ答えてもらうツール
+    ///
+    /// サーバーからクライアントへ `sampling/createMessage` を送るため、
+    /// サンプリングに対応したクライアントでのみ使える。
+    ///
+    /// # 引数
+    /// * AskLibrarianRequest - 質問と、根拠にする本を絞り込むキーワード
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 回答、回答したモデル、根拠にした本のISBN
+    #[tool(description = "Ask the client's LLM (via sampling) a reading question about the catalog")]
+    async fn ask_librarian(
+        &self,
+        #[tool(aggr)] AskLibrarianRequest { question, keyword }: AskLibrarianRequest,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if context.peer.peer_info().capabilities.sampling.is_none() {
+            return validation_failure(vec!["このクライアントはサンプリングに対応していません".to_string()]);
+        }
+        if question.trim().is_empty() {
+            return validation_failure(vec!["question は必須です".to_string()]);
+        }
+
+        let books = self.books().await?;
+        let terms = keyword.as_deref().map(parse_query).unwrap_or_default();
+        let sources: Vec<&Book> = books
+            .iter()
+            .filter(|book| terms.is_empty() || matches_query(book, &terms))
+            .take(self.config.max_search_limit)
+            .collect();
+        if sources.is_empty() {
+            return validation_failure(vec![format!(
+                "キーワード '{}' に一致する本が見つかりませんでした",
+                keyword.unwrap_or_default()
+            )]);
+        }
+
+        let result = context
+            .peer
+            .create_message(librarian::request(&question, &sources))
+            .await
+            .map_err(|e| McpError::internal_error(format!("sampling request failed: {}", e), None))?;
+        let Some(answer) = librarian::answer(&result) else {
+            return Err(McpError::internal_error("sampling returned a non-text message", None));
+        };
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "answer": answer,
+            "model": result.model,
+            "sources": sources.iter().map(|book| &book.isbn).collect::<Vec<_>>(),
+        }))?]))
+    }
+
+    /// Open Library から実在の本の情報を取得するツール
+    ///
+    /// # 引数
+    /// * FetchRealBookRequest - ISBNまたはタイトルと、カタログに追加するか
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 取得した本と、追加した本のISBN・追加できなかった理由
+    #[tool(description = "Fetch real books from Open Library by ISBN or title, optionally adding them")]
+    async fn fetch_real_book(&self, #[tool(aggr)] request: FetchRealBookRequest) -> Result<CallToolResult, McpError> {
+        if request.insert.unwrap_or(false) {
+            self.require_writable("fetch_real_book")?;
+        }
+        #[cfg(feature = "openlibrary")]
+        {
+            self.fetch_from_open_library(request).await
+        }
+
+        #[cfg(not(feature = "openlibrary"))]
+        {
+            let _ = request;
+            Err(McpError::invalid_params(
+                "fetch_real_book requires building with the `openlibrary` feature",
+                None,
+            ))
+        }
+    }
+
+    /// 設定済みの名前付きクエリを実行するツール
+    ///
+    /// # 引数
+    /// * NamedQueryRequest - 実行するクエリの名前
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 検索結果
+    #[tool(description = "Run a preset search query by name")]
+    async fn run_named_query(&self, #[tool(aggr)] NamedQueryRequest { name }: NamedQueryRequest) -> Result<CallToolResult, McpError> {
+        let queries = &self.config.named_queries;
+        let Some(query) = queries.get(&name) else {
+            return Err(McpError::invalid_params(
+                "named query not found",
+                Some(json!({
+                    "name": name,
+                    "available": queries.keys().collect::<Vec<_>>(),
+                })),
+            ));
+        };
+
+        let books = self.books().await?;
+        let scores = self.ranked_scores(query, books.len())?;
+        let results = run_search(&books, query, self.search_limits(), scores.as_ref())?;
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "name": name,
+            "books": results.books,
+            "next_cursor": results.next_cursor,
+        }))?]))
+    }
+
+    /// 複数の検索をまとめて実行するツール
+    ///
+    /// 1件のクエリが不正でも他のクエリは実行し、そのクエリの結果に `error` を入れて返す。
+    ///
+    /// # 引数
+    /// * BatchSearchRequest - 検索クエリの配列
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - クエリと同じ順の検索結果
+    #[tool(description = "Run several searches in one call")]
+    async fn batch_search(&self, #[tool(aggr)] BatchSearchRequest { queries }: BatchSearchRequest) -> Result<CallToolResult, McpError> {
+        if queries.len() > MAX_BATCH_QUERIES {
+            return Err(McpError::invalid_params(
+                "too many queries in batch",
+                Some(json!({
+                    "count": queries.len(),
+                    "max": MAX_BATCH_QUERIES,
+                })),
+            ));
+        }
+
+        let books = self.books().await?;
+        let results: Vec<_> = queries
+            .iter()
+            .map(|query| {
+                let results = self
+                    .ranked_scores(query, books.len())
+                    .and_then(|scores| run_search(&books, query, self.search_limits(), scores.as_ref()));
+                match results {
+                    Ok(results) => json!({
+                        "keyword": query.keyword,
+                        "filters": results.filters,
+                        "count": results.books.len(),
+                        "books": results.books,
+                        "next_cursor": results.next_cursor,
+                    }),
+                    Err(error) => json!({
+                        "keyword": query.keyword,
+                        "error": error.message,
+                    }),
+                }
+            })
+            .collect();
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": results.len(),
+            "results": results,
+        }))?]))
+    }
+
+    /// 一致する本を全て、`limit` 件ずつの塊に分けて返す検索ツール
+    ///
+    /// 検索は1回だけ行う。進捗トークンが付いていれば、塊ごとにその内容を進捗通知のメッセージとして
+    /// 送り（全体は一致した本の数）、結果には件数のまとめだけを入れる。トークンがなければ
+    /// 塊ごとに1つのコンテンツブロックを作り、まとめと一緒に1つの結果で返す。
+    ///
+    /// # 引数
+    /// * SearchQuery - 検索クエリ（`limit` は1つの塊の件数として使う）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 件数のまとめ（進捗トークンがなければ、その前に塊ごとのJSONのコンテンツブロック）
+    #[tool(description = "Search and return every match in chunks, streaming partial results as progress notifications")]
+    async fn search_stream(
+        &self,
+        #[tool(aggr)] mut query: SearchQuery,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let books = self.books().await?;
+        let scores = self.ranked_scores(&query, books.len())?;
+        let chunk_size = resolve_limit(query.limit, self.search_limits())?.max(1);
+        let start = resolve_offset(&query)?;
+        // 検索は1回だけ行い、一致した全ての本を塊に分ける
+        query.offset = None;
+        query.cursor = None;
+        let results = run_search(&books, &query, self.search_limits(), scores.as_ref())?;
+        let by_isbn: HashMap<&str, &Book> = books.iter().map(|book| (book.isbn.as_str(), book)).collect();
+        let matched: Vec<&Book> = results
+            .matched
+            .iter()
+            .skip(start)
+            .filter_map(|isbn| by_isbn.get(isbn).copied())
+            .collect();
+        let truncated = matched.len() > MAX_STREAM_RESULTS;
+        let matched = &matched[..matched.len().min(MAX_STREAM_RESULTS)];
+
+        let progress = ProgressReporter::new(&context, matched.len());
+        // 進捗通知で送った塊は、結果にもう一度入れない
+        let streamed = progress.is_active();
+        let mut content = Vec::new();
+        let mut sent = 0;
+        for (index, books) in matched.chunks(chunk_size).enumerate() {
+            if context.ct.is_cancelled() {
+                return Err(tool_cancelled("search_stream"));
+            }
+            let chunk = json!({
+                "chunk": index,
+                "offset": start + sent,
+                "books": books,
+            });
+            sent += books.len();
+            if streamed {
+                progress.report(sent, &chunk.to_string()).await;
+            } else {
+                content.push(Content::json(chunk)?);
+            }
+        }
+        let offset = start + sent;
+
+        content.push(Content::json(json!({
+            "keyword": query.keyword,
+            "count": sent,
+            "chunks": matched.len().div_ceil(chunk_size),
+            "streamed": streamed,
+            "truncated": truncated,
+            "next_offset": truncated.then_some(offset),
+        }))?);
+        Ok(CallToolResult::success(content))
+    }
+
+    /// 2つの検索結果の差分を求めるツール
+    ///
+    /// # 引数
+    /// * SearchDiffRequest - 比較する2つの検索クエリ
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - それぞれにのみ含まれるISBNと、共通するISBN
+    #[tool(description = "Compare the results of two searches")]
+    async fn search_diff(&self, #[tool(aggr)] SearchDiffRequest { left, right }: SearchDiffRequest) -> Result<CallToolResult, McpError> {
+        let books = self.books().await?;
+        let left_scores = self.ranked_scores(&left, books.len())?;
+        let right_scores = self.ranked_scores(&right, books.len())?;
+        // ページに分ける前の一致した全ての本で比べる
+        let left = run_search(&books, &left, self.search_limits(), left_scores.as_ref())?.matched;
+        let right = run_search(&books, &right, self.search_limits(), right_scores.as_ref())?.matched;
+
+        let only_left: Vec<&str> = left.iter().copied().filter(|isbn| !right.contains(isbn)).collect();
+        let only_right: Vec<&str> = right.iter().copied().filter(|isbn| !left.contains(isbn)).collect();
+        let common: Vec<&str> = left.iter().copied().filter(|isbn| right.contains(isbn)).collect();
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "only_left": only_left,
+            "only_right": only_right,
+            "common": common,
+        }))?]))
+    }
+
+    /// テーマごとの蔵書の網羅状況を集計するツール
+    ///
+    /// # 引数
+    /// * ThemeCoverageRequest - 調べるテーマの一覧
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - テーマごとの冊数と、一致する本がないテーマの一覧
+    #[tool(description = "Report how many books touch each theme and which themes are uncovered")]
+    async fn theme_coverage(&self, #[tool(aggr)] ThemeCoverageRequest { themes }: ThemeCoverageRequest) -> Result<CallToolResult, McpError> {
+        let themes = themes.unwrap_or_else(|| DEFAULT_THEMES.iter().map(|theme| theme.to_string()).collect());
+        let coverage = theme_coverage(&self.books().await?, &themes);
+        let uncovered: Vec<&str> = coverage
+            .iter()
+            .filter(|entry| entry.count == 0)
+            .map(|entry| entry.theme.as_str())
+            .collect();
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "coverage": coverage,
+            "uncovered": uncovered,
+        }))?]))
+    }
+
+    /// 説明文中で他の本のタイトルに言及している本を探すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 言及元と言及先のISBNの組の一覧
+    #[tool(description = "Find books whose description mentions another book's title")]
+    async fn cross_references(&self) -> Result<CallToolResult, McpError> {
+        let references = cross_references(&self.books().await?);
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "references": references,
+        }))?]))
+    }
+
+    /// 他の著者の本と最も多くテーマを共有する、蔵書のテーマの中心にいる著者を探すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 中心の著者（テーマを共有する著者がいなければ `null`）と、
+    ///   つながりの多い順に並べたすべての著者
+    #[tool(description = "Find the author whose books share the most themes with other authors' books")]
+    async fn central_author(&self) -> Result<CallToolResult, McpError> {
+        let authors = author_centrality(&self.books().await?);
+        let central = authors.first().filter(|author| !author.connected_authors.is_empty());
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "central": central,
+            "authors": authors,
+        }))?]))
+    }
+
+    /// 指定した本に似ている本を推薦するツール
+    ///
+    /// # 引数
+    /// * RecommendSimilarRequest - 基準にする本のISBNと返す本の最大数
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 類似度の高い順に並べた本と、共通するタグ
+    #[tool(description = "Recommend books similar to the given one by tags and description")]
+    async fn recommend_similar(&self, #[tool(aggr)] RecommendSimilarRequest { isbn, limit }: RecommendSimilarRequest) -> Result<CallToolResult, McpError> {
+        let books = self.books().await?;
+        let limit = limit.unwrap_or(DEFAULT_RECOMMENDATIONS).min(self.config.max_search_limit);
+        let Some(similar) = similar_books(&books, &isbn, limit) else {
+            return validation_failure(vec![self.lang().book_not_found(&isbn)]);
+        };
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "isbn": isbn,
+            "recommendations": similar,
+        }))?]))
+    }
+
+    /// 著者・出版年・タグの分布など、蔵書全体の統計を返すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 冊数・大きさ・著者ごとの冊数・年代ごとの分布・タグの分布・説明文の平均の長さ
+    #[tool(description = "Summarize the catalog: counts by author, publication decades and centuries, tags and sizes")]
+    async fn catalog_stats(&self) -> Result<CallToolResult, McpError> {
+        Ok(CallToolResult::success(vec![Content::json(catalog_stats(&self.books().await?))?]))
+    }
+
+    /// 重複の疑いがある本の組を探すツール
+    ///
+    /// # 引数
+    /// * FindDuplicatesRequest - タイトルと著者の類似度のしきい値
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 類似度の高い順に並べた本の組と、重複と判断した理由
+    #[tool(description = "Find likely duplicate books by ISBN or similar title and author")]
+    async fn find_duplicates(&self, #[tool(aggr)] FindDuplicatesRequest { threshold }: FindDuplicatesRequest) -> Result<CallToolResult, McpError> {
+        let threshold = threshold.unwrap_or(DEFAULT_DUPLICATE_THRESHOLD);
+        if !(0.0..=1.0).contains(&threshold) {
+            return validation_failure(vec![format!(
+                "threshold は0.0から1.0の範囲で指定してください（指定値: {}）",
+                threshold
+            )]);
+        }
+        let duplicates = find_duplicates(&self.books().await?, threshold);
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": duplicates.len(),
+            "duplicates": duplicates,
+        }))?]))
+    }
+
+    /// 重複している本を1冊に統合するツール
+    ///
+    /// # 引数
+    /// * MergeBooksRequest - 残す本と統合する本のISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 統合後の本と、付け替えたレビューの数・削除した本のISBN
+    #[tool(description = "Merge duplicate books into one, keeping reviews and tags from all of them")]
+    async fn merge_books(
+        &self,
+        #[tool(aggr)] MergeBooksRequest { primary, duplicates, dry_run }: MergeBooksRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let store = self.store();
+        let Some(current) = self.book(&primary).await? else {
+            return validation_failure(vec![self.lang().book_not_found(&primary)]);
+        };
+        let mut errors = Vec::new();
+        if duplicates.is_empty() {
+            errors.push("duplicates には1冊以上のISBNを指定してください".to_string());
+        }
+        let mut others: Vec<Book> = Vec::new();
+        for isbn in &duplicates {
+            if *isbn == primary {
+                errors.push(format!("ISBN '{}' は primary と同じ本です", isbn));
+            } else if others.iter().any(|other| other.isbn == *isbn) {
+                continue;
+            } else if let Some(checkout) = self.loans.get(isbn) {
+                errors.push(format!("ISBN '{}' の本は {} さんに貸し出し中のため統合できません", isbn, checkout.borrower));
+            } else {
+                match self.book(isbn).await? {
+                    Some(book) => others.push(book),
+                    None => errors.push(self.lang().book_not_found(isbn)),
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return validation_failure(errors);
+        }
+
+        let mut merged = current.clone();
+        let mut originals = Vec::new();
+        for other in others {
+            merge_book(&mut merged, &other);
+            let reviews = store.reviews(&other.isbn).await.map_err(store_error)?;
+            originals.push(BookState { book: other, reviews });
+        }
+        let reviews: Vec<&Review> = originals.iter().flat_map(|state| &state.reviews).collect();
+        let removed: Vec<&str> = originals.iter().map(|state| state.book.isbn.as_str()).collect();
+        if dry_run {
+            return Ok(CallToolResult::success(vec![Content::json(json!({
+                "dry_run": true,
+                "isbn": primary,
+                "changes": book_changes(&current, &merged),
+                "reviews_moved": reviews.len(),
+                "would_delete": removed,
+            }))?]));
+        }
+
+        let mut touched = vec![primary.as_str()];
+        touched.extend(&removed);
+        let before = self.capture(&touched).await?;
+        let original = BookState {
+            reviews: store.reviews(&primary).await.map_err(store_error)?,
+            book: current.clone(),
+        };
+        match store.update(merged.clone(), current.version).await.map_err(store_error)? {
+            VersionCheck::Applied(version) => merged.version = version,
+            VersionCheck::NotFound => return validation_failure(vec![self.lang().book_not_found(&primary)]),
+            VersionCheck::Conflict(version) => return Err(version_conflict(&primary, current.version, version)),
+        }
+        // 失敗した操作の結果と、それまでに削除した本の数
+        let mut failure = None;
+        // 本を削除するとレビューも消えるので、先に残す本へ付け替える
+        for review in &reviews {
+            let moved = Review {
+                isbn: primary.clone(),
+                ..(*review).clone()
+            };
+            if let Err(e) = store.add_review(moved).await {
+                failure = Some((Err(store_error(e)), 0));
+                break;
+            }
+        }
+        // 読み込んだあとに別の呼び出しが書き換えた本は削除しない
+        if failure.is_none() {
+            for (done, state) in originals.iter().enumerate() {
+                let result = match store.remove(&state.book.isbn, Some(state.book.version)).await {
+                    Ok(VersionCheck::Applied(_)) => continue,
+                    Ok(VersionCheck::Conflict(version)) => {
+                        Err(version_conflict(&state.book.isbn, state.book.version, version))
+                    }
+                    Ok(VersionCheck::NotFound) => validation_failure(vec![self.lang().book_not_found(&state.book.isbn)]),
+                    Err(e) => Err(store_error(e)),
+                };
+                failure = Some((result, done));
+                break;
+            }
+        }
+        if let Some((failure, done)) = failure {
+            let deleted: Vec<&BookState> = originals[..done].iter().collect();
+            let failed = self.restore_merged(&original, &deleted).await;
+            if !failed.is_empty() {
+                return Err(rollback_failed(&failed));
+            }
+            return failure;
+        }
+        for isbn in &removed {
+            self.publish(CatalogEvent::new(ChangeKind::Removed, *isbn));
+        }
+        self.record_change("merge_books", before).await?;
+        self.publish(CatalogEvent::new(ChangeKind::Updated, &primary));
+        tracing::info!("Merged {} duplicates into {}", removed.len(), primary);
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "merged": merged,
+            "reviews_moved": reviews.len(),
+            "removed": removed,
+        }))?]))
+    }
+
+    /// 検索を実行せずに検索クエリを検証するツール
+    ///
+    /// # 引数
+    /// * SearchQuery - 検証する検索クエリ
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 問題点の一覧と、実際に使われる正規化済みのクエリ
+    #[tool(description = "Validate a search query without executing it")]
+    fn validate_query(&self, #[tool(aggr)] query: SearchQuery) -> Result<CallToolResult, McpError> {
+        let problems = query_problems(&query);
+        let terms = parse_query(&query.keyword);
+        let limit = resolve_limit(query.limit, self.search_limits()).ok();
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "valid": problems.is_empty(),
+            "problems": problems,
+            "normalized": {
+                "terms": terms,
+                "limit": limit,
+                "offset": resolve_offset(&query).ok(),
+                "fuzzy": query.fuzzy.unwrap_or(false),
+                "threshold": resolve_threshold(query.threshold).ok(),
+            },
+        }))?]))
+    }
+
+    /// ISBNのチェックディジットを検証し、ISBN-10/13の両方の形式に変換するツール
+    ///
+    /// # 引数
+    /// * ValidateIsbnRequest - 検証するISBN
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 正しいかどうかと、正規化したISBN-10/13（または問題点）
+    #[tool(description = "Validate an ISBN-10/13 checksum and convert between the formats")]
+    fn validate_isbn(&self, #[tool(aggr)] ValidateIsbnRequest { isbn }: ValidateIsbnRequest) -> Result<CallToolResult, McpError> {
+        let result = match Isbn::parse(&isbn) {
+            Ok(parsed) => IsbnValidation {
+                valid: true,
+                normalized: parsed.as_str().to_string(),
+                isbn13: Some(parsed.to_isbn13()),
+                isbn10: parsed.to_isbn10(),
+                error: None,
+                input: isbn,
+            },
+            Err(e) => IsbnValidation {
+                valid: false,
+                normalized: isbn::normalize(&isbn),
+                isbn13: None,
+                isbn10: None,
+                error: Some(e.to_string()),
+                input: isbn,
+            },
+        };
+        Ok(CallToolResult::success(vec![Content::json(result)?]))
+    }
+
+    /// 蔵書一覧をHTML文書として書き出すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 全ての本を表にした自己完結型のHTML
+    #[tool(description = "Export the catalog as a self-contained HTML page")]
+    async fn export_html(&self) -> Result<CallToolResult, McpError> {
+        Ok(CallToolResult::success(vec![Content::text(render_catalog_html(&self.books().await?))]))
+    }
+
+    /// 蔵書全体をJSON・CSV・BibTeXのいずれかで書き出すツール
+    ///
+    /// # 引数
+    /// * ExportCatalogRequest - 書き出す形式と、書き出し先のファイル（省略可）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 書き出した内容、またはファイルに書き出した場合はそのパスと冊数
+    #[tool(description = "Export the whole catalog as JSON, CSV or BibTeX")]
+    async fn export_catalog(&self, #[tool(aggr)] ExportCatalogRequest { format, path }: ExportCatalogRequest) -> Result<CallToolResult, McpError> {
+        let books = self.books().await?;
+        let text = render_catalog(&books, format).map_err(|e| McpError::internal_error(e.to_string(), None))?;
+
+        let Some(path) = path else {
+            return Ok(CallToolResult::success(vec![Content::text(text)]));
+        };
+        self.require_writable("export_catalog")?;
+        // ネットワーク越しのクライアントにサーバー側のファイルを書かせない
+        if ACTIVE_TRANSPORT.get().copied().unwrap_or(Transport::Stdio) != Transport::Stdio {
+            return validation_failure(vec!["path はstdioで起動したサーバーでのみ指定できます".to_string()]);
+        }
+        std::fs::write(&path, text).map_err(|e| {
+            McpError::internal_error(
+                format!("failed to write export: {}", e),
+                Some(json!({
+                    "path": path
+                })),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "format": format,
+            "extension": format.extension(),
+            "path": path,
+            "count": books.len(),
+        }))?]))
+    }
+
+    /// 読み出せる全てのリソースのURIと、URIを組み立てるリソーステンプレートを一覧するツール
+    ///
+    /// 名前付きのカタログの本も含める。クライアントはURIの形を推測せずに全てのリソースをたどれる。
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - リソースのURI・名前・MIMEタイプと、リソーステンプレートの一覧
+    #[tool(description = "List every readable resource URI and resource template with its mime type")]
+    async fn resource_sitemap(&self) -> Result<CallToolResult, McpError> {
+        self.require(Capability::Resources, "resource_sitemap")?;
+        let entries: Vec<_> = self
+            .all_resources()
+            .await?
+            .into_iter()
+            .map(|resource| json!({ "uri": resource.raw.uri, "name": resource.raw.name, "mime_type": resource.raw.mime_type }))
+            .collect();
+        let templates: Vec<_> = resources::templates()
+            .into_iter()
+            .map(|template| {
+                json!({
+                    "uri_template": template.raw.uri_template,
+                    "name": template.raw.name,
+                    "mime_type": template.raw.mime_type,
+                })
+            })
+            .collect();
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": entries.len(),
+            "resources": entries,
+            "templates": templates,
+        }))?]))
+    }
+
+    /// 不具合報告に添付できるビルド・実行環境の情報を返すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - バージョン、プロトコルバージョン、トランスポート、ターゲット、稼働時間
+    #[tool(description = "Report build and runtime diagnostics")]
+    fn diagnostics(&self) -> Result<CallToolResult, McpError> {
+        let info = self.get_info();
+        let uptime = STARTED_AT.get_or_init(Instant::now).elapsed();
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "name": info.server_info.name,
+            "version": info.server_info.version,
+            "protocol_version": info.protocol_version,
+            "supported_protocol_versions": protocol::SUPPORTED_VERSIONS,
+            "protocol_features": self.features(),
+            "transport": ACTIVE_TRANSPORT.get().copied().unwrap_or(Transport::Stdio).as_str(),
+            "target": {
+                "arch": std::env::consts::ARCH,
+                "os": std::env::consts::OS,
+                "family": std::env::consts::FAMILY,
+            },
+            "uptime_secs": uptime.as_secs_f64(),
+        }))?]))
+    }
+
+    /// ツールごとの呼び出し回数・エラー率・所要時間を返すツール
+    ///
+    /// # 引数
+    /// * ServerStatsRequest - 出力形式（省略時はJSON）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - ツールごとの集計、または Prometheus のテキスト形式
+    #[tool(description = "Report per-tool call counts, error rates and latency")]
+    fn get_server_stats(&self, #[tool(aggr)] ServerStatsRequest { format }: ServerStatsRequest) -> Result<CallToolResult, McpError> {
+        match format.as_deref() {
+            None | Some("json") => Ok(CallToolResult::success(vec![Content::json(json!({
+                "uptime_secs": STARTED_AT.get_or_init(Instant::now).elapsed().as_secs_f64(),
+                "active_sessions": self.sessions.list().len(),
+                "tools": self.metrics.snapshot(),
+            }))?])),
+            Some("prometheus") => Ok(CallToolResult::success(vec![Content::text(self.metrics.render_prometheus())])),
+            Some(other) => Err(McpError::invalid_params(
+                "format must be `json` or `prometheus`",
+                Some(json!({
+                    "format": other
+                })),
+            )),
+        }
+    }
+
+    /// カタログを変更したツール呼び出しの記録を返すツール
+    ///
+    /// # 引数
+    /// * AuditLogRequest - 期間とツール名による絞り込み（いずれも省略可）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 古い順の記録（時刻・セッション・ツール・引数のダイジェスト・結果）
+    #[tool(description = "List recorded mutating tool calls, optionally within a time range")]
+    fn get_audit_log(&self, #[tool(aggr)] AuditLogRequest { since, until, tool }: AuditLogRequest) -> Result<CallToolResult, McpError> {
+        let since = parse_timestamp("since", since.as_deref())?;
+        let until = parse_timestamp("until", until.as_deref())?;
+        let entries: Vec<AuditEntry> = self
+            .audit
+            .entries(since, until)
+            .into_iter()
+            .filter(|entry| tool.as_ref().is_none_or(|tool| &entry.tool == tool))
+            .collect();
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": entries.len(),
+            "entries": entries,
+        }))?]))
+    }
+
+    /// 現在のカタログを `snapshot_dir` にスナップショットとして保存するツール
+    ///
+    /// # 引数
+    /// * CreateSnapshotRequest - スナップショット名に付ける目印（省略可）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - スナップショットの名前・ファイル・本とレビューの件数
+    #[tool(description = "Save the current catalog to a timestamped snapshot file")]
+    async fn create_snapshot(&self, #[tool(aggr)] CreateSnapshotRequest { label }: CreateSnapshotRequest) -> Result<CallToolResult, McpError> {
+        match snapshot::create(self.store().as_ref(), &self.config.snapshot_dir, label.as_deref()).await {
+            Ok(info) => Ok(CallToolResult::success(vec![Content::json(info)?])),
+            Err(e) => snapshot_failure(e),
+        }
+    }
+
+    /// カタログをスナップショットの時点の内容に戻すツール
+    ///
+    /// # 引数
+    /// * RestoreSnapshotRequest - 復元するスナップショットの名前
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 復元したスナップショットと、追加・更新・削除された本のISBN
+    #[tool(description = "Replace the catalog with the contents of a named snapshot")]
+    async fn restore_snapshot(&self, #[tool(aggr)] RestoreSnapshotRequest { name }: RestoreSnapshotRequest) -> Result<CallToolResult, McpError> {
+        let (info, report) = match snapshot::restore(self.store().as_ref(), &self.config.snapshot_dir, &name).await {
+            Ok(restored) => restored,
+            Err(e) => return snapshot_failure(e),
+        };
+        for isbn in &report.removed {
+            self.publish(CatalogEvent::new(ChangeKind::Removed, isbn));
+        }
+        for isbn in &report.updated {
+            self.publish(CatalogEvent::new(ChangeKind::Updated, isbn));
+        }
+        for isbn in &report.added {
+            self.publish(CatalogEvent::new(ChangeKind::Added, isbn));
+        }
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "snapshot": info,
+            "added": report.added,
+            "updated": report.updated,
+            "removed": report.removed,
+        }))?]))
+    }
+
+    /// 直前のカタログの変更（本の追加・更新・削除・統合）を取り消すツール
+    ///
+    /// 取り消した変更は `redo` でやり直せる。記録した後に別の呼び出しが同じ本を変更していれば拒否する。
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 取り消した操作と、再登録・更新・削除された本のISBN
+    #[tool(description = "Undo the most recent add, update, delete or merge of books")]
+    async fn undo_last_change(&self) -> Result<CallToolResult, McpError> {
+        let Some(catalog) = self.history_catalog() else {
+            return validation_failure(vec!["ファイルのストアを開いている間は変更を取り消せません".to_string()]);
+        };
+        match self.history.undo(catalog, self.store().as_ref()).await {
+            Ok(reverted) => self.reverted(reverted),
+            Err(e) => history_failure(e),
+        }
+    }
+
+    /// `undo_last_change` で取り消した変更をやり直すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - やり直した操作と、再登録・更新・削除された本のISBN
+    #[tool(description = "Redo the most recently undone change")]
+    async fn redo(&self) -> Result<CallToolResult, McpError> {
+        let Some(catalog) = self.history_catalog() else {
+            return validation_failure(vec!["ファイルのストアを開いている間は変更をやり直せません".to_string()]);
+        };
+        match self.history.redo(catalog, self.store().as_ref()).await {
+            Ok(reverted) => self.reverted(reverted),
+            Err(e) => history_failure(e),
+        }
+    }
+
+    /// クライアントのルートの下にあるJSONファイルを、このセッションのカタログとして開くツール
+    ///
+    /// ルートはクライアントが `initialized` を送った後でなければ問い合わせられないため、
+    /// 呼び出しのたびに `roots/list` で最新のルートを取得する。
+    ///
+    /// # 引数
+    /// * OpenFileStoreRequest - 開くファイル（なければ空のカタログとして作成する）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 開いたファイルの絶対パスと本の冊数
+    #[tool(description = "Use a JSON file under one of the client's roots as this session's catalog")]
+    async fn open_file_store(
+        &self,
+        #[tool(aggr)] OpenFileStoreRequest { path }: OpenFileStoreRequest,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if context.peer.peer_info().capabilities.roots.is_none() {
+            return validation_failure(vec!["このクライアントはルートを公開していません".to_string()]);
+        }
+        let result = context
+            .peer
+            .list_roots()
+            .await
+            .map_err(|e| McpError::internal_error(format!("roots/list request failed: {}", e), None))?;
+        let path = match roots::resolve(&path, &roots::root_paths(&result.roots)) {
+            Ok(path) => path,
+            Err(e) => {
+                tracing::warn!("Refused file store outside client roots: {}", e);
+                return validation_failure(vec![e.to_string()]);
+            }
+        };
+
+        let store = JsonFileStore::open(&path).map_err(store_error)?;
+        let books = store.all().await.map_err(store_error)?.len();
+        *self.file_store.write().expect("file store lock poisoned") = Some(Arc::new(store));
+        tracing::info!("Opened file store {} ({} books)", path.display(), books);
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "path": path,
+            "books": books,
+        }))?]))
+    }
+
+    /// `open_file_store` で開いたファイルを閉じ、共有のカタログに戻すツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 閉じたファイルのパス（開いていなければ null）
+    #[tool(description = "Close this session's file store and return to the shared catalog")]
+    async fn close_file_store(&self) -> Result<CallToolResult, McpError> {
+        let closed = self.file_store.write().expect("file store lock poisoned").take();
+        if let Some(store) = &closed {
+            store.flush().await.map_err(store_error)?;
+        }
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "closed": closed.as_ref().map(|store| store.path()),
+        }))?]))
+    }
+
+    /// このセッションの応答の言語と検索の既定件数を変更するツール
+    ///
+    /// # 引数
+    /// * SetPreferencesRequest - 変更する設定（指定しなかった項目はそのまま）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - セッションの番号・接続時刻・変更後の設定
+    #[tool(description = "Set this session's response language and default search limit")]
+    fn set_preferences(
+        &self,
+        #[tool(aggr)] SetPreferencesRequest { preferences, clear }: SetPreferencesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let max = self.config.max_search_limit;
+        let out_of_range = |limit: &usize| !(1..=max).contains(limit);
+        if let Some(limit) = preferences.default_limit.filter(out_of_range) {
+            return validation_failure(vec![format!(
+                "default_limit must be between 1 and {}, got {}",
+                max, limit
+            )]);
+        }
+
+        let info = self
+            .session
+            .update(|current| {
+                if clear {
+                    *current = Preferences::default();
+                }
+                if preferences.lang.is_some() {
+                    current.lang = preferences.lang;
+                }
+                if preferences.default_limit.is_some() {
+                    current.default_limit = preferences.default_limit;
+                }
+                if preferences.include_deleted.is_some() {
+                    current.include_deleted = preferences.include_deleted;
+                }
+            })
+            .ok_or_else(|| McpError::internal_error("session is no longer registered", None))?;
+        tracing::info!(session = info.id, "Updated session preferences");
+        Ok(CallToolResult::success(vec![Content::json(info)?]))
+    }
+
+    /// 名前付きのカタログを追加するツール
+    ///
+    /// # 引数
+    /// * CreateCatalogRequest - カタログの名前と、本をコピーする元のカタログ（省略可）
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - 作成したカタログの名前と冊数
+    #[tool(description = "Create a new named catalog, optionally copying books from another")]
+    async fn create_catalog(&self, #[tool(aggr)] CreateCatalogRequest { name, copy_from }: CreateCatalogRequest) -> Result<CallToolResult, McpError> {
+        let books = match copy_from.as_deref() {
+            Some(source) => self.in_catalog(source).map_err(catalog_error)?.books().await?,
+            None => Vec::new(),
+        };
+        let count = books.len();
+        if let Err(e) = self.catalogs.create(&name, Arc::new(MemoryStore::new(books))) {
+            return validation_failure(vec![e.to_string()]);
+        }
+        tracing::info!("Created catalog {} ({} books)", name, count);
+        // 新しいカタログは `catalog` 引数で選べるようになり、リソースにも加わる
+        self.events.list_changed(ListKind::Tools);
+        self.events.list_changed(ListKind::Resources);
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "catalog": name,
+            "books": count,
+        }))?]))
+    }
+
+    /// 既定のカタログと名前付きのカタログを一覧するツール
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - カタログごとの名前・冊数・リソースURI
+    #[tool(description = "List the default and named catalogs")]
+    async fn list_catalogs(&self) -> Result<CallToolResult, McpError> {
+        let mut catalogs = vec![CatalogSummary {
+            catalog: DEFAULT_CATALOG.to_string(),
+            books: self.store.all().await.map_err(store_error)?.len(),
+            uri: resources::CATALOG_URI.to_string(),
+        }];
+        for (name, store) in self.catalogs.all() {
+            catalogs.push(CatalogSummary {
+                books: store.all().await.map_err(store_error)?.len(),
+                uri: resources::in_catalog(Some(&name), resources::CATALOG_URI),
+                catalog: name,
+            });
+        }
+        Ok(CallToolResult::success(vec![Content::json(CatalogList { catalogs })?]))
+    }
+
+    /// 死活監視用の軽量なヘルスチェックツール
+    ///
+    /// `ready` は `/readyz` と同じ条件で決める。保存先には存在しないISBNを引くだけなので、
+    /// 頻繁に呼んでもカタログ全体を読み出すことはない。
+    ///
+    /// # 戻り値
+    /// * Result<CallToolResult, McpError> - `status` と、新しいリクエストを受けられる状態かを示す `ready`
+    #[tool(description = "Lightweight liveness/readiness check")]
+    async fn health(&self) -> Result<CallToolResult, McpError> {
+        let (ready, _) = self.server_health().await.readiness();
+        Ok(CallToolResult::success(vec![Content::json(Health {
+            status: "ok".to_string(),
+            ready,
+        })?]))
+    }
+}
+
+#[cfg(feature = "openlibrary")]
+impl BookSearch {
+    async fn fetch_from_open_library(&self, request: FetchRealBookRequest) -> Result<CallToolResult, McpError> {
+        let fetch_error = |e: anyhow::Error| McpError::internal_error(format!("Open Library request failed: {:#}", e), None);
+        let books = match (request.isbn, request.title) {
+            (Some(isbn), None) => openlibrary::fetch_by_isbn(&isbn::normalize(&isbn))
+                .await
+                .map_err(fetch_error)?
+                .into_iter()
+                .collect::<Vec<_>>(),
+            (None, Some(title)) => {
+                let limit = request.limit.unwrap_or(self.default_limit()).min(self.config.max_search_limit);
+                openlibrary::search_by_title(&title, limit).await.map_err(fetch_error)?
+            }
+            _ => return validation_failure(vec!["isbn と title のどちらか一方を指定してください".to_string()]),
+        };
+
+        let mut inserted = Vec::new();
+        let mut skipped = Vec::new();
+        if request.insert.unwrap_or(false) {
+            for book in &books {
+                let mut book = book.clone();
+                let mut errors = prepare_new_book(&mut book);
+                if errors.is_empty() && !self.store().insert(book.clone()).await.map_err(store_error)? {
+                    errors.push(format!("ISBN '{}' の本は既に登録されています", book.isbn));
+                }
+                if errors.is_empty() {
+                    self.publish(CatalogEvent::new(ChangeKind::Added, &book.isbn));
+                    inserted.push(book.isbn);
+                } else {
+                    skipped.push(json!({
+                        "isbn": book.isbn,
+                        "errors": errors,
+                    }));
+                }
+            }
+        }
+
+        Ok(CallToolResult::success(vec![Content::json(json!({
+            "count": books.len(),
+            "books": books,
+            "inserted": inserted,
+            "skipped": skipped,
+        }))?]))
+    }
+}
+
+impl ManagedServer for BookSearch {
+    fn new_session(&self) -> Self {
+        BookSearch::new_session(self)
+    }
+
+    fn with_policy(self, policy: ToolPolicy) -> Self {
+        BookSearch::with_policy(self, policy)
+    }
+
+    fn health(&self) -> impl Future<Output = ServerHealth> + Send {
+        self.server_health()
+    }
+
+    fn shutdown(&self) -> impl Future<Output = anyhow::Result<()>> + Send {
+        BookSearch::shutdown(self)
+    }
+}
+
+impl ServerHandler for BookSearch {
+    fn get_info(&self)  -> ServerInfo {
+        let version = self.protocol_version();
+        let mut capabilities = ServerCapabilities::builder()
+            .enable_logging()
+            .enable_prompts()
+            .enable_prompts_list_changed()
+            .enable_resources()
+            .enable_resources_subscribe()
+            .enable_resources_list_changed()
+            .enable_tools()
+            .enable_tool_list_changed()
+            .build();
+        let enabled = self.config.capabilities;
+        if !enabled.prompts {
+            capabilities.prompts = None;
+        }
+        if !enabled.resources {
+            capabilities.resources = None;
+        }
+        if !enabled.tools {
+            capabilities.tools = None;
+        }
+        if !enabled.logging {
+            capabilities.logging = None;
+        }
+        // 補完はプロンプトの引数とリソースのテンプレートに対して行う
+        if protocol::Features::of(&version).completions && (enabled.prompts || enabled.resources) {
+            capabilities.completions = Some(JsonObject::new());
+        }
+
+        ServerInfo {
+            protocol_version: protocol::to_protocol_version(&version),
+            capabilities,
+            server_info: Implementation::from_build_env(),
+            instructions: Some("架空の本のデータベースを検索するサーバーです。タイトル、著者、説明文で検索できます。".into()),
+        }
+    }
+
+    async fn initialize(
+        &self,
+        request: InitializeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<InitializeResult, McpError> {
+        let requested = protocol::version_str(&request.protocol_version);
+        let version = protocol::negotiate(&requested);
+        if version != requested {
+            tracing::info!("Client requested protocol {}, answering with {}", requested, version);
+        }
+        self.session.set_protocol_version(version);
+        Ok(self.get_info())
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        // 公開している一覧についてだけ、変わったことを知らせる
+        let enabled = self.config.capabilities;
+        let lists: Vec<ListKind> = [
+            (enabled.tools, ListKind::Tools),
+            (enabled.resources, ListKind::Resources),
+            (enabled.prompts, ListKind::Prompts),
+        ]
+        .into_iter()
+        .filter_map(|(on, list)| on.then_some(list))
+        .collect();
+        if !lists.is_empty() {
+            tokio::spawn(events::forward_list_changes(
+                self.events.subscribe_lists(),
+                lists,
+                context.peer,
+            ));
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: PaginatedRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        request_log::logged("tools/list", None, Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Tools, "tools/list")?;
+            let mut tools = Self::tool_box().list();
+            if self.config.read_only && self.config.hide_mutating_tools {
+                tools.retain(|tool| !is_mutating_tool(&tool.name));
+            }
+            let policy = self.tool_policy(&context);
+            tools.retain(|tool| policy.allows(&tool.name));
+            let catalogs: Vec<String> = std::iter::once(DEFAULT_CATALOG.to_string())
+                .chain(self.catalogs.names())
+                .collect();
+            for tool in tools.iter_mut().filter(|tool| !SERVER_TOOLS.contains(&&*tool.name)) {
+                with_catalog_argument(tool, &catalogs);
+            }
+            if self.features().structured_output {
+                for tool in &mut tools {
+                    tool.output_schema = output::output_schema(&tool.name)
+                        .map(|schema| self.config.naming.rename_schema(schema));
+                }
+            }
+            Ok(ListToolsResult {
+                next_cursor: None,
+                tools,
+            })
+        })
+        .await
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let name = request.name.clone();
+        let request_id = context.id.clone();
+        let outcome = |result: &CallToolResult| {
+            if result.is_error == Some(true) { "tool_error" } else { "ok" }
+        };
+        request_log::logged("tools/call", Some(&name), Some(self.session.id()), &request_id, outcome, async {
+            self.require(Capability::Tools, "tools/call")?;
+            if !self.tool_policy(&context).allows(&name) {
+                tracing::warn!("Denied tool call {} not allowed by the token's policy", name);
+                return Err(tool_not_permitted(&name));
+            }
+            if is_mutating_tool(&name) {
+                self.require_writable(&name)?;
+            }
+            // `catalog` を指定した呼び出しは、そのカタログのストアに差し替えた複製で処理する
+            let catalog = request
+                .arguments
+                .as_ref()
+                .and_then(|arguments| arguments.get("catalog"))
+                .and_then(|catalog| catalog.as_str())
+                .filter(|_| !SERVER_TOOLS.contains(&&*name));
+            let handler = match catalog {
+                Some(catalog) => self.in_catalog(catalog).map_err(catalog_error)?,
+                None => self.clone(),
+            };
+            let Some(_in_flight) = self.drain.begin() else {
+                return Err(McpError::internal_error("server is shutting down", None));
+            };
+            if let Some(rate_limit) = &self.rate_limit {
+                rate_limit.check(&name)?;
+            }
+
+            let started = Instant::now();
+            let audited = AUDITED_TOOLS.contains(&&*name).then(|| {
+                audit::digest(&serde_json::Value::Object(request.arguments.clone().unwrap_or_default()))
+            });
+            // 取り消しの通知を受けると rmcp がこのトークンを取り消す
+            let cancelled = context.ct.clone();
+            let timeout = self.config.tool_timeout(&name);
+            let limits = self.config.limits.for_tool(&name);
+            let result = async {
+                limits.check_input(&request)?;
+                let call = Self::tool_box().call(ToolCallContext::new(&handler, request, context));
+                // 打ち切るとツールの future を破棄するので、外部への問い合わせなどの待ちもそこで止まる
+                let result = tokio::select! {
+                    _ = cancelled.cancelled() => {
+                        tracing::info!("Tool call {} was cancelled by the client", name);
+                        return Err(tool_cancelled(&name));
+                    }
+                    result = async {
+                        match timeout {
+                            Some(timeout) => tokio::time::timeout(timeout, call).await.map_err(|_| tool_timed_out(&name, timeout))?,
+                            None => call.await,
+                        }
+                    } => result?,
+                };
+                // 構造化された出力も含め、クライアントへ送る形にしてから大きさを確かめる
+                let result = self.config.naming.rename_result(self.with_structured_content(&name, result));
+                limits.check_output(&name, &result)?;
+                Ok::<_, McpError>(result)
+            }
+            .await;
+            let is_error = match &result {
+                Ok(result) => result.is_error == Some(true),
+                Err(_) => true,
+            };
+            self.metrics.record(&name, started.elapsed(), is_error);
+            if let Some(digest) = audited {
+                let outcome = match &result {
+                    Ok(result) => outcome(result),
+                    Err(_) => "error",
+                };
+                self.audit.record(AuditEntry {
+                    timestamp: chrono::Utc::now(),
+                    session: self.session.id(),
+                    tool: name.to_string(),
+                    digest,
+                    outcome: outcome.to_string(),
+                });
+            }
+            result
+        })
+        .await
+    }
+
+    async fn list_resources(
+        &self,
+        request: PaginatedRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        request_log::logged("resources/list", None, Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Resources, "resources/list")?;
+            let offset = pagination::decode_cursor(pagination::request_cursor(&request))?;
+            let (resources, next_cursor) = pagination::paginate(self.all_resources().await?, offset, pagination::LIST_PAGE_SIZE);
+            Ok(ListResourcesResult {
+                resources,
+                next_cursor,
+            })
+        })
+        .await
+    }
+
+    async fn read_resource(
+        &self,
+        ReadResourceRequestParam { uri }: ReadResourceRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        request_log::logged("resources/read", Some(&uri), Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Resources, "resources/read")?;
+            let handler = self.for_uri(&uri)?;
+            if let Some(isbn) = resources::cover_isbn(&uri) {
+                let book = handler.book(&isbn).await?.ok_or_else(|| resources::not_found(&uri))?;
+                return match cover::load(&book).await {
+                    Ok(Some(cover)) => Ok(resources::cover_contents(&uri, &cover)),
+                    Ok(None) => Err(resources::not_found(&uri)),
+                    Err(e) => Err(McpError::internal_error(format!("failed to load cover: {:#}", e), None)),
+                };
+            }
+            resources::read(&uri, &handler.books_including(self.include_deleted()).await?)
+        })
+        .await
+    }
+
+    async fn subscribe(
+        &self,
+        SubscribeRequestParam { uri }: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        request_log::logged("resources/subscribe", Some(&uri), Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Resources, "resources/subscribe")?;
+            // 存在しないリソースは購読させない
+            resources::read(&uri, &self.for_uri(&uri)?.books().await?)?;
+
+            self.subscriptions.insert(uri.clone());
+            if self.subscriptions.start_forwarding() {
+                tokio::spawn(events::forward_updates(
+                    self.events.subscribe(),
+                    self.subscriptions.clone(),
+                    context.peer.clone(),
+                ));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn unsubscribe(
+        &self,
+        UnsubscribeRequestParam { uri }: UnsubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        request_log::logged("resources/unsubscribe", Some(&uri), Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Resources, "resources/unsubscribe")?;
+            self.subscriptions.remove(&uri);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn set_level(
+        &self,
+        SetLevelRequestParam { level }: SetLevelRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        request_log::logged("logging/setLevel", None, Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Logging, "logging/setLevel")?;
+            self.client_log.set_level(level);
+            if self.client_log.start_forwarding() {
+                tokio::spawn(logging::forward_to_client(
+                    logging::subscribe(),
+                    self.client_log.clone(),
+                    context.peer.clone(),
+                ));
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list_prompts(
+        &self,
+        request: PaginatedRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        request_log::logged("prompts/list", None, Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Prompts, "prompts/list")?;
+            let offset = pagination::decode_cursor(pagination::request_cursor(&request))?;
+            let (prompts, next_cursor) =
+                pagination::paginate(prompts::list(&self.books().await?), offset, pagination::LIST_PAGE_SIZE);
+            Ok(ListPromptsResult {
+                next_cursor,
+                prompts,
+            })
+        })
+        .await
+    }
+
+    async fn get_prompt(
+        &self,
+        GetPromptRequestParam { name, arguments }: GetPromptRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        request_log::logged("prompts/get", Some(&name), Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Prompts, "prompts/get")?;
+            prompts::get(&name, arguments.as_ref(), &self.books().await?)
+        })
+        .await
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: PaginatedRequestParam,
+        context: RequestContext<RoleServer>
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        request_log::logged("resources/templates/list", None, Some(self.session.id()), &context.id, request_log::ok, async {
+            self.require(Capability::Resources, "resources/templates/list")?;
+            Ok(ListResourceTemplatesResult {
+                next_cursor: None,
+                resource_templates: resources::templates(),
+            })
+        })
+        .await
+    }
+
+    async fn complete(
+        &self,
+        CompleteRequestParam { r#ref, argument }: CompleteRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CompleteResult, McpError> {
+        request_log::logged("completion/complete", Some(&argument.name), Some(self.session.id()), &context.id, request_log::ok, async {
+            let capability = match &r#ref {
+                Reference::Prompt(_) => Capability::Prompts,
+                Reference::Resource(_) => Capability::Resources,
+            };
+            self.require(capability, "completion/complete")?;
+            Ok(CompleteResult {
+                completion: completion::complete(&r#ref, &argument, &self.books().await?),
+            })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,957 @@
+//! プロセス内でつないだクライアントからのツール・リソース・プロンプトの呼び出し
+
+use rmcp::model::{ClientInfo, ErrorCode, LoggingLevel, ProtocolVersion};
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+use crate::audit::{self, AuditLog};
+use crate::config::{Capability, ServerConfig, ToolLimitsOverride};
+use crate::events::ListKind;
+use crate::model::{Review, fake_books};
+use crate::naming::Naming;
+use crate::protocol;
+use crate::search::{NormalizeStep, Normalizer};
+use crate::server::BookSearch;
+use crate::store::{BookStore, MemoryStore};
+use crate::testing::{
+    ConflictingStore, FormFiller, ListWatcher, ProgressWatcher, TestClient, json_content, mcp_error, resource_text,
+    server_with_books, server_with_config, test_server, text_content,
+};
+
+/// 架空の本にない、チェックディジットの正しいISBN
+const NEW_ISBN: &str = "9784012345991";
+
+fn new_book() -> Value {
+    json!({
+        "isbn": NEW_ISBN,
+        "title": "月面で始める養蜂",
+        "author": "月の養蜂家",
+        "year": 2300,
+        "description": "低重力の環境でミツバチを育てる方法を解説",
+        "tags": ["beekeeping", "space"],
+    })
+}
+
+#[tokio::test]
+async fn initialize_reports_enabled_capabilities() {
+    let client = TestClient::connect(test_server()).await;
+    let info = client.server_info();
+    assert!(info.capabilities.tools.is_some());
+    assert!(info.capabilities.resources.is_some());
+    assert!(info.capabilities.prompts.is_some());
+    assert!(info.capabilities.logging.is_some());
+    client.close().await;
+}
+
+#[tokio::test]
+async fn set_level_requires_the_logging_capability() {
+    let client = TestClient::connect(test_server()).await;
+    client.set_log_level(LoggingLevel::Warning).await.expect("logging/setLevel failed");
+    client.close().await;
+
+    let mut config = ServerConfig::default();
+    config.capabilities.set(Capability::Logging, false);
+    let client = TestClient::connect(server_with_config(fake_books(), config)).await;
+    assert!(client.server_info().capabilities.logging.is_none());
+    let denied = client.set_log_level(LoggingLevel::Debug).await;
+    assert_eq!(mcp_error(denied.expect_err("disabled logging must fail")).code, ErrorCode::METHOD_NOT_FOUND);
+    client.close().await;
+}
+
+/// `version` のリビジョンを要求するクライアントの情報
+fn client_requesting(version: &str) -> ClientInfo {
+    ClientInfo {
+        protocol_version: protocol::to_protocol_version(version),
+        ..ClientInfo::default()
+    }
+}
+
+#[tokio::test]
+async fn initialize_negotiates_the_protocol_version() {
+    let latest = TestClient::connect_as(test_server(), client_requesting(protocol::LATEST_VERSION)).await;
+    assert_eq!(protocol::version_str(&latest.server_info().protocol_version), protocol::LATEST_VERSION);
+    assert!(latest.server_info().capabilities.completions.is_some());
+    latest.close().await;
+
+    let old = TestClient::connect_as(test_server(), client_requesting("2024-11-05")).await;
+    assert_eq!(protocol::version_str(&old.server_info().protocol_version), "2024-11-05");
+    assert!(old.server_info().capabilities.completions.is_none());
+    old.close().await;
+
+    let unknown = TestClient::connect_as(test_server(), client_requesting("2099-01-01")).await;
+    assert_eq!(protocol::version_str(&unknown.server_info().protocol_version), protocol::LATEST_VERSION);
+    unknown.close().await;
+}
+
+#[tokio::test]
+async fn structured_output_follows_the_negotiated_version() {
+    let client = TestClient::connect_as(test_server(), client_requesting("2025-06-18")).await;
+    let tools = client.tools().await;
+    let search = tools.iter().find(|tool| tool.name == "search").expect("search tool is listed");
+    let schema = search.output_schema.as_ref().expect("search has an output schema");
+    assert!(schema["properties"].get("books").is_some());
+
+    // テキスト形式の結果にも構造化された結果が付く
+    let result = client.call("search", json!({ "keyword": "火星" })).await;
+    let structured = result.structured_content.expect("search returns structured content");
+    assert_eq!(structured["count"], 1);
+    assert_eq!(structured["books"][0]["isbn"], "9784012345632");
+
+    let tags = client.call("list_tags", json!({})).await;
+    assert_eq!(tags.structured_content, Some(json_content(&tags)));
+    client.close().await;
+
+    let old = TestClient::connect_as(test_server(), client_requesting("2025-03-26")).await;
+    assert!(old.tools().await.iter().all(|tool| tool.output_schema.is_none()));
+    let result = old.call("search", json!({ "keyword": "火星" })).await;
+    assert!(result.structured_content.is_none());
+    old.close().await;
+}
+
+#[tokio::test]
+async fn camel_case_naming_renames_keys_but_not_values() {
+    let mut books = fake_books();
+    let mars = books.iter_mut().find(|book| book.isbn == "9784012345632").expect("fake book exists");
+    mars.author_id = Some("mars-author".into());
+    mars.cover_path = Some("covers/mars_farm.png".into());
+    let year = mars.year;
+    let config = ServerConfig {
+        naming: Naming::CamelCase,
+        ..ServerConfig::default()
+    };
+    let client = TestClient::connect_as(server_with_config(books, config), client_requesting("2025-06-18")).await;
+
+    let result = client.call("search", json!({ "keyword": "火星", "output_format": "json" })).await;
+    let text = json_content(&result);
+    let book = &text["books"][0];
+    assert_eq!(book["isbn"], "9784012345632");
+    assert_eq!(book["year"], year);
+    assert_eq!(book["authorId"], "mars-author");
+    assert_eq!(book["coverPath"], "covers/mars_farm.png");
+    assert!(book.get("author_id").is_none() && book.get("cover_path").is_none());
+    assert!(text.get("resultId").is_some() && text.get("result_id").is_none());
+    assert_eq!(result.structured_content, Some(text));
+
+    let tools = client.tools().await;
+    let search = tools.iter().find(|tool| tool.name == "search").expect("search tool is listed");
+    let schema = search.output_schema.as_ref().expect("search has an output schema");
+    assert!(schema["properties"].get("resultId").is_some());
+    assert!(schema["properties"].get("result_id").is_none());
+    client.close().await;
+}
+
+#[tokio::test]
+async fn diagnostics_report_the_version_and_negotiated_protocol() {
+    let client = TestClient::connect_as(test_server(), client_requesting("2024-11-05")).await;
+    let diagnostics = json_content(&client.call("diagnostics", json!({})).await);
+    assert_eq!(diagnostics["protocol_version"], json!(ProtocolVersion::V_2024_11_05));
+    assert!(diagnostics["version"].as_str().is_some_and(|version| !version.is_empty()));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn list_tools_adds_catalog_argument_to_catalog_tools() {
+    let client = TestClient::connect(test_server()).await;
+    let tools = client.tools().await;
+    let properties = |name: &str| {
+        let tool = tools.iter().find(|tool| tool.name == name).unwrap_or_else(|| panic!("{} is not listed", name));
+        tool.input_schema.get("properties").cloned().unwrap_or_default()
+    };
+    assert!(properties("search").get("catalog").is_some());
+    assert!(properties("health").get("catalog").is_none());
+    client.close().await;
+}
+
+#[tokio::test]
+async fn search_finds_books_by_keyword() {
+    let client = TestClient::connect(test_server()).await;
+    let result = client.call("search", json!({ "keyword": "火星", "output_format": "json" })).await;
+    assert_ne!(result.is_error, Some(true));
+    let value = json_content(&result);
+    assert_eq!(value["count"], 1);
+    assert_eq!(value["books"][0]["isbn"], "9784012345632");
+    client.close().await;
+}
+
+#[tokio::test]
+async fn search_respects_limit_and_returns_cursor() {
+    let client = TestClient::connect(test_server()).await;
+    let result = client
+        .call("search", json!({ "keyword": "", "limit": 1, "output_format": "json" }))
+        .await;
+    let value = json_content(&result);
+    assert_eq!(value["books"].as_array().map(Vec::len), Some(1));
+    assert!(value["next_cursor"].is_string());
+    client.close().await;
+}
+
+#[tokio::test]
+async fn refine_search_narrows_a_previous_result() {
+    let client = TestClient::connect(test_server()).await;
+    let first = json_content(&client.call("search", json!({ "year_min": 2200, "output_format": "json" })).await);
+    assert_eq!(first["count"], 4);
+
+    let refined = client
+        .call("refine_search", json!({ "result_id": first["result_id"], "keyword": "火星" }))
+        .await;
+    let refined = json_content(&refined);
+    assert_eq!(refined["count"], 1);
+    assert_eq!(refined["books"][0]["isbn"], "9784012345632");
+    assert_ne!(refined["result_id"], first["result_id"]);
+
+    let unknown = client.call("refine_search", json!({ "result_id": "r999", "keyword": "火星" })).await;
+    assert_eq!(unknown.is_error, Some(true));
+    client.close().await;
+}
+
+/// JSONの配列の文字列を並べ替えて返す
+fn sorted_strings(value: &Value) -> Vec<&str> {
+    let mut strings: Vec<&str> = value
+        .as_array()
+        .expect("expected an array")
+        .iter()
+        .filter_map(Value::as_str)
+        .collect();
+    strings.sort_unstable();
+    strings
+}
+
+#[tokio::test]
+async fn search_diff_compares_all_matches_not_just_the_first_page() {
+    let client = TestClient::connect(test_server()).await;
+    let diff = client
+        .call(
+            "search_diff",
+            json!({
+                "left": { "keyword": "解説", "limit": 1 },
+                "right": { "keyword": "AI 方法", "limit": 1 },
+            }),
+        )
+        .await;
+    let diff = json_content(&diff);
+    assert_eq!(sorted_strings(&diff["only_left"]), ["9784012345625"]);
+    assert_eq!(sorted_strings(&diff["only_right"]), ["9784012345649"]);
+    assert_eq!(sorted_strings(&diff["common"]), ["9784012345618", "9784012345632", "9784012345656"]);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn named_queries_from_the_config_run_with_their_preset() {
+    let mut config = ServerConfig::default();
+    let preset = serde_json::from_value(json!({ "keyword": "解説", "year_min": 2300 })).expect("valid search query");
+    config.named_queries.insert("future-guides".to_string(), preset);
+    let client = TestClient::connect(server_with_config(fake_books(), config)).await;
+
+    let result = json_content(&client.call("run_named_query", json!({ "name": "future-guides" })).await);
+    let books = result["books"].as_array().expect("books is an array");
+    let isbns: Vec<&str> = books.iter().filter_map(|book| book["isbn"].as_str()).collect();
+    assert_eq!(isbns, ["9784012345625", "9784012345656"]);
+
+    let unknown = client.try_call("run_named_query", json!({ "name": "missing" })).await;
+    let error = mcp_error(unknown.expect_err("unknown name must fail"));
+    assert_eq!(error.code, ErrorCode::INVALID_PARAMS);
+    assert_eq!(error.data.as_ref().map(|data| data["available"].clone()), Some(json!(["future-guides"])));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn search_rejects_negative_limit() {
+    let client = TestClient::connect(test_server()).await;
+    let result = client.try_call("search", json!({ "keyword": "火星", "limit": -1 })).await;
+    let error = mcp_error(result.expect_err("negative limit must fail"));
+    assert_eq!(error.code, ErrorCode::INVALID_PARAMS);
+    assert_eq!(error.data, Some(json!({ "limit": -1 })));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn validate_query_reports_every_problem_search_would_reject() {
+    let client = TestClient::connect(test_server()).await;
+    let query = json!({ "keyword": "火星", "limit": -1, "ranked": true, "semantic": true });
+    let validated = json_content(&client.call("validate_query", query.clone()).await);
+    assert_eq!(validated["valid"], false);
+    let problems: Vec<&str> = validated["problems"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+    assert_eq!(problems.len(), 2, "{:?}", problems);
+    assert!(problems[0].contains("limit"));
+    assert!(problems[1].contains("semantic"));
+
+    let searched = client.try_call("search", query).await;
+    assert_eq!(mcp_error(searched.expect_err("invalid query must fail")).code, ErrorCode::INVALID_PARAMS);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn search_clamps_limit_to_the_configured_maximum() {
+    let config = ServerConfig {
+        max_search_limit: 2,
+        default_search_limit: 2,
+        ..ServerConfig::default()
+    };
+    let client = TestClient::connect(server_with_config(fake_books(), config)).await;
+    let result = client
+        .call("search", json!({ "keyword": "", "limit": 1000, "output_format": "json" }))
+        .await;
+    let value = json_content(&result);
+    assert_eq!(value["books"].as_array().map(Vec::len), Some(2));
+    assert!(value["next_cursor"].is_string());
+    client.close().await;
+}
+
+#[tokio::test]
+async fn added_book_is_searchable_and_cannot_be_added_twice() {
+    let client = TestClient::connect(test_server()).await;
+    let added = client.call("add_book", new_book()).await;
+    assert_ne!(added.is_error, Some(true), "{}", text_content(&added));
+
+    let found = client.call("search", json!({ "keyword": "養蜂", "output_format": "json" })).await;
+    assert_eq!(json_content(&found)["books"][0]["isbn"], NEW_ISBN);
+
+    let duplicate = client.call("add_book", new_book()).await;
+    assert_eq!(duplicate.is_error, Some(true));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn generated_books_can_be_paged_and_are_not_added_twice() {
+    let client = TestClient::connect(test_server()).await;
+    let generated = json_content(&client.call("generate_fake_books", json!({ "count": 250, "seed": 1 })).await);
+    assert_eq!(generated["generated"], 250);
+    assert_eq!(generated["next_start"], 250);
+
+    let mut seen = 0;
+    let mut cursor: Option<String> = None;
+    loop {
+        let mut query = json!({ "keyword": "", "limit": 100, "output_format": "json" });
+        if let Some(cursor) = &cursor {
+            query["cursor"] = json!(cursor);
+        }
+        let page = json_content(&client.call("search", query).await);
+        seen += page["books"].as_array().map_or(0, Vec::len);
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    assert_eq!(seen, fake_books().len() + 250);
+
+    let again = json_content(&client.call("generate_fake_books", json!({ "count": 250, "seed": 1 })).await);
+    assert_eq!(again["generated"], 0);
+    assert_eq!(again["skipped"], 250);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn add_book_asks_for_missing_fields() {
+    let filler = FormFiller::new(Some(json!({ "title": "月面で始める養蜂", "year": 2300 })));
+    let client = TestClient::connect_as(test_server(), filler.clone()).await;
+    let mut book = new_book();
+    book.as_object_mut().unwrap().remove("title");
+    book.as_object_mut().unwrap().remove("year");
+
+    let added = client.call("add_book", book).await;
+    assert_ne!(added.is_error, Some(true), "{}", text_content(&added));
+    assert_eq!(json_content(&added)["title"], "月面で始める養蜂");
+
+    let requests = filler.requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 1);
+    let properties = requests[0].requested_schema["properties"].as_object().unwrap();
+    assert_eq!(properties.keys().collect::<Vec<_>>(), ["title", "year"]);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn add_book_fails_when_missing_fields_are_not_provided() {
+    let mut book = new_book();
+    book.as_object_mut().unwrap().remove("author");
+
+    let declining = TestClient::connect_as(test_server(), FormFiller::new(None)).await;
+    let declined = declining.call("add_book", book.clone()).await;
+    assert_eq!(declined.is_error, Some(true));
+    declining.close().await;
+
+    // エリシテーションに対応していないクライアントには、足りない項目をエラーで伝える
+    let client = TestClient::connect(test_server()).await;
+    let failed = client.call("add_book", book).await;
+    assert_eq!(failed.is_error, Some(true));
+    assert_eq!(json_content(&failed)["errors"], json!(["author は必須です"]));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn changes_can_be_undone_and_redone() {
+    let client = TestClient::connect(test_server()).await;
+    client.call("add_book", new_book()).await;
+    let updated = client.call("update_book", json!({ "isbn": NEW_ISBN, "title": "月面養蜂の手引き" })).await;
+    assert_ne!(updated.is_error, Some(true), "{}", text_content(&updated));
+
+    let undone = json_content(&client.call("undo_last_change", json!({})).await);
+    assert_eq!(undone["tool"], "update_book");
+    let found = client.call("search", json!({ "isbn": NEW_ISBN, "output_format": "json" })).await;
+    assert_eq!(json_content(&found)["books"][0]["title"], "月面で始める養蜂");
+
+    let undone = json_content(&client.call("undo_last_change", json!({})).await);
+    assert_eq!(undone["removed"], json!([NEW_ISBN]));
+    let redone = json_content(&client.call("redo", json!({})).await);
+    assert_eq!(redone["added"], json!([NEW_ISBN]));
+
+    // やり直すものがなくなった後はエラーになる
+    client.call("redo", json!({})).await;
+    let nothing = client.call("redo", json!({})).await;
+    assert_eq!(nothing.is_error, Some(true));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn undoing_a_delete_restores_reviews() {
+    let client = TestClient::connect(test_server()).await;
+    let isbn = "9784012345632";
+    client
+        .call("add_review", json!({ "isbn": isbn, "rating": 5, "text": "面白い", "reviewer": "読者" }))
+        .await;
+    client.call("delete_book", json!({ "isbn": isbn })).await;
+    client.call("undo_last_change", json!({})).await;
+
+    let reviews = json_content(&client.call("list_reviews", json!({ "isbn": isbn })).await);
+    assert_eq!(reviews["count"], 1);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn undo_refuses_to_overwrite_later_changes() {
+    let store = Arc::new(MemoryStore::new(fake_books()));
+    let client = TestClient::connect(BookSearch::with_store(store.clone())).await;
+    client.call("update_book", json!({ "isbn": "9784012345632", "year": 2251 })).await;
+
+    // 履歴に残らない経路（ファイルの読み直しなど）で本が変わった
+    let mut book = store.get("9784012345632").await.unwrap().unwrap();
+    book.year = 2252;
+    store.update(book.clone(), book.version).await.unwrap();
+
+    let conflict = client.call("undo_last_change", json!({})).await;
+    assert_eq!(conflict.is_error, Some(true));
+    assert_eq!(store.get("9784012345632").await.unwrap().unwrap().year, 2252);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn deleted_books_go_to_the_trash_until_purged() {
+    let client = TestClient::connect(test_server()).await;
+    let isbn = "9784012345632";
+    client.call("delete_book", json!({ "isbn": isbn })).await;
+
+    let found = json_content(&client.call("search", json!({ "isbn": isbn, "output_format": "json" })).await);
+    assert_eq!(found["count"], 0);
+    let found = json_content(
+        &client
+            .call("search", json!({ "isbn": isbn, "include_deleted": true, "output_format": "json" }))
+            .await,
+    );
+    assert_eq!(found["count"], 1);
+    let trash = json_content(&client.call("list_trash", json!({})).await);
+    assert_eq!(trash["books"][0]["isbn"], isbn);
+
+    let restored = client.call("restore_book", json!({ "isbn": isbn })).await;
+    assert_ne!(restored.is_error, Some(true), "{}", text_content(&restored));
+    let found = json_content(&client.call("search", json!({ "isbn": isbn, "output_format": "json" })).await);
+    assert_eq!(found["count"], 1);
+
+    client.call("delete_book", json!({ "isbn": isbn })).await;
+    let purged = json_content(&client.call("purge_trash", json!({})).await);
+    assert_eq!(purged["isbns"], json!([isbn]));
+    let missing = client.call("restore_book", json!({ "isbn": isbn })).await;
+    assert_eq!(missing.is_error, Some(true));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn bulk_update_applies_the_patch_to_matching_books() {
+    let client = TestClient::connect(test_server()).await;
+    let request = json!({ "query": "tag:mars OR tag:quantum", "patch": { "add_tags": ["featured"] } });
+    let mut dry_run = request.clone();
+    dry_run["dry_run"] = json!(true);
+    let preview = json_content(&client.call("bulk_update_books", dry_run).await);
+    assert_eq!(preview["count"], 2);
+
+    let updated = json_content(&client.call("bulk_update_books", request.clone()).await);
+    assert_eq!(updated["count"], 2);
+    // 既にタグが付いている本は変わらない
+    let again = json_content(&client.call("bulk_update_books", request).await);
+    assert_eq!(again["count"], 0);
+
+    let undone = json_content(&client.call("undo_last_change", json!({})).await);
+    assert_eq!(undone["updated"].as_array().map(Vec::len), Some(2));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn renaming_an_author_updates_their_books() {
+    let client = TestClient::connect(test_server()).await;
+    let author = json_content(&client.call("get_author", json!({ "author": "火星の園芸家" })).await);
+    assert_eq!(author["registered"], false);
+    assert_eq!(author["books"][0]["isbn"], "9784012345632");
+
+    let updated = client
+        .call("update_author", json!({ "author": author["id"], "name": "火星の庭師", "bio": "赤い大地で土を耕す" }))
+        .await;
+    assert_eq!(json_content(&updated)["updated_books"], json!(["9784012345632"]));
+
+    // 以前の名前は別名として残る
+    let books = json_content(&client.call("books_by_author", json!({ "author": "火星の園芸家" })).await);
+    assert_eq!(books["author"]["id"], author["id"]);
+    assert_eq!(books["books"][0]["author"], "火星の庭師");
+    client.close().await;
+}
+
+#[tokio::test]
+async fn renaming_an_author_changes_nothing_when_a_book_conflicts() {
+    let mut books = fake_books();
+    let mut second = books[1].clone();
+    second.isbn = NEW_ISBN.to_string();
+    second.title = "火星の温室".to_string();
+    books.push(second);
+    let store = Arc::new(ConflictingStore::new(books));
+    let client = TestClient::connect(BookSearch::with_store(store.clone())).await;
+    let author = json_content(&client.call("get_author", json!({ "author": "火星の園芸家" })).await);
+    assert_eq!(author["books"].as_array().map(Vec::len), Some(2));
+
+    // 2冊目の本は別の呼び出しが先に書き換えている
+    store.conflict_on(NEW_ISBN);
+    let error = mcp_error(
+        client
+            .try_call("update_author", json!({ "author": author["id"], "name": "火星の庭師" }))
+            .await
+            .unwrap_err(),
+    );
+    assert_eq!(error.data.unwrap()["isbn"], NEW_ISBN);
+
+    // 1冊目の本も著者の記録も元のまま
+    let after = json_content(&client.call("get_author", json!({ "author": "火星の園芸家" })).await);
+    assert_eq!(after["name"], "火星の園芸家");
+    assert_eq!(after["registered"], false);
+    let book = store.get("9784012345632").await.unwrap().unwrap();
+    assert_eq!(book.author, "火星の園芸家");
+    assert_eq!(book.author_id, None);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn merging_restores_every_book_when_a_duplicate_conflicts() {
+    let mut books = fake_books();
+    let primary = books[1].clone();
+    let mut first = primary.clone();
+    first.isbn = "9784012345984".to_string();
+    first.tags = vec!["greenhouse".to_string()];
+    let mut second = primary.clone();
+    second.isbn = NEW_ISBN.to_string();
+    books.extend([first, second]);
+    let store = Arc::new(ConflictingStore::new(books));
+    let review = Review {
+        isbn: "9784012345984".to_string(),
+        rating: 4,
+        text: String::new(),
+        reviewer: "読者".to_string(),
+    };
+    store.add_review(review).await.unwrap();
+    let client = TestClient::connect(BookSearch::with_store(store.clone())).await;
+
+    // 2冊目の重複は別の呼び出しが先に書き換えている
+    store.conflict_on(NEW_ISBN);
+    let request = json!({ "primary": primary.isbn, "duplicates": ["9784012345984", NEW_ISBN] });
+    let error = mcp_error(client.try_call("merge_books", request).await.unwrap_err());
+    assert_eq!(error.data.unwrap()["isbn"], NEW_ISBN);
+
+    // 先に削除した重複はレビューごと戻り、残す本は統合前のまま
+    let restored = store.get("9784012345984").await.unwrap().expect("the deleted duplicate is restored");
+    assert_eq!(restored.tags, vec!["greenhouse"]);
+    assert_eq!(store.reviews("9784012345984").await.unwrap().len(), 1);
+    let kept = store.get(&primary.isbn).await.unwrap().unwrap();
+    assert_eq!(kept.tags, primary.tags);
+    assert!(store.reviews(&primary.isbn).await.unwrap().is_empty());
+    client.close().await;
+}
+
+#[tokio::test]
+async fn get_series_returns_volumes_in_order() {
+    let client = TestClient::connect(test_server()).await;
+    for (isbn, volume) in [("9784012345632", 2), ("9784012345618", 1)] {
+        let updated = client
+            .call("update_book", json!({ "isbn": isbn, "series": { "name": "星の書架", "volume": volume } }))
+            .await;
+        assert_ne!(updated.is_error, Some(true));
+    }
+
+    let series = json_content(&client.call("get_series", json!({ "name": "星の書架" })).await);
+    assert_eq!(series["count"], 2);
+    assert_eq!(series["volumes"][0]["isbn"], "9784012345618");
+    assert_eq!(series["volumes"][1]["series"]["volume"], 2);
+
+    let listed = json_content(&client.call("list_series", json!({})).await);
+    assert_eq!(listed["series"][0]["volumes"], 2);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn reading_lists_keep_books_across_calls() {
+    let client = TestClient::connect(test_server()).await;
+    let created = client
+        .call("create_reading_list", json!({ "name": "夏休み", "isbns": ["9784012345632"] }))
+        .await;
+    assert_ne!(created.is_error, Some(true), "{}", text_content(&created));
+    let duplicate = client.call("create_reading_list", json!({ "name": "夏休み" })).await;
+    assert_eq!(duplicate.is_error, Some(true));
+
+    let added = json_content(
+        &client
+            .call("add_to_reading_list", json!({ "name": "夏休み", "isbns": ["9784012345618", "9784012345632"] }))
+            .await,
+    );
+    assert_eq!(added["added"], json!(["9784012345618"]));
+    assert_eq!(added["already_listed"], json!(["9784012345632"]));
+    client
+        .call("remove_from_reading_list", json!({ "name": "夏休み", "isbns": ["9784012345632"] }))
+        .await;
+
+    let list = json_content(&client.call("get_reading_list", json!({ "name": "夏休み" })).await);
+    assert_eq!(list["count"], 1);
+    assert_eq!(list["books"][0]["isbn"], "9784012345618");
+    client.close().await;
+}
+
+#[tokio::test]
+async fn theme_coverage_reports_uncovered_themes() {
+    let client = TestClient::connect(test_server()).await;
+    let result = client.call("theme_coverage", json!({ "themes": ["料理", "恐竜"] })).await;
+    let value = json_content(&result);
+    assert_eq!(value["coverage"][0]["theme"], "料理");
+    assert_eq!(value["coverage"][0]["isbns"], json!(["9784012345618"]));
+    assert_eq!(value["coverage"][1]["count"], 0);
+    assert_eq!(value["uncovered"], json!(["恐竜"]));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn cross_references_find_a_title_mentioned_in_another_description() {
+    let mut books = fake_books();
+    // 大文字小文字の違いは区別しない
+    books[4].description = "『aiと恋愛の心理学』の続編として、テレパシーでコードを書く方法を解説。".to_string();
+    let client = TestClient::connect(server_with_books(books)).await;
+    let result = json_content(&client.call("cross_references", json!({})).await);
+    assert_eq!(result["references"], json!([{ "from": "9784012345656", "to": "9784012345649" }]));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn central_author_is_the_one_sharing_a_theme_with_every_other_author() {
+    let mut books: Vec<_> = fake_books().into_iter().take(4).collect();
+    let authors = [
+        ("中心の著者", vec!["ai", "cooking", "space"]),
+        ("料理の著者", vec!["cooking"]),
+        ("宇宙の著者", vec!["space"]),
+        ("AIの著者", vec!["ai"]),
+    ];
+    for (book, (author, tags)) in books.iter_mut().zip(authors) {
+        book.author = author.to_string();
+        book.tags = tags.into_iter().map(str::to_string).collect();
+    }
+    let client = TestClient::connect(server_with_books(books)).await;
+    let result = json_content(&client.call("central_author", json!({})).await);
+    assert_eq!(result["central"]["author"], "中心の著者");
+    assert_eq!(result["central"]["connected_authors"], json!(["AIの著者", "宇宙の著者", "料理の著者"]));
+    assert_eq!(result["central"]["shared_themes"], json!(["ai", "cooking", "space"]));
+    let others = result["authors"].as_array().expect("authors is an array");
+    assert_eq!(others.len(), 4);
+    assert!(others[1..].iter().all(|author| author["connected_authors"] == json!(["中心の著者"])));
+    client.close().await;
+}
+
+#[test]
+fn long_vowel_folding_matches_spelling_variants() {
+    let title = "量子コンピュータで料理する方法";
+    let default = Normalizer::default();
+    assert!(!default.normalize(title).contains(&default.normalize("コンピューター")));
+
+    let folding = Normalizer::new(vec![NormalizeStep::Nfkc, NormalizeStep::Lowercase, NormalizeStep::FoldLongVowel]);
+    assert!(folding.normalize(title).contains(&folding.normalize("コンピューター")));
+}
+
+#[tokio::test]
+async fn export_html_has_a_row_per_book_and_escapes_markup() {
+    let mut books = fake_books();
+    books[0].title = "<script>量子料理</script>".to_string();
+    let count = books.len();
+    let client = TestClient::connect(server_with_books(books)).await;
+    let html = text_content(&client.call("export_html", json!({})).await);
+    assert_eq!(html.matches("<tr><td>").count(), count);
+    assert!(html.contains("&lt;script&gt;量子料理&lt;/script&gt;"));
+    assert!(!html.contains("<script>"));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn read_catalog_resource_lists_all_books() {
+    let client = TestClient::connect(test_server()).await;
+    let result = client.read("book://catalog").await.expect("resources/read failed");
+    let books: Value = serde_json::from_str(resource_text(&result)).expect("catalog is JSON");
+    assert_eq!(books.as_array().map(Vec::len), Some(fake_books().len()));
+
+    let missing = client.read("book://isbn/0000000000000").await;
+    assert_eq!(mcp_error(missing.expect_err("unknown ISBN must fail")).code, ErrorCode::RESOURCE_NOT_FOUND);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn resource_sitemap_lists_every_book_and_the_catalog() {
+    let client = TestClient::connect(test_server()).await;
+    let sitemap = json_content(&client.call("resource_sitemap", json!({})).await);
+    let uris: Vec<&str> = sitemap["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|resource| resource["uri"].as_str())
+        .collect();
+    assert!(uris.contains(&"book://catalog"));
+    for book in fake_books() {
+        assert!(uris.contains(&format!("book://isbn/{}", book.isbn).as_str()), "{} is missing", book.isbn);
+    }
+    assert_eq!(sitemap["count"], fake_books().len() + 1);
+    assert_eq!(sitemap["resources"][0]["mime_type"], "application/json");
+
+    let templates: Vec<&str> = sitemap["templates"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|template| template["uri_template"].as_str())
+        .collect();
+    assert!(templates.contains(&"book://isbn/{isbn}"));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn search_stream_reports_each_chunk_as_progress() {
+    let watcher = ProgressWatcher::default();
+    let client = TestClient::connect_as(test_server(), watcher.clone()).await;
+    client
+        .call_with_progress("search_stream", json!({ "keyword": "", "limit": 2 }), "stream")
+        .await;
+
+    // 5冊を2冊ずつ送るので、塊は3つ
+    let total = fake_books().len();
+    let chunks = total.div_ceil(2);
+    assert!(watcher.wait_for(chunks).await, "expected {} progress notifications", chunks);
+    let received = watcher.received();
+    assert_eq!(received.len(), chunks);
+    assert_eq!(received.last().map(|progress| progress.progress), Some(total as u32));
+    let first: Value = serde_json::from_str(received[0].message.as_deref().expect("progress has a message")).unwrap();
+    assert_eq!(first["books"].as_array().map(Vec::len), Some(2));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn search_stream_returns_only_the_summary_when_streaming() {
+    let watcher = ProgressWatcher::default();
+    let client = TestClient::connect_as(test_server(), watcher.clone()).await;
+    let result = client
+        .call_with_progress("search_stream", json!({ "keyword": "", "limit": 2 }), "stream")
+        .await;
+
+    // 塊は進捗通知で届いたので、結果はまとめだけ
+    assert_eq!(result.content.len(), 1);
+    let summary = json_content(&result);
+    assert_eq!(summary["streamed"], true);
+    assert_eq!(summary["count"], fake_books().len());
+    assert_eq!(summary["chunks"], 3);
+    assert_eq!(summary["next_offset"], Value::Null);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn search_stream_returns_every_chunk_without_a_progress_token() {
+    let client = TestClient::connect(test_server()).await;
+    let result = client.call("search_stream", json!({ "keyword": "", "limit": 2 })).await;
+
+    // 塊ごとのブロックのあとに、まとめが続く
+    assert_eq!(result.content.len(), 4);
+    let chunks: Vec<Value> = result
+        .content
+        .iter()
+        .filter_map(|content| content.as_text())
+        .map(|text| serde_json::from_str(&text.text).unwrap())
+        .collect();
+    let books: usize = chunks[..3].iter().map(|chunk| chunk["books"].as_array().map_or(0, Vec::len)).sum();
+    assert_eq!(books, fake_books().len());
+    assert_eq!(chunks[3]["streamed"], false);
+    assert_eq!(chunks[3]["chunks"], 3);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn summarize_prompt_includes_the_book() {
+    let client = TestClient::connect(test_server()).await;
+    let prompt = client
+        .prompt("summarize_book", json!({ "isbn": "9784012345632" }))
+        .await
+        .expect("prompts/get failed");
+    assert_eq!(prompt.description.as_deref(), Some("『火星での園芸入門』の要約"));
+
+    let missing = client.prompt("summarize_book", json!({})).await;
+    assert_eq!(mcp_error(missing.expect_err("missing argument must fail")).code, ErrorCode::INVALID_PARAMS);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn prompt_list_has_a_discussion_prompt_per_book() {
+    let client = TestClient::connect(test_server()).await;
+    let first = client.prompts(None).await;
+    assert_eq!(first.prompts.len(), 2 + fake_books().len());
+    assert!(first.next_cursor.is_none());
+
+    // カタログが増えると一覧も増え、ページに分かれる
+    client.call("generate_fake_books", json!({ "count": 60 })).await;
+    let first = client.prompts(None).await;
+    let next = client.prompts(first.next_cursor.as_deref()).await;
+    assert_eq!(first.prompts.len() + next.prompts.len(), 2 + fake_books().len() + 60);
+
+    let prompt = client
+        .prompt("discuss_9784012345632", json!({ "focus": "土壌" }))
+        .await
+        .expect("prompts/get failed");
+    assert_eq!(prompt.description.as_deref(), Some("『火星での園芸入門』についての話し合い"));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn readiness_fails_once_shutdown_begins() {
+    let server = test_server();
+    assert!(server.server_health().await.liveness().0);
+    assert!(server.server_health().await.readiness().0);
+
+    server.shutdown().await.expect("shutdown failed");
+    let health = server.server_health().await;
+    assert!(health.liveness().0);
+    let (ready, report) = health.readiness();
+    assert!(!ready);
+    assert_eq!(report.status, "unavailable");
+}
+
+#[tokio::test]
+async fn health_tool_reports_the_same_readiness_as_readyz() {
+    let server = test_server();
+    let client = TestClient::connect(server.clone()).await;
+    let health = json_content(&client.call("health", json!({})).await);
+    assert_eq!(health, json!({ "status": "ok", "ready": true }));
+
+    let (ready, _) = server.server_health().await.readiness();
+    assert_eq!(health["ready"], ready);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn audit_log_keeps_only_the_newest_entries() {
+    let client = TestClient::connect(test_server().with_audit(Arc::new(AuditLog::new(2)))).await;
+    let reviews: Vec<Value> = (1..=3)
+        .map(|rating| json!({ "isbn": "9784012345632", "rating": rating, "text": "", "reviewer": "読者" }))
+        .collect();
+    for review in &reviews {
+        client.call("add_review", review.clone()).await;
+    }
+
+    // 最初の呼び出しの記録は捨てられている
+    let log = json_content(&client.call("get_audit_log", json!({})).await);
+    let digests: Vec<&Value> = log["entries"].as_array().unwrap().iter().map(|entry| &entry["digest"]).collect();
+    assert_eq!(digests, vec![&json!(audit::digest(&reviews[1])), &json!(audit::digest(&reviews[2]))]);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn read_only_mode_denies_mutating_tools() {
+    let config = ServerConfig {
+        read_only: true,
+        ..ServerConfig::default()
+    };
+    let client = TestClient::connect(server_with_config(fake_books(), config)).await;
+    let denied = client.try_call("add_book", new_book()).await;
+    assert_eq!(mcp_error(denied.expect_err("add_book must be denied")).code, ErrorCode::INVALID_REQUEST);
+
+    let search = client.call("search", json!({ "keyword": "火星" })).await;
+    assert_ne!(search.is_error, Some(true));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn oversized_arguments_are_rejected_before_the_tool_runs() {
+    let mut config = ServerConfig::default();
+    config.limits.max_input_bytes = 64;
+    let client = TestClient::connect(server_with_config(fake_books(), config)).await;
+    let result = client.try_call("search", json!({ "keyword": "火星".repeat(40) })).await;
+    let error = mcp_error(result.expect_err("oversized arguments must fail"));
+    assert_eq!(error.code, ErrorCode::INVALID_PARAMS);
+    assert_eq!(error.data.as_ref().map(|data| data["max"].clone()), Some(json!(64)));
+
+    let small = client.call("search", json!({ "keyword": "火星" })).await;
+    assert_ne!(small.is_error, Some(true));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn oversized_results_fail_with_the_tools_limit() {
+    let mut config = ServerConfig::default();
+    config.limits.tools.insert(
+        "search".to_string(),
+        ToolLimitsOverride {
+            max_output_bytes: Some(100),
+            ..ToolLimitsOverride::default()
+        },
+    );
+    let client = TestClient::connect(server_with_config(fake_books(), config)).await;
+    let result = client.try_call("search", json!({ "keyword": "" })).await;
+    let error = mcp_error(result.expect_err("oversized result must fail"));
+    assert_eq!(error.code, ErrorCode::INTERNAL_ERROR);
+    assert_eq!(error.message, "tool result too large");
+    assert_eq!(error.data.as_ref().map(|data| data["tool"].clone()), Some(json!("search")));
+
+    // 上限を指定していないツールには全体の上限が使われる
+    let tags = client.call("list_tags", json!({})).await;
+    assert_ne!(tags.is_error, Some(true));
+    client.close().await;
+}
+
+#[tokio::test]
+async fn named_catalogs_are_separate_from_the_default_catalog() {
+    let client = TestClient::connect(test_server()).await;
+    let created = client.call("create_catalog", json!({ "name": "fiction" })).await;
+    assert_ne!(created.is_error, Some(true), "{}", text_content(&created));
+
+    let mut book = new_book();
+    book["catalog"] = json!("fiction");
+    let added = client.call("add_book", book).await;
+    assert_ne!(added.is_error, Some(true), "{}", text_content(&added));
+
+    let fiction = client.read("book://fiction/catalog").await.expect("resources/read failed");
+    let books: Value = serde_json::from_str(resource_text(&fiction)).expect("catalog is JSON");
+    assert_eq!(books.as_array().map(Vec::len), Some(1));
+
+    let default = client.call("search", json!({ "keyword": "養蜂", "output_format": "json" })).await;
+    assert_eq!(json_content(&default)["count"], 0);
+
+    let unknown = client.try_call("search", json!({ "keyword": "養蜂", "catalog": "unknown" })).await;
+    assert_eq!(mcp_error(unknown.expect_err("unknown catalog must fail")).code, ErrorCode::INVALID_PARAMS);
+    client.close().await;
+}
+
+#[tokio::test]
+async fn creating_a_catalog_announces_the_new_tool_list() {
+    let watcher = ListWatcher::default();
+    let client = TestClient::connect_as(test_server(), watcher.clone()).await;
+    let tools = client.server_info().capabilities.tools.clone().expect("tools are enabled");
+    assert_eq!(tools.list_changed, Some(true));
+
+    client.call("create_catalog", json!({ "name": "fiction" })).await;
+    assert!(watcher.wait_for(ListKind::Tools).await, "no tools/list_changed after create_catalog");
+    assert!(watcher.wait_for(ListKind::Resources).await);
+    let search = client.tools().await.into_iter().find(|tool| tool.name == "search").expect("search is listed");
+    assert_eq!(search.input_schema["properties"]["catalog"]["enum"], json!(["default", "fiction"]));
+
+    client.call("add_book", new_book()).await;
+    assert!(watcher.wait_for(ListKind::Prompts).await, "no prompts/list_changed after add_book");
+    client.close().await;
+}
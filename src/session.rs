@@ -0,0 +1,113 @@
+//! セッションごとの状態（接続時刻と利用者の設定）の管理
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::i18n::Lang;
+
+/// セッションごとに変えられる設定（指定のない項目はサーバーの設定に従う）
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Preferences {
+    #[schemars(description = "応答の言語（\"ja\" または \"en\"）")]
+    pub lang: Option<Lang>,
+    #[schemars(description = "limit を省略した検索で返す件数")]
+    pub default_limit: Option<usize>,
+    #[schemars(description = "true の場合、ごみ箱に移した本も検索やリソースの一覧に含める")]
+    pub include_deleted: Option<bool>,
+}
+
+/// 接続中のセッション1つ分の情報
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub id: u64,
+    pub connected_at: DateTime<Utc>,
+    /// 初期化で取り決めたプロトコルのリビジョン（初期化前は `None`）
+    pub protocol_version: Option<String>,
+    pub preferences: Preferences,
+}
+
+/// 全セッションで共有するセッションの一覧
+#[derive(Debug, Default)]
+pub struct Sessions {
+    next_id: AtomicU64,
+    sessions: Mutex<BTreeMap<u64, SessionInfo>>,
+}
+
+impl Sessions {
+    /// 新しいセッションを登録する（返したハンドルが破棄されると登録を消す）
+    pub fn open(self: &Arc<Self>) -> SessionHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let info = SessionInfo {
+            id,
+            connected_at: Utc::now(),
+            protocol_version: None,
+            preferences: Preferences::default(),
+        };
+        self.sessions.lock().expect("session lock poisoned").insert(id, info);
+        tracing::debug!(session = id, "Session opened");
+        SessionHandle {
+            id,
+            sessions: self.clone(),
+        }
+    }
+
+    fn close(&self, id: u64) {
+        self.sessions.lock().expect("session lock poisoned").remove(&id);
+        tracing::debug!(session = id, "Session closed");
+    }
+
+    /// 接続中のセッションを接続順に返す
+    pub fn list(&self) -> Vec<SessionInfo> {
+        self.sessions.lock().expect("session lock poisoned").values().cloned().collect()
+    }
+}
+
+/// 1つのセッションの登録（セッションの全ての複製が破棄されたときに登録を消す）
+#[derive(Debug)]
+pub struct SessionHandle {
+    id: u64,
+    sessions: Arc<Sessions>,
+}
+
+impl SessionHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn info(&self) -> Option<SessionInfo> {
+        self.sessions.sessions.lock().expect("session lock poisoned").get(&self.id).cloned()
+    }
+
+    pub fn preferences(&self) -> Preferences {
+        self.info().map(|info| info.preferences).unwrap_or_default()
+    }
+
+    pub fn protocol_version(&self) -> Option<String> {
+        self.info().and_then(|info| info.protocol_version)
+    }
+
+    /// 初期化で取り決めたリビジョンを記録する
+    pub fn set_protocol_version(&self, version: &str) {
+        if let Some(info) = self.sessions.sessions.lock().expect("session lock poisoned").get_mut(&self.id) {
+            info.protocol_version = Some(version.to_string());
+        }
+    }
+
+    /// 設定を変更し、変更後のセッションの情報を返す
+    pub fn update(&self, change: impl FnOnce(&mut Preferences)) -> Option<SessionInfo> {
+        let mut sessions = self.sessions.sessions.lock().expect("session lock poisoned");
+        let info = sessions.get_mut(&self.id)?;
+        change(&mut info.preferences);
+        Some(info.clone())
+    }
+}
+
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        self.sessions.close(self.id);
+    }
+}
@@ -0,0 +1,87 @@
+//! 終了シグナルの待ち受けと、実行中のツール呼び出しの排出
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// 実行中のツール呼び出しを数え、終了時に新しい呼び出しを断る
+///
+/// 全セッションで共有し、どの接続からの呼び出しも排出の対象にする。
+#[derive(Debug, Default)]
+pub struct Drain {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+/// 実行中の呼び出し1件分。破棄されると実行中の件数から外れる
+#[derive(Debug)]
+pub struct InFlightGuard(Arc<Drain>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.in_flight.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl Drain {
+    /// 呼び出しの開始を記録する（排出中なら `None`）
+    pub fn begin(self: &Arc<Self>) -> Option<InFlightGuard> {
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        if self.draining.load(Ordering::Acquire) {
+            // 数えてしまった分を戻して、待っている排出処理を起こす
+            drop(InFlightGuard(self.clone()));
+            return None;
+        }
+        Some(InFlightGuard(self.clone()))
+    }
+
+    /// 新しい呼び出しを断り、実行中の呼び出しが終わるのを最大 `timeout` まで待つ
+    ///
+    /// 時間内に全て終わった場合は `true` を返す。
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.draining.store(true, Ordering::Release);
+        let idle = async {
+            loop {
+                let notified = self.idle.notified();
+                if self.in_flight.load(Ordering::Acquire) == 0 {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, idle).await.is_ok()
+    }
+
+    /// 終了処理に入り、新しい呼び出しを断っているか
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// まだ終わっていない呼び出しの数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+}
+
+/// SIGINT（Ctrl+C）または SIGTERM を受け取るまで待つ
+pub async fn signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await
+    }
+}
@@ -0,0 +1,213 @@
+//! カタログ全体のスナップショットの保存と復元
+//!
+//! スナップショットは `snapshot_dir` の下に、作成時刻を名前にしたJSONファイルとして保存する。
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::model::{Author, Book, ReadingList, Review};
+use crate::store::BookStore;
+
+/// スナップショットのファイルの拡張子
+const EXTENSION: &str = "json";
+
+/// ファイルに書き出すスナップショットの中身
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    created_at: DateTime<Utc>,
+    books: Vec<Book>,
+    reviews: Vec<Review>,
+    #[serde(default)]
+    authors: Vec<Author>,
+    #[serde(default)]
+    reading_lists: Vec<ReadingList>,
+}
+
+/// 作成または復元したスナップショットの概要
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotInfo {
+    /// `restore_snapshot` に渡す名前（拡張子を除いたファイル名）
+    pub name: String,
+    pub path: PathBuf,
+    pub created_at: DateTime<Utc>,
+    pub books: usize,
+    pub reviews: usize,
+}
+
+/// 復元によってカタログに起きた変更
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RestoreReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// スナップショットの名前の問題（ツールの入力エラーとして返す）
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// 英数字・`-`・`_` 以外を含む名前
+    InvalidName(String),
+    /// 指定した名前のスナップショットがない
+    NotFound { name: String, available: Vec<String> },
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidName(name) => write!(f, "スナップショット名に使えない文字が含まれています: {}", name),
+            Self::NotFound { name, .. } => write!(f, "スナップショット {} が見つかりません", name),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// ディレクトリの外を指せないように、名前を英数字・`-`・`_` に限る
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn path_of(dir: &Path, name: &str) -> PathBuf {
+    dir.join(name).with_extension(EXTENSION)
+}
+
+/// 保存されているスナップショットの名前を古い順に返す
+pub fn list(dir: &Path) -> Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("failed to read snapshot directory {}", dir.display()))? {
+        let path = entry?.path();
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|name| is_valid_name(name) && path.extension().is_some_and(|extension| extension == EXTENSION));
+        if let Some(name) = name {
+            names.push(name.to_string());
+        }
+    }
+    // 名前は作成時刻で始まるので、文字列の順がそのまま作成順になる
+    names.sort();
+    Ok(names)
+}
+
+/// 現在のカタログ（本とレビュー）を `dir` に書き出す
+///
+/// 名前は作成時刻（UTC）で、`label` を指定した場合はその後ろに付ける。
+pub async fn create(store: &dyn BookStore, dir: &Path, label: Option<&str>) -> Result<SnapshotInfo> {
+    if let Some(label) = label.filter(|label| !is_valid_name(label)) {
+        return Err(SnapshotError::InvalidName(label.to_string()).into());
+    }
+    let books = store.all().await?;
+    let mut reviews = Vec::new();
+    for book in &books {
+        reviews.extend(store.reviews(&book.isbn).await?);
+    }
+    let created_at = Utc::now();
+    let stamp = created_at.format("%Y%m%dT%H%M%S%3fZ");
+    let name = match label {
+        Some(label) => format!("{}-{}", stamp, label),
+        None => stamp.to_string(),
+    };
+
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create snapshot directory {}", dir.display()))?;
+    let path = path_of(dir, &name);
+    let snapshot = Snapshot {
+        created_at,
+        books,
+        reviews,
+        authors: store.authors().await?,
+        reading_lists: store.reading_lists().await?,
+    };
+    // 書き込み途中のファイルを復元しないように、一時ファイルに書いてから置き換える
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, serde_json::to_vec_pretty(&snapshot)?)
+        .with_context(|| format!("failed to write snapshot {}", partial.display()))?;
+    std::fs::rename(&partial, &path).with_context(|| format!("failed to write snapshot {}", path.display()))?;
+    tracing::info!("Created snapshot {} ({} books)", name, snapshot.books.len());
+
+    Ok(SnapshotInfo {
+        name,
+        path,
+        created_at,
+        books: snapshot.books.len(),
+        reviews: snapshot.reviews.len(),
+    })
+}
+
+/// `dir` のスナップショット `name` でカタログを置き換える
+///
+/// スナップショットにない本とレビューは削除される。残る本の版は現在の版より進めるので、
+/// 復元前に読み込んだ版を指定した更新は衝突として拒否される。
+pub async fn restore(store: &dyn BookStore, dir: &Path, name: &str) -> Result<(SnapshotInfo, RestoreReport)> {
+    let name = name.strip_suffix(".json").unwrap_or(name);
+    if !is_valid_name(name) {
+        return Err(SnapshotError::InvalidName(name.to_string()).into());
+    }
+    let path = path_of(dir, name);
+    if !path.exists() {
+        let available = list(dir)?;
+        return Err(SnapshotError::NotFound {
+            name: name.to_string(),
+            available,
+        }
+        .into());
+    }
+    let text = std::fs::read_to_string(&path).with_context(|| format!("failed to read snapshot {}", path.display()))?;
+    let snapshot: Snapshot =
+        serde_json::from_str(&text).with_context(|| format!("failed to parse snapshot {}", path.display()))?;
+
+    let mut report = RestoreReport::default();
+    let restored: HashSet<&str> = snapshot.books.iter().map(|book| book.isbn.as_str()).collect();
+    let mut previous_versions = HashMap::new();
+    // レビューは本ごとにしか消せないので、いったん全ての本を削除してから書き戻す
+    for book in store.all().await? {
+        if restored.contains(book.isbn.as_str()) {
+            previous_versions.insert(book.isbn.clone(), book.version);
+        } else {
+            report.removed.push(book.isbn.clone());
+        }
+        store.remove(&book.isbn, None).await?;
+    }
+    for mut book in snapshot.books.iter().cloned() {
+        match previous_versions.get(&book.isbn) {
+            Some(previous) => {
+                book.version = book.version.max(previous + 1);
+                report.updated.push(book.isbn.clone());
+            }
+            None => report.added.push(book.isbn.clone()),
+        }
+        store.put(book).await?;
+    }
+    for review in snapshot.reviews.iter().cloned() {
+        store.add_review(review).await?;
+    }
+    // 著者の記録と読書リストは消せないので、スナップショットにあるものだけを書き戻す
+    for author in snapshot.authors.iter().cloned() {
+        store.put_author(author).await?;
+    }
+    for list in snapshot.reading_lists.iter().cloned() {
+        store.put_reading_list(list).await?;
+    }
+    store.flush().await?;
+    tracing::info!(
+        "Restored snapshot {} ({} added, {} updated, {} removed)",
+        name,
+        report.added.len(),
+        report.updated.len(),
+        report.removed.len()
+    );
+
+    let info = SnapshotInfo {
+        name: name.to_string(),
+        path,
+        created_at: snapshot.created_at,
+        books: snapshot.books.len(),
+        reviews: snapshot.reviews.len(),
+    };
+    Ok((info, report))
+}
@@ -0,0 +1,305 @@
+//! 本の保存先を抽象化するストレージ層
+//!
+//! `BookStore` は非同期のトレイトで、ネットワーク越しのデータベース（Postgres）も同じように扱える。
+//! メモリ上・JSONファイル・SQLiteのストアは待たずに終わるので、中で待つことはない。
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use crate::config::{DatabaseConfig, RetryConfig};
+use crate::model::{Author, Book, ReadingList, Review, fake_books};
+
+mod file;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod retry;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use file::{FileChanges, JsonFileStore};
+
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStore;
+#[cfg(feature = "sqlite")]
+pub use retry::RetryStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+/// 版を確かめながら本を書き換えた結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionCheck {
+    /// 書き換えた（更新後の版。削除では削除した時点の版）
+    Applied(u64),
+    /// 指定したISBNの本がない
+    NotFound,
+    /// 別の呼び出しが先に書き換えていた（現在の版）
+    Conflict(u64),
+}
+
+/// 本の読み込み・保存・取得を行うストレージ
+#[async_trait]
+pub trait BookStore: Send + Sync {
+    /// 全ての本を登録順に返す
+    async fn all(&self) -> Result<Vec<Book>>;
+
+    /// ISBNで本を1冊取得する
+    async fn get(&self, isbn: &str) -> Result<Option<Book>>;
+
+    /// 本を保存する（同じISBNの本があれば置き換える）
+    async fn put(&self, book: Book) -> Result<()>;
+
+    /// 現在の版が `expected` のときだけ本を置き換え、版を1つ進める
+    ///
+    /// 版の確認と置き換えを不可分に行うため、同じ版をもとにした更新は片方だけが成功する。
+    async fn update(&self, book: Book, expected: u64) -> Result<VersionCheck>;
+
+    /// 同じISBNの本がなければ追加する（追加した場合は `true`）
+    ///
+    /// 存在の確認と追加を不可分に行うため、同時に同じ本を追加しようとしても片方だけが成功する。
+    async fn insert(&self, book: Book) -> Result<bool>;
+
+    /// ISBNで本を削除する。本に付いたレビューも削除する
+    ///
+    /// `expected` を指定した場合は、現在の版が一致するときだけ削除する。
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> Result<VersionCheck>;
+
+    /// 本にレビューを追加する
+    async fn add_review(&self, review: Review) -> Result<()>;
+
+    /// ISBNの本に付いたレビューを投稿順に返す
+    async fn reviews(&self, isbn: &str) -> Result<Vec<Review>>;
+
+    /// 著者の記録を登録順に返す
+    async fn authors(&self) -> Result<Vec<Author>>;
+
+    /// 著者の記録を保存する（同じIDの記録があれば置き換える）
+    async fn put_author(&self, author: Author) -> Result<()>;
+
+    /// 読書リストを作成順に返す
+    async fn reading_lists(&self) -> Result<Vec<ReadingList>>;
+
+    /// 読書リストを保存する（同じ名前のリストがあれば置き換える）
+    async fn put_reading_list(&self, list: ReadingList) -> Result<()>;
+
+    /// 名前で読書リストを1つ取得する
+    async fn reading_list(&self, name: &str) -> Result<Option<ReadingList>> {
+        Ok(self.reading_lists().await?.into_iter().find(|list| list.name == name))
+    }
+
+    /// まだ書き出していない変更を保存先へ書き出す（終了前に呼ばれる）
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// プロセスのメモリ上に本を保持するストア（再起動すると初期状態に戻る）
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    books: RwLock<Vec<Book>>,
+    reviews: RwLock<Vec<Review>>,
+    authors: RwLock<Vec<Author>>,
+    reading_lists: RwLock<Vec<ReadingList>>,
+}
+
+impl MemoryStore {
+    pub fn new(books: Vec<Book>) -> Self {
+        Self {
+            books: RwLock::new(books),
+            reviews: RwLock::default(),
+            authors: RwLock::default(),
+            reading_lists: RwLock::default(),
+        }
+    }
+
+    /// 全ての本を返す（JSONファイルのストアが、書き出しのロックを持ったまま待たずに読むのに使う）
+    fn all_books(&self) -> Vec<Book> {
+        self.books.read().expect("book store lock poisoned").clone()
+    }
+
+    /// 全ての本のレビューを投稿順に返す
+    fn all_reviews(&self) -> Vec<Review> {
+        self.reviews.read().expect("review store lock poisoned").clone()
+    }
+
+    fn all_authors(&self) -> Vec<Author> {
+        self.authors.read().expect("author store lock poisoned").clone()
+    }
+
+    fn all_reading_lists(&self) -> Vec<ReadingList> {
+        self.reading_lists.read().expect("reading list store lock poisoned").clone()
+    }
+
+    /// 本とレビューと著者の記録と読書リストを丸ごと置き換える
+    fn replace(&self, books: Vec<Book>, reviews: Vec<Review>, authors: Vec<Author>, reading_lists: Vec<ReadingList>) {
+        *self.books.write().expect("book store lock poisoned") = books;
+        *self.reviews.write().expect("review store lock poisoned") = reviews;
+        *self.authors.write().expect("author store lock poisoned") = authors;
+        *self.reading_lists.write().expect("reading list store lock poisoned") = reading_lists;
+    }
+}
+
+#[async_trait]
+impl BookStore for MemoryStore {
+    async fn all(&self) -> Result<Vec<Book>> {
+        Ok(self.all_books())
+    }
+
+    async fn get(&self, isbn: &str) -> Result<Option<Book>> {
+        let books = self.books.read().expect("book store lock poisoned");
+        Ok(books.iter().find(|book| book.isbn == isbn).cloned())
+    }
+
+    async fn put(&self, book: Book) -> Result<()> {
+        let mut books = self.books.write().expect("book store lock poisoned");
+        match books.iter_mut().find(|existing| existing.isbn == book.isbn) {
+            Some(existing) => *existing = book,
+            None => books.push(book),
+        }
+        Ok(())
+    }
+
+    async fn update(&self, mut book: Book, expected: u64) -> Result<VersionCheck> {
+        let mut books = self.books.write().expect("book store lock poisoned");
+        let Some(existing) = books.iter_mut().find(|existing| existing.isbn == book.isbn) else {
+            return Ok(VersionCheck::NotFound);
+        };
+        if existing.version != expected {
+            return Ok(VersionCheck::Conflict(existing.version));
+        }
+        book.version = expected + 1;
+        *existing = book;
+        Ok(VersionCheck::Applied(expected + 1))
+    }
+
+    async fn insert(&self, book: Book) -> Result<bool> {
+        let mut books = self.books.write().expect("book store lock poisoned");
+        if books.iter().any(|existing| existing.isbn == book.isbn) {
+            return Ok(false);
+        }
+        books.push(book);
+        Ok(true)
+    }
+
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> Result<VersionCheck> {
+        let mut books = self.books.write().expect("book store lock poisoned");
+        let Some(position) = books.iter().position(|book| book.isbn == isbn) else {
+            return Ok(VersionCheck::NotFound);
+        };
+        let current = books[position].version;
+        if expected.is_some_and(|expected| expected != current) {
+            return Ok(VersionCheck::Conflict(current));
+        }
+        books.remove(position);
+        let mut reviews = self.reviews.write().expect("review store lock poisoned");
+        reviews.retain(|review| review.isbn != isbn);
+        Ok(VersionCheck::Applied(current))
+    }
+
+    async fn add_review(&self, review: Review) -> Result<()> {
+        self.reviews.write().expect("review store lock poisoned").push(review);
+        Ok(())
+    }
+
+    async fn reviews(&self, isbn: &str) -> Result<Vec<Review>> {
+        let reviews = self.reviews.read().expect("review store lock poisoned");
+        Ok(reviews.iter().filter(|review| review.isbn == isbn).cloned().collect())
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>> {
+        Ok(self.all_authors())
+    }
+
+    async fn put_author(&self, author: Author) -> Result<()> {
+        let mut authors = self.authors.write().expect("author store lock poisoned");
+        match authors.iter_mut().find(|existing| existing.id == author.id) {
+            Some(existing) => *existing = author,
+            None => authors.push(author),
+        }
+        Ok(())
+    }
+
+    async fn reading_lists(&self) -> Result<Vec<ReadingList>> {
+        Ok(self.all_reading_lists())
+    }
+
+    async fn put_reading_list(&self, list: ReadingList) -> Result<()> {
+        let mut lists = self.reading_lists.write().expect("reading list store lock poisoned");
+        match lists.iter_mut().find(|existing| existing.name == list.name) {
+            Some(existing) => *existing = list,
+            None => lists.push(list),
+        }
+        Ok(())
+    }
+}
+
+/// 拡張子が `.json` のデータファイルか（SQLiteではなくJSONファイルのストアで開く）
+pub fn is_json_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// JSONファイルのストアを開き、新しく作成した場合は架空の本を入れておく
+pub async fn open_json(path: &Path) -> Result<Arc<JsonFileStore>> {
+    let store = JsonFileStore::open(path)?;
+    if store.was_created() {
+        tracing::info!("Seeding new data file {} with sample books", path.display());
+        for book in fake_books() {
+            store.put(book).await?;
+        }
+    }
+    Ok(Arc::new(store))
+}
+
+/// データファイルが指定されていればSQLite（`.json` の場合はJSONファイル）のストアを、
+/// なければメモリ上のストアを開く
+///
+/// SQLiteのストアは、ビジー・ロックのエラーで失敗した操作を `retry` の設定で繰り返す。
+pub async fn open(path: Option<&Path>, retry: RetryConfig) -> Result<Arc<dyn BookStore>> {
+    let Some(path) = path else {
+        return Ok(Arc::new(MemoryStore::new(fake_books())));
+    };
+    if is_json_file(path) {
+        return Ok(open_json(path).await?);
+    }
+
+    #[cfg(feature = "sqlite")]
+    {
+        let sqlite = SqliteStore::open(path)?;
+        let created = sqlite.was_created();
+        let store = RetryStore::new(sqlite, retry);
+        if created {
+            tracing::info!("Seeding new database {} with sample books", path.display());
+            for book in fake_books() {
+                store.put(book).await?;
+            }
+        }
+        Ok(Arc::new(store))
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    {
+        let _ = retry;
+        anyhow::bail!(
+            "database path {} was given but this binary was built without the `sqlite` feature",
+            path.display()
+        )
+    }
+}
+
+/// `database.url` のPostgresに接続し、テーブルを最新の形にしてから返す
+///
+/// 既存の図書館のデータベースにつなぐことを想定し、本が1冊もなくても架空の本は入れない。
+pub async fn connect(config: &DatabaseConfig) -> Result<Arc<dyn BookStore>> {
+    #[cfg(feature = "postgres")]
+    {
+        Ok(Arc::new(PostgresStore::connect(&config.url, config.max_connections).await?))
+    }
+
+    #[cfg(not(feature = "postgres"))]
+    {
+        let _ = config;
+        anyhow::bail!("database.url was given but this binary was built without the `postgres` feature")
+    }
+}
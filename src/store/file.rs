@@ -0,0 +1,210 @@
+//! 1つのJSONファイルに本とレビューと著者の記録と読書リストを保存するストア
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::{BookStore, MemoryStore, VersionCheck};
+use crate::model::{Author, Book, ReadingList, Review};
+
+/// ファイルに書き出す内容
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Contents {
+    books: Vec<Book>,
+    #[serde(default)]
+    reviews: Vec<Review>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<Author>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    reading_lists: Vec<ReadingList>,
+}
+
+/// メモリ上に本を保持し、変更のたびにファイル全体を書き直すストア
+pub struct JsonFileStore {
+    path: PathBuf,
+    inner: MemoryStore,
+    /// 書き込みが前後しないように、ファイルへの書き出しと読み直しを1つずつ行う
+    write: Mutex<()>,
+    created: bool,
+}
+
+/// ファイルを読み直したときに変わった本のISBN
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileChanges {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl FileChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+fn read_contents(path: &Path) -> Result<Contents> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+impl JsonFileStore {
+    /// ファイルを開く（存在しなければ空のカタログとして作成する）
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let created = !path.exists();
+        let contents = if created { Contents::default() } else { read_contents(&path)? };
+        let inner = MemoryStore::default();
+        inner.replace(contents.books, contents.reviews, contents.authors, contents.reading_lists);
+        let store = Self {
+            path,
+            inner,
+            write: Mutex::new(()),
+            created,
+        };
+        store.save()?;
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `open` の呼び出しでファイルを新規作成したかどうか
+    pub fn was_created(&self) -> bool {
+        self.created
+    }
+
+    /// 他のプロセスやエディタが書き換えたファイルを読み直し、変わった本を返す
+    ///
+    /// 自身の書き込みによる変更通知で読み直した場合は、内容が同じなので何も変わらない。
+    /// ファイルを解釈できない場合はエラーを返し、読み直す前の内容を保つ。
+    pub fn reload(&self) -> Result<FileChanges> {
+        let _guard = self.write.lock().expect("file store lock poisoned");
+        let contents = read_contents(&self.path)?;
+        let current = self.inner.all_books();
+
+        let mut changes = FileChanges::default();
+        for book in &contents.books {
+            match current.iter().find(|existing| existing.isbn == book.isbn) {
+                None => changes.added.push(book.isbn.clone()),
+                Some(existing) if serde_json::to_value(existing)? != serde_json::to_value(book)? => {
+                    changes.updated.push(book.isbn.clone());
+                }
+                Some(_) => {}
+            }
+        }
+        for book in &current {
+            if !contents.books.iter().any(|loaded| loaded.isbn == book.isbn) {
+                changes.removed.push(book.isbn.clone());
+            }
+        }
+        let reviews_changed = serde_json::to_value(&contents.reviews)? != serde_json::to_value(self.inner.all_reviews())?;
+        let authors_changed = contents.authors != self.inner.all_authors();
+        let lists_changed = contents.reading_lists != self.inner.all_reading_lists();
+        if changes.is_empty() && !reviews_changed && !authors_changed && !lists_changed {
+            return Ok(changes);
+        }
+
+        // レビューだけが変わった本も、その本のリソースの内容が変わったものとして扱う
+        if reviews_changed {
+            for review in &contents.reviews {
+                let known = changes.added.contains(&review.isbn) || changes.updated.contains(&review.isbn);
+                if !known && current.iter().any(|book| book.isbn == review.isbn) {
+                    changes.updated.push(review.isbn.clone());
+                }
+            }
+        }
+        self.inner
+            .replace(contents.books, contents.reviews, contents.authors, contents.reading_lists);
+        Ok(changes)
+    }
+
+    fn save(&self) -> Result<()> {
+        let _guard = self.write.lock().expect("file store lock poisoned");
+        let contents = Contents {
+            books: self.inner.all_books(),
+            reviews: self.inner.all_reviews(),
+            authors: self.inner.all_authors(),
+            reading_lists: self.inner.all_reading_lists(),
+        };
+        // 書き込み途中で止まっても元のファイルが壊れないように、一時ファイルから置き換える
+        let partial = self.path.with_extension("partial");
+        std::fs::write(&partial, serde_json::to_vec_pretty(&contents)?)
+            .with_context(|| format!("failed to write {}", partial.display()))?;
+        std::fs::rename(&partial, &self.path).with_context(|| format!("failed to write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BookStore for JsonFileStore {
+    async fn all(&self) -> Result<Vec<Book>> {
+        self.inner.all().await
+    }
+
+    async fn get(&self, isbn: &str) -> Result<Option<Book>> {
+        self.inner.get(isbn).await
+    }
+
+    async fn put(&self, book: Book) -> Result<()> {
+        self.inner.put(book).await?;
+        self.save()
+    }
+
+    async fn update(&self, book: Book, expected: u64) -> Result<VersionCheck> {
+        let result = self.inner.update(book, expected).await?;
+        if matches!(result, VersionCheck::Applied(_)) {
+            self.save()?;
+        }
+        Ok(result)
+    }
+
+    async fn insert(&self, book: Book) -> Result<bool> {
+        let inserted = self.inner.insert(book).await?;
+        if inserted {
+            self.save()?;
+        }
+        Ok(inserted)
+    }
+
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> Result<VersionCheck> {
+        let result = self.inner.remove(isbn, expected).await?;
+        if matches!(result, VersionCheck::Applied(_)) {
+            self.save()?;
+        }
+        Ok(result)
+    }
+
+    async fn add_review(&self, review: Review) -> Result<()> {
+        self.inner.add_review(review).await?;
+        self.save()
+    }
+
+    async fn reviews(&self, isbn: &str) -> Result<Vec<Review>> {
+        self.inner.reviews(isbn).await
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>> {
+        self.inner.authors().await
+    }
+
+    async fn put_author(&self, author: Author) -> Result<()> {
+        self.inner.put_author(author).await?;
+        self.save()
+    }
+
+    async fn reading_lists(&self) -> Result<Vec<ReadingList>> {
+        self.inner.reading_lists().await
+    }
+
+    async fn put_reading_list(&self, list: ReadingList) -> Result<()> {
+        self.inner.put_reading_list(list).await?;
+        self.save()
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.save()
+    }
+}
@@ -0,0 +1,275 @@
+//! Postgresのデータベースに本を永続化するストア
+//!
+//! 接続はプールして使い回す。テーブルは `migrations/postgres` のマイグレーションで作り、
+//! 接続したときにまだ適用していないものを適用する。
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use sqlx::postgres::{PgArguments, PgPool, PgPoolOptions, PgRow};
+use sqlx::query::Query;
+use sqlx::{Postgres, Row, Transaction};
+
+use super::{BookStore, VersionCheck};
+use crate::model::{Author, Book, ReadingList, Review, Series};
+
+type PgQuery<'q> = Query<'q, Postgres, PgArguments>;
+
+/// 本を読むときの列（`row_to_book` が読む）
+const BOOK_COLUMNS: &str = "isbn, title, author, author_id, year, description, tags, series, series_volume, version, \
+                            cover_path, cover_url, deleted_at";
+
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    /// `url` のデータベースに最大 `max_connections` 本の接続を張り、テーブルを最新の形にする
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self> {
+        // URLにはパスワードが入っていることがあるので、エラーに含めない
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await
+            .context("failed to connect to Postgres")?;
+        sqlx::migrate!("./migrations/postgres")
+            .run(&pool)
+            .await
+            .context("failed to migrate the Postgres database")?;
+        Ok(Self { pool })
+    }
+}
+
+/// 本の現在の版を、トランザクションが終わるまで他から書き換えられないようにして取得する（本がなければ `None`）
+async fn current_version(tx: &mut Transaction<'_, Postgres>, isbn: &str) -> Result<Option<u64>> {
+    let version: Option<i64> = sqlx::query_scalar("SELECT version FROM books WHERE isbn = $1 FOR UPDATE")
+        .bind(isbn)
+        .fetch_optional(&mut **tx)
+        .await?;
+    Ok(version.map(u64::try_from).transpose()?)
+}
+
+/// `$1` から `$13` に本の列を `BOOK_COLUMNS` の順で割り当てる（版は `version` にする）
+fn bind_book<'q>(query: PgQuery<'q>, book: &'q Book, version: u64) -> Result<PgQuery<'q>> {
+    let volume = book.series.as_ref().and_then(|series| series.volume).map(i32::try_from).transpose()?;
+    Ok(query
+        .bind(&book.isbn)
+        .bind(&book.title)
+        .bind(&book.author)
+        .bind(&book.author_id)
+        .bind(book.year)
+        .bind(&book.description)
+        .bind(&book.tags)
+        .bind(book.series.as_ref().map(|series| series.name.as_str()))
+        .bind(volume)
+        .bind(i64::try_from(version)?)
+        .bind(&book.cover_path)
+        .bind(&book.cover_url)
+        .bind(book.deleted_at))
+}
+
+fn row_to_book(row: &PgRow) -> Result<Book> {
+    let series = match row.try_get::<Option<String>, _>("series")? {
+        Some(name) => Some(Series {
+            name,
+            volume: row.try_get::<Option<i32>, _>("series_volume")?.map(u32::try_from).transpose()?,
+        }),
+        None => None,
+    };
+    Ok(Book {
+        isbn: row.try_get("isbn")?,
+        title: row.try_get("title")?,
+        author: row.try_get("author")?,
+        author_id: row.try_get("author_id")?,
+        year: row.try_get("year")?,
+        description: row.try_get("description")?,
+        tags: row.try_get("tags")?,
+        series,
+        version: u64::try_from(row.try_get::<i64, _>("version")?)?,
+        cover_path: row.try_get("cover_path")?,
+        cover_url: row.try_get("cover_url")?,
+        deleted_at: row.try_get("deleted_at")?,
+    })
+}
+
+fn row_to_review(row: &PgRow) -> Result<Review> {
+    Ok(Review {
+        isbn: row.try_get("isbn")?,
+        rating: u8::try_from(row.try_get::<i16, _>("rating")?)?,
+        text: row.try_get("text")?,
+        reviewer: row.try_get("reviewer")?,
+    })
+}
+
+fn row_to_reading_list(row: &PgRow) -> Result<ReadingList> {
+    Ok(ReadingList {
+        name: row.try_get("name")?,
+        description: row.try_get("description")?,
+        isbns: row.try_get("isbns")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+#[async_trait]
+impl BookStore for PostgresStore {
+    async fn all(&self) -> Result<Vec<Book>> {
+        let rows = sqlx::query(&format!("SELECT {} FROM books ORDER BY position", BOOK_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(row_to_book).collect()
+    }
+
+    async fn get(&self, isbn: &str) -> Result<Option<Book>> {
+        let row = sqlx::query(&format!("SELECT {} FROM books WHERE isbn = $1", BOOK_COLUMNS))
+            .bind(isbn)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(row_to_book).transpose()
+    }
+
+    async fn put(&self, book: Book) -> Result<()> {
+        let query = sqlx::query(
+            "INSERT INTO books (isbn, title, author, author_id, year, description, tags, series, series_volume, version,
+                cover_path, cover_url, deleted_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+             ON CONFLICT (isbn) DO UPDATE SET
+                title = EXCLUDED.title,
+                author = EXCLUDED.author,
+                author_id = EXCLUDED.author_id,
+                year = EXCLUDED.year,
+                description = EXCLUDED.description,
+                tags = EXCLUDED.tags,
+                series = EXCLUDED.series,
+                series_volume = EXCLUDED.series_volume,
+                version = EXCLUDED.version,
+                cover_path = EXCLUDED.cover_path,
+                cover_url = EXCLUDED.cover_url,
+                deleted_at = EXCLUDED.deleted_at",
+        );
+        bind_book(query, &book, book.version)?.execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn update(&self, book: Book, expected: u64) -> Result<VersionCheck> {
+        let mut tx = self.pool.begin().await?;
+        match current_version(&mut tx, &book.isbn).await? {
+            None => return Ok(VersionCheck::NotFound),
+            Some(current) if current != expected => return Ok(VersionCheck::Conflict(current)),
+            Some(_) => {}
+        }
+        let query = sqlx::query(
+            "UPDATE books SET title = $2, author = $3, author_id = $4, year = $5, description = $6, tags = $7,
+                series = $8, series_volume = $9, version = $10, cover_path = $11, cover_url = $12, deleted_at = $13
+             WHERE isbn = $1",
+        );
+        bind_book(query, &book, expected + 1)?.execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(VersionCheck::Applied(expected + 1))
+    }
+
+    async fn insert(&self, book: Book) -> Result<bool> {
+        let query = sqlx::query(
+            "INSERT INTO books (isbn, title, author, author_id, year, description, tags, series, series_volume, version,
+                cover_path, cover_url, deleted_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+             ON CONFLICT (isbn) DO NOTHING",
+        );
+        let inserted = bind_book(query, &book, book.version)?.execute(&self.pool).await?;
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> Result<VersionCheck> {
+        let mut tx = self.pool.begin().await?;
+        let current = match current_version(&mut tx, isbn).await? {
+            None => return Ok(VersionCheck::NotFound),
+            Some(current) if expected.is_some_and(|expected| expected != current) => {
+                return Ok(VersionCheck::Conflict(current));
+            }
+            Some(current) => current,
+        };
+        sqlx::query("DELETE FROM books WHERE isbn = $1").bind(isbn).execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM reviews WHERE isbn = $1").bind(isbn).execute(&mut *tx).await?;
+        tx.commit().await?;
+        Ok(VersionCheck::Applied(current))
+    }
+
+    async fn add_review(&self, review: Review) -> Result<()> {
+        sqlx::query("INSERT INTO reviews (isbn, rating, text, reviewer) VALUES ($1, $2, $3, $4)")
+            .bind(&review.isbn)
+            .bind(i16::from(review.rating))
+            .bind(&review.text)
+            .bind(&review.reviewer)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn reviews(&self, isbn: &str) -> Result<Vec<Review>> {
+        let rows = sqlx::query("SELECT isbn, rating, text, reviewer FROM reviews WHERE isbn = $1 ORDER BY id")
+            .bind(isbn)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(row_to_review).collect()
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>> {
+        let rows = sqlx::query("SELECT id, name, bio, aliases FROM authors ORDER BY position")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|row| {
+                Ok(Author {
+                    id: row.try_get("id")?,
+                    name: row.try_get("name")?,
+                    bio: row.try_get("bio")?,
+                    aliases: row.try_get("aliases")?,
+                })
+            })
+            .collect()
+    }
+
+    async fn put_author(&self, author: Author) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO authors (id, name, bio, aliases) VALUES ($1, $2, $3, $4)
+             ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name, bio = EXCLUDED.bio, aliases = EXCLUDED.aliases",
+        )
+        .bind(&author.id)
+        .bind(&author.name)
+        .bind(&author.bio)
+        .bind(&author.aliases)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn reading_lists(&self) -> Result<Vec<ReadingList>> {
+        let rows = sqlx::query("SELECT name, description, isbns, created_at, updated_at FROM reading_lists ORDER BY position")
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter().map(row_to_reading_list).collect()
+    }
+
+    async fn reading_list(&self, name: &str) -> Result<Option<ReadingList>> {
+        let row = sqlx::query("SELECT name, description, isbns, created_at, updated_at FROM reading_lists WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        row.as_ref().map(row_to_reading_list).transpose()
+    }
+
+    async fn put_reading_list(&self, list: ReadingList) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO reading_lists (name, description, isbns, created_at, updated_at) VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (name) DO UPDATE SET description = EXCLUDED.description, isbns = EXCLUDED.isbns,
+                updated_at = EXCLUDED.updated_at",
+        )
+        .bind(&list.name)
+        .bind(&list.description)
+        .bind(&list.isbns)
+        .bind(list.created_at)
+        .bind(list.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+//! ビジー・ロックのエラーで失敗したSQLiteの操作を、間隔を空けて繰り返すストア
+//!
+//! 同じデータベースファイルを別のプロセスが書き込み中だと `SQLITE_BUSY` や `SQLITE_LOCKED` で失敗する。
+//! これらは少し待てば成功するので、上限の回数まで待ち時間を倍にしながら繰り返す。
+//! それ以外のエラーはそのまま返す。
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rusqlite::ErrorCode;
+use std::future::Future;
+use std::time::Duration;
+
+use super::{BookStore, VersionCheck};
+use crate::config::RetryConfig;
+use crate::model::{Author, Book, ReadingList, Review};
+
+/// `inner` の操作を、一時的なエラーのときだけ繰り返すストア
+pub struct RetryStore<S> {
+    inner: S,
+    config: RetryConfig,
+}
+
+impl<S: BookStore> RetryStore<S> {
+    pub fn new(inner: S, config: RetryConfig) -> Self {
+        Self { inner, config }
+    }
+
+    /// `operation` を、一時的なエラーで失敗する限り `max_retries` 回まで繰り返す
+    async fn retry<T, F, Fut>(&self, name: &str, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut backoff = Duration::from_millis(self.config.initial_backoff_ms);
+        let max_backoff = Duration::from_millis(self.config.max_backoff_ms);
+        let mut retries = 0;
+        loop {
+            match operation().await {
+                Err(e) if retries < self.config.max_retries && is_transient(&e) => {
+                    retries += 1;
+                    tracing::warn!(
+                        "Store {} failed ({:#}), retrying in {:?} ({}/{})",
+                        name,
+                        e,
+                        backoff,
+                        retries,
+                        self.config.max_retries
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// 待てば成功するエラー（データベースがビジー、またはロックされている）か
+fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<rusqlite::Error>(),
+            Some(rusqlite::Error::SqliteFailure(failure, _))
+                if matches!(failure.code, ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+        )
+    })
+}
+
+#[async_trait]
+impl<S: BookStore> BookStore for RetryStore<S> {
+    async fn all(&self) -> Result<Vec<Book>> {
+        self.retry("all", || self.inner.all()).await
+    }
+
+    async fn get(&self, isbn: &str) -> Result<Option<Book>> {
+        self.retry("get", || self.inner.get(isbn)).await
+    }
+
+    async fn put(&self, book: Book) -> Result<()> {
+        self.retry("put", || self.inner.put(book.clone())).await
+    }
+
+    async fn update(&self, book: Book, expected: u64) -> Result<VersionCheck> {
+        self.retry("update", || self.inner.update(book.clone(), expected)).await
+    }
+
+    async fn insert(&self, book: Book) -> Result<bool> {
+        self.retry("insert", || self.inner.insert(book.clone())).await
+    }
+
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> Result<VersionCheck> {
+        self.retry("remove", || self.inner.remove(isbn, expected)).await
+    }
+
+    async fn add_review(&self, review: Review) -> Result<()> {
+        self.retry("add_review", || self.inner.add_review(review.clone())).await
+    }
+
+    async fn reviews(&self, isbn: &str) -> Result<Vec<Review>> {
+        self.retry("reviews", || self.inner.reviews(isbn)).await
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>> {
+        self.retry("authors", || self.inner.authors()).await
+    }
+
+    async fn put_author(&self, author: Author) -> Result<()> {
+        self.retry("put_author", || self.inner.put_author(author.clone())).await
+    }
+
+    async fn reading_lists(&self) -> Result<Vec<ReadingList>> {
+        self.retry("reading_lists", || self.inner.reading_lists()).await
+    }
+
+    async fn put_reading_list(&self, list: ReadingList) -> Result<()> {
+        self.retry("put_reading_list", || self.inner.put_reading_list(list.clone())).await
+    }
+
+    async fn reading_list(&self, name: &str) -> Result<Option<ReadingList>> {
+        self.retry("reading_list", || self.inner.reading_list(name)).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.retry("flush", || self.inner.flush()).await
+    }
+}
+
+#[cfg(test)]
+mod tests;
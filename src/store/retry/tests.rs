@@ -0,0 +1,124 @@
+//! 一時的なエラーだけを繰り返し、それ以外のエラーはすぐに返すことの確認
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use super::RetryStore;
+use crate::config::RetryConfig;
+use crate::model::{Author, Book, ReadingList, Review, fake_books};
+use crate::store::{BookStore, MemoryStore, VersionCheck};
+
+const RETRY: RetryConfig = RetryConfig {
+    max_retries: 3,
+    initial_backoff_ms: 1,
+    max_backoff_ms: 4,
+};
+
+/// 本の一覧の読み出しを、決まった回数だけ `error` で失敗させるストア
+struct FailingStore {
+    inner: MemoryStore,
+    failures: AtomicU32,
+    calls: AtomicU32,
+    error: fn() -> anyhow::Error,
+}
+
+impl FailingStore {
+    fn new(failures: u32, error: fn() -> anyhow::Error) -> Self {
+        Self {
+            inner: MemoryStore::new(fake_books()),
+            failures: AtomicU32::new(failures),
+            calls: AtomicU32::new(0),
+            error,
+        }
+    }
+}
+
+fn busy() -> anyhow::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY), None).into()
+}
+
+fn corrupt() -> anyhow::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT), None).into()
+}
+
+#[async_trait]
+impl BookStore for FailingStore {
+    async fn all(&self) -> Result<Vec<Book>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1))
+            .is_ok();
+        if failing {
+            return Err((self.error)());
+        }
+        self.inner.all().await
+    }
+
+    async fn get(&self, isbn: &str) -> Result<Option<Book>> {
+        self.inner.get(isbn).await
+    }
+
+    async fn put(&self, book: Book) -> Result<()> {
+        self.inner.put(book).await
+    }
+
+    async fn update(&self, book: Book, expected: u64) -> Result<VersionCheck> {
+        self.inner.update(book, expected).await
+    }
+
+    async fn insert(&self, book: Book) -> Result<bool> {
+        self.inner.insert(book).await
+    }
+
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> Result<VersionCheck> {
+        self.inner.remove(isbn, expected).await
+    }
+
+    async fn add_review(&self, review: Review) -> Result<()> {
+        self.inner.add_review(review).await
+    }
+
+    async fn reviews(&self, isbn: &str) -> Result<Vec<Review>> {
+        self.inner.reviews(isbn).await
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>> {
+        self.inner.authors().await
+    }
+
+    async fn put_author(&self, author: Author) -> Result<()> {
+        self.inner.put_author(author).await
+    }
+
+    async fn reading_lists(&self) -> Result<Vec<ReadingList>> {
+        self.inner.reading_lists().await
+    }
+
+    async fn put_reading_list(&self, list: ReadingList) -> Result<()> {
+        self.inner.put_reading_list(list).await
+    }
+}
+
+#[tokio::test]
+async fn busy_errors_are_retried_until_the_call_succeeds() {
+    let store = RetryStore::new(FailingStore::new(2, busy), RETRY);
+    let books = store.all().await.unwrap();
+    assert_eq!(books.len(), fake_books().len());
+    assert_eq!(store.inner.calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn busy_errors_beyond_the_retry_budget_are_returned() {
+    let store = RetryStore::new(FailingStore::new(5, busy), RETRY);
+    assert!(store.all().await.is_err());
+    assert_eq!(store.inner.calls.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn other_errors_are_returned_without_retrying() {
+    let store = RetryStore::new(FailingStore::new(1, corrupt), RETRY);
+    assert!(store.all().await.is_err());
+    assert_eq!(store.inner.calls.load(Ordering::SeqCst), 1);
+}
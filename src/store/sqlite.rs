@@ -0,0 +1,370 @@
+//! SQLiteファイルに本を永続化するストア
+
+use anyhow::Result;
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::{BookStore, VersionCheck};
+use crate::model::{Author, Book, ReadingList, Review, Series};
+
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    created: bool,
+}
+
+impl SqliteStore {
+    /// データベースファイルを開く（存在しなければテーブルごと作成する）
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'books')",
+            [],
+            |row| row.get(0),
+        )?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS books (
+                isbn TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                author TEXT NOT NULL,
+                year INTEGER NOT NULL,
+                description TEXT NOT NULL,
+                tags TEXT NOT NULL DEFAULT '[]',
+                version INTEGER NOT NULL DEFAULT 0,
+                cover_path TEXT,
+                cover_url TEXT,
+                deleted_at TEXT,
+                author_id TEXT,
+                series TEXT,
+                series_volume INTEGER
+            );
+            CREATE TABLE IF NOT EXISTS reviews (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                isbn TEXT NOT NULL,
+                rating INTEGER NOT NULL,
+                text TEXT NOT NULL,
+                reviewer TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS reviews_isbn ON reviews (isbn);
+            CREATE TABLE IF NOT EXISTS authors (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                bio TEXT NOT NULL DEFAULT '',
+                aliases TEXT NOT NULL DEFAULT '[]'
+            );
+            CREATE TABLE IF NOT EXISTS reading_lists (
+                name TEXT PRIMARY KEY,
+                description TEXT NOT NULL DEFAULT '',
+                isbns TEXT NOT NULL DEFAULT '[]',
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )?;
+        migrate_columns(&conn)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            created: !exists,
+        })
+    }
+
+    /// `open` の呼び出しでテーブルを新規作成したかどうか
+    pub fn was_created(&self) -> bool {
+        self.created
+    }
+}
+
+/// タグ・版・表紙画像・ごみ箱に移した日時・著者のID・シリーズの列のない古いデータベースに列を追加する
+fn migrate_columns(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('books')")?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    if !columns.iter().any(|column| column == "tags") {
+        conn.execute_batch("ALTER TABLE books ADD COLUMN tags TEXT NOT NULL DEFAULT '[]'")?;
+    }
+    if !columns.iter().any(|column| column == "version") {
+        conn.execute_batch("ALTER TABLE books ADD COLUMN version INTEGER NOT NULL DEFAULT 0")?;
+    }
+    for column in ["cover_path", "cover_url", "deleted_at", "author_id", "series"] {
+        if !columns.iter().any(|existing| existing == column) {
+            conn.execute_batch(&format!("ALTER TABLE books ADD COLUMN {} TEXT", column))?;
+        }
+    }
+    if !columns.iter().any(|column| column == "series_volume") {
+        conn.execute_batch("ALTER TABLE books ADD COLUMN series_volume INTEGER")?;
+    }
+    Ok(())
+}
+
+/// 本の現在の版を取得する（本がなければ `None`）
+fn current_version(conn: &Connection, isbn: &str) -> rusqlite::Result<Option<u64>> {
+    conn.query_row("SELECT version FROM books WHERE isbn = ?1", params![isbn], |row| row.get(0))
+        .optional()
+}
+
+fn row_to_book(row: &Row<'_>) -> rusqlite::Result<Book> {
+    let tags: String = row.get("tags")?;
+    let tags = serde_json::from_str(&tags).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    let series = match row.get::<_, Option<String>>("series")? {
+        Some(name) => Some(Series {
+            name,
+            volume: row.get("series_volume")?,
+        }),
+        None => None,
+    };
+    Ok(Book {
+        isbn: row.get("isbn")?,
+        title: row.get("title")?,
+        author: row.get("author")?,
+        author_id: row.get("author_id")?,
+        year: row.get("year")?,
+        description: row.get("description")?,
+        tags,
+        series,
+        version: row.get("version")?,
+        cover_path: row.get("cover_path")?,
+        cover_url: row.get("cover_url")?,
+        deleted_at: row.get("deleted_at")?,
+    })
+}
+
+#[async_trait]
+impl BookStore for SqliteStore {
+    async fn all(&self) -> Result<Vec<Book>> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        let mut stmt = conn.prepare(
+            "SELECT isbn, title, author, author_id, year, description, tags, series, series_volume, version, cover_path, cover_url, deleted_at FROM books ORDER BY rowid",
+        )?;
+        let books = stmt
+            .query_map([], row_to_book)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(books)
+    }
+
+    async fn get(&self, isbn: &str) -> Result<Option<Book>> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        let book = conn
+            .query_row(
+                "SELECT isbn, title, author, author_id, year, description, tags, series, series_volume, version, cover_path, cover_url, deleted_at FROM books WHERE isbn = ?1",
+                params![isbn],
+                row_to_book,
+            )
+            .optional()?;
+        Ok(book)
+    }
+
+    async fn put(&self, book: Book) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        conn.execute(
+            "INSERT INTO books (isbn, title, author, year, description, tags, version, cover_path, cover_url, deleted_at, author_id, series, series_volume)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT(isbn) DO UPDATE SET
+                title = excluded.title,
+                author = excluded.author,
+                year = excluded.year,
+                description = excluded.description,
+                tags = excluded.tags,
+                version = excluded.version,
+                cover_path = excluded.cover_path,
+                cover_url = excluded.cover_url,
+                deleted_at = excluded.deleted_at,
+                author_id = excluded.author_id,
+                series = excluded.series,
+                series_volume = excluded.series_volume",
+            params![
+                book.isbn,
+                book.title,
+                book.author,
+                book.year,
+                book.description,
+                serde_json::to_string(&book.tags)?,
+                book.version,
+                book.cover_path,
+                book.cover_url,
+                book.deleted_at,
+                book.author_id,
+                book.series.as_ref().map(|series| series.name.as_str()),
+                book.series.as_ref().and_then(|series| series.volume),
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn update(&self, book: Book, expected: u64) -> Result<VersionCheck> {
+        let mut conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        let tx = conn.transaction()?;
+        match current_version(&tx, &book.isbn)? {
+            None => return Ok(VersionCheck::NotFound),
+            Some(current) if current != expected => return Ok(VersionCheck::Conflict(current)),
+            Some(_) => {}
+        }
+        tx.execute(
+            "UPDATE books SET title = ?2, author = ?3, year = ?4, description = ?5, tags = ?6, version = ?7,
+                cover_path = ?8, cover_url = ?9, deleted_at = ?10, author_id = ?11,
+                series = ?12, series_volume = ?13
+             WHERE isbn = ?1",
+            params![
+                book.isbn,
+                book.title,
+                book.author,
+                book.year,
+                book.description,
+                serde_json::to_string(&book.tags)?,
+                expected + 1,
+                book.cover_path,
+                book.cover_url,
+                book.deleted_at,
+                book.author_id,
+                book.series.as_ref().map(|series| series.name.as_str()),
+                book.series.as_ref().and_then(|series| series.volume),
+            ],
+        )?;
+        tx.commit()?;
+        Ok(VersionCheck::Applied(expected + 1))
+    }
+
+    async fn insert(&self, book: Book) -> Result<bool> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        let inserted = conn.execute(
+            "INSERT INTO books (isbn, title, author, year, description, tags, version, cover_path, cover_url, deleted_at, author_id, series, series_volume)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+             ON CONFLICT(isbn) DO NOTHING",
+            params![
+                book.isbn,
+                book.title,
+                book.author,
+                book.year,
+                book.description,
+                serde_json::to_string(&book.tags)?,
+                book.version,
+                book.cover_path,
+                book.cover_url,
+                book.deleted_at,
+                book.author_id,
+                book.series.as_ref().map(|series| series.name.as_str()),
+                book.series.as_ref().and_then(|series| series.volume),
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> Result<VersionCheck> {
+        let mut conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        let tx = conn.transaction()?;
+        let current = match current_version(&tx, isbn)? {
+            None => return Ok(VersionCheck::NotFound),
+            Some(current) if expected.is_some_and(|expected| expected != current) => {
+                return Ok(VersionCheck::Conflict(current));
+            }
+            Some(current) => current,
+        };
+        tx.execute("DELETE FROM books WHERE isbn = ?1", params![isbn])?;
+        tx.execute("DELETE FROM reviews WHERE isbn = ?1", params![isbn])?;
+        tx.commit()?;
+        Ok(VersionCheck::Applied(current))
+    }
+
+    async fn add_review(&self, review: Review) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        conn.execute(
+            "INSERT INTO reviews (isbn, rating, text, reviewer) VALUES (?1, ?2, ?3, ?4)",
+            params![review.isbn, review.rating, review.text, review.reviewer],
+        )?;
+        Ok(())
+    }
+
+    async fn reviews(&self, isbn: &str) -> Result<Vec<Review>> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        let mut stmt = conn.prepare(
+            "SELECT isbn, rating, text, reviewer FROM reviews WHERE isbn = ?1 ORDER BY id",
+        )?;
+        let reviews = stmt
+            .query_map(params![isbn], |row| {
+                Ok(Review {
+                    isbn: row.get("isbn")?,
+                    rating: row.get("rating")?,
+                    text: row.get("text")?,
+                    reviewer: row.get("reviewer")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(reviews)
+    }
+
+    async fn authors(&self) -> Result<Vec<Author>> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        let mut stmt = conn.prepare("SELECT id, name, bio, aliases FROM authors ORDER BY rowid")?;
+        let authors = stmt
+            .query_map([], |row| {
+                let aliases: String = row.get("aliases")?;
+                Ok(Author {
+                    id: row.get("id")?,
+                    name: row.get("name")?,
+                    bio: row.get("bio")?,
+                    aliases: serde_json::from_str(&aliases).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+                    })?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(authors)
+    }
+
+    async fn put_author(&self, author: Author) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        conn.execute(
+            "INSERT INTO authors (id, name, bio, aliases) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, bio = excluded.bio, aliases = excluded.aliases",
+            params![author.id, author.name, author.bio, serde_json::to_string(&author.aliases)?],
+        )?;
+        Ok(())
+    }
+
+    async fn reading_lists(&self) -> Result<Vec<ReadingList>> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        let mut stmt =
+            conn.prepare("SELECT name, description, isbns, created_at, updated_at FROM reading_lists ORDER BY rowid")?;
+        let lists = stmt
+            .query_map([], |row| {
+                let isbns: String = row.get("isbns")?;
+                Ok(ReadingList {
+                    name: row.get("name")?,
+                    description: row.get("description")?,
+                    isbns: serde_json::from_str(&isbns).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+                    })?,
+                    created_at: row.get("created_at")?,
+                    updated_at: row.get("updated_at")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(lists)
+    }
+
+    async fn put_reading_list(&self, list: ReadingList) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        conn.execute(
+            "INSERT INTO reading_lists (name, description, isbns, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(name) DO UPDATE SET description = excluded.description, isbns = excluded.isbns,
+                updated_at = excluded.updated_at",
+            params![
+                list.name,
+                list.description,
+                serde_json::to_string(&list.isbns)?,
+                list.created_at,
+                list.updated_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let conn = self.conn.lock().expect("sqlite connection lock poisoned");
+        conn.cache_flush()?;
+        Ok(())
+    }
+}
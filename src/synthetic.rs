@@ -0,0 +1,152 @@
+//! ベンチマークや負荷試験に使う、それらしい架空の本の生成
+//!
+//! 同じシードからは常に同じ本が同じ順に生成される。タイトル・著者・説明は語彙の組み合わせで、
+//! タグは題材に対応するものを付けるので、タグや出版年による絞り込みも現実的な割合で一致する。
+
+use crate::isbn;
+use crate::model::Book;
+
+/// 生成するISBNの先頭（`fake_books` の `978-4-01` と重ならない `979-1`）
+const ISBN_PREFIX: &str = "9791";
+
+/// 題材と、その題材の本に付けるタグ
+const TOPICS: &[(&str, &str)] = &[
+    ("量子コンピュータ", "quantum"),
+    ("タイムトラベル", "time-travel"),
+    ("火星", "mars"),
+    ("人工知能", "ai"),
+    ("テレパシー", "psychic"),
+    ("宇宙エレベーター", "space"),
+    ("深海都市", "ocean"),
+    ("ロボット", "robotics"),
+    ("空飛ぶ車", "transport"),
+    ("月面基地", "moon"),
+    ("クローン", "biology"),
+    ("反重力", "physics"),
+];
+
+/// 題材の後ろに続ける分野と、その分野の本に付けるタグ
+const SUBJECTS: &[(&str, &str)] = &[
+    ("料理", "cooking"),
+    ("園芸", "gardening"),
+    ("税金対策", "finance"),
+    ("恋愛", "romance"),
+    ("心理学", "psychology"),
+    ("プログラミング", "programming"),
+    ("建築", "architecture"),
+    ("音楽", "music"),
+    ("子育て", "family"),
+    ("旅行", "travel"),
+    ("経営", "business"),
+    ("歴史", "history"),
+];
+
+/// タイトルの形（`{0}` が題材、`{1}` が分野）
+const TITLE_PATTERNS: &[&str] = &[
+    "{0}で{1}する方法",
+    "{0}と{1}",
+    "{0}時代の{1}入門",
+    "はじめての{0}{1}",
+    "{0}が変える{1}の未来",
+    "実践 {0}{1}",
+];
+
+const AUTHOR_TITLES: &[&str] = &["Dr. ", "教授", "未来の", "火星の", "銀河", "伝説の", ""];
+
+const AUTHOR_NAMES: &[&str] = &[
+    "スーパーサイエンティスト",
+    "会計士",
+    "園芸家",
+    "ロボット心理学者",
+    "サイキックエンジニア",
+    "宇宙料理人",
+    "時間旅行者",
+    "量子建築家",
+    "深海探検家",
+    "アンドロイド作家",
+];
+
+const DESCRIPTION_PATTERNS: &[&str] = &[
+    "{0}を活用した{1}の新しい方法を解説。",
+    "{0}の時代に{1}はどう変わるのかを、豊富な事例とともに考察する。",
+    "{1}の基礎から{0}を使った応用まで、初心者にも分かりやすく紹介。",
+    "{0}の専門家が、{1}の実践的なノウハウを惜しみなく公開する一冊。",
+];
+
+/// 出版年の範囲（架空の未来）
+const YEARS: std::ops::Range<i32> = 2100..3100;
+
+/// 外部のクレートに頼らない、シードから決まる乱数列（SplitMix64）
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// 本ごとの乱数列（途中の番号から生成しても同じ本になるよう、シードと番号だけで決める）
+    fn for_book(seed: u64, n: usize) -> Self {
+        Self(Self(seed ^ (n as u64).rotate_left(32)).next())
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+fn fill(pattern: &str, topic: &str, subject: &str) -> String {
+    pattern.replace("{0}", topic).replace("{1}", subject)
+}
+
+/// `seed` から決まる架空の本を `count` 冊生成する
+///
+/// ISBNは `979-1` で始まる生成順の連番なので、1億冊までは重複しない。
+pub fn books(count: usize, seed: u64) -> Vec<Book> {
+    books_from(0, count, seed)
+}
+
+/// `start` 番目から `count` 冊を生成する（既に生成した本に続けて追加する場合に使う）
+pub fn books_from(start: usize, count: usize, seed: u64) -> Vec<Book> {
+    (start..start + count).map(|n| book(&mut SplitMix64::for_book(seed, n), n)).collect()
+}
+
+fn book(rng: &mut SplitMix64, n: usize) -> Book {
+    let (topic, topic_tag) = *rng.pick(TOPICS);
+    let (subject, subject_tag) = *rng.pick(SUBJECTS);
+    let title = fill(rng.pick(TITLE_PATTERNS), topic, subject);
+    let author = format!("{}{}", rng.pick(AUTHOR_TITLES), rng.pick(AUTHOR_NAMES)).trim().to_string();
+    let description = fill(rng.pick(DESCRIPTION_PATTERNS), topic, subject);
+    let year = YEARS.start + rng.below(YEARS.len()) as i32;
+    // 半分ほどの本には、題材とは別の題材のタグも付ける
+    let mut tags = vec![topic_tag.to_string(), subject_tag.to_string()];
+    let (_, extra_tag) = *rng.pick(TOPICS);
+    if rng.next() % 2 == 0 && !tags.iter().any(|tag| tag == extra_tag) {
+        tags.push(extra_tag.to_string());
+    }
+
+    Book {
+        title,
+        author,
+        year,
+        description,
+        isbn: isbn::complete_isbn13(&format!("{}{:08}", ISBN_PREFIX, n)),
+        tags,
+        version: 0,
+        cover_path: None,
+        cover_url: None,
+        deleted_at: None,
+        author_id: None,
+        series: None,
+    }
+}
+
+#[cfg(test)]
+mod tests;
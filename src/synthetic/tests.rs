@@ -0,0 +1,41 @@
+//! 生成した本が決まった内容で、そのまま登録できる形であることの確認
+
+use std::collections::HashSet;
+
+use super::{books, books_from};
+use crate::model::prepare_new_book;
+
+#[test]
+fn same_seed_generates_same_books() {
+    let first = books(100, 7);
+    let second = books(100, 7);
+    assert_eq!(
+        serde_json::to_value(&first).unwrap(),
+        serde_json::to_value(&second).unwrap()
+    );
+    let other = books(100, 8);
+    assert_ne!(
+        serde_json::to_value(&first).unwrap(),
+        serde_json::to_value(&other).unwrap()
+    );
+}
+
+#[test]
+fn continuing_from_an_offset_matches_generating_at_once() {
+    let all = books(50, 3);
+    let mut parts = books(20, 3);
+    parts.extend(books_from(20, 30, 3));
+    assert_eq!(serde_json::to_value(&all).unwrap(), serde_json::to_value(&parts).unwrap());
+}
+
+#[test]
+fn generated_books_are_valid_and_unique() {
+    let generated = books(1_000, 11);
+    let isbns: HashSet<&str> = generated.iter().map(|book| book.isbn.as_str()).collect();
+    assert_eq!(isbns.len(), generated.len());
+    for book in &generated {
+        let mut prepared = book.clone();
+        assert_eq!(prepare_new_book(&mut prepared), Vec::<String>::new(), "{:?}", book);
+        assert_eq!(prepared.isbn, book.isbn);
+    }
+}
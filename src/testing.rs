@@ -0,0 +1,395 @@
+//! ハンドラーをプロセス内で呼び出すテスト用の道具
+//!
+//! stdio や子プロセスを使わず、メモリ上のパイプ（`tokio::io::duplex`）でサーバーとクライアントを
+//! つなぐ。ツールやリソースの読み出しは本物の `RequestContext` を受け取って動くので、
+//! 進捗通知や取り消しを使うツールもそのまま試せる。
+//!
+//! ```ignore
+//! let client = TestClient::connect(test_server()).await;
+//! let result = client.call("search", json!({ "keyword": "火星" })).await;
+//! assert_eq!(json_content(&result)["count"], 1);
+//! ```
+
+use async_trait::async_trait;
+use rmcp::model::*;
+use rmcp::service::{NotificationContext, RequestContext, RunningService};
+use rmcp::{ClientHandler, Error as McpError, RoleClient, ServiceExt};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+use crate::config::ServerConfig;
+use crate::events::ListKind;
+use crate::model::{Author, Book, ReadingList, Review, fake_books};
+use crate::server::BookSearch;
+use crate::store::{BookStore, MemoryStore, VersionCheck};
+
+/// サーバーとクライアントの間のパイプの容量（バイト）
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// 架空の本を入れたメモリ上のストアを使うサーバー
+pub fn test_server() -> BookSearch {
+    server_with_books(fake_books())
+}
+
+/// `books` だけを入れたメモリ上のストアを使うサーバー
+pub fn server_with_books(books: Vec<Book>) -> BookSearch {
+    server_with_config(books, ServerConfig::default())
+}
+
+/// `books` を入れたメモリ上のストアと、`config` の設定を使うサーバー
+pub fn server_with_config(books: Vec<Book>, config: ServerConfig) -> BookSearch {
+    BookSearch::with_store(Arc::new(MemoryStore::new(books))).with_config(config)
+}
+
+/// `conflict_on` で指定した本の更新と削除を、別の呼び出しが先に書き換えたものとして断るストア
+pub struct ConflictingStore {
+    inner: MemoryStore,
+    conflict_on: Mutex<Option<String>>,
+}
+
+impl ConflictingStore {
+    pub fn new(books: Vec<Book>) -> Self {
+        Self {
+            inner: MemoryStore::new(books),
+            conflict_on: Mutex::new(None),
+        }
+    }
+
+    /// 以降、`isbn` の本の更新と削除を版の衝突で断る
+    pub fn conflict_on(&self, isbn: &str) {
+        *self.conflict_on.lock().expect("conflict_on lock poisoned") = Some(isbn.to_string());
+    }
+
+    fn conflicts(&self, isbn: &str) -> bool {
+        self.conflict_on.lock().expect("conflict_on lock poisoned").as_deref() == Some(isbn)
+    }
+}
+
+#[async_trait]
+impl BookStore for ConflictingStore {
+    async fn all(&self) -> anyhow::Result<Vec<Book>> {
+        self.inner.all().await
+    }
+
+    async fn get(&self, isbn: &str) -> anyhow::Result<Option<Book>> {
+        self.inner.get(isbn).await
+    }
+
+    async fn put(&self, book: Book) -> anyhow::Result<()> {
+        self.inner.put(book).await
+    }
+
+    async fn update(&self, book: Book, expected: u64) -> anyhow::Result<VersionCheck> {
+        if self.conflicts(&book.isbn) {
+            return Ok(VersionCheck::Conflict(expected + 1));
+        }
+        self.inner.update(book, expected).await
+    }
+
+    async fn insert(&self, book: Book) -> anyhow::Result<bool> {
+        self.inner.insert(book).await
+    }
+
+    async fn remove(&self, isbn: &str, expected: Option<u64>) -> anyhow::Result<VersionCheck> {
+        if let Some(expected) = expected.filter(|_| self.conflicts(isbn)) {
+            return Ok(VersionCheck::Conflict(expected + 1));
+        }
+        self.inner.remove(isbn, expected).await
+    }
+
+    async fn add_review(&self, review: Review) -> anyhow::Result<()> {
+        self.inner.add_review(review).await
+    }
+
+    async fn reviews(&self, isbn: &str) -> anyhow::Result<Vec<Review>> {
+        self.inner.reviews(isbn).await
+    }
+
+    async fn authors(&self) -> anyhow::Result<Vec<Author>> {
+        self.inner.authors().await
+    }
+
+    async fn put_author(&self, author: Author) -> anyhow::Result<()> {
+        self.inner.put_author(author).await
+    }
+
+    async fn reading_lists(&self) -> anyhow::Result<Vec<ReadingList>> {
+        self.inner.reading_lists().await
+    }
+
+    async fn put_reading_list(&self, list: ReadingList) -> anyhow::Result<()> {
+        self.inner.put_reading_list(list).await
+    }
+}
+
+/// エリシテーションの要求に決まった答えを返すクライアント
+#[derive(Debug, Clone)]
+pub struct FormFiller {
+    /// 入力を求められたときに送る値（`None` なら入力を断る）
+    pub answer: Option<Value>,
+    /// 受け取った要求
+    pub requests: Arc<Mutex<Vec<CreateElicitationRequestParam>>>,
+}
+
+impl FormFiller {
+    pub fn new(answer: Option<Value>) -> Self {
+        Self {
+            answer,
+            requests: Arc::default(),
+        }
+    }
+}
+
+impl ClientHandler for FormFiller {
+    async fn create_elicitation(
+        &self,
+        request: CreateElicitationRequestParam,
+        _context: RequestContext<RoleClient>,
+    ) -> Result<CreateElicitationResult, McpError> {
+        self.requests.lock().expect("requests lock poisoned").push(request);
+        Ok(match &self.answer {
+            Some(content) => CreateElicitationResult {
+                action: ElicitationAction::Accept,
+                content: Some(content.clone()),
+            },
+            None => CreateElicitationResult {
+                action: ElicitationAction::Decline,
+                content: None,
+            },
+        })
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        let mut info = ClientInfo::default();
+        info.capabilities.elicitation = Some(ElicitationCapability::default());
+        info
+    }
+}
+
+/// 受け取った `notifications/*/list_changed` を記録するクライアント
+#[derive(Debug, Clone, Default)]
+pub struct ListWatcher {
+    received: Arc<Mutex<Vec<ListKind>>>,
+}
+
+impl ListWatcher {
+    fn record(&self, list: ListKind) {
+        self.received.lock().expect("received lock poisoned").push(list);
+    }
+
+    /// これまでに変更を知らされた一覧（受け取った順）
+    pub fn received(&self) -> Vec<ListKind> {
+        self.received.lock().expect("received lock poisoned").clone()
+    }
+
+    /// `list` の変更を知らされるまで待つ（1秒待っても届かなければ `false`）
+    pub async fn wait_for(&self, list: ListKind) -> bool {
+        for _ in 0..100 {
+            if self.received().contains(&list) {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        false
+    }
+}
+
+impl ClientHandler for ListWatcher {
+    async fn on_tool_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        self.record(ListKind::Tools);
+    }
+
+    async fn on_resource_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        self.record(ListKind::Resources);
+    }
+
+    async fn on_prompt_list_changed(&self, _context: NotificationContext<RoleClient>) {
+        self.record(ListKind::Prompts);
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        ClientInfo::default()
+    }
+}
+
+/// 受け取った `notifications/progress` を記録するクライアント
+#[derive(Debug, Clone, Default)]
+pub struct ProgressWatcher {
+    received: Arc<Mutex<Vec<ProgressNotificationParam>>>,
+}
+
+impl ProgressWatcher {
+    /// これまでに受け取った進捗通知（受け取った順）
+    pub fn received(&self) -> Vec<ProgressNotificationParam> {
+        self.received.lock().expect("received lock poisoned").clone()
+    }
+
+    /// `count` 件の進捗通知を受け取るまで待つ（1秒待っても届かなければ `false`）
+    pub async fn wait_for(&self, count: usize) -> bool {
+        for _ in 0..100 {
+            if self.received().len() >= count {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        false
+    }
+}
+
+impl ClientHandler for ProgressWatcher {
+    async fn on_progress(&self, params: ProgressNotificationParam, _context: NotificationContext<RoleClient>) {
+        self.received.lock().expect("received lock poisoned").push(params);
+    }
+
+    fn get_info(&self) -> ClientInfo {
+        ClientInfo::default()
+    }
+}
+
+/// プロセス内でサーバーとつないだクライアント
+pub struct TestClient<C: ClientHandler = ClientInfo> {
+    service: RunningService<RoleClient, C>,
+}
+
+impl TestClient {
+    /// `server` をメモリ上のパイプで起動し、初期化を済ませたクライアントを返す
+    pub async fn connect(server: BookSearch) -> Self {
+        Self::connect_as(server, ClientInfo::default()).await
+    }
+}
+
+impl<C: ClientHandler> TestClient<C> {
+    /// `client` をクライアント側のハンドラー（要求するプロトコルのリビジョンや機能）として初期化したクライアントを返す
+    pub async fn connect_as(server: BookSearch, client: C) -> Self {
+        let (server_side, client_side) = tokio::io::duplex(PIPE_CAPACITY);
+        tokio::spawn(async move {
+            let service = server
+                .serve(tokio::io::split(server_side))
+                .await
+                .expect("test server failed to initialize");
+            let _ = service.waiting().await;
+        });
+        let service = client
+            .serve(tokio::io::split(client_side))
+            .await
+            .expect("test client failed to initialize");
+        Self { service }
+    }
+
+    /// 初期化の応答で受け取ったサーバーの情報
+    pub fn server_info(&self) -> &ServerInfo {
+        self.service.peer_info().expect("server info is available after initialization")
+    }
+
+    /// ツールを一覧する
+    pub async fn tools(&self) -> Vec<Tool> {
+        self.service
+            .list_tools(Default::default())
+            .await
+            .expect("tools/list failed")
+            .tools
+    }
+
+    /// ツールを呼び出す（プロトコルのエラーは `Err` になる）
+    pub async fn try_call(&self, tool: &str, arguments: Value) -> Result<CallToolResult, rmcp::ServiceError> {
+        self.service
+            .call_tool(CallToolRequestParam {
+                name: tool.to_string().into(),
+                arguments: arguments.as_object().cloned(),
+            })
+            .await
+    }
+
+    /// 進捗トークン `token` を付けてツールを呼び出す（プロトコルのエラーで失敗する）
+    pub async fn call_with_progress(&self, tool: &str, arguments: Value, token: &str) -> CallToolResult {
+        let mut meta = Meta::new();
+        meta.set_progress_token(ProgressToken(NumberOrString::String(token.into())));
+        let mut request = CallToolRequest::new(CallToolRequestParam {
+            name: tool.to_string().into(),
+            arguments: arguments.as_object().cloned(),
+        });
+        request.extensions.insert(meta);
+        match self.service.send_request(ClientRequest::CallToolRequest(request)).await {
+            Ok(ServerResult::CallToolResult(result)) => result,
+            other => panic!("tools/call {} failed: {:?}", tool, other),
+        }
+    }
+
+    /// ツールを呼び出す（プロトコルのエラーで失敗する）
+    pub async fn call(&self, tool: &str, arguments: Value) -> CallToolResult {
+        self.try_call(tool, arguments)
+            .await
+            .unwrap_or_else(|e| panic!("tools/call {} failed: {:?}", tool, e))
+    }
+
+    pub async fn read(&self, uri: &str) -> Result<ReadResourceResult, rmcp::ServiceError> {
+        self.service
+            .read_resource(ReadResourceRequestParam { uri: uri.to_string() })
+            .await
+    }
+
+    /// クライアントへ送るログの重要度を指定する
+    pub async fn set_log_level(&self, level: LoggingLevel) -> Result<(), rmcp::ServiceError> {
+        self.service.set_level(SetLevelRequestParam { level }).await
+    }
+
+    /// プロンプトを1ページ分一覧する（`cursor` は前のページの `next_cursor`）
+    pub async fn prompts(&self, cursor: Option<&str>) -> ListPromptsResult {
+        self.service
+            .list_prompts(Some(PaginatedRequestParamInner {
+                cursor: cursor.map(str::to_string),
+            }))
+            .await
+            .expect("prompts/list failed")
+    }
+
+    pub async fn prompt(&self, name: &str, arguments: Value) -> Result<GetPromptResult, rmcp::ServiceError> {
+        self.service
+            .get_prompt(GetPromptRequestParam {
+                name: name.to_string(),
+                arguments: arguments.as_object().cloned(),
+            })
+            .await
+    }
+
+    /// 接続を閉じる
+    pub async fn close(self) {
+        let _ = self.service.cancel().await;
+    }
+}
+
+/// ツールの結果の全てのテキストをつなげて返す
+pub fn text_content(result: &CallToolResult) -> String {
+    result
+        .content
+        .iter()
+        .filter_map(|content| content.as_text().map(|text| text.text.clone()))
+        .collect()
+}
+
+/// ツールの結果の最初のテキストをJSONとして解釈する
+pub fn json_content(result: &CallToolResult) -> Value {
+    let text = result
+        .content
+        .first()
+        .and_then(|content| content.as_text())
+        .map(|text| text.text.as_str())
+        .expect("tool result has no text content");
+    serde_json::from_str(text).unwrap_or_else(|e| panic!("tool result is not JSON ({}): {}", e, text))
+}
+
+/// `resources/read` の結果の最初のテキストを返す
+pub fn resource_text(result: &ReadResourceResult) -> &str {
+    match result.contents.first() {
+        Some(ResourceContents::TextResourceContents { text, .. }) => text,
+        other => panic!("resource has no text contents: {:?}", other),
+    }
+}
+
+/// プロトコルのエラーから MCP のエラーを取り出す
+pub fn mcp_error(error: rmcp::ServiceError) -> McpError {
+    match error {
+        rmcp::ServiceError::McpError(error) => error,
+        other => panic!("expected an MCP error, got {:?}", other),
+    }
+}
@@ -0,0 +1,254 @@
+//! サーバーを公開するトランスポートの選択と起動
+
+use anyhow::Result;
+use clap::ValueEnum;
+use rmcp::{ServerHandler, ServiceExt, transport::stdio};
+use serde::Deserialize;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::auth::{Authenticator, ToolPolicy};
+use crate::config::CompressionConfig;
+use crate::health::Health;
+use crate::shutdown;
+
+#[cfg(feature = "websocket")]
+mod websocket;
+
+/// `--transport` で選択できるトランスポート
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transport {
+    Stdio,
+    Sse,
+    StreamableHttp,
+    Ws,
+}
+
+impl Transport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stdio => "stdio",
+            Self::Sse => "sse",
+            Self::StreamableHttp => "streamable-http",
+            Self::Ws => "ws",
+        }
+    }
+}
+
+/// トランスポートから起動できるMCPサーバー
+pub trait ManagedServer: ServerHandler + Clone {
+    /// ネットワーク越しの接続ごとに使うセッションを作る（既定では複製するだけ）
+    fn new_session(&self) -> Self {
+        self.clone()
+    }
+
+    /// 接続時に認証したトークンに許可するツールを設定する（既定では無視する）
+    fn with_policy(self, _policy: ToolPolicy) -> Self {
+        self
+    }
+
+    /// HTTPのトランスポートの `/healthz` と `/readyz` で返す状態（既定では確認する項目がなく、常に正常）
+    fn health(&self) -> impl Future<Output = Health> + Send {
+        async { Health::default() }
+    }
+
+    /// 終了前に実行中の処理を待ち、保存先へ書き出す（既定では何もしない）
+    fn shutdown(&self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+}
+
+/// 選択されたトランスポートでサーバーを起動し、終了するまで待つ
+///
+/// ネットワーク越しのトランスポートでは、接続ごとに `server` から新しいセッションを作る。
+/// `auth` を指定すると、ベアラートークンを検証できなかった接続を 401 で拒否する（stdio では無視する）。
+/// `compression` を指定すると、HTTPのトランスポートの応答を圧縮する（stdio と ws では無視する）。
+/// SIGINT / SIGTERM を受けると新しいツール呼び出しを断り、実行中の呼び出しと
+/// ストアへの書き出しが終わってから接続を閉じる。
+pub async fn serve<S: ManagedServer>(
+    transport: Transport,
+    server: S,
+    listen: SocketAddr,
+    auth: Option<Authenticator>,
+    compression: Option<CompressionConfig>,
+) -> Result<()> {
+    crate::server::record_start(transport);
+    let auth = auth.map(Arc::new);
+    match transport {
+        Transport::Stdio => serve_stdio(server).await,
+        Transport::Sse => serve_sse(server, listen, auth, compression).await,
+        Transport::StreamableHttp => serve_streamable_http(server, listen, auth, compression).await,
+        Transport::Ws => serve_ws(server, listen, auth).await,
+    }
+}
+
+async fn serve_stdio<S: ManagedServer>(server: S) -> Result<()> {
+    let handle = server.clone();
+    let service = server.serve(stdio()).await.inspect_err(|e| {
+        tracing::error!("servign error: {:?}", e);
+    })?;
+
+    let ct = service.cancellation_token();
+    let waiting = service.waiting();
+    tokio::pin!(waiting);
+    tokio::select! {
+        result = &mut waiting => {
+            result?;
+            handle.shutdown().await?;
+        }
+        result = shutdown::signal() => {
+            result?;
+            tracing::info!("Shutting down");
+            handle.shutdown().await?;
+            ct.cancel();
+            waiting.await?;
+        }
+    }
+    Ok(())
+}
+
+/// `config.min_bytes` 以上の応答を、クライアントの `Accept-Encoding` に応じて圧縮する
+///
+/// 既定の条件で、SSEのイベントストリームと画像は圧縮しない。
+#[cfg(any(feature = "sse", feature = "streamable-http"))]
+pub(crate) fn compress(router: axum::Router, config: CompressionConfig) -> axum::Router {
+    use tower_http::compression::CompressionLayer;
+    use tower_http::compression::predicate::{DefaultPredicate, Predicate, SizeAbove};
+
+    let predicate = DefaultPredicate::new().and(SizeAbove::new(config.min_bytes));
+    router.layer(CompressionLayer::new().compress_when(predicate))
+}
+
+/// axum のルーターを `listen` で公開し、`ct` が取り消されたら受け付けをやめる
+///
+/// `/healthz` と `/readyz` は、プローブがトークンを持たなくてよいように認証の外に置く。
+#[cfg(any(feature = "sse", feature = "streamable-http"))]
+async fn spawn_router<S: ManagedServer>(
+    router: axum::Router,
+    server: &S,
+    listen: SocketAddr,
+    auth: Option<Arc<Authenticator>>,
+    compression: Option<CompressionConfig>,
+    ct: tokio_util::sync::CancellationToken,
+) -> Result<()> {
+    let router = match auth {
+        Some(auth) => crate::auth::require_bearer(router, auth),
+        None => router,
+    };
+    let server = server.clone();
+    let router = router.merge(crate::health::router(move || {
+        let server = server.clone();
+        async move { server.health().await }
+    }));
+    let router = match compression {
+        Some(config) => compress(router, config),
+        None => router,
+    };
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    tokio::spawn(async move {
+        let served = axum::serve(listener, router)
+            .with_graceful_shutdown(async move { ct.cancelled().await })
+            .await;
+        if let Err(e) = served {
+            tracing::error!("HTTP server error: {:?}", e);
+        }
+    });
+    Ok(())
+}
+
+/// SSEのエンドポイント（`/sse` と `/message`）で接続ごとにサーバーを起動する
+#[cfg(feature = "sse")]
+async fn serve_sse<S: ManagedServer>(
+    server: S,
+    listen: SocketAddr,
+    auth: Option<Arc<Authenticator>>,
+    compression: Option<CompressionConfig>,
+) -> Result<()> {
+    use rmcp::transport::sse_server::{SseServer, SseServerConfig};
+
+    tracing::info!("Listening for SSE connections on {}", listen);
+    let (sse_server, router) = SseServer::new(SseServerConfig {
+        bind: listen,
+        sse_path: "/sse".to_string(),
+        post_path: "/message".to_string(),
+        ct: tokio_util::sync::CancellationToken::new(),
+        sse_keep_alive: None,
+    });
+    spawn_router(router, &server, listen, auth, compression, sse_server.config.ct.child_token()).await?;
+    let ct = sse_server.with_service({
+        let server = server.clone();
+        move || server.new_session()
+    });
+
+    shutdown::signal().await?;
+    tracing::info!("Shutting down");
+    server.shutdown().await?;
+    ct.cancel();
+    Ok(())
+}
+
+#[cfg(not(feature = "sse"))]
+async fn serve_sse<S: ManagedServer>(
+    _server: S,
+    _listen: SocketAddr,
+    _auth: Option<Arc<Authenticator>>,
+    _compression: Option<CompressionConfig>,
+) -> Result<()> {
+    anyhow::bail!("the sse transport requires building with the `sse` feature")
+}
+
+/// Streamable HTTPのエンドポイントで接続ごとにサーバーを起動する
+#[cfg(feature = "streamable-http")]
+async fn serve_streamable_http<S: ManagedServer>(
+    server: S,
+    listen: SocketAddr,
+    auth: Option<Arc<Authenticator>>,
+    compression: Option<CompressionConfig>,
+) -> Result<()> {
+    use rmcp::transport::streamable_http_server::axum::{StreamableHttpServer, StreamableHttpServerConfig};
+
+    tracing::info!("Listening for streamable HTTP connections on {}", listen);
+    let (http_server, router) = StreamableHttpServer::new(StreamableHttpServerConfig {
+        bind: listen,
+        path: "/mcp".to_string(),
+        ct: tokio_util::sync::CancellationToken::new(),
+        sse_keep_alive: None,
+    });
+    spawn_router(router, &server, listen, auth, compression, http_server.config.ct.child_token()).await?;
+    let ct = http_server.with_service({
+        let server = server.clone();
+        move || server.new_session()
+    });
+
+    shutdown::signal().await?;
+    tracing::info!("Shutting down");
+    server.shutdown().await?;
+    ct.cancel();
+    Ok(())
+}
+
+#[cfg(not(feature = "streamable-http"))]
+async fn serve_streamable_http<S: ManagedServer>(
+    _server: S,
+    _listen: SocketAddr,
+    _auth: Option<Arc<Authenticator>>,
+    _compression: Option<CompressionConfig>,
+) -> Result<()> {
+    anyhow::bail!("the streamable-http transport requires building with the `streamable-http` feature")
+}
+
+/// WebSocketで接続ごとにサーバーを起動する
+#[cfg(feature = "websocket")]
+async fn serve_ws<S: ManagedServer>(server: S, listen: SocketAddr, auth: Option<Arc<Authenticator>>) -> Result<()> {
+    websocket::serve(server, listen, auth).await
+}
+
+#[cfg(not(feature = "websocket"))]
+async fn serve_ws<S: ManagedServer>(_server: S, _listen: SocketAddr, _auth: Option<Arc<Authenticator>>) -> Result<()> {
+    anyhow::bail!("the ws transport requires building with the `websocket` feature")
+}
+
+#[cfg(all(test, any(feature = "sse", feature = "streamable-http")))]
+mod tests;
@@ -0,0 +1,64 @@
+//! HTTPの応答の圧縮が、クライアントの `Accept-Encoding` と応答の大きさに従うことの確認
+
+use axum::Router;
+use axum::routing::get;
+use serde_json::json;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::compress;
+use crate::config::CompressionConfig;
+
+/// `/large` は `min_bytes` を超え、`/small` は下回るJSONを返すルーター
+fn router() -> Router {
+    let large = json!({ "books": vec!["火星での園芸入門"; 200] });
+    Router::new()
+        .route("/large", get(move || async move { axum::Json(large.clone()) }))
+        .route("/small", get(|| async { axum::Json(json!({ "status": "ok" })) }))
+}
+
+async fn serve(router: Router) -> SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    addr
+}
+
+/// `path` を取得し、応答のヘッダーの `content-encoding` を返す（圧縮されていなければ `None`）
+async fn content_encoding(addr: SocketAddr, path: &str, accept_encoding: Option<&str>) -> Option<String> {
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let accept = accept_encoding
+        .map(|encoding| format!("Accept-Encoding: {}\r\n", encoding))
+        .unwrap_or_default();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n", path, accept);
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let end = response.windows(4).position(|window| window == b"\r\n\r\n").expect("response has headers");
+    String::from_utf8_lossy(&response[..end])
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-encoding"))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+#[tokio::test]
+async fn large_responses_are_compressed_with_the_accepted_encoding() {
+    let addr = serve(compress(router(), CompressionConfig { min_bytes: 1024 })).await;
+    assert_eq!(content_encoding(addr, "/large", Some("gzip")).await.as_deref(), Some("gzip"));
+    assert_eq!(content_encoding(addr, "/large", Some("br")).await.as_deref(), Some("br"));
+    // 対応する形式を送らないクライアントには圧縮しない
+    assert_eq!(content_encoding(addr, "/large", None).await, None);
+}
+
+#[tokio::test]
+async fn responses_below_the_threshold_are_not_compressed() {
+    let addr = serve(compress(router(), CompressionConfig { min_bytes: 1024 })).await;
+    assert_eq!(content_encoding(addr, "/small", Some("gzip")).await, None);
+}
+
+#[tokio::test]
+async fn responses_are_not_compressed_without_the_layer() {
+    let addr = serve(router()).await;
+    assert_eq!(content_encoding(addr, "/large", Some("gzip")).await, None);
+}
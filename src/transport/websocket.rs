@@ -0,0 +1,201 @@
+//! WebSocketのトランスポート（1つのテキストフレームに1つのJSON-RPCメッセージを載せる）
+
+use anyhow::Result;
+use futures::channel::mpsc;
+use futures::{SinkExt, StreamExt};
+use rmcp::{
+    RoleServer, ServiceExt,
+    service::{RxJsonRpcMessage, TxJsonRpcMessage},
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{StatusCode, header};
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+use super::ManagedServer;
+use crate::auth::{Authenticator, ToolPolicy};
+use crate::shutdown;
+
+/// 接続が生きているかを確かめる Ping の間隔（次の Ping までに Pong がなければ切断する）
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// サーバーからクライアントへ送るメッセージを溜めておく数
+const OUTGOING_CAPACITY: usize = 64;
+
+/// `listen` で待ち受け、接続ごとにサーバーのセッションを起動する
+pub(super) async fn serve<S: ManagedServer>(server: S, listen: SocketAddr, auth: Option<Arc<Authenticator>>) -> Result<()> {
+    let listener = TcpListener::bind(listen).await?;
+    tracing::info!("Listening for WebSocket connections on {}", listen);
+
+    let (closing, closed) = watch::channel(false);
+    let mut connections = JoinSet::new();
+    let signal = shutdown::signal();
+    tokio::pin!(signal);
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        tracing::warn!("Failed to accept WebSocket connection: {}", e);
+                        continue;
+                    }
+                };
+                let session = server.new_session();
+                let closed = closed.clone();
+                let auth = auth.clone();
+                connections.spawn(async move {
+                    if let Err(e) = serve_connection(session, stream, auth, closed).await {
+                        tracing::warn!("WebSocket connection from {} ended with error: {:?}", peer, e);
+                    }
+                });
+            }
+            result = &mut signal => {
+                result?;
+                break;
+            }
+        }
+    }
+
+    tracing::info!("Shutting down");
+    server.shutdown().await?;
+    let _ = closing.send(true);
+    while connections.join_next().await.is_some() {}
+    Ok(())
+}
+
+/// ハンドシェイクの `Authorization` ヘッダーを検証し、通らなければ 401 で拒否する
+///
+/// 通った場合は、そのトークンに許可するツールを `policy` に書き込む。
+#[allow(clippy::result_large_err)]
+fn authorize(
+    auth: Option<&Authenticator>,
+    request: &Request,
+    response: Response,
+    policy: &mut ToolPolicy,
+) -> Result<Response, ErrorResponse> {
+    let Some(auth) = auth else {
+        return Ok(response);
+    };
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    match auth.verify(authorization) {
+        Ok(granted) => {
+            *policy = granted;
+            Ok(response)
+        }
+        Err(e) => {
+            tracing::warn!("Rejected unauthenticated WebSocket handshake: {}", e);
+            let mut rejection = ErrorResponse::new(None);
+            *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+            if let Ok(value) = "Bearer".parse() {
+                rejection.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+            }
+            Err(rejection)
+        }
+    }
+}
+
+/// 1つの接続でハンドシェイクを行い、セッションが終わるまでメッセージを中継する
+async fn serve_connection<S: ManagedServer>(
+    session: S,
+    stream: TcpStream,
+    auth: Option<Arc<Authenticator>>,
+    closed: watch::Receiver<bool>,
+) -> Result<()> {
+    let mut policy = ToolPolicy::All;
+    let socket = tokio_tungstenite::accept_hdr_async(stream, |request: &Request, response: Response| {
+        authorize(auth.as_deref(), request, response, &mut policy)
+    })
+    .await?;
+    let session = session.with_policy(policy);
+
+    let (incoming_tx, incoming_rx) = mpsc::unbounded::<RxJsonRpcMessage<RoleServer>>();
+    let (outgoing_tx, outgoing_rx) = mpsc::channel::<TxJsonRpcMessage<RoleServer>>(OUTGOING_CAPACITY);
+    let relay = tokio::spawn(relay(socket, incoming_tx, outgoing_rx, closed));
+
+    let service = session.serve((outgoing_tx, incoming_rx)).await?;
+    service.waiting().await?;
+    relay.await??;
+    Ok(())
+}
+
+/// WebSocketとセッションの間でメッセージを受け渡し、Ping で接続を監視する
+///
+/// クライアントが接続を閉じるか、セッションが終わるか、サーバーが終了するまで続ける。
+async fn relay(
+    mut socket: tokio_tungstenite::WebSocketStream<TcpStream>,
+    incoming: mpsc::UnboundedSender<RxJsonRpcMessage<RoleServer>>,
+    mut outgoing: mpsc::Receiver<TxJsonRpcMessage<RoleServer>>,
+    mut closed: watch::Receiver<bool>,
+) -> Result<()> {
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+    ping.tick().await;
+    let mut awaiting_pong = false;
+
+    loop {
+        tokio::select! {
+            message = outgoing.next() => {
+                let Some(message) = message else {
+                    // セッションが終わった
+                    close(&mut socket, CloseCode::Normal, "session ended").await;
+                    return Ok(());
+                };
+                socket.send(Message::Text(serde_json::to_string(&message)?.into())).await?;
+            }
+            frame = socket.next() => {
+                let text = match frame {
+                    Some(Ok(Message::Text(text))) => text.to_string(),
+                    Some(Ok(Message::Binary(bytes))) => String::from_utf8_lossy(&bytes).into_owned(),
+                    Some(Ok(Message::Pong(_))) => {
+                        awaiting_pong = false;
+                        continue;
+                    }
+                    // Ping への Pong は tungstenite が自動で返す
+                    Some(Ok(Message::Ping(_) | Message::Frame(_))) => continue,
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Err(e)) => return Err(e.into()),
+                };
+                match serde_json::from_str(&text) {
+                    Ok(message) => {
+                        if incoming.unbounded_send(message).is_err() {
+                            return Ok(());
+                        }
+                    }
+                    Err(e) => tracing::warn!("Ignoring malformed WebSocket message: {}", e),
+                }
+            }
+            _ = ping.tick() => {
+                if awaiting_pong {
+                    tracing::info!("Closing WebSocket connection after missing pong");
+                    close(&mut socket, CloseCode::Away, "ping timeout").await;
+                    return Ok(());
+                }
+                socket.send(Message::Ping(Vec::new().into())).await?;
+                awaiting_pong = true;
+            }
+            _ = closed.changed() => {
+                close(&mut socket, CloseCode::Away, "server shutting down").await;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Close フレームを送る（既に切断されていれば何もしない）
+async fn close(socket: &mut tokio_tungstenite::WebSocketStream<TcpStream>, code: CloseCode, reason: &str) {
+    let frame = CloseFrame {
+        code,
+        reason: reason.to_string().into(),
+    };
+    let _ = socket.close(Some(frame)).await;
+}
@@ -0,0 +1,91 @@
+//! JSONのデータファイルの監視と再読み込み
+//!
+//! エディタや他のプロセスがファイルを書き換えると、サーバーを再起動せずにカタログを読み直し、
+//! 変わった本ごとに [`CatalogEvent`] を流す。購読しているセッションにはイベントバスを通じて
+//! `notifications/resources/updated` が届く。
+
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::events::{CatalogEvent, CatalogEvents, ChangeKind};
+use crate::index::RankedIndex;
+use crate::store::{BookStore, FileChanges, JsonFileStore};
+
+/// 保存中の連続した変更通知をまとめるために待つ時間
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// `store` のファイルの監視を始める
+///
+/// エディタは別名で書いてから置き換えることが多いので、ファイルではなく親ディレクトリを監視する。
+/// 監視はランタイムが止まるまで続く。読み直した本は `indexes` の各インデックスにも反映する。
+pub fn spawn(store: Arc<JsonFileStore>, events: CatalogEvents, indexes: Vec<Arc<dyn RankedIndex>>) -> Result<()> {
+    let path = store.path().to_path_buf();
+    let file_name = path.file_name().context("data file has no file name")?.to_os_string();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => std::path::PathBuf::from("."),
+    };
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| match result {
+        Ok(event) if !event.kind.is_access() && event.paths.iter().any(|p| p.file_name() == Some(&file_name)) => {
+            let _ = sender.send(());
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Data file watch error: {}", e),
+    })?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("failed to watch {}", dir.display()))?;
+    tracing::info!("Watching {} for changes", path.display());
+
+    tokio::spawn(async move {
+        // タスクが生きている間だけ監視を続けるため、ウォッチャーをここで保持する
+        let _watcher = watcher;
+        while receiver.recv().await.is_some() {
+            tokio::time::sleep(DEBOUNCE).await;
+            while receiver.try_recv().is_ok() {}
+            match store.reload() {
+                Ok(changes) if changes.is_empty() => {}
+                Ok(changes) => apply(&store, &events, &indexes, changes).await,
+                Err(e) => tracing::warn!("Keeping the current catalog; failed to reload {}: {:#}", path.display(), e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// 読み直した変更をインデックスに反映し、イベントとして流す
+async fn apply(store: &JsonFileStore, events: &CatalogEvents, indexes: &[Arc<dyn RankedIndex>], changes: FileChanges) {
+    tracing::info!(
+        "Reloaded {} ({} added, {} updated, {} removed)",
+        store.path().display(),
+        changes.added.len(),
+        changes.updated.len(),
+        changes.removed.len()
+    );
+    let changed = changes
+        .added
+        .into_iter()
+        .map(|isbn| (ChangeKind::Added, isbn))
+        .chain(changes.updated.into_iter().map(|isbn| (ChangeKind::Updated, isbn)))
+        .chain(changes.removed.into_iter().map(|isbn| (ChangeKind::Removed, isbn)));
+    for (kind, isbn) in changed {
+        for index in indexes {
+            let result = match kind {
+                ChangeKind::Removed => index.remove(&isbn),
+                _ => store
+                    .get(&isbn)
+                    .await
+                    .and_then(|book| book.map_or(Ok(()), |book| index.upsert(&book))),
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to reindex {}: {:#}", isbn, e);
+            }
+        }
+        events.publish(CatalogEvent::new(kind, isbn));
+    }
+}
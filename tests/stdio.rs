@@ -0,0 +1,78 @@
+//! ビルドした book_server を子プロセスとして起動し、stdio 越しに rmcp のクライアントで呼び出す
+
+use rmcp::model::{CallToolRequestParam, CallToolResult};
+use rmcp::service::RunningService;
+use rmcp::transport::TokioChildProcess;
+use rmcp::{RoleClient, ServiceExt};
+use serde_json::{Value, json};
+use std::process::Stdio;
+use tokio::process::Command;
+
+async fn connect() -> RunningService<RoleClient, ()> {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_book_server"));
+    command.arg("--transport").arg("stdio").stderr(Stdio::null());
+    let transport = TokioChildProcess::new(&mut command).expect("failed to spawn book_server");
+    ().serve(transport).await.expect("initialize failed")
+}
+
+fn json_content(result: &CallToolResult) -> Value {
+    let text = result
+        .content
+        .first()
+        .and_then(|content| content.as_text())
+        .map(|text| text.text.as_str())
+        .expect("tool result has no text content");
+    serde_json::from_str(text).expect("tool result is not JSON")
+}
+
+#[tokio::test]
+async fn initialize_identifies_the_server() {
+    let client = connect().await;
+    let info = client.peer_info().expect("server info after initialize");
+    assert!(info.instructions.as_deref().is_some_and(|text| text.contains("架空の本")));
+    assert!(info.capabilities.tools.is_some());
+    client.cancel().await.expect("failed to close the session");
+}
+
+#[tokio::test]
+async fn list_tools_includes_search_with_its_schema() {
+    let client = connect().await;
+    let tools = client.list_tools(Default::default()).await.expect("tools/list failed").tools;
+    let search = tools.iter().find(|tool| tool.name == "search").expect("search is listed");
+    let properties = search.input_schema.get("properties").expect("search has properties");
+    assert!(properties.get("keyword").is_some());
+    client.cancel().await.expect("failed to close the session");
+}
+
+#[tokio::test]
+async fn search_returns_structured_results() {
+    let client = connect().await;
+    let result = client
+        .call_tool(CallToolRequestParam {
+            name: "search".into(),
+            arguments: json!({ "keyword": "火星", "output_format": "json" }).as_object().cloned(),
+        })
+        .await
+        .expect("tools/call failed");
+    assert_ne!(result.is_error, Some(true));
+
+    let value = json_content(&result);
+    assert_eq!(value["keyword"], "火星");
+    assert_eq!(value["count"], 1);
+    assert_eq!(value["books"][0]["isbn"], "9784012345632");
+    assert_eq!(value["books"][0]["title"], "火星での園芸入門");
+    client.cancel().await.expect("failed to close the session");
+}
+
+#[tokio::test]
+async fn unknown_tool_is_a_protocol_error() {
+    let client = connect().await;
+    let result = client
+        .call_tool(CallToolRequestParam {
+            name: "no_such_tool".into(),
+            arguments: None,
+        })
+        .await;
+    assert!(result.is_err());
+    client.cancel().await.expect("failed to close the session");
+}